@@ -1,5 +1,6 @@
 use near_sdk::env::panic_str;
 use near_sdk::FunctionError;
+use sbt::ExpiresAtError;
 
 /// Contract errors
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
@@ -8,6 +9,21 @@ pub enum Error {
     NotMinter,
     RequiredDeposit(u128),
     ClassNotFound,
+    /// `metadata.expires_at` must be in the future and at most `now + max_ttl` (the value
+    /// carried by the error).
+    WrongExpiresAt(u64),
+    /// `metadata.expires_at` must be at least `now + min_ttl` (the value carried by the error).
+    ExpiresAtTooEarly(u64),
+    SelfMintDisabled,
+    /// the caller already self minted a token of the class.
+    AlreadyMinted,
+    /// self mint rate limit. Contains the time (in ms) when the next self mint is possible.
+    RateLimited(u64),
+    /// the class owner can't be removed from the class admins.
+    RemoveOwner,
+    NotOwner,
+    /// the mint fee must be a positive amount.
+    ZeroMintFee,
 }
 
 impl FunctionError for Error {
@@ -19,6 +35,34 @@ impl FunctionError for Error {
                 panic_str(&format!("deposit must be at least {}yN", min_deposit))
             }
             Error::ClassNotFound => panic_str("class not found"),
+            Error::WrongExpiresAt(max) => panic_str(&format!(
+                "metadata.expires_at must be in the future and not bigger than {}",
+                max
+            )),
+            Error::ExpiresAtTooEarly(min) => panic_str(&format!(
+                "metadata.expires_at must not be smaller than {}",
+                min
+            )),
+            Error::SelfMintDisabled => panic_str("self mint is not enabled for the class"),
+            Error::AlreadyMinted => panic_str("caller already self minted a token of the class"),
+            Error::RateLimited(next) => panic_str(&format!(
+                "self mint rate limit, next mint is possible at {}",
+                next
+            )),
+            Error::RemoveOwner => panic_str(
+                "class owner can't be removed from the admins, transfer the ownership first",
+            ),
+            Error::NotOwner => panic_str("not authorized: required class owner"),
+            Error::ZeroMintFee => panic_str("mint fee must be positive"),
+        }
+    }
+}
+
+impl From<ExpiresAtError> for Error {
+    fn from(e: ExpiresAtError) -> Self {
+        match e {
+            ExpiresAtError::Wrong(max) => Error::WrongExpiresAt(max),
+            ExpiresAtError::TooEarly(min) => Error::ExpiresAtTooEarly(min),
         }
     }
 }
@@ -0,0 +1,191 @@
+use near_sdk::{json_types::U128, serde::Serialize, serde_json::json, AccountId, Balance};
+use sbt::{ClassId, EventPayload, NearEvent};
+
+use crate::MintFee;
+
+fn emit_co_event<T: Serialize>(event: EventPayload<T>) {
+    NearEvent {
+        standard: "community_open",
+        version: "1.0.0",
+        event,
+    }
+    .emit();
+}
+
+/// `admin`: account which acquired the class and became its admin.
+pub(crate) fn emit_class_acquired(
+    class: ClassId,
+    admin: AccountId,
+    requires_iah: bool,
+    minters: &[AccountId],
+    max_ttl: u64,
+    memo: Option<String>,
+) {
+    emit_co_event(EventPayload {
+        event: "class_acquired",
+        data: json!({
+            "class": class,
+            "admin": admin,
+            "requires_iah": requires_iah,
+            "minters": minters,
+            "max_ttl": max_ttl,
+            "memo": memo,
+        }),
+    });
+}
+
+/// `minters`: list of accounts which were actually added (existing minters are skipped).
+pub(crate) fn emit_add_minters(class: ClassId, minters: Vec<AccountId>, memo: Option<String>) {
+    emit_co_event(EventPayload {
+        event: "add_minters",
+        data: json!({ "class": class, "minters": minters, "memo": memo }),
+    });
+}
+
+/// `minters`: list of accounts which were actually removed (unknown minters are skipped).
+pub(crate) fn emit_remove_minters(class: ClassId, minters: Vec<AccountId>, memo: Option<String>) {
+    emit_co_event(EventPayload {
+        event: "remove_minters",
+        data: json!({ "class": class, "minters": minters, "memo": memo }),
+    });
+}
+
+/// `admins`: list of accounts which were actually added (existing admins are skipped).
+pub(crate) fn emit_add_class_admins(class: ClassId, admins: Vec<AccountId>, memo: Option<String>) {
+    emit_co_event(EventPayload {
+        event: "add_class_admins",
+        data: json!({ "class": class, "admins": admins, "memo": memo }),
+    });
+}
+
+pub(crate) fn emit_remove_class_admin(class: ClassId, admin: AccountId, memo: Option<String>) {
+    emit_co_event(EventPayload {
+        event: "remove_class_admin",
+        data: json!({ "class": class, "admin": admin, "memo": memo }),
+    });
+}
+
+/// `from`: previous class owner, `to`: new class owner (and the only class admin).
+pub(crate) fn emit_transfer_class_ownership(
+    class: ClassId,
+    from: AccountId,
+    to: AccountId,
+    memo: Option<String>,
+) {
+    emit_co_event(EventPayload {
+        event: "transfer_class_ownership",
+        data: json!({ "class": class, "from": from, "to": to, "memo": memo }),
+    });
+}
+
+pub(crate) fn emit_set_requires_iah(class: ClassId, requires_iah: bool) {
+    emit_co_event(EventPayload {
+        event: "set_requires_iah",
+        data: json!({ "class": class, "requires_iah": requires_iah }),
+    });
+}
+
+/// `min_ttl`: new min TTL in milliseconds.
+pub(crate) fn emit_set_min_ttl(class: ClassId, min_ttl: u64) {
+    emit_co_event(EventPayload {
+        event: "set_min_ttl",
+        data: json!({ "class": class, "min_ttl": min_ttl }),
+    });
+}
+
+/// `max_ttl`: new max TTL in milliseconds.
+pub(crate) fn emit_set_max_ttl(class: ClassId, max_ttl: u64) {
+    emit_co_event(EventPayload {
+        event: "set_max_ttl",
+        data: json!({ "class": class, "max_ttl": max_ttl }),
+    });
+}
+
+/// `ttl` and `min_interval` are zero when self mint is disabled.
+pub(crate) fn emit_set_self_mint(class: ClassId, enabled: bool, ttl: u64, min_interval: u64) {
+    emit_co_event(EventPayload {
+        event: "set_self_mint",
+        data: json!({
+            "class": class,
+            "enabled": enabled,
+            "ttl": ttl,
+            "min_interval": min_interval,
+        }),
+    });
+}
+
+/// `admin`: class owner which released the class, `payer`: account receiving the `bond`
+/// (null if the class has no bond), `bond`: returned bond in yoctoNEAR.
+pub(crate) fn emit_class_released(
+    class: ClassId,
+    admin: AccountId,
+    payer: Option<AccountId>,
+    bond: Balance,
+) {
+    emit_co_event(EventPayload {
+        event: "class_released",
+        data: json!({ "class": class, "admin": admin, "payer": payer, "bond": U128(bond) }),
+    });
+}
+
+/// `fee` (in yoctoNEAR) and `payout` are null when the fee is removed.
+pub(crate) fn emit_set_mint_fee(class: ClassId, fee: Option<&MintFee>) {
+    emit_co_event(EventPayload {
+        event: "set_mint_fee",
+        data: json!({
+            "class": class,
+            "fee": fee.map(|f| f.fee),
+            "payout": fee.map(|f| &f.payout),
+        }),
+    });
+}
+
+pub(crate) fn emit_set_class_metadata(class: ClassId) {
+    emit_co_event(EventPayload {
+        event: "set_class_metadata",
+        data: json!({ "class": class }),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils;
+
+    use super::*;
+
+    fn acc(idx: u8) -> AccountId {
+        AccountId::new_unchecked(format!("user-{}.near", idx))
+    }
+
+    #[test]
+    fn log_class_events() {
+        let expected1 = r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"class_acquired","data":{"admin":"user-1.near","class":3,"max_ttl":100,"memo":null,"minters":["user-2.near"],"requires_iah":true}}"#;
+        emit_class_acquired(3, acc(1), true, &[acc(2)], 100, None);
+        assert_eq!(vec![expected1], test_utils::get_logs());
+
+        let expected2 = r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"add_minters","data":{"class":3,"memo":"m","minters":["user-3.near","user-4.near"]}}"#;
+        emit_add_minters(3, vec![acc(3), acc(4)], Some("m".to_owned()));
+        assert_eq!(vec![expected1, expected2], test_utils::get_logs());
+
+        let expected3 = r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"set_max_ttl","data":{"class":3,"max_ttl":200}}"#;
+        emit_set_max_ttl(3, 200);
+        assert_eq!(
+            vec![expected1, expected2, expected3],
+            test_utils::get_logs()
+        );
+
+        let expected4 = r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"class_released","data":{"admin":"user-1.near","bond":"100","class":3,"payer":"user-2.near"}}"#;
+        emit_class_released(3, acc(1), Some(acc(2)), 100);
+        assert_eq!(
+            vec![expected1, expected2, expected3, expected4],
+            test_utils::get_logs()
+        );
+
+        let expected5 = r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"transfer_class_ownership","data":{"class":3,"from":"user-1.near","memo":null,"to":"user-2.near"}}"#;
+        emit_transfer_class_ownership(3, acc(1), acc(2), None);
+        assert_eq!(
+            vec![expected1, expected2, expected3, expected4, expected5],
+            test_utils::get_logs()
+        );
+    }
+}
@@ -1,21 +1,33 @@
 use std::collections::HashMap;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap};
-use near_sdk::{env, near_bindgen, require, AccountId, PanicOnDefault, Promise, ONE_NEAR};
-
-use cost::{calculate_iah_mint_gas, calculate_mint_gas, mint_deposit};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedSet};
+use near_sdk::json_types::U128;
+use near_sdk::{
+    env, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseError,
+    ONE_NEAR,
+};
+
+use cost::{
+    calculate_iah_mint_gas, calculate_mint_gas, calculate_renew_gas, calculate_revoke_gas,
+    mint_deposit,
+};
 use sbt::*;
 
 pub use crate::errors::*;
 pub use crate::storage::*;
 
 mod errors;
+mod events;
 pub mod migrate;
 mod storage;
 
 const MIN_TTL: u64 = 86_400_000; // 24 hours in miliseconds
+/// Maximum `limit` for paginated queries.
+const MAX_LIMIT: u32 = 100;
 const MILI_NEAR: u128 = ONE_NEAR / 1000;
+const SELF_MINT_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+const MINT_FEES_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -29,6 +41,20 @@ pub struct Contract {
     pub metadata: LazyOption<ContractMetadata>,
     pub class_metadata: LookupMap<ClassId, ClassMetadata>,
     pub registration_cost: u64, // cost in milinear
+    /// map of classId -> self mint configuration. Self mint is enabled for a class only if
+    /// it has an entry in this map.
+    pub self_mint: LookupMap<ClassId, SelfMint>,
+    /// set of (class, account) pairs of accounts which already self minted a class token.
+    pub self_minted: LookupSet<(ClassId, AccountId)>,
+    /// map of classId -> bond paid in `acquire_next_class`, returned to the payer by
+    /// `release_class_bond`.
+    pub class_bonds: LookupMap<ClassId, ClassBond>,
+    /// map of classId -> fee charged to the class minters (see `set_mint_fee`).
+    pub mint_fees: LookupMap<ClassId, MintFee>,
+    /// index of all existing classes (released classes are removed).
+    pub class_ids: UnorderedSet<ClassId>,
+    /// map of admin -> classes administered by the account.
+    pub admin_classes: LookupMap<AccountId, Vec<ClassId>>,
 }
 
 // Implement the contract structure
@@ -44,6 +70,12 @@ impl Contract {
             metadata: LazyOption::new(StorageKey::ContractMetadata, Some(&metadata)),
             class_metadata: LookupMap::new(StorageKey::ClassMetadata),
             registration_cost: 100, // 0.1 Near
+            self_mint: LookupMap::new(StorageKey::SelfMint),
+            self_minted: LookupSet::new(StorageKey::SelfMinted),
+            class_bonds: LookupMap::new(StorageKey::ClassBonds),
+            mint_fees: LookupMap::new(StorageKey::MintFees),
+            class_ids: UnorderedSet::new(StorageKey::ClassIds),
+            admin_classes: LookupMap::new(StorageKey::AdminClasses),
         }
     }
 
@@ -56,6 +88,64 @@ impl Contract {
         self.classes.get(&class)
     }
 
+    /// Returns self mint configuration of the class. Returns none if self mint is not
+    /// enabled for the class.
+    pub fn self_mint(&self, class: ClassId) -> Option<SelfMint> {
+        self.self_mint.get(&class)
+    }
+
+    /// Returns the bond (in yoctoNEAR) paid for the class registration. Returns none if the
+    /// class has no bond (eg it was acquired before the bonds were introduced).
+    pub fn class_bond(&self, class: ClassId) -> Option<U128> {
+        self.class_bonds.get(&class).map(|b| U128(b.amount))
+    }
+
+    /// Returns the fee charged to the class minters. Returns none if the class has no fee.
+    pub fn mint_fee(&self, class: ClassId) -> Option<MintFee> {
+        self.mint_fees.get(&class)
+    }
+
+    /// Returns existing classes with their metadata and minting authorities. Classes are
+    /// listed in the index order (which changes when a class is released): to get all
+    /// classes, continue querying with `from_index = previous from_index + limit`, until
+    /// the returned list is shorter than `limit`.
+    /// If `limit` is not specified or is bigger than `MAX_LIMIT`, then `MAX_LIMIT` is used.
+    /// Panics if `limit == 0`.
+    pub fn classes(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u32>,
+    ) -> Vec<(ClassId, ClassMetadata, ClassMinters)> {
+        let limit = limit.unwrap_or(MAX_LIMIT);
+        require!(limit > 0, "limit must be bigger than 0");
+        let from_index = from_index.unwrap_or(0);
+        let ids = self.class_ids.as_vector();
+        let end = std::cmp::min(
+            ids.len(),
+            from_index.saturating_add(limit.min(MAX_LIMIT) as u64),
+        );
+        (from_index..end)
+            .filter_map(|i| {
+                let class = ids.get(i)?;
+                Some((
+                    class,
+                    self.class_metadata.get(&class)?,
+                    self.classes.get(&class)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns IDs of classes administered by the `account`.
+    pub fn classes_by_admin(&self, account: AccountId) -> Vec<ClassId> {
+        self.admin_classes.get(&account).unwrap_or_default()
+    }
+
+    /// Returns the deposit (in yoctoNEAR) required by `acquire_next_class`.
+    pub fn registration_cost(&self) -> U128 {
+        U128(self.registration_cost as u128 * MILI_NEAR)
+    }
+
     /// Returns registry address.
     pub fn registry(&self) -> AccountId {
         self.registry.clone()
@@ -67,7 +157,10 @@ impl Contract {
 
     /// Mints a new SBT for the given receiver.
     /// If `metadata.expires_at` is None then we set it to max: ` now+max_ttl`.
-    /// Panics if `metadata.expires_at > now+max_ttl` or when ClassID is not set or not 1.
+    /// Returns `WrongExpiresAt` error if `metadata.expires_at` is not in the future or
+    /// `metadata.expires_at > now+max_ttl`, and `ExpiresAtTooEarly` error if
+    /// `metadata.expires_at < now+min_ttl`.
+    /// Panics when ClassID is not set or not 1.
     #[payable]
     #[handle_result]
     pub fn sbt_mint(
@@ -83,6 +176,12 @@ impl Contract {
     /// Similar to `sbt_mint`, but allows to mint many tokens at once. See `sbt_mint` doc for
     /// more details.
     /// * `tokens` is list of pairs: token recipient and token metadata to mint for given recipient.
+    ///   Each metadata can have a custom `expires_at`, bounded by the class `min_ttl` and
+    ///   `max_ttl`.
+    /// Minters (other than the class admins) must additionally attach the class `mint_fee`
+    /// for every token. The fees are forwarded to the class payout accounts once the tokens
+    /// are minted, or returned to the minter if the registry mint fails (then an empty list
+    /// of tokens is returned).
     #[payable]
     #[handle_result]
     pub fn sbt_mint_many(
@@ -92,13 +191,15 @@ impl Contract {
     ) -> Result<Promise, Error> {
         let now_ms = env::block_timestamp_ms();
         let mut requires_iah = false;
-        let mut class_info_map: HashMap<ClassId, (bool, u64)> = HashMap::new();
+        let mut class_info_map: HashMap<ClassId, (bool, u64, u64)> = HashMap::new();
+        let mut class_fees: HashMap<ClassId, Option<MintFee>> = HashMap::new();
+        let mut fees: HashMap<AccountId, Balance> = HashMap::new();
         let mut total_len = 0;
         for (_, token_metadatas) in &mut token_spec {
             total_len += token_metadatas.len();
             for m in token_metadatas {
-                let (cls_requires_iah, ttl) = match class_info_map.get(&m.class) {
-                    Some(ci) => (ci.0, ci.1),
+                let (cls_requires_iah, min_ttl, max_ttl) = match class_info_map.get(&m.class) {
+                    Some(ci) => *ci,
                     None => {
                         let ci = self.class_info_minter(m.class)?;
                         class_info_map.insert(m.class, ci);
@@ -106,12 +207,19 @@ impl Contract {
                     }
                 };
                 requires_iah = requires_iah || cls_requires_iah;
-                m.expires_at = Some(now_ms + ttl);
+                m.expires_at = Some(check_expires_at(m.expires_at, now_ms, min_ttl, max_ttl)?);
                 m.issued_at = Some(now_ms);
+                let fee = class_fees
+                    .entry(m.class)
+                    .or_insert_with(|| self.caller_mint_fee(m.class));
+                if let Some(f) = fee {
+                    *fees.entry(f.payout.clone()).or_default() += f.fee.0;
+                }
             }
         }
 
-        let required_deposit = mint_deposit(total_len);
+        let total_fees: Balance = fees.values().sum();
+        let required_deposit = mint_deposit(total_len) + total_fees;
         let attached_deposit = env::attached_deposit();
         if attached_deposit < required_deposit {
             return Err(Error::RequiredDeposit(required_deposit));
@@ -121,8 +229,8 @@ impl Contract {
             env::log_str(&format!("SBT mint memo: {}", memo));
         }
 
-        let sbt_reg =
-            ext_registry::ext(self.registry.clone()).with_attached_deposit(attached_deposit);
+        let sbt_reg = ext_registry::ext(self.registry.clone())
+            .with_attached_deposit(attached_deposit - total_fees);
         let promise = if requires_iah {
             let gas = calculate_iah_mint_gas(total_len, token_spec.len());
             sbt_reg.with_static_gas(gas).sbt_mint_iah(token_spec)
@@ -131,13 +239,132 @@ impl Contract {
                 .with_static_gas(calculate_mint_gas(total_len))
                 .sbt_mint(token_spec)
         };
+        if total_fees == 0 {
+            return Ok(promise);
+        }
+        let fees = fees.into_iter().map(|(a, f)| (a, U128(f))).collect();
+        Ok(promise.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(MINT_FEES_CALLBACK_GAS)
+                .on_mint_fees_callback(env::predecessor_account_id(), fees),
+        ))
+    }
+
+    /// Callback for `sbt_mint_many` with mint fees. Forwards the `fees` to the payout
+    /// accounts if the tokens were minted, otherwise returns them to the `minter`.
+    /// Returns the minted token IDs (empty list if the mint failed).
+    #[private]
+    pub fn on_mint_fees_callback(
+        &mut self,
+        minter: AccountId,
+        fees: Vec<(AccountId, U128)>,
+        #[callback_result] minted: Result<Vec<TokenId>, PromiseError>,
+    ) -> Vec<TokenId> {
+        match minted {
+            Ok(tokens) => {
+                for (payout, fee) in fees {
+                    Promise::new(payout).transfer(fee.0);
+                }
+                tokens
+            }
+            Err(_) => {
+                // we can't panic here in order to return the fees.
+                let total: Balance = fees.iter().map(|(_, f)| f.0).sum();
+                Promise::new(minter).transfer(total);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Mints a token of the given `class` to the caller. Self mint must be enabled for the
+    /// class (see `set_self_mint`). The mint is routed through `registry.sbt_mint_iah`, so
+    /// the caller must be a verified human. Every account can self mint at most one token
+    /// of a class.
+    /// Must attach `cost.mint_deposit(1)` deposit. The deposit is returned if the mint fails.
+    #[payable]
+    #[handle_result]
+    pub fn sbt_self_mint(
+        &mut self,
+        class: ClassId,
+        memo: Option<String>,
+    ) -> Result<Promise, Error> {
+        let mut sm = match self.self_mint.get(&class) {
+            None => return Err(Error::SelfMintDisabled),
+            Some(sm) => sm,
+        };
+        let caller = env::predecessor_account_id();
+        let now_ms = env::block_timestamp_ms();
+        let next_mint = sm.last_mint + sm.min_interval;
+        if sm.last_mint > 0 && next_mint > now_ms {
+            return Err(Error::RateLimited(next_mint));
+        }
+        let required_deposit = mint_deposit(1);
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < required_deposit {
+            return Err(Error::RequiredDeposit(required_deposit));
+        }
+        if !self.self_minted.insert(&(class, caller.clone())) {
+            return Err(Error::AlreadyMinted);
+        }
+        let prev_mint = sm.last_mint;
+        sm.last_mint = now_ms;
+        self.self_mint.insert(&class, &sm);
 
+        if let Some(memo) = memo {
+            env::log_str(&format!("SBT self mint memo: {}", memo));
+        }
+
+        let metadata = TokenMetadata {
+            class,
+            issued_at: Some(now_ms),
+            expires_at: Some(now_ms + sm.ttl),
+            reference: None,
+            reference_hash: None,
+        };
+        let promise = ext_registry::ext(self.registry.clone())
+            .with_attached_deposit(attached_deposit)
+            .with_static_gas(calculate_iah_mint_gas(1, 1))
+            .sbt_mint_iah(vec![(caller.clone(), vec![metadata])])
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(SELF_MINT_CALLBACK_GAS)
+                    .on_self_mint_callback(class, caller, U128(attached_deposit), prev_mint),
+            );
         Ok(promise)
     }
 
+    /// Callback for `sbt_self_mint`. If the registry mint failed, allows the caller to self
+    /// mint again, restores the class `last_mint` to `prev_mint` (so failed mints don't
+    /// rate limit the class) and returns the deposit. Returns the minted token ID or None if
+    /// the mint failed.
+    #[private]
+    pub fn on_self_mint_callback(
+        &mut self,
+        class: ClassId,
+        caller: AccountId,
+        deposit: U128,
+        prev_mint: u64,
+        #[callback_result] minted: Result<Vec<TokenId>, PromiseError>,
+    ) -> Option<TokenId> {
+        match minted {
+            Ok(tokens) => Some(tokens[0]),
+            Err(_) => {
+                // we can't panic here in order to preserve the state change.
+                self.self_minted.remove(&(class, caller.clone()));
+                if let Some(mut sm) = self.self_mint.get(&class) {
+                    sm.last_mint = prev_mint;
+                    self.self_mint.insert(&class, &sm);
+                }
+                Promise::new(caller).transfer(deposit.0);
+                None
+            }
+        }
+    }
+
     /// Updates the expire time of provided tokens.
     /// `ttl` is duration in milliseconds to set expire time: `now+ttl`.
-    /// Panics if `ttl > self.minters[class].max_ttl` or ttl < `MIN_TTL` or `tokens` is an empty list.
+    /// Panics if `ttl > self.minters[class].max_ttl` or `ttl < self.minters[class].min_ttl`
+    /// or ttl < `MIN_TTL` or `tokens` is an empty list.
     /// Only minters are allowed to renew the tokens.
     pub fn sbt_renew(&mut self, tokens: Vec<TokenId>, ttl: u64, memo: Option<String>) -> Promise {
         require!(!tokens.is_empty(), "tokens must be a non empty list");
@@ -160,30 +387,31 @@ impl Contract {
         #[callback_result] token_classes: Result<Vec<Option<ClassId>>, near_sdk::PromiseError>,
     ) -> Promise {
         let ts = token_classes.expect("error while retrieving tokens data from registry");
-        let mut cached_class_info: HashMap<u64, (Vec<AccountId>, u64)> = HashMap::new();
+        let mut cached_class_info: HashMap<u64, (Vec<AccountId>, u64, u64)> = HashMap::new();
         for token_class in ts {
-            let max_ttl: u64;
+            let (min_ttl, max_ttl): (u64, u64);
             let class_id: u64 = token_class.expect("token not found");
-            if let Some((cached_minters, cached_ttl)) = cached_class_info.get(&class_id) {
-                max_ttl = *cached_ttl;
+            if let Some((cached_minters, cached_min, cached_max)) = cached_class_info.get(&class_id)
+            {
+                (min_ttl, max_ttl) = (*cached_min, *cached_max);
                 self.assert_minter(caller, cached_minters);
             } else {
-                max_ttl = self.get_ttl(class_id);
-                let minters = self
-                    .class_minter(class_id)
-                    .expect("class not found")
-                    .minters;
-                self.assert_minter(caller, &minters);
-                cached_class_info.insert(class_id, (minters, max_ttl));
+                let c = self.class_minter(class_id).expect("class not found");
+                (min_ttl, max_ttl) = (c.min_ttl, c.max_ttl);
+                self.assert_minter(caller, &c.minters);
+                cached_class_info.insert(class_id, (c.minters, min_ttl, max_ttl));
             }
             self.assert_ttl(ttl, max_ttl);
+            self.assert_min_ttl(ttl, min_ttl);
         }
         if let Some(memo) = memo {
             env::log_str(&format!("SBT renew memo: {}", memo));
         }
 
         let expires_at_ms = env::block_timestamp_ms() + ttl;
-        ext_registry::ext(self.registry.clone()).sbt_renew(tokens, expires_at_ms)
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_renew_gas(tokens.len()))
+            .sbt_renew(tokens, expires_at_ms)
     }
 
     /// Revokes list of tokens. If `burn==true`, the tokens are burned (removed). Otherwise,
@@ -233,7 +461,9 @@ impl Contract {
         if let Some(memo) = memo {
             env::log_str(&format!("SBT revoke memo: {}", memo));
         }
-        ext_registry::ext(self.registry.clone()).sbt_revoke(tokens, burn)
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_revoke_gas(tokens.len(), burn))
+            .sbt_revoke(tokens, burn)
     }
 
     /// Admin: remove SBT from the given accounts.
@@ -266,16 +496,74 @@ impl Contract {
         if c.requires_iah != requires_iah {
             c.requires_iah = requires_iah;
             self.classes.insert(&class, &c);
+            events::emit_set_requires_iah(class, requires_iah);
         }
         Ok(())
     }
 
     /// Allows admin to change Max TTL, expected time duration in miliseconds.
+    /// Panics if `max_ttl` is smaller than the class `min_ttl`.
     #[handle_result]
     pub fn set_max_ttl(&mut self, class: ClassId, max_ttl: u64) -> Result<(), Error> {
         let mut c = self.class_info_admin(class)?;
+        self.assert_min_ttl(max_ttl, c.min_ttl);
         c.max_ttl = max_ttl;
         self.classes.insert(&class, &c);
+        events::emit_set_max_ttl(class, max_ttl);
+        Ok(())
+    }
+
+    /// Allows admin to change Min TTL, expected time duration in miliseconds: minters can't
+    /// mint nor renew tokens expiring before `now + min_ttl`. Zero disables the minimum.
+    /// Panics if `min_ttl` is bigger than the class `max_ttl`.
+    #[handle_result]
+    pub fn set_min_ttl(&mut self, class: ClassId, min_ttl: u64) -> Result<(), Error> {
+        let mut c = self.class_info_admin(class)?;
+        self.assert_ttl(min_ttl, c.max_ttl);
+        c.min_ttl = min_ttl;
+        self.classes.insert(&class, &c);
+        events::emit_set_min_ttl(class, min_ttl);
+        Ok(())
+    }
+
+    /// Allows admin to enable or disable self minting of the class: when enabled, any
+    /// verified human can mint a token of that class to themselves (see `sbt_self_mint`).
+    /// * `ttl`: time to live in ms of self minted tokens, must be between `MIN_TTL` (and the
+    ///   class `min_ttl`) and the class `max_ttl`.
+    /// * `min_interval`: minimum time in ms between two consecutive self mints. Zero means
+    ///   no limit.
+    /// `ttl` and `min_interval` are ignored when `enabled == false`.
+    #[handle_result]
+    pub fn set_self_mint(
+        &mut self,
+        class: ClassId,
+        enabled: bool,
+        ttl: u64,
+        min_interval: u64,
+    ) -> Result<(), Error> {
+        let c = self.class_info_admin(class)?;
+        if !enabled {
+            if self.self_mint.remove(&class).is_some() {
+                events::emit_set_self_mint(class, false, 0, 0);
+            }
+            return Ok(());
+        }
+        require!(
+            MIN_TTL <= ttl,
+            format!("ttl must be at least {}ms", MIN_TTL)
+        );
+        self.assert_ttl(ttl, c.max_ttl);
+        self.assert_min_ttl(ttl, c.min_ttl);
+        let last_mint = self.self_mint.get(&class).map_or(0, |sm| sm.last_mint);
+        self.self_mint.insert(
+            &class,
+            &SelfMint {
+                ttl,
+                min_interval,
+                last_mint,
+            },
+        );
+        events::emit_set_self_mint(class, true, ttl, min_interval);
         Ok(())
     }
 
@@ -289,12 +577,15 @@ impl Contract {
     ) -> Result<(), Error> {
         self.class_info_admin(class)?;
         self.class_metadata.insert(&class, &metadata);
+        events::emit_set_class_metadata(class);
         Ok(())
     }
 
     /// Acquires a new, unused class and authorizes minter to issue SBTs of that class.
-    /// Caller will become an admin of the class.
-    /// Must attach at least REGISTRATION_COST yNEAR to cover storage and bond cost.
+    /// Caller will become the owner and an admin of the class.
+    /// Must attach at least `registration_cost` yNEAR to cover storage and bond cost. The
+    /// whole deposit is kept as the class bond, returned to the caller by
+    /// `release_class_bond`.
     /// Returns the new class ID.
     #[payable]
     pub fn acquire_next_class(
@@ -303,34 +594,110 @@ impl Contract {
         minters: Vec<AccountId>,
         max_ttl: u64,
         metadata: ClassMetadata,
-        #[allow(unused_variables)] memo: Option<String>,
+        memo: Option<String>,
     ) -> ClassId {
         require!(
             MIN_TTL <= max_ttl,
             format!("ttl must be at least {}ms", MIN_TTL)
         );
+        let bond = env::attached_deposit();
+        let required_deposit = self.registration_cost as u128 * MILI_NEAR;
         require!(
-            MIN_TTL <= max_ttl,
-            format!(
-                "deposit must be at least {}yNEAR",
-                self.registration_cost as u128 * MILI_NEAR
-            )
+            bond >= required_deposit,
+            format!("deposit must be at least {}yNEAR", required_deposit)
         );
         let cls = self.next_class;
         self.next_class += 1;
+        let admin = env::predecessor_account_id();
+        events::emit_class_acquired(cls, admin.clone(), requires_iah, &minters, max_ttl, memo);
+        self.class_ids.insert(&cls);
+        self.index_admin_class(&admin, cls);
         self.classes.insert(
             &cls,
             &ClassMinters {
-                admins: vec![env::predecessor_account_id()],
+                admins: vec![admin.clone()],
                 requires_iah,
                 minters,
                 max_ttl,
+                min_ttl: 0,
+                owner: admin,
             },
         );
         self.class_metadata.insert(&cls, &metadata);
+        self.class_bonds.insert(
+            &cls,
+            &ClassBond {
+                payer: env::predecessor_account_id(),
+                amount: bond,
+            },
+        );
         cls
     }
 
+    /// Sets the `fee` (in yoctoNEAR) which minters (other than the class admins) must attach
+    /// for every minted token of the `class`, forwarded to the `payout` account (the caller
+    /// by default). `None` removes the fee.
+    /// Must be called by a class admin. Returns `ZeroMintFee` error if `fee` is zero.
+    #[handle_result]
+    pub fn set_mint_fee(
+        &mut self,
+        class: ClassId,
+        fee: Option<U128>,
+        payout: Option<AccountId>,
+    ) -> Result<(), Error> {
+        self.class_info_admin(class)?;
+        let mint_fee = match fee {
+            None => {
+                self.mint_fees.remove(&class);
+                None
+            }
+            Some(fee) => {
+                if fee.0 == 0 {
+                    return Err(Error::ZeroMintFee);
+                }
+                let mf = MintFee {
+                    fee,
+                    payout: payout.unwrap_or_else(env::predecessor_account_id),
+                };
+                self.mint_fees.insert(&class, &mf);
+                Some(mf)
+            }
+        };
+        events::emit_set_mint_fee(class, mint_fee.as_ref());
+        Ok(())
+    }
+
+    /// Deletes the class (minters, settings and metadata) and returns the class bond to the
+    /// account which paid it (or received it with `transfer_class_ownership`). Already minted
+    /// tokens stay in the registry, but they can't be renewed nor revoked through this
+    /// contract anymore. The class ID is not reused.
+    /// Must be called by the class owner. Returns the released bond.
+    #[handle_result]
+    pub fn release_class_bond(&mut self, class: ClassId) -> Result<U128, Error> {
+        let c = self.class_info_owner(class)?;
+        for a in &c.admins {
+            self.unindex_admin_class(a, class);
+        }
+        self.class_ids.remove(&class);
+        self.classes.remove(&class);
+        self.class_metadata.remove(&class);
+        self.self_mint.remove(&class);
+        self.mint_fees.remove(&class);
+        let admin = env::predecessor_account_id();
+        let bond = match self.class_bonds.remove(&class) {
+            Some(b) => {
+                Promise::new(b.payer.clone()).transfer(b.amount);
+                events::emit_class_released(class, admin, Some(b.payer), b.amount);
+                b.amount
+            }
+            None => {
+                events::emit_class_released(class, admin, None, 0);
+                0
+            }
+        };
+        Ok(U128(bond))
+    }
+
     /// Admin: authorize `minter` to mint tokens of a `class`.
     /// Must be called by a class admin, panics otherwise.
     #[handle_result]
@@ -338,18 +705,19 @@ impl Contract {
         &mut self,
         class: ClassId,
         minters: Vec<AccountId>,
-        #[allow(unused_variables)] memo: Option<String>,
+        memo: Option<String>,
     ) -> Result<(), Error> {
         let mut c = self.class_info_admin(class)?;
-        let mut ok = false;
+        let mut added = Vec::new();
         for m in minters {
             if !c.minters.contains(&m) {
-                c.minters.push(m);
-                ok = true;
+                c.minters.push(m.clone());
+                added.push(m);
             }
         }
-        if ok {
+        if !added.is_empty() {
             self.classes.insert(&class, &c);
+            events::emit_add_minters(class, added, memo);
         }
         Ok(())
     }
@@ -361,22 +729,108 @@ impl Contract {
         &mut self,
         class: ClassId,
         minters: Vec<AccountId>,
-        #[allow(unused_variables)] memo: Option<String>,
+        memo: Option<String>,
     ) -> Result<(), Error> {
         let mut c = self.class_info_admin(class)?;
-        let mut ok = false;
+        let mut removed = Vec::new();
         for m in minters {
             if let Some(idx) = c.minters.iter().position(|x| x == &m) {
                 c.minters.swap_remove(idx);
-                ok = true;
+                removed.push(m);
             }
         }
-        if ok {
+        if !removed.is_empty() {
+            self.classes.insert(&class, &c);
+            events::emit_remove_minters(class, removed, memo);
+        }
+        Ok(())
+    }
+
+    /// Adds `admins` to the `class` admins. Admins can manage the class settings, minters
+    /// and admins.
+    /// Must be called by a class admin.
+    #[handle_result]
+    pub fn add_class_admins(
+        &mut self,
+        class: ClassId,
+        admins: Vec<AccountId>,
+        memo: Option<String>,
+    ) -> Result<(), Error> {
+        let mut c = self.class_info_admin(class)?;
+        let mut added = Vec::new();
+        for a in admins {
+            if !c.admins.contains(&a) {
+                self.index_admin_class(&a, class);
+                c.admins.push(a.clone());
+                added.push(a);
+            }
+        }
+        if !added.is_empty() {
+            self.classes.insert(&class, &c);
+            events::emit_add_class_admins(class, added, memo);
+        }
+        Ok(())
+    }
+
+    /// Removes `admin` from the `class` admins.
+    /// Must be called by the class owner, or by the `admin` itself. Returns `RemoveOwner`
+    /// error if the `admin` is the class owner.
+    #[handle_result]
+    pub fn remove_class_admin(
+        &mut self,
+        class: ClassId,
+        admin: AccountId,
+        memo: Option<String>,
+    ) -> Result<(), Error> {
+        let mut c = if admin == env::predecessor_account_id() {
+            self.class_info_admin(class)?
+        } else {
+            self.class_info_owner(class)?
+        };
+        if admin == c.owner {
+            return Err(Error::RemoveOwner);
+        }
+        if let Some(idx) = c.admins.iter().position(|x| x == &admin) {
+            c.admins.swap_remove(idx);
+            self.unindex_admin_class(&admin, class);
             self.classes.insert(&class, &c);
+            events::emit_remove_class_admin(class, admin, memo);
         }
         Ok(())
     }
 
+    /// Transfers the `class` ownership: `new_owner` becomes the class owner and the only
+    /// class admin (all other admins are removed). The class bond is transferred together
+    /// with the class: it will be returned to `new_owner`.
+    /// Must be called by the class owner.
+    #[handle_result]
+    pub fn transfer_class_ownership(
+        &mut self,
+        class: ClassId,
+        new_owner: AccountId,
+        memo: Option<String>,
+    ) -> Result<(), Error> {
+        let mut c = self.class_info_owner(class)?;
+        for a in &c.admins {
+            self.unindex_admin_class(a, class);
+        }
+        self.index_admin_class(&new_owner, class);
+        c.admins = vec![new_owner.clone()];
+        c.owner = new_owner.clone();
+        self.classes.insert(&class, &c);
+        if let Some(mut b) = self.class_bonds.get(&class) {
+            b.payer = new_owner.clone();
+            self.class_bonds.insert(&class, &b);
+        }
+        events::emit_transfer_class_ownership(
+            class,
+            env::predecessor_account_id(),
+            new_owner,
+            memo,
+        );
+        Ok(())
+    }
+
     /// admin: updates this SBT ContractMetadata
     /// Must be called by a contract admin, panics otherwise.
     #[handle_result]
@@ -403,15 +857,29 @@ impl Contract {
         }
     }
 
-    /// Returns (requires_iah, max_ttl).
+    /// Returns error if class is not found or not called by the class owner.
+    fn class_info_owner(&self, class: ClassId) -> Result<ClassMinters, Error> {
+        match self.class_minter(class) {
+            None => Err(Error::ClassNotFound),
+            Some(cm) => {
+                if cm.owner == env::predecessor_account_id() {
+                    Ok(cm)
+                } else {
+                    Err(Error::NotOwner)
+                }
+            }
+        }
+    }
+
+    /// Returns (requires_iah, min_ttl, max_ttl).
     /// Returns error if class is not found or not called by a minter nor an admin.
-    fn class_info_minter(&self, class: ClassId) -> Result<(bool, u64), Error> {
+    fn class_info_minter(&self, class: ClassId) -> Result<(bool, u64, u64), Error> {
         match self.class_minter(class) {
             None => Err(Error::ClassNotFound),
             Some(cm) => {
                 let a = &env::predecessor_account_id();
                 if cm.minters.contains(a) || cm.admins.contains(a) {
-                    Ok((cm.requires_iah, cm.max_ttl))
+                    Ok((cm.requires_iah, cm.min_ttl, cm.max_ttl))
                 } else {
                     Err(Error::NotMinter)
                 }
@@ -419,11 +887,39 @@ impl Contract {
         }
     }
 
-    /// returns ttl for a given token class
-    fn get_ttl(&self, class: ClassId) -> u64 {
-        match self.class_minter(class) {
-            None => panic!("class not found"),
-            Some(cm) => cm.max_ttl,
+    /// Adds the `class` to the `admin` classes index.
+    fn index_admin_class(&mut self, admin: &AccountId, class: ClassId) {
+        let mut classes = self.admin_classes.get(admin).unwrap_or_default();
+        if !classes.contains(&class) {
+            classes.push(class);
+            self.admin_classes.insert(admin, &classes);
+        }
+    }
+
+    /// Removes the `class` from the `admin` classes index.
+    fn unindex_admin_class(&mut self, admin: &AccountId, class: ClassId) {
+        let mut classes = self.admin_classes.get(admin).unwrap_or_default();
+        if let Some(idx) = classes.iter().position(|c| *c == class) {
+            classes.remove(idx);
+            if classes.is_empty() {
+                self.admin_classes.remove(admin);
+            } else {
+                self.admin_classes.insert(admin, &classes);
+            }
+        }
+    }
+
+    /// Returns the mint fee of the `class` which must be paid by the caller: class admins
+    /// don't pay the fee.
+    fn caller_mint_fee(&self, class: ClassId) -> Option<MintFee> {
+        let fee = self.mint_fees.get(&class)?;
+        let is_admin = self
+            .class_minter(class)
+            .map_or(false, |c| c.admins.contains(&env::predecessor_account_id()));
+        if is_admin {
+            None
+        } else {
+            Some(fee)
         }
     }
 
@@ -434,6 +930,13 @@ impl Contract {
         );
     }
 
+    fn assert_min_ttl(&self, ttl: u64, min_ttl: u64) {
+        require!(
+            ttl >= min_ttl,
+            format!("ttl must be bigger or equal than {}ms", min_ttl)
+        );
+    }
+
     fn assert_minter(&self, caller: &AccountId, minters: &Vec<AccountId>) {
         require!(minters.contains(caller), "caller must be a minter");
     }
@@ -455,17 +958,24 @@ impl SBTIssuer for Contract {
 mod tests {
     use cost::mint_deposit;
     use near_sdk::{
+        collections::{LazyOption, LookupMap},
+        env,
+        json_types::U128,
+        mock::VmAction,
         test_utils::{
+            self,
             test_env::{alice, bob, carol},
             VMContextBuilder,
         },
-        testing_env, AccountId, Balance, VMContext,
+        testing_env, AccountId, Balance, PromiseError, VMContext,
     };
     use sbt::{ClassId, ClassMetadata, ContractMetadata, SBTIssuer, TokenMetadata};
 
-    use crate::{ClassMinters, Contract, Error, MIN_TTL};
+    use crate::migrate::{OldClassMinters, OldContract};
+    use crate::{ClassMinters, Contract, Error, MintFee, SelfMint, StorageKey, MILI_NEAR, MIN_TTL};
 
     const START: u64 = 10;
+    const MSECOND: u64 = 1_000_000;
 
     fn registry() -> AccountId {
         AccountId::new_unchecked("registry.near".to_string())
@@ -497,6 +1007,8 @@ mod tests {
             requires_iah,
             minters,
             max_ttl,
+            min_ttl: 0,
+            owner: admin(),
         }
     }
 
@@ -519,6 +1031,8 @@ mod tests {
         ctx.attached_deposit = deposit.unwrap_or(mint_deposit(1));
         testing_env!(ctx.clone());
         let mut ctr = Contract::new(registry(), contract_metadata());
+        // registration cost is tested in `acquire_next_class`
+        ctr.registration_cost = 0;
         let c = ctr.acquire_next_class(true, vec![auth(1)], MIN_TTL, class_metadata(1), None);
         assert_eq!(c, 1);
         ctx.predecessor_account_id = predecessor.clone();
@@ -564,6 +1078,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn class_lifecycle_events() -> Result<(), Error> {
+        let (ctx, mut ctr) = setup(&admin(), None);
+        testing_env!(ctx);
+
+        ctr.add_minters(1, vec![auth(1), auth(2)], Some("new".to_owned()))?;
+        ctr.remove_minters(1, vec![auth(3)], None)?; // unknown minter: no event
+        ctr.set_requires_iah(1, true)?; // no change: no event
+        ctr.set_requires_iah(1, false)?;
+        ctr.set_max_ttl(1, MIN_TTL * 2)?;
+        assert_eq!(
+            test_utils::get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"add_minters","data":{"class":1,"memo":"new","minters":["authority2.near"]}}"#,
+                r#"EVENT_JSON:{"standard":"community_open","version":"1.0.0","event":"set_requires_iah","data":{"class":1,"requires_iah":false}}"#,
+                &format!(
+                    r#"EVENT_JSON:{{"standard":"community_open","version":"1.0.0","event":"set_max_ttl","data":{{"class":1,"max_ttl":{}}}}}"#,
+                    MIN_TTL * 2
+                ),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn add_minter() -> Result<(), Error> {
         let (mut ctx, mut ctr) = setup(&admin(), None);
@@ -786,6 +1324,423 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mint_min_ttl() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.set_min_ttl(1, 100)?;
+        assert_eq!(ctr.class_minter(1).unwrap().min_ttl, 100);
+        ctx.predecessor_account_id = auth(1);
+        testing_env!(ctx);
+
+        // now_ms = 0 (block timestamp is START ns)
+        let mut m = mk_meteadata(1);
+        m.expires_at = Some(99);
+        match ctr.sbt_mint(alice(), m.clone(), None) {
+            Err(Error::ExpiresAtTooEarly(100)) => (),
+            Ok(_) => panic!("expected ExpiresAtTooEarly, got: Ok"),
+            Err(x) => panic!("expected ExpiresAtTooEarly, got: {:?}", x),
+        };
+
+        m.expires_at = Some(100);
+        ctr.sbt_mint(alice(), m.clone(), None)?;
+        m.expires_at = None;
+        ctr.sbt_mint(bob(), m, None)?;
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "ttl must be smaller or equal than 86400000ms")]
+    fn set_min_ttl_above_max() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.set_min_ttl(1, MIN_TTL + 1).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "ttl must be bigger or equal than 100ms")]
+    fn set_max_ttl_below_min() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.set_min_ttl(1, 100).unwrap();
+        ctr.set_max_ttl(1, 99).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "ttl must be bigger or equal than 10ms")]
+    fn assert_min_ttl() {
+        let (_, ctr) = setup(&admin(), None);
+        ctr.assert_min_ttl(1, 10);
+    }
+
+    #[test]
+    fn class_admins() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.add_class_admins(1, vec![alice(), admin(), bob()], None)?;
+        assert_eq!(
+            ctr.class_minter(1).unwrap().admins,
+            vec![admin(), alice(), bob()]
+        );
+        assert_eq!(test_utils::get_logs().len(), 1);
+
+        // only the owner can remove other admins, transfer the class and release the bond
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.remove_class_admin(1, admin(), None),
+            Err(Error::NotOwner)
+        );
+        assert_eq!(ctr.remove_class_admin(1, bob(), None), Err(Error::NotOwner));
+        assert_eq!(
+            ctr.transfer_class_ownership(1, alice(), None),
+            Err(Error::NotOwner)
+        );
+        assert_eq!(ctr.release_class_bond(1), Err(Error::NotOwner));
+        // an admin can remove itself
+        ctr.remove_class_admin(1, alice(), Some("leave".to_owned()))?;
+        assert_eq!(ctr.class_minter(1).unwrap().admins, vec![admin(), bob()]);
+        // alice is not an admin anymore
+        assert_eq!(
+            ctr.add_class_admins(1, vec![carol()], None),
+            Err(Error::NotAdmin)
+        );
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.remove_class_admin(1, admin(), None),
+            Err(Error::RemoveOwner)
+        );
+        ctr.remove_class_admin(1, bob(), Some("rotate".to_owned()))?;
+        assert_eq!(ctr.class_minter(1).unwrap().admins, vec![admin()]);
+        ctr.add_class_admins(1, vec![alice()], None)?;
+        ctr.transfer_class_ownership(1, carol(), None)?;
+        let c = ctr.class_minter(1).unwrap();
+        assert_eq!(c.admins, vec![carol()]);
+        assert_eq!(c.owner, carol());
+        assert_eq!(
+            ctr.transfer_class_ownership(1, bob(), None),
+            Err(Error::NotOwner)
+        );
+
+        // the bond goes with the class
+        ctx.predecessor_account_id = carol();
+        testing_env!(ctx);
+        assert_eq!(ctr.release_class_bond(1)?, U128(mint_deposit(1)));
+        assert_eq!(test_utils::get_created_receipts()[0].receiver_id, carol());
+        Ok(())
+    }
+
+    #[test]
+    fn acquire_next_class() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&alice(), None);
+        assert_eq!(ctr.class_bond(1), Some(U128(mint_deposit(1))));
+        ctr.registration_cost = 100;
+        assert_eq!(ctr.registration_cost(), U128(100 * MILI_NEAR));
+
+        ctx.attached_deposit = 101 * MILI_NEAR;
+        testing_env!(ctx.clone());
+        let cls = ctr.acquire_next_class(false, vec![auth(2)], MIN_TTL, class_metadata(2), None);
+        assert_eq!(ctr.class_bond(cls), Some(U128(101 * MILI_NEAR)));
+
+        // only the class owner can release the bond
+        ctx.predecessor_account_id = alice();
+        ctx.attached_deposit = 0;
+        testing_env!(ctx.clone());
+        ctr.add_class_admins(cls, vec![bob()], None)?;
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.release_class_bond(cls), Err(Error::NotOwner));
+
+        // the bond is returned to the account which paid it
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.release_class_bond(cls)?, U128(101 * MILI_NEAR));
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, alice());
+        match &receipts[0].actions[0] {
+            VmAction::Transfer { deposit } => assert_eq!(*deposit, 101 * MILI_NEAR),
+            a => panic!("expected transfer, got {:?}", a),
+        }
+        assert_eq!(ctr.class_minter(cls), None);
+        assert_eq!(ctr.sbt_class_metadata(cls), None);
+        assert_eq!(ctr.class_bond(cls), None);
+        assert_eq!(ctr.release_class_bond(cls), Err(Error::ClassNotFound));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "deposit must be at least 100000000000000000000000yNEAR")]
+    fn acquire_next_class_no_deposit() {
+        let (mut ctx, mut ctr) = setup(&alice(), None);
+        ctr.registration_cost = 100;
+        ctx.attached_deposit = 100 * MILI_NEAR - 1;
+        testing_env!(ctx);
+        ctr.acquire_next_class(false, vec![auth(2)], MIN_TTL, class_metadata(2), None);
+    }
+
+    #[test]
+    fn migrate_class_minters() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut classes = LookupMap::new(StorageKey::MintingAuthority);
+        classes.insert(
+            &1,
+            &OldClassMinters {
+                requires_iah: true,
+                admins: vec![admin()],
+                minters: vec![auth(1)],
+                max_ttl: MIN_TTL,
+            },
+        );
+        env::state_write(&OldContract {
+            classes,
+            next_class: 3,
+            registry: registry(),
+            metadata: LazyOption::new(StorageKey::ContractMetadata, Some(&contract_metadata())),
+            class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            registration_cost: 100,
+        });
+
+        let ctr = Contract::migrate();
+        // the first admin becomes the owner
+        assert_eq!(
+            ctr.class_minter(1),
+            Some(class_minter(true, vec![auth(1)], MIN_TTL))
+        );
+        assert_eq!(ctr.class_minter(2), None);
+        assert_eq!(ctr.class_ids.to_vec(), vec![1]);
+        assert_eq!(ctr.admin_classes.get(&admin()), Some(vec![1]));
+    }
+
+    #[test]
+    fn self_mint() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&admin(), Some(mint_deposit(1)));
+        assert_eq!(ctr.self_mint(1), None);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        match ctr.sbt_self_mint(1, None) {
+            Err(Error::SelfMintDisabled) => (),
+            Ok(_) => panic!("expected SelfMintDisabled, got: Ok"),
+            Err(x) => panic!("expected SelfMintDisabled, got: {:?}", x),
+        };
+        assert_eq!(ctr.set_self_mint(1, true, MIN_TTL, 0), Err(Error::NotAdmin));
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.set_self_mint(1, true, MIN_TTL, 100)?;
+        let sm = SelfMint {
+            ttl: MIN_TTL,
+            min_interval: 100,
+            last_mint: 0,
+        };
+        assert_eq!(ctr.self_mint(1), Some(sm));
+
+        ctx.predecessor_account_id = alice();
+        ctx.attached_deposit = mint_deposit(1) - 1;
+        testing_env!(ctx.clone());
+        match ctr.sbt_self_mint(1, None) {
+            Err(Error::RequiredDeposit(_)) => (),
+            Ok(_) => panic!("expected RequiredDeposit, got: Ok"),
+            Err(x) => panic!("expected RequiredDeposit, got: {:?}", x),
+        };
+
+        ctx.attached_deposit = mint_deposit(1);
+        ctx.block_timestamp = 200 * MSECOND;
+        testing_env!(ctx.clone());
+        ctr.sbt_self_mint(1, None)?;
+        assert_eq!(ctr.self_mint(1).unwrap().last_mint, 200);
+
+        // rate limit
+        ctx.predecessor_account_id = bob();
+        ctx.block_timestamp = 250 * MSECOND;
+        testing_env!(ctx.clone());
+        match ctr.sbt_self_mint(1, None) {
+            Err(Error::RateLimited(300)) => (),
+            Ok(_) => panic!("expected RateLimited, got: Ok"),
+            Err(x) => panic!("expected RateLimited, got: {:?}", x),
+        };
+
+        // one mint per account
+        ctx.predecessor_account_id = alice();
+        ctx.block_timestamp = 300 * MSECOND;
+        testing_env!(ctx.clone());
+        match ctr.sbt_self_mint(1, None) {
+            Err(Error::AlreadyMinted) => (),
+            Ok(_) => panic!("expected AlreadyMinted, got: Ok"),
+            Err(x) => panic!("expected AlreadyMinted, got: {:?}", x),
+        };
+
+        // failed registry mint allows to self mint again and doesn't rate limit the class
+        ctr.on_self_mint_callback(
+            1,
+            alice(),
+            U128(mint_deposit(1)),
+            200,
+            Err(PromiseError::Failed),
+        );
+        assert_eq!(ctr.self_mint(1).unwrap().last_mint, 200);
+        ctr.sbt_self_mint(1, None)?;
+        assert_eq!(ctr.self_mint(1).unwrap().last_mint, 300);
+
+        ctx.predecessor_account_id = bob();
+        ctx.block_timestamp = 400 * MSECOND;
+        testing_env!(ctx.clone());
+        ctr.sbt_self_mint(1, None)?;
+        ctr.on_self_mint_callback(
+            1,
+            bob(),
+            U128(mint_deposit(1)),
+            300,
+            Err(PromiseError::Failed),
+        );
+        assert_eq!(ctr.self_mint(1).unwrap().last_mint, 300);
+        ctr.sbt_self_mint(1, None)?;
+        assert_eq!(ctr.self_mint(1).unwrap().last_mint, 400);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx);
+        ctr.set_self_mint(1, false, 0, 0)?;
+        assert_eq!(ctr.self_mint(1), None);
+        Ok(())
+    }
+
+    #[test]
+    fn mint_fee() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&auth(1), None);
+        assert_eq!(ctr.mint_fee(1), None);
+        assert_eq!(
+            ctr.set_mint_fee(1, Some(U128(MILI_NEAR)), None),
+            Err(Error::NotAdmin)
+        );
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.set_mint_fee(1, Some(U128(0)), None),
+            Err(Error::ZeroMintFee)
+        );
+        ctr.set_mint_fee(1, Some(U128(MILI_NEAR)), Some(carol()))?;
+        let fee = MintFee {
+            fee: U128(MILI_NEAR),
+            payout: carol(),
+        };
+        assert_eq!(ctr.mint_fee(1), Some(fee));
+
+        // class admin doesn't pay the fee
+        ctr.sbt_mint_many(vec![(alice(), vec![mk_meteadata(1)])], None)?;
+        assert_eq!(test_utils::get_created_receipts().len(), 1);
+
+        ctx.predecessor_account_id = auth(1);
+        testing_env!(ctx.clone());
+        let tokens = vec![
+            (alice(), vec![mk_meteadata(1)]),
+            (bob(), vec![mk_meteadata(1)]),
+        ];
+        let required = mint_deposit(2) + 2 * MILI_NEAR;
+        match ctr.sbt_mint_many(tokens.clone(), None) {
+            Err(Error::RequiredDeposit(r)) => assert_eq!(r, required),
+            Ok(_) => panic!("expected RequiredDeposit, got: Ok"),
+            Err(x) => panic!("expected RequiredDeposit, got: {:?}", x),
+        };
+
+        ctx.attached_deposit = required;
+        testing_env!(ctx.clone());
+        ctr.sbt_mint_many(tokens, None)?;
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, registry());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { deposit, .. } => assert_eq!(*deposit, mint_deposit(2)),
+            a => panic!("expected function call, got {:?}", a),
+        }
+        match &receipts[1].actions[0] {
+            VmAction::FunctionCall { function_name, .. } => {
+                assert_eq!(function_name, "on_mint_fees_callback")
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+
+        // fees are forwarded to the payout account once the tokens are minted
+        testing_env!(ctx.clone());
+        let fees = vec![(carol(), U128(2 * MILI_NEAR))];
+        assert_eq!(
+            ctr.on_mint_fees_callback(auth(1), fees.clone(), Ok(vec![2, 3])),
+            vec![2, 3]
+        );
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, carol());
+        match &receipts[0].actions[0] {
+            VmAction::Transfer { deposit } => assert_eq!(*deposit, 2 * MILI_NEAR),
+            a => panic!("expected transfer, got {:?}", a),
+        }
+
+        // or returned to the minter if the mint failed
+        testing_env!(ctx.clone());
+        let minted = ctr.on_mint_fees_callback(auth(1), fees, Err(PromiseError::Failed));
+        assert!(minted.is_empty());
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, auth(1));
+        match &receipts[0].actions[0] {
+            VmAction::Transfer { deposit } => assert_eq!(*deposit, 2 * MILI_NEAR),
+            a => panic!("expected transfer, got {:?}", a),
+        }
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx);
+        ctr.set_mint_fee(1, None, None)?;
+        assert_eq!(ctr.mint_fee(1), None);
+        Ok(())
+    }
+
+    #[test]
+    fn classes() -> Result<(), Error> {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        let cls2 = ctr.acquire_next_class(true, vec![auth(2)], MIN_TTL, class_metadata(2), None);
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        let cls3 = ctr.acquire_next_class(false, vec![auth(3)], MIN_TTL, class_metadata(3), None);
+
+        let ids = |v: Vec<(ClassId, ClassMetadata, ClassMinters)>| -> Vec<ClassId> {
+            v.into_iter().map(|(c, _, _)| c).collect()
+        };
+        let all = ctr.classes(None, None);
+        assert_eq!(all[2].1, class_metadata(3));
+        assert_eq!(all[2].2.admins, vec![alice()]);
+        assert_eq!(ids(all), vec![1, cls2, cls3]);
+        assert_eq!(ids(ctr.classes(Some(1), Some(1))), vec![cls2]);
+        assert_eq!(ids(ctr.classes(Some(2), Some(5))), vec![cls3]);
+        assert!(ctr.classes(Some(3), None).is_empty());
+
+        assert_eq!(ctr.classes_by_admin(admin()), vec![1, cls2]);
+        assert_eq!(ctr.classes_by_admin(alice()), vec![cls3]);
+        assert!(ctr.classes_by_admin(bob()).is_empty());
+
+        // admins index follows the class admins changes
+        ctr.add_class_admins(cls3, vec![bob()], None)?;
+        assert_eq!(ctr.classes_by_admin(bob()), vec![cls3]);
+        ctr.transfer_class_ownership(cls3, bob(), None)?;
+        assert!(ctr.classes_by_admin(alice()).is_empty());
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.transfer_class_ownership(cls2, bob(), None)?;
+        assert_eq!(ctr.classes_by_admin(admin()), vec![1]);
+        assert_eq!(ctr.classes_by_admin(bob()), vec![cls3, cls2]);
+
+        // released class is removed from the indexes
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx);
+        ctr.release_class_bond(cls3)?;
+        assert_eq!(ctr.classes_by_admin(bob()), vec![cls2]);
+        assert_eq!(ids(ctr.classes(None, None)), vec![1, cls2]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be bigger than 0")]
+    fn classes_zero_limit() {
+        let (_, ctr) = setup(&admin(), None);
+        ctr.classes(None, Some(0));
+    }
+
     #[test]
     fn assert_admin() {
         let (mut ctx, ctr) = setup(&admin(), None);
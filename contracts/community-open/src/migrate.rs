@@ -2,9 +2,18 @@ use crate::*;
 
 // community-open/v1.0.0 structs
 
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldClassMinters {
+    pub requires_iah: bool,
+    pub admins: Vec<AccountId>,
+    pub minters: Vec<AccountId>,
+    pub max_ttl: u64,
+}
+
 #[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(BorshSerialize))]
 pub struct OldContract {
-    pub classes: LookupMap<ClassId, ClassMinters>,
+    pub classes: LookupMap<ClassId, OldClassMinters>,
     pub next_class: ClassId,
     pub registry: AccountId,
     pub metadata: LazyOption<ContractMetadata>,
@@ -21,15 +30,53 @@ impl Contract {
         let old_state: OldContract = env::state_read().expect("can't deserialize contract");
 
         // changed fields:
-        // -
+        // * classes: ClassMinters got `min_ttl` (zero) and `owner` (the first admin), all
+        //   classes are rewritten.
+        // new fields:
+        // + self_mint
+        // + self_minted
+        // + class_bonds: classes acquired before have no bond
+        // + mint_fees
+        // + class_ids, admin_classes: indexes of the existing classes
 
-        Self {
-            classes: old_state.classes,
+        let mut ctr = Self {
+            classes: LookupMap::new(StorageKey::MintingAuthority),
             next_class: old_state.next_class,
             registry: old_state.registry,
             metadata: old_state.metadata,
             class_metadata: old_state.class_metadata,
             registration_cost: old_state.registration_cost,
+            self_mint: LookupMap::new(StorageKey::SelfMint),
+            self_minted: LookupSet::new(StorageKey::SelfMinted),
+            class_bonds: LookupMap::new(StorageKey::ClassBonds),
+            mint_fees: LookupMap::new(StorageKey::MintFees),
+            class_ids: UnorderedSet::new(StorageKey::ClassIds),
+            admin_classes: LookupMap::new(StorageKey::AdminClasses),
+        };
+        let mut old_classes = old_state.classes;
+        for class in 1..ctr.next_class {
+            // remove the old entry before inserting the new one: `insert` decodes the
+            // replaced value with the new layout.
+            if let Some(c) = old_classes.remove(&class) {
+                ctr.class_ids.insert(&class);
+                for a in &c.admins {
+                    ctr.index_admin_class(a, class);
+                }
+                // v1 classes have exactly one admin: the account which acquired the class
+                let owner = c.admins.first().cloned().expect("class without admins");
+                ctr.classes.insert(
+                    &class,
+                    &ClassMinters {
+                        requires_iah: c.requires_iah,
+                        admins: c.admins,
+                        minters: c.minters,
+                        max_ttl: c.max_ttl,
+                        min_ttl: 0,
+                        owner,
+                    },
+                );
+            }
         }
+        ctr
     }
 }
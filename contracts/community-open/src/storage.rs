@@ -1,4 +1,5 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{AccountId, BorshStorageKey};
 
@@ -9,6 +10,12 @@ pub enum StorageKey {
     ContractMetadata,
     MintingAuthority,
     ClassMetadata,
+    SelfMint,
+    SelfMinted,
+    ClassBonds,
+    MintFees,
+    ClassIds,
+    AdminClasses,
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -25,4 +32,44 @@ pub struct ClassMinters {
     /// time to live in ms. Overwrites metadata.expire_at.
     pub max_ttl: u64,
     // TODO handle "dynamic" storage
+    /// minimum time to live in ms of minted and renewed tokens. Zero means no minimum.
+    #[serde(default)]
+    pub min_ttl: u64,
+    /// class owner (one of the admins): the only account which can remove other admins,
+    /// transfer the class ownership and release the class bond.
+    pub owner: AccountId,
+}
+
+/// Fee charged to class minters for every minted token (see `set_mint_fee`).
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintFee {
+    /// fee in yoctoNEAR per minted token.
+    pub fee: U128,
+    /// account receiving the fees.
+    pub payout: AccountId,
+}
+
+/// Self mint configuration of a class.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SelfMint {
+    /// time to live in ms of self minted tokens. Must not be bigger than the class `max_ttl`.
+    pub ttl: u64,
+    /// minimum time in ms between two consecutive self mints of the class. Zero means no limit.
+    pub min_interval: u64,
+    /// time in ms of the last self mint.
+    pub last_mint: u64,
+}
+
+/// Class bond paid in `acquire_next_class`.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+pub struct ClassBond {
+    /// account which receives the bond when the class is released.
+    pub payer: AccountId,
+    /// bond amount in yoctoNEAR.
+    pub amount: u128,
 }
@@ -1,5 +1,6 @@
 use near_sdk::env::panic_str;
 use near_sdk::FunctionError;
+use sbt::{ClassId, ExpiresAtError};
 
 /// Contract errors
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
@@ -7,6 +8,14 @@ pub enum MintError {
     NotMinter,
     RequiredDeposit(u128),
     ClassNotEnabled,
+    /// `metadata.expires_at` must be in the future and at most `now + max_ttl` (the value
+    /// carried by the error).
+    WrongExpiresAt(u64),
+    /// the minter daily quota of the class (first value) is exceeded, the second value is
+    /// the number of tokens the minter can still mint today.
+    QuotaExceeded(ClassId, u32),
+    SelfMintDisabled,
+    ClassFrozen(ClassId),
 }
 
 impl FunctionError for MintError {
@@ -17,6 +26,26 @@ impl FunctionError for MintError {
                 panic_str(&format!("deposit must be at least {}yN", min_deposit))
             }
             MintError::ClassNotEnabled => panic_str("class not enabled"),
+            MintError::WrongExpiresAt(max) => panic_str(&format!(
+                "metadata.expires_at must be in the future and not bigger than {}",
+                max
+            )),
+            MintError::QuotaExceeded(class, remaining) => panic_str(&format!(
+                "minter daily quota of class {} exceeded, remaining: {}",
+                class, remaining
+            )),
+            MintError::SelfMintDisabled => panic_str("self mint is not enabled for the class"),
+            MintError::ClassFrozen(class) => panic_str(&format!("class {} is frozen", class)),
+        }
+    }
+}
+
+impl From<ExpiresAtError> for MintError {
+    fn from(e: ExpiresAtError) -> Self {
+        match e {
+            ExpiresAtError::Wrong(max) => MintError::WrongExpiresAt(max),
+            // classes don't have the minimum TTL
+            ExpiresAtError::TooEarly(_) => unreachable!("min_ttl is zero"),
         }
     }
 }
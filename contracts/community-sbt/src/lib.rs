@@ -1,10 +1,17 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap};
-use near_sdk::{env, near_bindgen, require, AccountId, PanicOnDefault, Promise};
-
-use cost::{calculate_iah_mint_gas, calculate_mint_gas, mint_deposit};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet};
+use near_sdk::json_types::U128;
+use near_sdk::{
+    env, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseError,
+};
+
+use cost::{
+    calculate_iah_mint_gas, calculate_mint_gas, calculate_renew_gas, calculate_revoke_gas,
+    mint_deposit,
+};
 use sbt::*;
 
 pub use crate::errors::*;
@@ -15,11 +22,24 @@ pub mod migrate;
 mod storage;
 
 const MIN_TTL: u64 = 86_400_000; // 24 hours in miliseconds
+const VERIFY_REGISTRY_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const VERIFY_REGISTRY_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const TOKEN_INFO_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+const TOKEN_INFO_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+const DAY_MS: u64 = 86_400_000;
+const SELF_MINT_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+const MINT_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+/// Maximum number of accounts processed by a single `revoke_for` call.
+pub const MAX_REVOKE_FOR_ACCOUNTS: usize = 10;
+/// Maximum number of `revoke_for` classes, same as the registry `sbt_revoke_by_owner` limit.
+pub const MAX_REVOKE_FOR_CLASSES: usize = 25;
+/// Maximum `limit` of `classes_summary`. Bigger values are clamped to `MAX_CLASSES_LIMIT`.
+pub const MAX_CLASSES_LIMIT: u32 = 100;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
-    /// Accounts authorized to add new minting authority
+    /// Accounts with the `SuperAdmin` role.
     pub admins: LazyOption<Vec<AccountId>>,
     /// map of classId -> to set of accounts authorized to mint
     pub classes: LookupMap<ClassId, ClassMinters>,
@@ -29,14 +49,29 @@ pub struct Contract {
     /// contract metadata
     pub metadata: LazyOption<ContractMetadata>,
     pub class_metadata: LookupMap<ClassId, ClassMetadata>,
+    /// true if the `registry` responded to the verification call (see `verify_registry`).
+    pub registry_verified: bool,
+    /// number of tokens minted today by minters of classes with `max_mints_per_day`.
+    pub minter_mints: LookupMap<(ClassId, AccountId), MinterMints>,
+    /// map of classId -> price in yoctoNEAR of a self minted token, for classes with self
+    /// mint enabled (see `enable_self_mint`).
+    pub self_mint_price: LookupMap<ClassId, Balance>,
+    /// account receiving the self mint payments.
+    pub treasury: Option<AccountId>,
+    /// classes frozen by an admin: tokens of these classes can't be minted nor renewed.
+    pub frozen_classes: LookupSet<ClassId>,
+    /// set of (class, account) with the `ClassAdmin` role.
+    pub class_admins: LookupSet<(ClassId, AccountId)>,
 }
 
 // Implement the contract structure
 #[near_bindgen]
 impl Contract {
     /// @admin: account authorized to add new minting authority
+    /// Schedules a verification call to the `registry`, see `verify_registry`.
     #[init]
     pub fn new(registry: AccountId, admin: AccountId, metadata: ContractMetadata) -> Self {
+        verify_registry_promise(registry.clone());
         Self {
             admins: LazyOption::new(StorageKey::Admins, Some(&vec![admin])),
             classes: LookupMap::new(StorageKey::MintingAuthority),
@@ -44,6 +79,12 @@ impl Contract {
             registry,
             metadata: LazyOption::new(StorageKey::ContractMetadata, Some(&metadata)),
             class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            registry_verified: false,
+            minter_mints: LookupMap::new(StorageKey::MinterMints),
+            self_mint_price: LookupMap::new(StorageKey::SelfMint),
+            treasury: None,
+            frozen_classes: LookupSet::new(StorageKey::FrozenClasses),
+            class_admins: LookupSet::new(StorageKey::ClassAdmins),
         }
     }
 
@@ -56,18 +97,86 @@ impl Contract {
         self.classes.get(&class)
     }
 
+    /// Returns settings and metadata name of the enabled classes, ordered by class ID, so
+    /// wallets can list all classes with a single call.
+    /// `from`: first class ID to return, default 1.
+    /// `limit`: default and max is `MAX_CLASSES_LIMIT`.
+    pub fn classes_summary(&self, from: Option<ClassId>, limit: Option<u32>) -> Vec<ClassSummary> {
+        let limit = std::cmp::min(limit.unwrap_or(MAX_CLASSES_LIMIT), MAX_CLASSES_LIMIT);
+        (from.unwrap_or(1).max(1)..self.next_class)
+            .filter_map(|class_id| {
+                let c = self.classes.get(&class_id)?;
+                Some(ClassSummary {
+                    class_id,
+                    name: self.class_metadata.get(&class_id).map(|m| m.name),
+                    requires_iah: c.requires_iah,
+                    max_ttl: c.max_ttl,
+                    minters_count: c.minters.len() as u32,
+                    status: self.class_status_unchecked(class_id),
+                })
+            })
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Returns the number of tokens of the `class` minted by the `minter` today (UTC). Only
+    /// mints of classes with `max_mints_per_day` are counted.
+    pub fn minted_today(&self, class: ClassId, minter: AccountId) -> u32 {
+        self.minter_mints_today(class, &minter).minted
+    }
+
+    /// Returns true if the `account` has the `role`.
+    pub fn has_role(&self, account: AccountId, role: Role) -> bool {
+        match role {
+            Role::SuperAdmin => self.admins.get().unwrap_or_default().contains(&account),
+            Role::ClassAdmin(class) => self.class_admins.contains(&(class, account)),
+            Role::Minter(class) => self
+                .classes
+                .get(&class)
+                .map_or(false, |c| c.minters.contains(&account)),
+        }
+    }
+
+    /// Returns the class status. Returns none if the class is not enabled.
+    pub fn class_status(&self, class: ClassId) -> Option<ClassStatus> {
+        if !self.classes.contains_key(&class) {
+            return None;
+        }
+        Some(self.class_status_unchecked(class))
+    }
+
+    /// Returns the self mint price (in yoctoNEAR) of the `class`. Returns None if self mint
+    /// is not enabled for the class.
+    pub fn self_mint_price(&self, class: ClassId) -> Option<U128> {
+        self.self_mint_price.get(&class).map(U128)
+    }
+
+    /// Returns the account receiving the self mint payments.
+    pub fn treasury(&self) -> Option<AccountId> {
+        self.treasury.clone()
+    }
+
     /// Returns registry address.
     pub fn registry(&self) -> AccountId {
         self.registry.clone()
     }
 
+    /// Returns true if the `registry` address was verified: the registry responded to the
+    /// `sbt_supply` call scheduled at `new` (or by `verify_registry`). Deploy scripts should
+    /// assert it, a wrong registry address breaks all minting.
+    pub fn registry_verified(&self) -> bool {
+        self.registry_verified
+    }
+
     /**********
      * Transactions
      **********/
 
     /// Mints a new SBT for the given receiver.
     /// If `metadata.expires_at` is None then we set it to max: ` now+max_ttl`.
-    /// Panics if `metadata.expires_at > now+max_ttl` or when ClassID is not set or not 1.
+    /// Returns `WrongExpiresAt` error if `metadata.expires_at` is not in the future or
+    /// `metadata.expires_at > now+max_ttl`.
+    /// Panics when ClassID is not set or not 1.
     #[payable]
     #[handle_result]
     pub fn sbt_mint(
@@ -83,6 +192,7 @@ impl Contract {
     /// Similar to `sbt_mint`, but allows to mint many tokens at once. See `sbt_mint` doc for
     /// more details.
     /// * `tokens` is list of pairs: token recipient and token metadata to mint for given recipient.
+    ///   Each metadata can have a custom `expires_at`, capped by the class `max_ttl`.
     #[payable]
     #[handle_result]
     pub fn sbt_mint_many(
@@ -92,24 +202,23 @@ impl Contract {
     ) -> Result<Promise, MintError> {
         let now_ms = env::block_timestamp_ms();
         let mut requires_iah = false;
-        let mut class_info_map: HashMap<ClassId, (bool, u64)> = HashMap::new();
+        let mut class_info_map: HashMap<ClassId, ClassMinters> = HashMap::new();
+        let mut class_mints: HashMap<ClassId, u32> = HashMap::new();
         let mut total_len = 0;
         for (_, token_metadatas) in &mut token_spec {
             total_len += token_metadatas.len();
             for m in token_metadatas {
-                let (cls_requires_iah, ttl) = match class_info_map.get(&m.class) {
-                    Some(ci) => (ci.0, ci.1),
-                    None => {
-                        let ci = self.class_info_minter(m.class)?;
-                        class_info_map.insert(m.class, ci);
-                        ci
-                    }
+                let ci = match class_info_map.entry(m.class) {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => e.insert(self.class_info_minter(m.class)?),
                 };
-                requires_iah = requires_iah || cls_requires_iah;
-                m.expires_at = Some(now_ms + ttl);
+                requires_iah = requires_iah || ci.requires_iah;
+                m.expires_at = Some(check_expires_at(m.expires_at, now_ms, 0, ci.max_ttl)?);
                 m.issued_at = Some(now_ms);
+                *class_mints.entry(m.class).or_default() += 1;
             }
         }
+        let quotas = self.use_mint_quotas(&class_info_map, class_mints)?;
 
         let required_deposit = mint_deposit(total_len);
         let attached_deposit = env::attached_deposit();
@@ -132,9 +241,118 @@ impl Contract {
                 .sbt_mint(token_spec)
         };
 
+        Ok(promise.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(MINT_CALLBACK_GAS)
+                .on_mint_callback(
+                    env::predecessor_account_id(),
+                    env::block_timestamp_ms() / DAY_MS,
+                    quotas,
+                    U128(attached_deposit),
+                ),
+        ))
+    }
+
+    /// Mints a token of the given `class` to the caller (open enrollment). Self mint must be
+    /// enabled for the class (see `enable_self_mint`). The mint is routed through
+    /// `registry.sbt_mint_iah`, so the caller must be a verified human. The token expires
+    /// after the class `max_ttl`.
+    /// Must attach the class self mint price plus `cost.mint_deposit(1)` storage deposit.
+    /// Once the token is minted the price is forwarded to the treasury, if the mint fails
+    /// the whole deposit is returned.
+    #[payable]
+    #[handle_result]
+    pub fn sbt_self_mint(
+        &mut self,
+        class: ClassId,
+        memo: Option<String>,
+    ) -> Result<Promise, MintError> {
+        let cm = self.classes.get(&class).ok_or(MintError::ClassNotEnabled)?;
+        self.check_not_frozen(class)?;
+        let price = self
+            .self_mint_price
+            .get(&class)
+            .ok_or(MintError::SelfMintDisabled)?;
+        let required_deposit = price + mint_deposit(1);
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < required_deposit {
+            return Err(MintError::RequiredDeposit(required_deposit));
+        }
+
+        if let Some(memo) = memo {
+            env::log_str(&format!("SBT self mint memo: {}", memo));
+        }
+
+        let caller = env::predecessor_account_id();
+        let now_ms = env::block_timestamp_ms();
+        let metadata = TokenMetadata {
+            class,
+            issued_at: Some(now_ms),
+            expires_at: Some(now_ms + cm.max_ttl),
+            reference: None,
+            reference_hash: None,
+        };
+        let promise = ext_registry::ext(self.registry.clone())
+            .with_attached_deposit(attached_deposit - price)
+            .with_static_gas(calculate_iah_mint_gas(1, 1))
+            .sbt_mint_iah(vec![(caller.clone(), vec![metadata])])
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(SELF_MINT_CALLBACK_GAS)
+                    .on_self_mint_callback(caller, U128(price), U128(attached_deposit)),
+            );
         Ok(promise)
     }
 
+    /// Callback for `sbt_self_mint`. Forwards the `price` to the treasury if the token was
+    /// minted, otherwise returns the `deposit` to the caller. Returns the minted token ID or
+    /// None if the mint failed.
+    #[private]
+    pub fn on_self_mint_callback(
+        &mut self,
+        caller: AccountId,
+        price: U128,
+        deposit: U128,
+        #[callback_result] minted: Result<Vec<TokenId>, PromiseError>,
+    ) -> Option<TokenId> {
+        match minted {
+            Ok(tokens) => {
+                if price.0 > 0 {
+                    // treasury is required to enable self mint with a price
+                    Promise::new(self.treasury.clone().unwrap()).transfer(price.0);
+                }
+                Some(tokens[0])
+            }
+            Err(_) => {
+                Promise::new(caller).transfer(deposit.0);
+                None
+            }
+        }
+    }
+
+    /// Callback for `sbt_mint_many`. If the registry mint failed, gives back the `minter`
+    /// daily `quotas` used by the mint (pairs of class and number of tokens) and returns the
+    /// `deposit`. Returns the minted token IDs or None if the mint failed.
+    #[private]
+    pub fn on_mint_callback(
+        &mut self,
+        minter: AccountId,
+        day: u64,
+        quotas: Vec<(ClassId, u32)>,
+        deposit: U128,
+        #[callback_result] minted: Result<Vec<TokenId>, PromiseError>,
+    ) -> Option<Vec<TokenId>> {
+        match minted {
+            Ok(tokens) => Some(tokens),
+            Err(_) => {
+                // we can't panic here in order to preserve the state change.
+                self.restore_mint_quotas(&minter, day, quotas);
+                Promise::new(minter).transfer(deposit.0);
+                None
+            }
+        }
+    }
+
     /// Updates the expire time of provided tokens.
     /// `ttl` is duration in milliseconds to set expire time: `now+ttl`.
     /// Panics if `ttl > self.minters[class].max_ttl` or ttl < `MIN_TTL` or `tokens` is an empty list.
@@ -175,6 +393,7 @@ impl Contract {
                     .minters;
                 self.assert_minter(caller, &minters);
                 cached_class_info.insert(class_id, (minters, max_ttl));
+                self.assert_not_frozen(class_id);
             }
             self.assert_ttl(ttl, max_ttl);
         }
@@ -183,7 +402,63 @@ impl Contract {
         }
 
         let expires_at_ms = env::block_timestamp_ms() + ttl;
-        ext_registry::ext(self.registry.clone()).sbt_renew(tokens, expires_at_ms)
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_renew_gas(tokens.len()))
+            .sbt_renew(tokens, expires_at_ms)
+    }
+
+    /// Renews the `account` token of the `class` without knowing the token ID: queries the
+    /// registry for the account token and renews it in the callback (see `sbt_renew`).
+    /// Panics if the caller is not a minter of the class or `ttl > max_ttl` of the class.
+    /// The callback panics if the account doesn't have a token of the class.
+    pub fn sbt_renew_by_owner(
+        &mut self,
+        account: AccountId,
+        class: ClassId,
+        ttl: u64,
+        memo: Option<String>,
+    ) -> Promise {
+        let cm = self.class_minter(class).expect("class not found");
+        self.assert_minter(&env::predecessor_account_id(), &cm.minters);
+        self.assert_ttl(ttl, cm.max_ttl);
+        self.assert_not_frozen(class);
+        let ctr = env::current_account_id();
+        ext_registry::ext(self.registry.clone())
+            .sbt_tokens_by_owner_private(
+                account,
+                Some(ctr.clone()),
+                Some(class),
+                Some(1),
+                Some(true),
+            )
+            .then(Self::ext(ctr).on_sbt_renew_by_owner_callback(class, ttl, memo))
+    }
+
+    /// Callback for `sbt_renew_by_owner`. Renews the token returned by the registry query.
+    #[private]
+    pub fn on_sbt_renew_by_owner_callback(
+        &self,
+        class: ClassId,
+        ttl: u64,
+        memo: Option<String>,
+        #[callback_result] tokens: Result<Vec<(AccountId, Vec<OwnedToken>)>, PromiseError>,
+    ) -> Promise {
+        let tokens = tokens.expect("error while retrieving tokens data from registry");
+        // the query can return a token of the next class if the account doesn't have the
+        // token of the requested class.
+        let token = tokens
+            .first()
+            .and_then(|(_, ts)| ts.first())
+            .filter(|t| t.metadata.class == class)
+            .expect("account doesn't have a token of the class");
+        if let Some(memo) = memo {
+            env::log_str(&format!("SBT renew memo: {}", memo));
+        }
+
+        let expires_at_ms = env::block_timestamp_ms() + ttl;
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_renew_gas(1))
+            .sbt_renew(vec![token.token], expires_at_ms)
     }
 
     /// Revokes list of tokens. If `burn==true`, the tokens are burned (removed). Otherwise,
@@ -233,27 +508,61 @@ impl Contract {
         if let Some(memo) = memo {
             env::log_str(&format!("SBT revoke memo: {}", memo));
         }
-        ext_registry::ext(self.registry.clone()).sbt_revoke(tokens, burn)
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_revoke_gas(tokens.len(), burn))
+            .sbt_revoke(tokens, burn)
     }
 
-    /// Admin: remove SBT from the given accounts.
-    /// Panics if `accounts` is an empty list.
+    /// Admin: revokes (or burns if `burn`) SBTs of this contract from the given accounts,
+    /// using the registry `sbt_revoke_by_owner` (or `sbt_revoke_by_owner_classes` when `classes`
+    /// is set), so tokens IDs are not needed.
+    /// Requires `SuperAdmin` role, or `ClassAdmin` role of all the `classes`.
+    /// If `classes` is set, only tokens of the listed classes are revoked. Otherwise the
+    /// registry revokes at most 25 tokens per account, and the method must be called again
+    /// for accounts for which the registry returned false.
+    /// Panics if `accounts` is an empty list or has more than `MAX_REVOKE_FOR_ACCOUNTS`
+    /// elements, or `classes` is an empty list or has more than `MAX_REVOKE_FOR_CLASSES`
+    /// elements.
     pub fn revoke_for(
         &mut self,
         accounts: Vec<AccountId>,
-        #[allow(unused_variables)] memo: Option<String>,
-    ) {
-        self.assert_admin();
-        require!(!accounts.is_empty(), "accounts must be a non empty list");
-        env::panic_str("not implemented");
-        // todo: requires registry update.
-        // let mut tokens = Vec::with_capacity(accounts.len());
-        // for a in accounts {
-        //     tokens.push(t);
-        // }
-        // if !tokens.is_empty() {
-        //     SbtTokensEvent { tokens, memo }.emit_revoke();
-        // }
+        burn: bool,
+        classes: Option<Vec<ClassId>>,
+        memo: Option<String>,
+    ) -> Promise {
+        match &classes {
+            Some(classes) => classes.iter().for_each(|c| self.assert_class_admin(*c)),
+            None => self.assert_admin(),
+        }
+        require!(
+            !accounts.is_empty() && accounts.len() <= MAX_REVOKE_FOR_ACCOUNTS,
+            format!(
+                "accounts must be a non empty list of at most {} elements",
+                MAX_REVOKE_FOR_ACCOUNTS
+            )
+        );
+        if let Some(classes) = &classes {
+            require!(
+                !classes.is_empty() && classes.len() <= MAX_REVOKE_FOR_CLASSES,
+                format!(
+                    "classes must be a non empty list of at most {} elements",
+                    MAX_REVOKE_FOR_CLASSES
+                )
+            );
+        }
+        if let Some(memo) = memo {
+            env::log_str(&format!("SBT revoke memo: {}", memo));
+        }
+        let revoke = |owner: AccountId| {
+            let registry = ext_registry::ext(self.registry.clone());
+            match &classes {
+                None => registry.sbt_revoke_by_owner(owner, burn),
+                Some(classes) => registry.sbt_revoke_by_owner_classes(owner, classes.clone(), burn),
+            }
+        };
+        let mut accounts = accounts.into_iter();
+        let first = revoke(accounts.next().unwrap());
+        accounts.fold(first, |p, owner| p.and(revoke(owner)))
     }
 
     /**********
@@ -263,7 +572,7 @@ impl Contract {
     /// Allows admin to change if the specific class requires IAH verification.
     /// Panics if class is not found.
     pub fn set_requires_iah(&mut self, class: ClassId, requires_iah: bool) {
-        self.assert_admin();
+        self.assert_class_admin(class);
         let mut c = self.classes.get(&class).expect("class not found");
         if c.requires_iah != requires_iah {
             c.requires_iah = requires_iah;
@@ -273,16 +582,73 @@ impl Contract {
 
     /// Allows admin to change Max TTL, expected time duration in miliseconds.
     pub fn set_max_ttl(&mut self, class: ClassId, max_ttl: u64) {
-        self.assert_admin();
+        self.assert_class_admin(class);
         let mut cm = self.classes.get(&class).expect("class not found");
         cm.max_ttl = max_ttl;
         self.classes.insert(&class, &cm);
     }
 
+    /// Allows admin to set the maximum number of tokens each minter can mint per day (UTC)
+    /// in the `class`, so a compromised minter can't flood the class. None removes the limit.
+    pub fn set_max_mints_per_day(&mut self, class: ClassId, max_mints_per_day: Option<u32>) {
+        self.assert_class_admin(class);
+        let mut cm = self.classes.get(&class).expect("class not found");
+        cm.max_mints_per_day = max_mints_per_day;
+        self.classes.insert(&class, &cm);
+    }
+
+    /// Admin: freezes the `class`, to sunset it without removing its minters and metadata:
+    /// tokens of a frozen class can't be minted nor renewed, but can be revoked.
+    /// Panics if the class is not enabled.
+    pub fn freeze_class(&mut self, class: ClassId) {
+        self.assert_class_admin(class);
+        require!(self.classes.contains_key(&class), "class not found");
+        self.frozen_classes.insert(&class);
+    }
+
+    /// Admin: reverts `freeze_class`.
+    pub fn unfreeze_class(&mut self, class: ClassId) {
+        self.assert_class_admin(class);
+        self.frozen_classes.remove(&class);
+    }
+
+    /// Admin: enables self minting of the `class` (open enrollment): any verified human can
+    /// mint a token of that class to themselves by paying the `price` (in yoctoNEAR, see
+    /// `sbt_self_mint`). Overwrites the previous price.
+    /// Panics if the class is not enabled or the `price` is not zero and the treasury is
+    /// not set.
+    pub fn enable_self_mint(&mut self, class: ClassId, price: U128) {
+        self.assert_class_admin(class);
+        require!(self.classes.contains_key(&class), "class not found");
+        require!(
+            price.0 == 0 || self.treasury.is_some(),
+            "treasury must be set to enable paid self mint"
+        );
+        self.self_mint_price.insert(&class, &price.0);
+    }
+
+    /// Admin: disables self minting of the `class`.
+    pub fn disable_self_mint(&mut self, class: ClassId) {
+        self.assert_class_admin(class);
+        self.self_mint_price.remove(&class);
+    }
+
+    /// Admin: sets the account receiving the self mint payments.
+    pub fn set_treasury(&mut self, treasury: AccountId) {
+        self.assert_admin();
+        self.treasury = Some(treasury);
+    }
+
+    /// Admin: resets the `minter` daily quota usage of the `class`.
+    pub fn reset_minter_quota(&mut self, class: ClassId, minter: AccountId) {
+        self.assert_class_admin(class);
+        self.minter_mints.remove(&(class, minter));
+    }
+
     /// Allows admin to update class metadata.
     /// Panics if class is not enabled.
     pub fn set_sbt_class_metadata(&mut self, class: ClassId, metadata: ClassMetadata) {
-        self.assert_admin();
+        self.assert_class_admin(class);
         require!(class < self.next_class, "class not found");
         self.class_metadata.insert(&class, &metadata);
     }
@@ -310,21 +676,62 @@ impl Contract {
                 requires_iah,
                 minters: vec![minter],
                 max_ttl,
+                max_mints_per_day: None,
             },
         );
         self.class_metadata.insert(&cls, &metadata);
         cls
     }
 
+    /// Configures a community in one call: optionally replaces the contract metadata and
+    /// enables all classes from the `config` with their minters, max TTL and metadata.
+    /// Returns the new class IDs, in the same order as `config.classes`.
+    /// Must be called by admin. Panics (and no change is applied) if any class has an empty
+    /// minters list or the max TTL is smaller than 24 hours.
+    pub fn setup_community(
+        &mut self,
+        config: CommunitySetup,
+        #[allow(unused_variables)] memo: Option<String>,
+    ) -> Vec<ClassId> {
+        self.assert_admin();
+        for c in &config.classes {
+            require!(!c.minters.is_empty(), "minters must be a non empty list");
+            require!(
+                MIN_TTL <= c.max_ttl,
+                format!("ttl must be at least {}ms", MIN_TTL)
+            );
+        }
+        if let Some(metadata) = config.metadata {
+            self.metadata.replace(&metadata);
+        }
+        let mut classes = Vec::with_capacity(config.classes.len());
+        for c in config.classes {
+            let cls = self.next_class;
+            self.next_class += 1;
+            self.classes.insert(
+                &cls,
+                &ClassMinters {
+                    requires_iah: c.requires_iah,
+                    minters: c.minters,
+                    max_ttl: c.max_ttl,
+                    max_mints_per_day: None,
+                },
+            );
+            self.class_metadata.insert(&cls, &c.metadata);
+            classes.push(cls);
+        }
+        classes
+    }
+
     /// Admin: authorize `minter` to mint tokens of a `class`.
-    /// Must be called by admin, panics otherwise.
+    /// Must be called by a super admin or the class admin, panics otherwise.
     pub fn authorize(
         &mut self,
         class: ClassId,
         minter: AccountId,
         #[allow(unused_variables)] memo: Option<String>,
     ) {
-        self.assert_admin();
+        self.assert_class_admin(class);
         let mut c = self.classes.get(&class).expect("class not found");
         if !c.minters.contains(&minter) {
             c.minters.push(minter);
@@ -333,14 +740,14 @@ impl Contract {
     }
 
     /// admin: revokes `class` minting for `minter`.
-    /// Must be called by admin, panics otherwise.
+    /// Must be called by a super admin or the class admin, panics otherwise.
     pub fn unauthorize(
         &mut self,
         class: ClassId,
         minter: AccountId,
         #[allow(unused_variables)] memo: Option<String>,
     ) {
-        self.assert_admin();
+        self.assert_class_admin(class);
         let mut c = self.classes.get(&class).expect("class not found");
         if let Some(idx) = c.minters.iter().position(|x| x == &minter) {
             c.minters.swap_remove(idx);
@@ -348,6 +755,48 @@ impl Contract {
         }
     }
 
+    /// Grants the `role` to the `account`. Super admins can grant any role, class admins
+    /// only the `Minter` role of their class.
+    /// Panics if the role class is not enabled.
+    pub fn grant_role(&mut self, account: AccountId, role: Role) {
+        match role {
+            Role::SuperAdmin => {
+                self.assert_admin();
+                let mut admins = self.admins.get().unwrap_or_default();
+                if !admins.contains(&account) {
+                    admins.push(account);
+                    self.admins.set(&admins);
+                }
+            }
+            Role::ClassAdmin(class) => {
+                self.assert_admin();
+                require!(self.classes.contains_key(&class), "class not found");
+                self.class_admins.insert(&(class, account));
+            }
+            Role::Minter(class) => self.authorize(class, account, None),
+        }
+    }
+
+    /// Revokes the `role` from the `account`. Same permissions as in `grant_role` apply.
+    /// Panics when revoking the last `SuperAdmin`.
+    pub fn revoke_role(&mut self, account: AccountId, role: Role) {
+        match role {
+            Role::SuperAdmin => {
+                self.assert_admin();
+                let mut admins = self.admins.get().unwrap_or_default();
+                admins.retain(|a| a != &account);
+                require!(!admins.is_empty(), "can't revoke the last super admin");
+                self.admins.set(&admins);
+            }
+            Role::ClassAdmin(class) => {
+                self.assert_admin();
+                self.class_admins.remove(&(class, account));
+            }
+            Role::Minter(class) => self.unauthorize(class, account, None),
+        }
+    }
+
+    /// Sets the list of super admins.
     pub fn set_admin_list(&mut self, new_admin_list: Vec<AccountId>) {
         self.assert_admin();
         self.admins.set(&new_admin_list);
@@ -360,10 +809,70 @@ impl Contract {
         self.metadata.replace(&metadata);
     }
 
+    /// Checks the `registry` address by calling its `sbt_supply` view and records the
+    /// result in `registry_verified`. Can be called by anyone, e.g. after a migration or if
+    /// the registry was not deployed yet when the contract was initialized.
+    pub fn verify_registry(&self) -> Promise {
+        verify_registry_promise(self.registry.clone())
+    }
+
+    /// Returns full token details: the token data (owner, expiration...) stored in the
+    /// registry merged with the class metadata of this contract. Returns None if the token
+    /// doesn't exist.
+    /// Views can't make cross contract calls, so it must be called as a transaction (the
+    /// result is the transaction outcome), or frontends can do it in two steps: call the
+    /// registry `sbt(issuer, token)` view and then `sbt_class_metadata(class)` of this contract.
+    pub fn token_info(&self, token: TokenId) -> Promise {
+        ext_registry::ext(self.registry.clone())
+            .with_static_gas(TOKEN_INFO_GAS)
+            .sbt(env::current_account_id(), token)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(TOKEN_INFO_CALLBACK_GAS)
+                    .on_token_info(),
+            )
+    }
+
+    /// Callback for `token_info`.
+    #[private]
+    pub fn on_token_info(
+        &self,
+        #[callback_result] token: Result<Option<Token>, PromiseError>,
+    ) -> Option<TokenInfo> {
+        let token = token.expect("error while retrieving token data from registry")?;
+        let class = token.metadata.class;
+        Some(TokenInfo {
+            token,
+            class_metadata: self.class_metadata.get(&class),
+            requires_iah: self.class_minter(class).map_or(false, |cm| cm.requires_iah),
+        })
+    }
+
+    /// Callback for the registry verification. Returns the verification result.
+    #[private]
+    pub fn on_registry_verified(
+        &mut self,
+        #[callback_result] supply: Result<u64, PromiseError>,
+    ) -> bool {
+        self.registry_verified = supply.is_ok();
+        if !self.registry_verified {
+            env::log_str(&format!("registry {} verification failed", self.registry));
+        }
+        self.registry_verified
+    }
+
     /**********
      * INTERNAL
      **********/
 
+    /// Panics if the caller is not a super admin nor the `class` admin.
+    fn assert_class_admin(&self, class: ClassId) {
+        let caller = env::predecessor_account_id();
+        if !self.class_admins.contains(&(class, caller)) {
+            self.assert_admin();
+        }
+    }
+
     fn assert_admin(&self) {
         if let Some(admins) = self.admins.get() {
             require!(
@@ -377,12 +886,13 @@ impl Contract {
 
     /// Returns (requires_iah, max_ttl).
     /// Returns error if class is not found  or not called by a minter.
-    fn class_info_minter(&self, class: ClassId) -> Result<(bool, u64), MintError> {
+    fn class_info_minter(&self, class: ClassId) -> Result<ClassMinters, MintError> {
         match self.class_minter(class) {
             None => Err(MintError::ClassNotEnabled),
             Some(cm) => {
+                self.check_not_frozen(class)?;
                 if cm.minters.contains(&env::predecessor_account_id()) {
-                    Ok((cm.requires_iah, cm.max_ttl))
+                    Ok(cm)
                 } else {
                     Err(MintError::NotMinter)
                 }
@@ -390,6 +900,82 @@ impl Contract {
         }
     }
 
+    fn class_status_unchecked(&self, class: ClassId) -> ClassStatus {
+        if self.frozen_classes.contains(&class) {
+            ClassStatus::Frozen
+        } else {
+            ClassStatus::Active
+        }
+    }
+
+    fn check_not_frozen(&self, class: ClassId) -> Result<(), MintError> {
+        if self.frozen_classes.contains(&class) {
+            return Err(MintError::ClassFrozen(class));
+        }
+        Ok(())
+    }
+
+    fn assert_not_frozen(&self, class: ClassId) {
+        require!(
+            !self.frozen_classes.contains(&class),
+            format!("class {} is frozen", class)
+        );
+    }
+
+    /// Returns the `minter` mints of the `class` for the current day.
+    fn minter_mints_today(&self, class: ClassId, minter: &AccountId) -> MinterMints {
+        let day = env::block_timestamp_ms() / DAY_MS;
+        match self.minter_mints.get(&(class, minter.clone())) {
+            Some(mm) if mm.day == day => mm,
+            _ => MinterMints { day, minted: 0 },
+        }
+    }
+
+    /// Checks the caller daily quota of the classes with `max_mints_per_day` and records
+    /// the new mints. All classes are checked before any update.
+    /// Returns the used quotas: pairs of class and number of recorded mints.
+    fn use_mint_quotas(
+        &mut self,
+        classes: &HashMap<ClassId, ClassMinters>,
+        class_mints: HashMap<ClassId, u32>,
+    ) -> Result<Vec<(ClassId, u32)>, MintError> {
+        let minter = env::predecessor_account_id();
+        let mut updates = Vec::new();
+        for (class, num) in class_mints {
+            if let Some(max) = classes[&class].max_mints_per_day {
+                let mut mm = self.minter_mints_today(class, &minter);
+                if mm.minted + num > max {
+                    return Err(MintError::QuotaExceeded(
+                        class,
+                        max.saturating_sub(mm.minted),
+                    ));
+                }
+                mm.minted += num;
+                updates.push((class, num, mm));
+            }
+        }
+        let mut quotas = Vec::with_capacity(updates.len());
+        for (class, num, mm) in updates {
+            self.minter_mints.insert(&(class, minter.clone()), &mm);
+            quotas.push((class, num));
+        }
+        Ok(quotas)
+    }
+
+    /// Gives back the `minter` quotas used at `day` by a failed mint. Quotas renewed (next
+    /// day) or reset in the meantime are not changed below zero.
+    fn restore_mint_quotas(&mut self, minter: &AccountId, day: u64, quotas: Vec<(ClassId, u32)>) {
+        for (class, num) in quotas {
+            let key = (class, minter.clone());
+            if let Some(mut mm) = self.minter_mints.get(&key) {
+                if mm.day == day {
+                    mm.minted = mm.minted.saturating_sub(num);
+                    self.minter_mints.insert(&key, &mm);
+                }
+            }
+        }
+    }
+
     /// returns ttl for a given token class
     fn get_ttl(&self, class: ClassId) -> u64 {
         match self.class_minter(class) {
@@ -410,6 +996,17 @@ impl Contract {
     }
 }
 
+fn verify_registry_promise(registry: AccountId) -> Promise {
+    ext_registry::ext(registry)
+        .with_static_gas(VERIFY_REGISTRY_GAS)
+        .sbt_supply(env::current_account_id())
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(VERIFY_REGISTRY_CALLBACK_GAS)
+                .on_registry_verified(),
+        )
+}
+
 #[near_bindgen]
 impl SBTIssuer for Contract {
     fn sbt_metadata(&self) -> ContractMetadata {
@@ -424,17 +1021,29 @@ impl SBTIssuer for Contract {
 
 #[cfg(test)]
 mod tests {
-    use cost::mint_deposit;
+    use cost::{mint_deposit, MILI_NEAR};
     use near_sdk::{
+        collections::{LazyOption, LookupMap},
+        env,
+        json_types::U128,
+        mock::VmAction,
         test_utils::{
+            self,
             test_env::{alice, bob, carol},
             VMContextBuilder,
         },
-        testing_env, AccountId, Balance, VMContext,
+        testing_env, AccountId, Balance, PromiseError, VMContext,
+    };
+    use sbt::{
+        ClassId, ClassMetadata, ContractMetadata, OwnedToken, SBTIssuer, Token, TokenMetadata,
+        MILLISECOND,
     };
-    use sbt::{ClassId, ClassMetadata, ContractMetadata, SBTIssuer, TokenMetadata};
 
-    use crate::{ClassMinters, Contract, MintError, MIN_TTL};
+    use crate::migrate::{OldClassMinters, OldContract};
+    use crate::{
+        ClassMinters, ClassSetup, ClassStatus, ClassSummary, CommunitySetup, Contract, MintError,
+        Role, StorageKey, TokenInfo, DAY_MS, MIN_TTL,
+    };
 
     const START: u64 = 10;
 
@@ -467,6 +1076,7 @@ mod tests {
             requires_iah,
             minters,
             max_ttl,
+            max_mints_per_day: None,
         }
     }
 
@@ -539,6 +1149,67 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn setup_community() {
+        let (_, mut ctr) = setup(&admin(), None);
+        let mut metadata = contract_metadata();
+        metadata.name = "new-community".to_string();
+        let classes = ctr.setup_community(
+            CommunitySetup {
+                metadata: Some(metadata),
+                classes: vec![
+                    ClassSetup {
+                        requires_iah: true,
+                        minters: vec![authority(2)],
+                        max_ttl: MIN_TTL,
+                        metadata: class_metadata(2),
+                    },
+                    ClassSetup {
+                        requires_iah: false,
+                        minters: vec![authority(2), authority(3)],
+                        max_ttl: 2 * MIN_TTL,
+                        metadata: class_metadata(3),
+                    },
+                ],
+            },
+            None,
+        );
+        assert_eq!(classes, vec![2, 3]);
+        assert_eq!(ctr.sbt_metadata().name, "new-community");
+        assert_eq!(
+            ctr.class_minter(2),
+            Some(class_minter(true, vec![authority(2)], MIN_TTL))
+        );
+        assert_eq!(
+            ctr.class_minter(3),
+            Some(class_minter(
+                false,
+                vec![authority(2), authority(3)],
+                2 * MIN_TTL
+            ))
+        );
+        assert_eq!(ctr.sbt_class_metadata(3), Some(class_metadata(3)));
+        assert_eq!(ctr.next_class, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "minters must be a non empty list")]
+    fn setup_community_no_minters() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.setup_community(
+            CommunitySetup {
+                metadata: None,
+                classes: vec![ClassSetup {
+                    requires_iah: true,
+                    minters: vec![],
+                    max_ttl: MIN_TTL,
+                    metadata: class_metadata(2),
+                }],
+            },
+            None,
+        );
+    }
+
     #[test]
     #[should_panic(expected = "not an admin")]
     fn authorize_only_admin() {
@@ -790,4 +1461,594 @@ mod tests {
 
         assert_eq!(ctr.admins.get().unwrap(), vec![admin(), alice()]);
     }
+
+    #[test]
+    fn mint_quota() -> Result<(), MintError> {
+        let (mut ctx, mut ctr) = setup(&admin(), Some(mint_deposit(2)));
+        ctr.set_max_mints_per_day(1, Some(2));
+        assert_eq!(ctr.class_minter(1).unwrap().max_mints_per_day, Some(2));
+
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx.clone());
+        ctr.sbt_mint_many(
+            vec![
+                (alice(), vec![mk_meteadata(1)]),
+                (bob(), vec![mk_meteadata(1)]),
+            ],
+            None,
+        )?;
+        assert_eq!(ctr.minted_today(1, authority(1)), 2);
+        assert_eq!(ctr.minted_today(1, authority(2)), 0);
+        match ctr.sbt_mint(carol(), mk_meteadata(1), None) {
+            Err(MintError::QuotaExceeded(1, 0)) => (),
+            Ok(_) => panic!("expected QuotaExceeded, got: Ok"),
+            Err(x) => panic!("expected QuotaExceeded, got: {:?}", x),
+        };
+
+        // quota is renewed the next day
+        ctx.block_timestamp += DAY_MS * MILLISECOND;
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.minted_today(1, authority(1)), 0);
+        ctr.sbt_mint(carol(), mk_meteadata(1), None)?;
+        assert_eq!(ctr.minted_today(1, authority(1)), 1);
+        match ctr.sbt_mint_many(
+            vec![
+                (alice(), vec![mk_meteadata(1)]),
+                (bob(), vec![mk_meteadata(1)]),
+            ],
+            None,
+        ) {
+            Err(MintError::QuotaExceeded(1, 1)) => (),
+            Ok(_) => panic!("expected QuotaExceeded, got: Ok"),
+            Err(x) => panic!("expected QuotaExceeded, got: {:?}", x),
+        };
+
+        // failed registry mint gives back the quota
+        let day = ctx.block_timestamp / MILLISECOND / DAY_MS;
+        let res = ctr.on_mint_callback(
+            authority(1),
+            day,
+            vec![(1, 1)],
+            U128(mint_deposit(1)),
+            Err(PromiseError::Failed),
+        );
+        assert_eq!(res, None);
+        assert_eq!(ctr.minted_today(1, authority(1)), 0);
+        // quotas of a past day are not changed
+        ctr.sbt_mint(carol(), mk_meteadata(1), None)?;
+        ctr.on_mint_callback(
+            authority(1),
+            day - 1,
+            vec![(1, 1)],
+            U128(mint_deposit(1)),
+            Err(PromiseError::Failed),
+        );
+        assert_eq!(ctr.minted_today(1, authority(1)), 1);
+        let res = ctr.on_mint_callback(
+            authority(1),
+            day,
+            vec![(1, 1)],
+            U128(mint_deposit(1)),
+            Ok(vec![7]),
+        );
+        assert_eq!(res, Some(vec![7]));
+        assert_eq!(ctr.minted_today(1, authority(1)), 1);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.reset_minter_quota(1, authority(1));
+        assert_eq!(ctr.minted_today(1, authority(1)), 0);
+        ctr.set_max_mints_per_day(1, None);
+
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx);
+        ctr.sbt_mint_many(
+            vec![
+                (alice(), vec![mk_meteadata(1)]),
+                (bob(), vec![mk_meteadata(1)]),
+            ],
+            None,
+        )?;
+        // mints of classes without quota are not recorded
+        assert_eq!(ctr.minted_today(1, authority(1)), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_class_minters() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut classes = LookupMap::new(StorageKey::MintingAuthority);
+        classes.insert(
+            &1,
+            &OldClassMinters {
+                requires_iah: true,
+                minters: vec![authority(1)],
+                max_ttl: MIN_TTL,
+            },
+        );
+        env::state_write(&OldContract {
+            admins: LazyOption::new(StorageKey::Admins, Some(&vec![admin()])),
+            classes,
+            next_class: 3,
+            registry: registry(),
+            metadata: LazyOption::new(StorageKey::ContractMetadata, Some(&contract_metadata())),
+            class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+        });
+
+        let ctr = Contract::migrate();
+        assert_eq!(
+            ctr.class_minter(1),
+            Some(class_minter(true, vec![authority(1)], MIN_TTL))
+        );
+        assert_eq!(ctr.class_minter(2), None);
+        assert_eq!(ctr.admins.get(), Some(vec![admin()]));
+    }
+
+    fn treasury() -> AccountId {
+        AccountId::new_unchecked("treasury.near".to_string())
+    }
+
+    #[test]
+    fn self_mint() -> Result<(), MintError> {
+        let price = MILI_NEAR;
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        assert_eq!(ctr.self_mint_price(1), None);
+        ctr.set_treasury(treasury());
+        assert_eq!(ctr.treasury(), Some(treasury()));
+        ctr.enable_self_mint(1, U128(price));
+        assert_eq!(ctr.self_mint_price(1), Some(U128(price)));
+
+        ctx.predecessor_account_id = alice();
+        ctx.attached_deposit = mint_deposit(1);
+        testing_env!(ctx.clone());
+        match ctr.sbt_self_mint(1, None) {
+            Err(MintError::RequiredDeposit(d)) => assert_eq!(d, price + mint_deposit(1)),
+            Ok(_) => panic!("expected RequiredDeposit, got: Ok"),
+            Err(x) => panic!("expected RequiredDeposit, got: {:?}", x),
+        };
+        match ctr.sbt_self_mint(2, None) {
+            Err(MintError::ClassNotEnabled) => (),
+            Ok(_) => panic!("expected ClassNotEnabled, got: Ok"),
+            Err(x) => panic!("expected ClassNotEnabled, got: {:?}", x),
+        };
+
+        let deposit = price + mint_deposit(1) + 5;
+        ctx.attached_deposit = deposit;
+        testing_env!(ctx.clone());
+        let _ = ctr.sbt_self_mint(1, Some("join".to_owned()))?;
+        assert_eq!(test_utils::get_logs(), vec!["SBT self mint memo: join"]);
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, registry());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall {
+                function_name,
+                deposit: d,
+                ..
+            } => {
+                assert_eq!(function_name, "sbt_mint_iah");
+                assert_eq!(*d, mint_deposit(1) + 5);
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+
+        // price is forwarded to the treasury once minted
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        ctx.attached_deposit = 0;
+        testing_env!(ctx.clone());
+        let transfers = |receiver: AccountId| -> Vec<Balance> {
+            test_utils::get_created_receipts()
+                .into_iter()
+                .filter(|r| r.receiver_id == receiver)
+                .flat_map(|r| r.actions)
+                .filter_map(|a| match a {
+                    VmAction::Transfer { deposit } => Some(deposit),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            ctr.on_self_mint_callback(alice(), U128(price), U128(deposit), Ok(vec![7])),
+            Some(7)
+        );
+        assert_eq!(transfers(treasury()), vec![price]);
+
+        // deposit is returned if the mint failed
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.on_self_mint_callback(
+                alice(),
+                U128(price),
+                U128(deposit),
+                Err(PromiseError::Failed)
+            ),
+            None
+        );
+        assert_eq!(transfers(treasury()), vec![]);
+        assert_eq!(transfers(alice()), vec![deposit]);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.disable_self_mint(1);
+        assert_eq!(ctr.self_mint_price(1), None);
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        match ctr.sbt_self_mint(1, None) {
+            Err(MintError::SelfMintDisabled) => (),
+            Ok(_) => panic!("expected SelfMintDisabled, got: Ok"),
+            Err(x) => panic!("expected SelfMintDisabled, got: {:?}", x),
+        };
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "treasury must be set to enable paid self mint")]
+    fn enable_self_mint_no_treasury() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.enable_self_mint(1, U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn enable_self_mint_not_admin() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.enable_self_mint(1, U128(0));
+    }
+
+    #[test]
+    fn sbt_renew_by_owner() {
+        let (mut ctx, mut ctr) = setup(&authority(1), None);
+        let _ = ctr.sbt_renew_by_owner(alice(), 1, MIN_TTL, Some("renew".to_owned()));
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, registry());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { function_name, .. } => {
+                assert_eq!(function_name, "sbt_tokens_by_owner_private")
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        let mut m = mk_meteadata(1);
+        m.expires_at = Some(START);
+        let tokens = vec![(
+            ctx.current_account_id.clone(),
+            vec![OwnedToken {
+                token: 12,
+                metadata: m,
+            }],
+        )];
+        let _ =
+            ctr.on_sbt_renew_by_owner_callback(1, MIN_TTL, Some("renew".to_owned()), Ok(tokens));
+        assert_eq!(test_utils::get_logs(), vec!["SBT renew memo: renew"]);
+        let receipts = test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall {
+                function_name,
+                args,
+                ..
+            } => {
+                assert_eq!(function_name, "sbt_renew");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(
+                    args,
+                    serde_json::json!({"tokens": [12], "expires_at": START / MILLISECOND + MIN_TTL})
+                );
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "account doesn't have a token of the class")]
+    fn sbt_renew_by_owner_no_token() {
+        let (_, ctr) = setup(&admin(), None);
+        let tokens = vec![(
+            admin(),
+            vec![OwnedToken {
+                token: 12,
+                metadata: mk_meteadata(2),
+            }],
+        )];
+        let _ = ctr.on_sbt_renew_by_owner_callback(1, MIN_TTL, None, Ok(tokens));
+    }
+
+    #[test]
+    #[should_panic(expected = "caller must be a minter")]
+    fn sbt_renew_by_owner_not_minter() {
+        let (_, mut ctr) = setup(&authority(2), None);
+        let _ = ctr.sbt_renew_by_owner(alice(), 1, MIN_TTL, None);
+    }
+
+    #[test]
+    fn revoke_for() {
+        let (_, mut ctr) = setup(&admin(), None);
+        let _ = ctr.revoke_for(
+            vec![alice(), bob()],
+            true,
+            Some(vec![1]),
+            Some("spam".to_owned()),
+        );
+        assert_eq!(test_utils::get_logs(), vec!["SBT revoke memo: spam"]);
+
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        for (r, owner) in receipts.iter().zip([alice(), bob()]) {
+            assert_eq!(r.receiver_id, registry());
+            match &r.actions[0] {
+                VmAction::FunctionCall {
+                    function_name,
+                    args,
+                    ..
+                } => {
+                    assert_eq!(function_name, "sbt_revoke_by_owner_classes");
+                    let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                    assert_eq!(
+                        args,
+                        serde_json::json!({"owner": owner, "burn": true, "classes": [1]})
+                    );
+                }
+                a => panic!("expected function call, got {:?}", a),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "accounts must be a non empty list of at most 10 elements")]
+    fn revoke_for_no_accounts() {
+        let (_, mut ctr) = setup(&admin(), None);
+        let _ = ctr.revoke_for(vec![], false, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "classes must be a non empty list")]
+    fn revoke_for_empty_classes() {
+        let (_, mut ctr) = setup(&admin(), None);
+        let _ = ctr.revoke_for(vec![alice()], false, Some(vec![]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "classes must be a non empty list of at most 25 elements")]
+    fn revoke_for_too_many_classes() {
+        let (_, mut ctr) = setup(&admin(), None);
+        let _ = ctr.revoke_for(vec![alice()], false, Some((1..=26).collect()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn revoke_for_not_admin() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        let _ = ctr.revoke_for(vec![bob()], false, None, None);
+    }
+
+    #[test]
+    fn freeze_class() -> Result<(), MintError> {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        assert_eq!(ctr.class_status(1), Some(ClassStatus::Active));
+        assert_eq!(ctr.class_status(2), None);
+        ctr.freeze_class(1);
+        assert_eq!(ctr.class_status(1), Some(ClassStatus::Frozen));
+        assert_eq!(
+            ctr.classes_summary(None, None)[0].status,
+            ClassStatus::Frozen
+        );
+        ctr.enable_self_mint(1, U128(0));
+
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx.clone());
+        match ctr.sbt_mint(alice(), mk_meteadata(1), None) {
+            Err(MintError::ClassFrozen(1)) => (),
+            Ok(_) => panic!("expected ClassFrozen, got: Ok"),
+            Err(x) => panic!("expected ClassFrozen, got: {:?}", x),
+        };
+        match ctr.sbt_self_mint(1, None) {
+            Err(MintError::ClassFrozen(1)) => (),
+            Ok(_) => panic!("expected ClassFrozen, got: Ok"),
+            Err(x) => panic!("expected ClassFrozen, got: {:?}", x),
+        };
+        // revoke is still possible
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        let _ = ctr.on_sbt_revoke_callback(&authority(1), vec![1], false, None, Ok(vec![Some(1)]));
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.unfreeze_class(1);
+        assert_eq!(ctr.class_status(1), Some(ClassStatus::Active));
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx);
+        ctr.sbt_mint(alice(), mk_meteadata(1), None)?;
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "class 1 is frozen")]
+    fn freeze_class_renew() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.freeze_class(1);
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx);
+        let _ = ctr.on_sbt_renew_callback(&authority(1), vec![1], MIN_TTL, None, Ok(vec![Some(1)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "class 1 is frozen")]
+    fn freeze_class_renew_by_owner() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.freeze_class(1);
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx);
+        let _ = ctr.sbt_renew_by_owner(alice(), 1, MIN_TTL, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn freeze_class_not_admin() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctx.predecessor_account_id = authority(1);
+        testing_env!(ctx);
+        ctr.freeze_class(1);
+    }
+
+    #[test]
+    fn roles() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.enable_next_class(false, authority(2), MIN_TTL, class_metadata(2), None);
+        assert!(ctr.has_role(admin(), Role::SuperAdmin));
+        assert!(!ctr.has_role(alice(), Role::SuperAdmin));
+        assert!(ctr.has_role(authority(1), Role::Minter(1)));
+        assert!(!ctr.has_role(authority(1), Role::Minter(2)));
+
+        ctr.grant_role(alice(), Role::ClassAdmin(1));
+        assert!(ctr.has_role(alice(), Role::ClassAdmin(1)));
+        assert!(!ctr.has_role(alice(), Role::ClassAdmin(2)));
+
+        // class admin manages only its class
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.grant_role(bob(), Role::Minter(1));
+        assert!(ctr.has_role(bob(), Role::Minter(1)));
+        ctr.set_max_ttl(1, 2 * MIN_TTL);
+        ctr.freeze_class(1);
+        ctr.revoke_role(authority(1), Role::Minter(1));
+        assert!(!ctr.has_role(authority(1), Role::Minter(1)));
+        assert_eq!(
+            ctr.class_minter(1).unwrap(),
+            ClassMinters {
+                requires_iah: true,
+                minters: vec![bob()],
+                max_ttl: 2 * MIN_TTL,
+                max_mints_per_day: None,
+            }
+        );
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.grant_role(carol(), Role::SuperAdmin);
+        ctr.revoke_role(admin(), Role::SuperAdmin);
+        assert!(!ctr.has_role(admin(), Role::SuperAdmin));
+        ctx.predecessor_account_id = carol();
+        testing_env!(ctx);
+        ctr.revoke_role(alice(), Role::ClassAdmin(1));
+        assert!(!ctr.has_role(alice(), Role::ClassAdmin(1)));
+        assert_eq!(ctr.admins.get().unwrap(), vec![carol()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn class_admin_other_class() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.enable_next_class(false, authority(2), MIN_TTL, class_metadata(2), None);
+        ctr.grant_role(alice(), Role::ClassAdmin(1));
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.authorize(2, bob(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn class_admin_grant_class_admin() {
+        let (mut ctx, mut ctr) = setup(&admin(), None);
+        ctr.grant_role(alice(), Role::ClassAdmin(1));
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.grant_role(bob(), Role::ClassAdmin(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "can't revoke the last super admin")]
+    fn revoke_last_super_admin() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.revoke_role(admin(), Role::SuperAdmin);
+    }
+
+    #[test]
+    fn classes_summary() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.authorize(1, authority(2), None);
+        let cls = ctr.enable_next_class(false, authority(3), 2 * MIN_TTL, class_metadata(2), None);
+        assert_eq!(cls, 2);
+
+        let summary = |class_id, requires_iah, max_ttl, minters_count| ClassSummary {
+            class_id,
+            name: Some(format!("cls-{}", class_id)),
+            requires_iah,
+            max_ttl,
+            minters_count,
+            status: ClassStatus::Active,
+        };
+        assert_eq!(
+            ctr.classes_summary(None, None),
+            vec![
+                summary(1, true, MIN_TTL, 2),
+                summary(2, false, 2 * MIN_TTL, 1)
+            ]
+        );
+        assert_eq!(
+            ctr.classes_summary(Some(0), Some(1)),
+            vec![summary(1, true, MIN_TTL, 2)]
+        );
+        assert_eq!(
+            ctr.classes_summary(Some(2), Some(10)),
+            vec![summary(2, false, 2 * MIN_TTL, 1)]
+        );
+        assert_eq!(ctr.classes_summary(Some(3), None), vec![]);
+    }
+
+    #[test]
+    fn token_info() {
+        let (_, mut ctr) = setup(&admin(), None);
+        ctr.enable_next_class(true, authority(1), MIN_TTL, class_metadata(1), None);
+        let token = Token {
+            token: 10,
+            owner: alice(),
+            metadata: TokenMetadata {
+                class: 1,
+                issued_at: Some(START),
+                expires_at: Some(START + MIN_TTL),
+                reference: None,
+                reference_hash: None,
+            },
+        };
+        assert_eq!(
+            ctr.on_token_info(Ok(Some(token.clone()))),
+            Some(TokenInfo {
+                token: token.clone(),
+                class_metadata: Some(class_metadata(1)),
+                requires_iah: true,
+            })
+        );
+        assert_eq!(ctr.on_token_info(Ok(None)), None);
+
+        // class not enabled in the contract
+        let mut token2 = token;
+        token2.metadata.class = 5;
+        assert_eq!(
+            ctr.on_token_info(Ok(Some(token2.clone()))),
+            Some(TokenInfo {
+                token: token2,
+                class_metadata: None,
+                requires_iah: false,
+            })
+        );
+    }
+
+    #[test]
+    fn registry_verification() {
+        let (_, mut ctr) = setup(&alice(), None);
+        assert!(!ctr.registry_verified());
+        assert!(ctr.on_registry_verified(Ok(0)));
+        assert!(ctr.registry_verified());
+
+        assert!(!ctr.on_registry_verified(Err(PromiseError::Failed)));
+        assert!(!ctr.registry_verified());
+        assert_eq!(
+            test_utils::get_logs(),
+            vec!["registry registry.near verification failed"]
+        );
+    }
 }
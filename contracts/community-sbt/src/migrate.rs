@@ -1,18 +1,26 @@
 use crate::*;
 
-// community-sbt/v4.2.0 old structs
+// community-sbt/v5.0.0 old structs
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldClassMinters {
+    pub requires_iah: bool,
+    pub minters: Vec<AccountId>,
+    pub max_ttl: u64,
+}
 
 #[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(BorshSerialize))]
 pub struct OldContract {
-    pub admin: AccountId,
-    pub classes: LookupMap<ClassId, ClassMinters>,
+    pub admins: LazyOption<Vec<AccountId>>,
+    pub classes: LookupMap<ClassId, OldClassMinters>,
     pub next_class: ClassId,
     pub registry: AccountId,
     pub metadata: LazyOption<ContractMetadata>,
     pub class_metadata: LookupMap<ClassId, ClassMetadata>,
 }
 
-// migration to community-sbt/v4.2.0
+// migration to community-sbt/v5.1.0
 #[near_bindgen]
 impl Contract {
     #[private]
@@ -21,17 +29,48 @@ impl Contract {
         let old_state: OldContract = env::state_read().expect("can't deserialize contract");
 
         // changed fields:
-        // ttl -- removed
-        // pub admin: AccountId,
-        //   changed to ->  pub admins: LazyOption<Vec<AccountId>>,
+        // * classes: ClassMinters got `max_mints_per_day` (no limit), all classes are
+        //   rewritten.
+        // new fields:
+        // + registry_verified: bool
+        // + minter_mints: LookupMap<(ClassId, AccountId), MinterMints>
+        // + self_mint_price: LookupMap<ClassId, Balance>
+        // + treasury: Option<AccountId>
+        // + frozen_classes: LookupSet<ClassId>
+        // + class_admins: LookupSet<(ClassId, AccountId)>
+        // NOTE: `admins` are the `SuperAdmin` role holders.
+        // NOTE: call `verify_registry` after the migration.
 
-        Self {
-            admins: LazyOption::new(StorageKey::Admins, Some(&vec![old_state.admin])),
-            classes: old_state.classes,
+        let mut ctr = Self {
+            admins: old_state.admins,
+            classes: LookupMap::new(StorageKey::MintingAuthority),
             next_class: old_state.next_class,
             registry: old_state.registry,
             metadata: old_state.metadata,
             class_metadata: old_state.class_metadata,
+            registry_verified: false,
+            minter_mints: LookupMap::new(StorageKey::MinterMints),
+            self_mint_price: LookupMap::new(StorageKey::SelfMint),
+            treasury: None,
+            frozen_classes: LookupSet::new(StorageKey::FrozenClasses),
+            class_admins: LookupSet::new(StorageKey::ClassAdmins),
+        };
+        let mut old_classes = old_state.classes;
+        for class in 1..ctr.next_class {
+            // remove the old entry before inserting the new one: `insert` decodes the
+            // replaced value with the new layout.
+            if let Some(c) = old_classes.remove(&class) {
+                ctr.classes.insert(
+                    &class,
+                    &ClassMinters {
+                        requires_iah: c.requires_iah,
+                        minters: c.minters,
+                        max_ttl: c.max_ttl,
+                        max_mints_per_day: None,
+                    },
+                );
+            }
         }
+        ctr
     }
 }
@@ -1,6 +1,7 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{AccountId, BorshStorageKey};
+use sbt::{ClassId, ClassMetadata, ContractMetadata, Token};
 
 /// Helper structure for keys of the persistent collections.
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -9,6 +10,23 @@ pub enum StorageKey {
     ContractMetadata,
     MintingAuthority,
     ClassMetadata,
+    MinterMints,
+    SelfMint,
+    FrozenClasses,
+    ClassAdmins,
+}
+
+/// Access control roles.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// can manage all classes, the contract metadata and grant any role.
+    SuperAdmin,
+    /// can manage the class settings and grant or revoke the `Minter` role of the class.
+    ClassAdmin(ClassId),
+    /// can mint, renew and revoke tokens of the class.
+    Minter(ClassId),
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -22,4 +40,81 @@ pub struct ClassMinters {
     pub minters: Vec<AccountId>,
     /// time to live in ms. Overwrites metadata.expire_at.
     pub max_ttl: u64,
+    /// maximum number of tokens each minter can mint per day (UTC). None means no limit.
+    #[serde(default)]
+    pub max_mints_per_day: Option<u32>,
+}
+
+/// Number of tokens minted by a minter in a class during the `day`.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+pub struct MinterMints {
+    /// day number since the unix epoch.
+    pub day: u64,
+    pub minted: u32,
+}
+
+/// Token data from the registry merged with the class data, returned by `token_info`.
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenInfo {
+    /// token as recorded in the registry: owner, class, issued_at and expires_at.
+    pub token: Token,
+    /// None if the class metadata is not set.
+    pub class_metadata: Option<ClassMetadata>,
+    /// true if the class requires IAH verification (false if the class is not enabled).
+    pub requires_iah: bool,
+}
+
+/// Class settings merged with the class metadata, returned by `classes_summary`.
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClassSummary {
+    pub class_id: ClassId,
+    /// class metadata name, None if the class metadata is not set.
+    pub name: Option<String>,
+    /// if true only iah verifed accounts can obrain the SBT
+    pub requires_iah: bool,
+    /// time to live in ms.
+    pub max_ttl: u64,
+    /// number of accounts allowed to mint the SBT
+    pub minters_count: u32,
+    pub status: ClassStatus,
+}
+
+/// Status of an enabled class.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum ClassStatus {
+    /// tokens of the class can be minted and renewed.
+    Active,
+    /// the class was frozen by an admin (see `freeze_class`): tokens can't be minted nor
+    /// renewed, but can be revoked.
+    Frozen,
+}
+
+/// Configuration of a new community, used by `setup_community`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CommunitySetup {
+    /// if set, replaces the contract metadata.
+    pub metadata: Option<ContractMetadata>,
+    /// list of classes to enable.
+    pub classes: Vec<ClassSetup>,
+}
+
+/// Configuration of a class enabled by `setup_community`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClassSetup {
+    /// if true only iah verifed accounts can obrain the SBT
+    pub requires_iah: bool,
+    /// accounts allowed to mint the SBT, must be a non empty list
+    pub minters: Vec<AccountId>,
+    /// time to live in ms, must be at least 24 hours.
+    pub max_ttl: u64,
+    pub metadata: ClassMetadata,
 }
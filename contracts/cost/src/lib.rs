@@ -11,12 +11,28 @@ pub const IS_HUMAN_GAS: Gas = Gas(2 * Gas::ONE_TERA.0);
 pub const BLACKLIST_GAS: Gas = Gas(6 * Gas::ONE_TERA.0);
 
 /// calculates amount of gas required by registry for `sbt_renew` call.
+#[deprecated(note = "use calculate_renew_gas")]
 #[inline]
 pub const fn renew_gas(num_tokens: usize) -> Gas {
-    // 2tera + num_tokens * 0.2tera * num_tokens
+    calculate_renew_gas(num_tokens)
+}
+
+/// calculates amount of gas required by registry for `sbt_renew` call.
+pub const fn calculate_renew_gas(num_tokens: usize) -> Gas {
+    // 2tera + num_tokens * 0.2tera
     Gas(2 * Gas::ONE_TERA.0 + num_tokens as u64 * 200_000_000_000)
 }
 
+/// calculates amount of gas required by registry for `sbt_revoke` call. Revoke without burn
+/// only updates the tokens expiry (same as renew), while burn reverts the mint storage updates.
+pub const fn calculate_revoke_gas(num_tokens: usize, burn: bool) -> Gas {
+    if burn {
+        calculate_mint_gas(num_tokens)
+    } else {
+        calculate_renew_gas(num_tokens)
+    }
+}
+
 pub const fn calculate_mint_gas(num_tokens: usize) -> Gas {
     Gas((num_tokens as u64 + 1) * MINT_GAS.0)
 }
@@ -28,3 +44,49 @@ pub const fn calculate_iah_mint_gas(num_tokens: usize, accounts: usize) -> Gas {
 pub const fn mint_deposit(num_tokens: usize) -> Balance {
     num_tokens as u128 * MINT_COST
 }
+
+/// Deposit to attach to the registry `sbt_renew` call: renew doesn't allocate new storage,
+/// and the method is not payable.
+pub const fn renew_deposit(_num_tokens: usize) -> Balance {
+    0
+}
+
+/// Deposit to attach to the registry `sbt_revoke` call: revoke frees (burn) or doesn't
+/// allocate new storage, and the method is not payable.
+pub const fn revoke_deposit(_num_tokens: usize, _burn: bool) -> Balance {
+    0
+}
+
+/// Storage byte cost configured by the protocol: 10^19 yoctoNEAR (1 NEAR per 100kb).
+pub const STORAGE_BYTE_COST: Balance = 10_000_000_000_000_000_000;
+
+// Registry storage used by a mint, in bytes, including the storage records overhead.
+// Account IDs are counted with the maximum length.
+const ACCOUNT_LEN: u64 = 64;
+/// balance, issuer token and token provenance records (the owner account is stored twice)
+/// and an upper bound of a new class supply record.
+const MINT_TOKEN_STORAGE: u64 = 2 * ACCOUNT_LEN + 324 + 72;
+/// supply by owner, total supply by owner and human counters records.
+const MINT_RECIPIENT_STORAGE: u64 = 4 * ACCOUNT_LEN + 109;
+/// issuer counters, created by the first issuer mint.
+const MINT_BASE_STORAGE: u64 = 272;
+
+/// Returns an upper bound of the storage deposit required by the registry to mint
+/// `num_tokens` tokens to `recipients` accounts.
+/// `metadata_size_hint`: maximum length (in bytes) of the `reference` and `reference_hash`
+/// of a token metadata, if the tokens have references.
+pub const fn required_mint_deposit(
+    num_tokens: u32,
+    recipients: u32,
+    metadata_size_hint: Option<u32>,
+) -> Balance {
+    let metadata_size = match metadata_size_hint {
+        // borsh length prefixes of reference and reference_hash
+        Some(hint) => hint as u64 + 8,
+        None => 0,
+    };
+    let bytes = MINT_BASE_STORAGE
+        + num_tokens as u64 * (MINT_TOKEN_STORAGE + metadata_size)
+        + recipients as u64 * MINT_RECIPIENT_STORAGE;
+    bytes as Balance * STORAGE_BYTE_COST
+}
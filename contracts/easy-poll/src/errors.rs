@@ -1,7 +1,7 @@
 use near_sdk::env::panic_str;
 use near_sdk::FunctionError;
 
-use crate::MAX_TEXT_ANSWER_LEN;
+use crate::{PollId, MAX_TEXT_ANSWER_LEN};
 
 /// Contract errors
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
@@ -16,6 +16,27 @@ pub enum PollError {
     AlredyAnswered,
     AnswerTooLong(usize),
     InsufficientDeposit(u128),
+    NotApplicable(usize),
+    NotEnded,
+    AlreadyFinalized,
+    /// the creator already has an active poll (carried by the error) with the same tag
+    ActiveTagPoll(PollId),
+    NotCreator,
+    AlreadyStarted,
+    Cancelled,
+    /// the responder doesn't hold the SBTs required by the poll eligibility
+    NotEligible,
+    /// the poll is sealed, responses must be encrypted
+    Sealed,
+    NotSealed,
+    /// the secret key of the sealed poll was not revealed yet
+    KeyNotRevealed,
+    /// not all ballots of the sealed poll were tallied
+    NotTallied,
+    /// the poll reached the maximum number of participants
+    MaxParticipants,
+    /// the account has no response bond to withdraw
+    NoBond,
 }
 
 impl FunctionError for PollError {
@@ -35,6 +56,24 @@ impl FunctionError for PollError {
             PollError::AlredyAnswered => panic_str("user has already answered"),
             PollError::AnswerTooLong(len) => {panic_str(&format!("the answer too long, max_len:{}, got:{}", MAX_TEXT_ANSWER_LEN, len))},
             PollError::InsufficientDeposit(req_deposit) => {panic_str(&format!("not enough storage deposit, required: {}", req_deposit))}
+            PollError::NotApplicable(index) => {
+                panic_str(&format!("question index={} is not applicable and can't be answered", index))
+            }
+            PollError::NotEnded => panic_str("poll has not ended yet"),
+            PollError::AlreadyFinalized => panic_str("poll is already finalized"),
+            PollError::ActiveTagPoll(poll_id) => {
+                panic_str(&format!("creator already has an active poll with the same tag, poll_id={}", poll_id))
+            }
+            PollError::NotCreator => panic_str("only the poll creator can modify the poll"),
+            PollError::AlreadyStarted => panic_str("poll has already started"),
+            PollError::Cancelled => panic_str("poll is cancelled"),
+            PollError::NotEligible => panic_str("voter doesn't hold the SBTs required by the poll"),
+            PollError::Sealed => panic_str("poll is sealed, responses must be encrypted"),
+            PollError::NotSealed => panic_str("poll is not sealed"),
+            PollError::KeyNotRevealed => panic_str("poll secret key is not revealed"),
+            PollError::NotTallied => panic_str("not all ballots are tallied"),
+            PollError::MaxParticipants => panic_str("poll reached the maximum number of participants"),
+            PollError::NoBond => panic_str("no response bond to withdraw"),
         }
     }
 }
@@ -1,4 +1,4 @@
-use near_sdk::{serde::Serialize, AccountId};
+use near_sdk::{json_types::U128, serde::Serialize, AccountId, Balance};
 use serde_json::json;
 
 use sbt::{EventPayload, NearEvent};
@@ -28,6 +28,48 @@ pub(crate) fn emit_respond(poll_id: PollId, responder: AccountId) {
     });
 }
 
+pub(crate) fn emit_respond_updated(poll_id: PollId, responder: AccountId) {
+    emit_event(EventPayload {
+        event: "respond_updated",
+        data: json!({ "poll_id": poll_id, "responder": responder }),
+    });
+}
+
+pub(crate) fn emit_finalize(poll_id: PollId, humans_count: Option<u64>) {
+    emit_event(EventPayload {
+        event: "finalize",
+        data: json!({ "poll_id": poll_id, "humans_count": humans_count }),
+    });
+}
+
+pub(crate) fn emit_update_poll(poll_id: PollId) {
+    emit_event(EventPayload {
+        event: "update_poll",
+        data: json!({ "poll_id": poll_id }),
+    });
+}
+
+pub(crate) fn emit_cancel_poll(poll_id: PollId) {
+    emit_event(EventPayload {
+        event: "cancel_poll",
+        data: json!({ "poll_id": poll_id }),
+    });
+}
+
+pub(crate) fn emit_reveal_key(poll_id: PollId) {
+    emit_event(EventPayload {
+        event: "reveal_key",
+        data: json!({ "poll_id": poll_id }),
+    });
+}
+
+pub(crate) fn emit_withdraw_bond(poll_id: PollId, responder: AccountId, amount: Balance) {
+    emit_event(EventPayload {
+        event: "withdraw_bond",
+        data: json!({ "poll_id": poll_id, "responder": responder, "amount": U128(amount) }),
+    });
+}
+
 #[cfg(test)]
 mod unit_tests {
     use near_sdk::{test_utils, AccountId};
@@ -46,5 +88,11 @@ mod unit_tests {
         assert_eq!(vec![expected1], test_utils::get_logs());
         emit_respond(22, acc(1));
         assert_eq!(vec![expected1, expected2], test_utils::get_logs());
+        let expected3 = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"respond_updated","data":{"poll_id":22,"responder":"user-1.near"}}"#;
+        emit_respond_updated(22, acc(1));
+        assert_eq!(
+            vec![expected1, expected2, expected3],
+            test_utils::get_logs()
+        );
     }
 }
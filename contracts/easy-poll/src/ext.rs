@@ -1,9 +1,18 @@
 pub use crate::storage::*;
 use near_sdk::{ext_contract, AccountId};
-use sbt::TokenId;
+use sbt::{OwnedToken, TokenId};
 
 #[ext_contract(ext_registry)]
 trait ExtRegistry {
     // queries
     fn is_human(&self, account: AccountId) -> Vec<(AccountId, Vec<TokenId>)>;
+    fn humans_count(&self) -> u64;
+    fn sbt_tokens_by_owner(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)>;
 }
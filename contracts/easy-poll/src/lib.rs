@@ -1,22 +1,63 @@
 pub use crate::errors::PollError;
+use crate::events::emit_cancel_poll;
 use crate::events::emit_create_poll;
+use crate::events::emit_finalize;
 use crate::events::emit_respond;
+use crate::events::emit_respond_updated;
+use crate::events::emit_reveal_key;
+use crate::events::emit_update_poll;
+use crate::events::emit_withdraw_bond;
 pub use crate::ext::*;
 pub use crate::storage::*;
 use ext::ext_registry;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
-use near_sdk::collections::LookupSet;
+use near_sdk::collections::TreeMap;
+use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::Gas;
-use near_sdk::{env, near_bindgen, require, AccountId, PanicOnDefault};
+use near_sdk::{
+    env, near_bindgen, require, AccountId, Balance, PanicOnDefault, Promise, PromiseError,
+    PromiseOrValue, PromiseResult,
+};
+use sbt::{ClassId, ClassSet, OwnedToken, SBTs};
+use std::ops::Bound;
 
 mod errors;
 mod events;
 mod ext;
+mod sealed;
 mod storage;
 
 pub const RESPOND_CALLBACK_GAS: Gas = Gas(2 * Gas::ONE_TERA.0);
+pub const HUMANS_COUNT_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+pub const FINALIZE_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+pub const ELIGIBILITY_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+pub const REFUND_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+/// maximum number of issuers in the poll eligibility class set
+pub const MAX_ELIGIBILITY_ISSUERS: usize = 4;
 pub const MAX_TEXT_ANSWER_LEN: usize = 500; // TODO: decide on the maximum length of the text answers to
+/// maximum number of localized variants per question
+pub const MAX_LOCALES: usize = 16;
+/// maximum length of a locale code
+pub const MAX_LOCALE_LEN: usize = 10;
+/// maximum length of a localized question title or description
+pub const MAX_LOCALIZED_TEXT_LEN: usize = 1000;
+/// maximum `limit` of the `text_answers` query
+pub const MAX_TEXT_ANSWERS_LIMIT: u64 = 100;
+/// maximum `limit` of the `participants` query
+pub const MAX_PARTICIPANTS_LIMIT: u64 = 100;
+/// maximum `limit` of the poll listing queries
+pub const MAX_POLLS_LIMIT: u64 = 50;
+/// maximum length of a sealed poll ballot
+pub const MAX_BALLOT_LEN: usize = 4096;
+/// maximum number of ballots tallied in one `tally` call
+pub const MAX_TALLY_LIMIT: u64 = 50;
+/// time (in milliseconds) after the poll end given to the sealed poll creator to reveal the
+/// secret key. Afterwards, a sealed poll with a not revealed key is cancelled by `finalize`.
+pub const REVEAL_PERIOD: u64 = 7 * 24 * 3600 * 1000;
+/// storage bytes accounted by the protocol for each stored record, in addition to the key
+/// and the value bytes
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -25,24 +66,65 @@ pub struct Contract {
     pub polls: LookupMap<PollId, Poll>,
     /// map of all results summarized
     pub results: LookupMap<PollId, Results>,
-    /// lookup set of (poll_id, responder)
-    pub participants: LookupSet<(PollId, AccountId)>,
+    /// map of (poll_id, responder) -> response_idx: index of the responder in the order of
+    /// first responses.
+    pub participants: LookupMap<(PollId, AccountId), u64>,
     /// SBT registry.
     pub sbt_registry: AccountId,
     /// next poll id
     pub next_poll_id: PollId,
+    /// map of (poll_id, responder) -> answers contributing to the results. Recorded only
+    /// for editable polls, text answers are stored in `text_answers`.
+    pub responses: LookupMap<(PollId, AccountId), Vec<Option<Answer>>>,
+    /// contract admin
+    pub admin: AccountId,
+    /// if true, a creator can have only one active poll with a given tag at a time
+    pub unique_active_tags: bool,
+    /// map of (creator, tag) -> last poll created by the creator with the tag
+    pub creator_tags: LookupMap<(AccountId, String), PollId>,
+    /// map of (poll_id, question_idx, response_idx) -> text answer
+    pub text_answers: LookupMap<(PollId, u32, u64), String>,
+    /// map of (poll_id, response_idx) -> responder, to enumerate the poll participants
+    pub responders: LookupMap<(PollId, u64), AccountId>,
+    /// map of poll_id -> sealed ballots configuration, only for sealed polls
+    pub sealed_polls: LookupMap<PollId, SealedPoll>,
+    /// map of (poll_id, response_idx) -> encrypted response, waiting to be tallied
+    pub ballots: LookupMap<(PollId, u64), Ballot>,
+    /// map of poll_id -> (starts_at, ends_at) of polls which are neither finalized nor
+    /// cancelled, ordered by poll id (the creation order). Ended polls are removed by
+    /// `finalize` or `prune_ended_polls`.
+    pub open_polls: TreeMap<PollId, (u64, u64)>,
+    /// map of tag -> polls with the tag, in the creation order
+    pub tag_polls: LookupMap<String, Vec<PollId>>,
+    /// map of creator -> polls created by the account, in the creation order
+    pub creator_polls: LookupMap<AccountId, Vec<PollId>>,
+    /// map of (poll_id, responder) -> locked response bond
+    pub bonds: LookupMap<(PollId, AccountId), Balance>,
 }
 
 #[near_bindgen]
 impl Contract {
+    /// @admin: account allowed to change the contract configuration.
     #[init]
-    pub fn new(sbt_registry: AccountId) -> Self {
+    pub fn new(sbt_registry: AccountId, admin: AccountId) -> Self {
         Self {
             polls: LookupMap::new(StorageKey::Polls),
             results: LookupMap::new(StorageKey::Results),
-            participants: LookupSet::new(StorageKey::Participants),
+            participants: LookupMap::new(StorageKey::Participants),
             sbt_registry,
             next_poll_id: 1,
+            responses: LookupMap::new(StorageKey::Responses),
+            admin,
+            unique_active_tags: false,
+            creator_tags: LookupMap::new(StorageKey::CreatorTags),
+            text_answers: LookupMap::new(StorageKey::TextAnswers),
+            responders: LookupMap::new(StorageKey::Responders),
+            sealed_polls: LookupMap::new(StorageKey::SealedPolls),
+            ballots: LookupMap::new(StorageKey::Ballots),
+            open_polls: TreeMap::new(StorageKey::OpenPolls),
+            tag_polls: LookupMap::new(StorageKey::TagPolls),
+            creator_polls: LookupMap::new(StorageKey::CreatorPolls),
+            bonds: LookupMap::new(StorageKey::Bonds),
         }
     }
 
@@ -55,36 +137,239 @@ impl Contract {
         self.polls.get(&poll_id)
     }
 
+    /// Returns sorted list of locales for which at least one question of the poll has a
+    /// localized variant. UI can use it to offer a language selection.
+    /// Returns None if poll not found.
+    pub fn poll_locales(&self, poll_id: PollId) -> Option<Vec<String>> {
+        let poll = self.polls.get(&poll_id)?;
+        let mut locales: Vec<String> = poll
+            .questions
+            .into_iter()
+            .filter_map(|q| q.locales)
+            .flat_map(|l| l.into_keys())
+            .collect();
+        locales.sort();
+        locales.dedup();
+        Some(locales)
+    }
+
+    /// Returns answers of the `responder` recorded for an editable poll (without text
+    /// answers, see `text_answers`). Returns None if the poll is not editable or the responder didn't answer.
+    pub fn response(&self, poll_id: PollId, responder: AccountId) -> Option<Vec<Option<Answer>>> {
+        self.responses.get(&(poll_id, responder))
+    }
+
     /// Returns poll results (except for text answers), if poll not found returns None.
     pub fn results(&self, poll_id: u64) -> Option<Results> {
         self.results.get(&poll_id)
     }
 
+    /// Returns text answers to the `question` (index) of the poll as a list of
+    /// (response_idx, answer) pairs, for responses from `from_index` (inclusive) to
+    /// `from_index + limit` (exclusive). Responses without an answer to the question are
+    /// skipped, so the list can be shorter than `limit`: continue querying with
+    /// `from_index = previous from_index + limit` until `from_index` reaches the poll
+    /// `participants_num`.
+    /// If limit is not specified or is bigger than `MAX_TEXT_ANSWERS_LIMIT`, then
+    /// `MAX_TEXT_ANSWERS_LIMIT` is used.
+    /// Returns None if poll not found.
+    pub fn text_answers(
+        &self,
+        poll_id: PollId,
+        question: u32,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Option<Vec<(u64, String)>> {
+        let results = self.results.get(&poll_id)?;
+        let limit = limit.map_or(MAX_TEXT_ANSWERS_LIMIT, |l| {
+            std::cmp::min(l, MAX_TEXT_ANSWERS_LIMIT)
+        });
+        let from_index = from_index.unwrap_or(0);
+        let end = std::cmp::min(results.participants_num, from_index.saturating_add(limit));
+        Some(
+            (from_index..end)
+                .filter_map(|idx| {
+                    self.text_answers
+                        .get(&(poll_id, question, idx))
+                        .map(|a| (idx, a))
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns true if the `account` responded to the poll.
+    pub fn has_responded(&self, poll_id: PollId, account: AccountId) -> bool {
+        self.participants.contains_key(&(poll_id, account))
+    }
+
+    /// Returns the poll participants in the order of their first responses, starting from
+    /// `from_index`.
+    /// If limit is not specified or is bigger than `MAX_PARTICIPANTS_LIMIT`, then
+    /// `MAX_PARTICIPANTS_LIMIT` is used.
+    /// Returns None if poll not found.
+    pub fn participants(
+        &self,
+        poll_id: PollId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Option<Vec<AccountId>> {
+        let results = self.results.get(&poll_id)?;
+        let limit = limit.map_or(MAX_PARTICIPANTS_LIMIT, |l| {
+            std::cmp::min(l, MAX_PARTICIPANTS_LIMIT)
+        });
+        let from_index = from_index.unwrap_or(0);
+        let end = std::cmp::min(results.participants_num, from_index.saturating_add(limit));
+        Some(
+            (from_index..end)
+                .filter_map(|idx| self.responders.get(&(poll_id, idx)))
+                .collect(),
+        )
+    }
+
+    /// Returns all polls in the creation order, starting from `from_index`.
+    /// If limit is not specified or is bigger than `MAX_POLLS_LIMIT`, then `MAX_POLLS_LIMIT`
+    /// is used.
+    pub fn polls(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<(PollId, Poll)> {
+        // poll ids are sequential, starting from 1, so they make the creation order index.
+        let from = from_index.unwrap_or(0).saturating_add(1);
+        (from..self.next_poll_id)
+            .take(polls_limit(limit))
+            .filter_map(|id| self.polls.get(&id).map(|p| (id, p)))
+            .collect()
+    }
+
+    /// Returns polls which are active (started, not ended and not cancelled), ordered from
+    /// the oldest one, starting from the `from_poll` poll id (inclusive). To query the next
+    /// page, use the last returned poll id + 1.
+    /// If limit is not specified or is bigger than `MAX_POLLS_LIMIT`, then `MAX_POLLS_LIMIT`
+    /// is used.
+    pub fn active_polls(
+        &self,
+        from_poll: Option<PollId>,
+        limit: Option<u64>,
+    ) -> Vec<(PollId, Poll)> {
+        let now = env::block_timestamp_ms();
+        let from = Bound::Included(from_poll.unwrap_or(0));
+        self.open_polls
+            .range((from, Bound::Unbounded))
+            .filter(|(_, (starts_at, ends_at))| *starts_at <= now && now <= *ends_at)
+            .take(polls_limit(limit))
+            .filter_map(|(id, _)| self.polls.get(&id).map(|p| (id, p)))
+            .collect()
+    }
+
+    /// Returns polls with the `tag`, in the creation order, starting from `from_index`.
+    /// If limit is not specified or is bigger than `MAX_POLLS_LIMIT`, then `MAX_POLLS_LIMIT`
+    /// is used.
+    pub fn polls_by_tag(
+        &self,
+        tag: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<(PollId, Poll)> {
+        let ids = self.tag_polls.get(&tag).unwrap_or_default();
+        self.polls_page(&ids, from_index, limit)
+    }
+
+    /// Returns polls created by the `creator`, in the creation order, starting from
+    /// `from_index`.
+    /// If limit is not specified or is bigger than `MAX_POLLS_LIMIT`, then `MAX_POLLS_LIMIT`
+    /// is used.
+    pub fn polls_by_creator(
+        &self,
+        creator: AccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<(PollId, Poll)> {
+        let ids = self.creator_polls.get(&creator).unwrap_or_default();
+        self.polls_page(&ids, from_index, limit)
+    }
+
+    /// Returns the response bond locked by the `account` in the poll.
+    pub fn bond(&self, poll_id: PollId, account: AccountId) -> Option<U128> {
+        self.bonds.get(&(poll_id, account)).map(U128)
+    }
+
+    /// Returns the sealed ballots configuration and tally progress. Returns None if the poll
+    /// is not sealed.
+    pub fn sealed_poll(&self, poll_id: PollId) -> Option<SealedPoll> {
+        self.sealed_polls.get(&poll_id)
+    }
+
+    /// Returns participation of a finalized IAH poll relative to the number of humans in
+    /// the registry at finalization, in basis points (10000 = 100%).
+    /// Returns None if the poll is not found, not finalized or not IAH only.
+    pub fn quorum(&self, poll_id: PollId) -> Option<u64> {
+        let results = self.results.get(&poll_id)?;
+        match results.humans_count? {
+            0 => Some(0),
+            humans => Some(results.participants_num * 10_000 / humans),
+        }
+    }
+
+    /// Returns true if a creator can have only one active poll with a given tag at a time.
+    pub fn unique_active_tags(&self) -> bool {
+        self.unique_active_tags
+    }
+
     /**********
      * TRANSACTIONS
      **********/
 
-    /// User can update the poll if starts_at > now
+    /// Creates a new poll with the given `params`. The caller becomes the poll creator: they
+    /// can update the poll until it starts (`update_poll`) and cancel it (`cancel_poll`).
     /// it panics if
     /// - user tries to create an invalid poll
-    /// - if poll aready exists and starts_at < now
+    /// - starts_at <= now
+    /// - question localized variants exceed the size limits (see `MAX_LOCALES`,
+    ///   `MAX_LOCALE_LEN`, `MAX_LOCALIZED_TEXT_LEN`)
+    /// - question condition doesn't refer to a previous YesNo question
+    /// Returns `ActiveTagPoll` error if `unique_active_tags` is enabled and the caller already
+    /// has a poll with one of the `tags`, which is not finished.
+    /// `editable`: if true, responders can edit their answers until `ends_at`. Default: false.
+    /// `eligibility`: if set, only holders of all the listed SBT classes can vote. At most
+    /// `MAX_ELIGIBILITY_ISSUERS` issuers, each with at least one class.
+    /// `weighting`: if set, results are additionally aggregated with the response weights
+    /// (see `Results.weighted`). Weighted poll must be IAH only or have eligibility, and
+    /// can't be editable.
     /// emits create_poll event
-    pub fn create_poll(
-        &mut self,
-        iah_only: bool,
-        questions: Vec<Question>,
-        starts_at: u64,
-        ends_at: u64,
-        title: String,
-        tags: Vec<String>,
-        description: String,
-        link: String,
-    ) -> PollId {
+    #[handle_result]
+    pub fn create_poll(&mut self, params: PollParams) -> Result<PollId, PollError> {
+        let PollParams {
+            iah_only,
+            questions,
+            starts_at,
+            ends_at,
+            title,
+            tags,
+            description,
+            link,
+            editable,
+            eligibility,
+            weighting,
+            max_participants,
+            response_bond,
+        } = params;
         let created_at = env::block_timestamp_ms();
         require!(created_at < starts_at, "poll start must be in the future");
+        assert_questions(&questions);
+        assert_response_limits(max_participants, response_bond);
+        assert_eligibility(&eligibility);
+        let editable = editable.unwrap_or(false);
+        assert_weighting(&weighting, iah_only, &eligibility, editable);
+        let creator = env::predecessor_account_id();
+        if self.unique_active_tags {
+            self.assert_no_active_tag_poll(&creator, &tags)?;
+        }
         let poll_id = self.next_poll_id;
         self.next_poll_id += 1;
-        self.initialize_results(poll_id, &questions);
+        self.index_tags(&creator, &tags, poll_id);
+        self.index_tag_polls(&tags, poll_id);
+        self.open_polls.insert(&poll_id, &(starts_at, ends_at));
+        let mut creator_polls = self.creator_polls.get(&creator).unwrap_or_default();
+        creator_polls.push(poll_id);
+        self.creator_polls.insert(&creator, &creator_polls);
+        self.initialize_results(poll_id, &questions, weighting.is_some());
         self.polls.insert(
             &poll_id,
             &Poll {
@@ -97,20 +382,124 @@ impl Contract {
                 description,
                 link,
                 created_at,
+                editable,
+                creator,
+                eligibility,
+                weighting,
+                max_participants,
+                response_bond,
             },
         );
         emit_create_poll(poll_id);
-        poll_id
+        Ok(poll_id)
+    }
+
+    /// Replaces the poll content with `params`. Must be called by the poll creator before the
+    /// poll starts (`starts_at > now`). The `params` have the same meaning and validation as
+    /// in `create_poll`.
+    /// Returns `NotCreator` error if the caller is not the poll creator, `AlreadyStarted`
+    /// error if the poll already started and `Cancelled` error if the poll is cancelled.
+    /// emits update_poll event
+    #[handle_result]
+    pub fn update_poll(&mut self, poll_id: PollId, params: PollParams) -> Result<(), PollError> {
+        let PollParams {
+            iah_only,
+            questions,
+            starts_at,
+            ends_at,
+            title,
+            tags,
+            description,
+            link,
+            editable,
+            eligibility,
+            weighting,
+            max_participants,
+            response_bond,
+        } = params;
+        let poll = self.creator_poll(poll_id)?;
+        let now = env::block_timestamp_ms();
+        if poll.starts_at <= now {
+            return Err(PollError::AlreadyStarted);
+        }
+        require!(now < starts_at, "poll start must be in the future");
+        assert_questions(&questions);
+        assert_response_limits(max_participants, response_bond);
+        assert_eligibility(&eligibility);
+        let editable = editable.unwrap_or(false);
+        assert_weighting(&weighting, iah_only, &eligibility, editable);
+        // the poll itself doesn't block its new tags
+        for tag in &poll.tags {
+            let key = (poll.creator.clone(), tag.clone());
+            if self.creator_tags.get(&key) == Some(poll_id) {
+                self.creator_tags.remove(&key);
+            }
+            if let Some(mut ids) = self.tag_polls.get(tag) {
+                ids.retain(|id| *id != poll_id);
+                self.tag_polls.insert(tag, &ids);
+            }
+        }
+        if self.unique_active_tags {
+            self.assert_no_active_tag_poll(&poll.creator, &tags)?;
+        }
+        self.index_tags(&poll.creator, &tags, poll_id);
+        self.index_tag_polls(&tags, poll_id);
+        // the poll didn't start, so there are no responses
+        self.initialize_results(poll_id, &questions, weighting.is_some());
+        self.open_polls.insert(&poll_id, &(starts_at, ends_at));
+        self.polls.insert(
+            &poll_id,
+            &Poll {
+                iah_only,
+                questions,
+                starts_at,
+                ends_at,
+                title,
+                tags,
+                description,
+                link,
+                created_at: poll.created_at,
+                editable,
+                creator: poll.creator,
+                eligibility,
+                weighting,
+                max_participants,
+                response_bond,
+            },
+        );
+        emit_update_poll(poll_id);
+        Ok(())
+    }
+
+    /// Cancels the poll: responses are not accepted anymore and the poll can't be finalized.
+    /// Results collected so far stay available, with the `Cancelled` status.
+    /// Must be called by the poll creator.
+    /// Returns `NotCreator` error if the caller is not the poll creator, `AlreadyFinalized`
+    /// error if the poll is finalized and `Cancelled` error if the poll is already cancelled.
+    /// emits cancel_poll event
+    #[handle_result]
+    pub fn cancel_poll(&mut self, poll_id: PollId) -> Result<(), PollError> {
+        self.creator_poll(poll_id)?;
+        self.cancel(poll_id)
     }
 
-    /// Allows user to respond to a poll, once the answers are submited they cannot be changed.
+    /// Allows user to respond to a poll, once the answers are submited they cannot be changed,
+    /// unless the poll is editable. Responding again to an editable poll replaces the
+    /// previous answers.
     /// it panics if
     /// - poll not found
     /// - poll not active
-    /// - user alredy answered
+    /// - user alredy answered and the poll is not editable
     /// - poll.verified_humans_only is true, and user is not verified on IAH
+    /// - poll.eligibility is set, and user doesn't hold all the required SBTs
+    /// - poll is sealed (see `respond_encrypted`)
     /// - user tries to vote with an invalid answer to a question
-    /// emits repond event
+    /// - attached deposit doesn't cover the response storage. The minimum storage of a new
+    ///   response is checked before calling the registry, the exact one once the response
+    ///   is recorded.
+    /// The deposit above the response storage cost is refunded. If the response is rejected
+    /// after the registry verification, the whole deposit is refunded.
+    /// emits repond event, or respond_updated event when the answers are edited
     #[payable]
     #[handle_result]
     pub fn respond(
@@ -119,43 +508,384 @@ impl Contract {
         answers: Vec<Option<Answer>>,
     ) -> Result<(), PollError> {
         let caller = env::predecessor_account_id();
-        let storage_start = env::storage_usage();
         let storage_deposit = env::attached_deposit();
 
         self.assert_active(poll_id)?;
 
-        self.assert_not_answered(poll_id, &caller)?;
         let poll = match self.polls.get(&poll_id) {
             None => return Err(PollError::NotFound),
             Some(poll) => poll,
         };
-        // if iah calls the registry to verify the iah sbt
-        if poll.iah_only {
+        if self.sealed_polls.contains_key(&poll_id) {
+            return Err(PollError::Sealed);
+        }
+        if !poll.editable {
+            self.assert_not_answered(poll_id, &caller)?;
+        }
+        // fail fast, before the registry round-trip, when the deposit can't cover the
+        // storage of a new response. Edits can even decrease the storage.
+        if !self.participants.contains_key(&(poll_id, caller.clone())) {
+            self.assert_participants_limit(&poll, poll_id)?;
+            let required_deposit = min_response_storage(poll_id, &caller, &answers, poll.editable)
+                as u128
+                * env::storage_byte_cost()
+                + poll.response_bond.map_or(0, |b| b.0);
+            if storage_deposit < required_deposit {
+                return Err(PollError::InsufficientDeposit(required_deposit));
+            }
+        }
+        if poll.eligibility.is_some() {
+            self.registry_queries(&poll, &caller)
+                .unwrap()
+                .then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(ELIGIBILITY_CALLBACK_GAS)
+                        .with_attached_deposit(storage_deposit)
+                        .on_eligibility_verified(caller.clone(), poll_id, answers),
+                )
+                .then(refund_on_failure(caller, storage_deposit));
+        } else if poll.iah_only {
+            // if iah calls the registry to verify the iah sbt
             ext_registry::ext(self.sbt_registry.clone())
                 .is_human(caller.clone())
                 .then(
                     Self::ext(env::current_account_id())
                         .with_static_gas(RESPOND_CALLBACK_GAS)
-                        .on_human_verifed(true, caller, poll_id, answers),
-                );
+                        .with_attached_deposit(storage_deposit)
+                        .on_human_verifed(true, caller.clone(), poll_id, answers),
+                )
+                .then(refund_on_failure(caller, storage_deposit));
         } else {
             self.on_human_verifed(vec![], false, caller, poll_id, answers)?
         }
+        Ok(())
+    }
+
+    /// Seals the poll: responses must be encrypted with the X25519 `public_key` and
+    /// submitted with `respond_encrypted`, so the results are not known until the poll ends
+    /// and the creator reveals the secret key (see `reveal_key` and `tally`).
+    /// Must be called by the poll creator before the poll starts.
+    /// Returns `NotCreator` error if the caller is not the poll creator and `AlreadyStarted`
+    /// error if the poll already started.
+    #[handle_result]
+    pub fn seal_poll(&mut self, poll_id: PollId, public_key: Base64VecU8) -> Result<(), PollError> {
+        let poll = self.creator_poll(poll_id)?;
+        if poll.starts_at <= env::block_timestamp_ms() {
+            return Err(PollError::AlreadyStarted);
+        }
+        require!(
+            public_key.0.len() == sealed::KEY_LEN,
+            format!("public key must have {} bytes", sealed::KEY_LEN)
+        );
+        self.sealed_polls.insert(
+            &poll_id,
+            &SealedPoll {
+                public_key,
+                secret_key: None,
+                tallied: 0,
+                invalid: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Responds to a sealed poll with the `ballot`: list of answers (as in `respond`)
+    /// serialized with borsh and encrypted with the poll public key (see the `sealed`
+    /// module for the encryption scheme). The ballot is validated only during the `tally`:
+    /// invalid ballots are not counted in the results.
+    /// Same requirements as `respond` apply (poll active, IAH, eligibility, storage
+    /// deposit, deposit refunds). Responding again to an editable poll replaces the previous ballot.
+    /// Returns `NotSealed` error if the poll is not sealed.
+    /// emits repond event, or respond_updated event when the ballot is replaced
+    #[payable]
+    #[handle_result]
+    pub fn respond_encrypted(
+        &mut self,
+        poll_id: PollId,
+        ballot: Base64VecU8,
+    ) -> Result<(), PollError> {
+        let caller = env::predecessor_account_id();
+        self.assert_active(poll_id)?;
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        if !self.sealed_polls.contains_key(&poll_id) {
+            return Err(PollError::NotSealed);
+        }
+        require!(
+            ballot.0.len() <= MAX_BALLOT_LEN,
+            format!("ballot must have at most {} bytes", MAX_BALLOT_LEN)
+        );
+        if !poll.editable {
+            self.assert_not_answered(poll_id, &caller)?;
+        }
+        if !self.participants.contains_key(&(poll_id, caller.clone())) {
+            self.assert_participants_limit(&poll, poll_id)?;
+            let required_deposit = min_ballot_storage(&caller, ballot.0.len()) as u128
+                * env::storage_byte_cost()
+                + poll.response_bond.map_or(0, |b| b.0);
+            if env::attached_deposit() < required_deposit {
+                return Err(PollError::InsufficientDeposit(required_deposit));
+            }
+        }
+        match self.registry_queries(&poll, &caller) {
+            Some(queries) => {
+                queries
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(ELIGIBILITY_CALLBACK_GAS)
+                            .with_attached_deposit(env::attached_deposit())
+                            .on_ballot_verified(caller.clone(), poll_id, ballot),
+                    )
+                    .then(refund_on_failure(caller, env::attached_deposit()));
+            }
+            None => self.record_ballot(caller, poll_id, ballot.0, 0)?,
+        }
+        Ok(())
+    }
 
-        let required_deposit =
-            (env::storage_usage() - storage_start) as u128 * env::storage_byte_cost();
-        if storage_deposit < required_deposit {
-            return Err(PollError::InsufficientDeposit(required_deposit));
+    /// Reveals the sealed poll `secret_key`, which allows to `tally` the ballots.
+    /// Must be called by the poll creator once the poll ended, at latest `REVEAL_PERIOD`
+    /// after the poll end, otherwise the poll can be cancelled by `finalize`. Panics if the
+    /// key doesn't match the poll public key or was already revealed.
+    /// Returns `NotCreator` error if the caller is not the poll creator, `NotEnded` error if
+    /// the poll has not ended yet and `NotSealed` error if the poll is not sealed.
+    /// emits reveal_key event
+    #[handle_result]
+    pub fn reveal_key(
+        &mut self,
+        poll_id: PollId,
+        secret_key: Base64VecU8,
+    ) -> Result<(), PollError> {
+        let poll = self.creator_poll(poll_id)?;
+        if poll.ends_at >= env::block_timestamp_ms() {
+            return Err(PollError::NotEnded);
         }
+        let mut sealed = self
+            .sealed_polls
+            .get(&poll_id)
+            .ok_or(PollError::NotSealed)?;
+        require!(
+            sealed.secret_key.is_none(),
+            "secret key is already revealed"
+        );
+        let sk: [u8; sealed::KEY_LEN] = secret_key
+            .0
+            .as_slice()
+            .try_into()
+            .unwrap_or_else(|_| env::panic_str("wrong secret key length"));
+        require!(
+            sealed::public_key(&sk)[..] == sealed.public_key.0[..],
+            "secret key doesn't match the poll public key"
+        );
+        sealed.secret_key = Some(secret_key);
+        self.sealed_polls.insert(&poll_id, &sealed);
+        emit_reveal_key(poll_id);
         Ok(())
     }
 
+    /// Decrypts and counts the next (at most `limit`) ballots of a sealed poll, once the
+    /// secret key is revealed. Invalid ballots (which can't be decrypted or have invalid
+    /// answers) are skipped. Tallied ballots are removed.
+    /// Can be called by anyone. Returns number of ballots which are not tallied yet.
+    /// If `limit` is not specified or is bigger than `MAX_TALLY_LIMIT`, then
+    /// `MAX_TALLY_LIMIT` is used.
+    /// Returns `NotSealed` error if the poll is not sealed, `KeyNotRevealed` error if the
+    /// secret key is not revealed, and `AlreadyFinalized` or `Cancelled` errors if the
+    /// poll is finalized or cancelled.
+    #[handle_result]
+    pub fn tally(&mut self, poll_id: PollId, limit: Option<u64>) -> Result<u64, PollError> {
+        self.assert_not_finalized(poll_id)?;
+        let mut sealed = self
+            .sealed_polls
+            .get(&poll_id)
+            .ok_or(PollError::NotSealed)?;
+        let sk: [u8; sealed::KEY_LEN] = match &sealed.secret_key {
+            Some(sk) => sk.0.as_slice().try_into().unwrap(),
+            None => return Err(PollError::KeyNotRevealed),
+        };
+        let pk: [u8; sealed::KEY_LEN] = sealed.public_key.0.as_slice().try_into().unwrap();
+        let questions = self
+            .polls
+            .get(&poll_id)
+            .ok_or(PollError::NotFound)?
+            .questions;
+        let mut results = self.results.get(&poll_id).ok_or(PollError::NotFound)?;
+        let limit = limit.map_or(MAX_TALLY_LIMIT, |l| std::cmp::min(l, MAX_TALLY_LIMIT));
+        let end = std::cmp::min(results.participants_num, sealed.tallied + limit);
+        for idx in sealed.tallied..end {
+            let ballot = match self.ballots.remove(&(poll_id, idx)) {
+                Some(b) => b,
+                None => continue,
+            };
+            let answers = sealed::decrypt(&sk, &pk, &ballot.ciphertext)
+                .and_then(|b| Vec::<Option<Answer>>::try_from_slice(&b).ok());
+            // add_answers validates the answers before updating the results, so invalid
+            // ballots are not counted
+            match answers {
+                Some(answers)
+                    if add_answers(&questions, &answers, &mut results, ballot.weight).is_ok() =>
+                {
+                    self.store_text_answers(poll_id, &answers, idx);
+                }
+                _ => sealed.invalid += 1,
+            }
+        }
+        sealed.tallied = end;
+        self.sealed_polls.insert(&poll_id, &sealed);
+        self.results.insert(&poll_id, &results);
+        Ok(results.participants_num - end)
+    }
+
+    /// Finalizes the poll once it ended: sets the results status to `Finished`. For IAH only
+    /// polls, the registry `humans_count` is queried and stored with the results, to report
+    /// participation relative to the verified population (see `quorum`).
+    /// Sealed polls can be finalized once all ballots are tallied. If the secret key of a
+    /// sealed poll was not revealed within `REVEAL_PERIOD` after the poll end, the poll is
+    /// cancelled instead, so the response bonds can be withdrawn.
+    /// Can be called by anyone.
+    /// it panics if
+    /// - poll not found
+    /// - poll has not ended yet
+    /// - poll is already finalized
+    /// emits finalize event
+    #[handle_result]
+    pub fn finalize(&mut self, poll_id: PollId) -> Result<PromiseOrValue<()>, PollError> {
+        let poll = match self.polls.get(&poll_id) {
+            None => return Err(PollError::NotFound),
+            Some(poll) => poll,
+        };
+        if poll.ends_at >= env::block_timestamp_ms() {
+            return Err(PollError::NotEnded);
+        }
+        self.assert_not_finalized(poll_id)?;
+        if let Some(sealed) = self.sealed_polls.get(&poll_id) {
+            if sealed.secret_key.is_none() {
+                if poll.ends_at + REVEAL_PERIOD >= env::block_timestamp_ms() {
+                    return Err(PollError::KeyNotRevealed);
+                }
+                self.cancel(poll_id)?;
+                return Ok(PromiseOrValue::Value(()));
+            }
+            let participants = self.results.get(&poll_id).map_or(0, |r| r.participants_num);
+            if sealed.tallied < participants {
+                return Err(PollError::NotTallied);
+            }
+        }
+        if poll.iah_only {
+            let p = ext_registry::ext(self.sbt_registry.clone())
+                .with_static_gas(HUMANS_COUNT_GAS)
+                .humans_count()
+                .then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(FINALIZE_CALLBACK_GAS)
+                        .on_humans_count(poll_id),
+                );
+            return Ok(PromiseOrValue::Promise(p));
+        }
+        self.finalize_results(poll_id, None)?;
+        Ok(PromiseOrValue::Value(()))
+    }
+
+    /// Removes ended polls from the open polls index (used by `active_polls`), checking at
+    /// most `limit` open polls starting from the `from_poll` poll id (inclusive). The polls
+    /// can still be finalized.
+    /// If limit is not specified or is bigger than `MAX_POLLS_LIMIT`, then `MAX_POLLS_LIMIT`
+    /// is used.
+    /// Can be called by anyone. Returns the number of removed polls.
+    pub fn prune_ended_polls(&mut self, from_poll: Option<PollId>, limit: Option<u64>) -> u64 {
+        let now = env::block_timestamp_ms();
+        let from = Bound::Included(from_poll.unwrap_or(0));
+        let ended: Vec<PollId> = self
+            .open_polls
+            .range((from, Bound::Unbounded))
+            .take(polls_limit(limit))
+            .filter(|(_, (_, ends_at))| *ends_at < now)
+            .map(|(id, _)| id)
+            .collect();
+        for id in &ended {
+            self.open_polls.remove(id);
+        }
+        ended.len() as u64
+    }
+
+    /// Returns the response bond locked by the caller, once the poll ended or was cancelled.
+    /// Returns `NotEnded` error if the poll is active or didn't start yet and `NoBond` error
+    /// if the caller has no bond in the poll.
+    /// emits withdraw_bond event
+    #[handle_result]
+    pub fn withdraw_bond(&mut self, poll_id: PollId) -> Result<Promise, PollError> {
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        let cancelled = matches!(
+            self.results.get(&poll_id),
+            Some(Results {
+                status: Status::Cancelled,
+                ..
+            })
+        );
+        if !cancelled && poll.ends_at >= env::block_timestamp_ms() {
+            return Err(PollError::NotEnded);
+        }
+        let caller = env::predecessor_account_id();
+        let bond = self
+            .bonds
+            .remove(&(poll_id, caller.clone()))
+            .ok_or(PollError::NoBond)?;
+        emit_withdraw_bond(poll_id, caller.clone(), bond);
+        Ok(Promise::new(caller).transfer(bond))
+    }
+
     /**********
      * PRIVATE
      **********/
 
-    /// Callback for the respond method.
+    /// Callback for the registry `humans_count` query made in `finalize`.
+    #[private]
+    #[handle_result]
+    pub fn on_humans_count(
+        &mut self,
+        poll_id: PollId,
+        #[callback_result] humans_count: Result<u64, PromiseError>,
+    ) -> Result<(), PollError> {
+        let humans_count = humans_count.expect("registry humans_count failed");
+        self.finalize_results(poll_id, Some(humans_count))
+    }
+
+    /// Callback for the respond method of polls with eligibility. Results of the registry
+    /// queries are: `is_human` (only for IAH polls) followed by `sbt_tokens_by_owner` for
+    /// each issuer of the poll eligibility class set.
+    #[private]
+    #[payable]
+    #[handle_result]
+    pub fn on_eligibility_verified(
+        &mut self,
+        caller: AccountId,
+        poll_id: PollId,
+        answers: Vec<Option<Answer>>,
+    ) -> Result<(), PollError> {
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        let (iah_tokens, weight) = verify_proof(&poll)?;
+        self.record_response(iah_tokens, poll.iah_only, caller, poll_id, answers, weight)
+    }
+
+    /// Callback for the `respond_encrypted` method. Results of the registry queries are the
+    /// same as in `on_eligibility_verified`.
+    #[private]
+    #[payable]
+    #[handle_result]
+    pub fn on_ballot_verified(
+        &mut self,
+        caller: AccountId,
+        poll_id: PollId,
+        ballot: Base64VecU8,
+    ) -> Result<(), PollError> {
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        let (_, weight) = verify_proof(&poll)?;
+        self.record_ballot(caller, poll_id, ballot.0, weight)
+    }
+
+    /// Callback for the respond method. The respond deposit is attached to the callback and
+    /// must cover the storage used by the recorded response.
     #[private]
+    #[payable]
     #[handle_result]
     pub fn on_human_verifed(
         &mut self,
@@ -165,16 +895,64 @@ impl Contract {
         poll_id: PollId,
         answers: Vec<Option<Answer>>,
     ) -> Result<(), PollError> {
+        let weight = proof_size(&tokens);
+        self.record_response(tokens, iah_only, caller, poll_id, answers, weight)
+    }
+
+    /// Callback for the response recording (`on_human_verifed`, `on_eligibility_verified`
+    /// or `on_ballot_verified`). If the response was rejected, returns the `deposit` to the
+    /// `caller`: deposit attached to the failed callback is returned to this contract.
+    /// Returns true if the response was recorded.
+    #[private]
+    pub fn on_response_recorded(
+        &mut self,
+        caller: AccountId,
+        deposit: U128,
+        #[callback_result] recorded: Result<(), PromiseError>,
+    ) -> bool {
+        if recorded.is_err() && deposit.0 > 0 {
+            Promise::new(caller).transfer(deposit.0);
+        }
+        recorded.is_ok()
+    }
+
+    /**********
+     * ADMIN
+     **********/
+
+    /// Enables or disables the limit of one active poll per creator per tag.
+    /// Must be called by the admin.
+    pub fn set_unique_active_tags(&mut self, enabled: bool) {
+        self.assert_admin();
+        self.unique_active_tags = enabled;
+    }
+
+    /**********
+     * INTERNAL
+     **********/
+
+    /// Validates and records the response. `weight` is used only by weighted polls.
+    fn record_response(
+        &mut self,
+        tokens: SBTs,
+        iah_only: bool,
+        caller: AccountId,
+        poll_id: PollId,
+        answers: Vec<Option<Answer>>,
+        weight: u32,
+    ) -> Result<(), PollError> {
+        let storage_start = env::storage_usage();
         // Check for IAH requirement if iah_only is set
         if iah_only && tokens.is_empty() {
             return Err(PollError::NotIAH);
         }
 
         // Retrieve questions and poll results
-        let questions = match self.polls.get(&poll_id) {
-            Some(poll) => poll.questions,
+        let poll = match self.polls.get(&poll_id) {
+            Some(poll) => poll,
             None => return Err(PollError::NotFound),
         };
+        let questions = &poll.questions;
         let mut poll_results = match self.results.get(&poll_id) {
             Some(results) => results,
             None => return Err(PollError::NotFound),
@@ -185,121 +963,758 @@ impl Contract {
             return Err(PollError::IncorrectAnswerVector);
         }
 
-        for i in 0..questions.len() {
-            let q = &questions[i];
-            let a = &answers[i];
-
-            match (a, &mut poll_results.results[i]) {
-                (Some(Answer::YesNo(response)), PollResult::YesNo((yes_count, no_count))) => {
-                    if *response {
-                        *yes_count += 1;
-                    } else {
-                        *no_count += 1;
-                    }
-                }
-                (Some(Answer::TextChoices(choices)), PollResult::TextChoices(results))
-                | (Some(Answer::PictureChoices(choices)), PollResult::PictureChoices(results)) => {
-                    for choice in choices {
-                        results[*choice as usize] += 1;
-                    }
-                }
-                (Some(Answer::OpinionRange(opinion)), PollResult::OpinionRange(results)) => {
-                    if *opinion < 1 || *opinion > 10 {
-                        return Err(PollError::OpinionRange);
-                    }
-                    results.sum += *opinion as u64;
-                    results.num += 1;
-                }
-                (Some(Answer::TextAnswer(answer)), PollResult::TextAnswer) => {
-                    if answer.len() > MAX_TEXT_ANSWER_LEN {
-                        return Err(PollError::AnswerTooLong(answer.len()));
+        let key = (poll_id, caller.clone());
+        let (edit, response_idx) = match self.participants.get(&key) {
+            Some(idx) => (true, idx),
+            None => (false, poll_results.participants_num),
+        };
+        if edit {
+            if !poll.editable {
+                return Err(PollError::AlredyAnswered);
+            }
+            // subtract the previous contribution
+            if let Some(prev) = self.responses.get(&key) {
+                remove_contribution(&prev, &mut poll_results.results);
+                for (i, a) in applicable_questions(questions, &prev).iter().enumerate() {
+                    if *a {
+                        poll_results.applicable[i] -= 1;
                     }
                 }
-                // if the answer is not provided do nothing
-                (None, _) => {
-                    if q.required {
-                        return Err(PollError::RequiredAnswer(i));
-                    }
+            }
+            // the previous text answers are replaced (or removed) by the edited response
+            for (i, r) in poll_results.results.iter().enumerate() {
+                if matches!(r, PollResult::TextAnswer) {
+                    self.text_answers.remove(&(poll_id, i as u32, response_idx));
                 }
-                (_, _) => return Err(PollError::WrongAnswer),
             }
         }
 
-        // Update the participants lookupset to ensure user cannot answer twice
-        self.participants.insert(&(poll_id, caller.clone()));
-        poll_results.participants_num += 1;
+        add_answers(questions, &answers, &mut poll_results, weight)?;
+        self.store_text_answers(poll_id, &answers, response_idx);
+        if poll.editable {
+            // text answers don't contribute to the results, so we don't store them.
+            let contribution: Vec<Option<Answer>> = answers
+                .into_iter()
+                .map(|a| match a {
+                    Some(Answer::TextAnswer(_)) => None,
+                    a => a,
+                })
+                .collect();
+            self.responses.insert(&key, &contribution);
+        }
+        let mut bond = 0;
+        if edit {
+            emit_respond_updated(poll_id, caller.clone());
+        } else {
+            // Update the participants to ensure user cannot answer twice
+            bond = self.add_participant(poll_id, &poll, &mut poll_results, &caller)?;
+        }
         self.results.insert(&poll_id, &poll_results);
-        emit_respond(poll_id, caller);
-
-        Ok(())
+        // storage can decrease when editing a response
+        charge_storage(storage_start, caller, bond)
     }
 
-    /**********
-     * INTERNAL
-     **********/
+    /// Records the encrypted response of a sealed poll. `weight` is used only by weighted
+    /// polls.
+    fn record_ballot(
+        &mut self,
+        caller: AccountId,
+        poll_id: PollId,
+        ciphertext: Vec<u8>,
+        weight: u32,
+    ) -> Result<(), PollError> {
+        let storage_start = env::storage_usage();
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        let mut poll_results = self.results.get(&poll_id).ok_or(PollError::NotFound)?;
 
-    fn assert_active(&self, poll_id: PollId) -> Result<(), PollError> {
-        let poll = match self.polls.get(&poll_id) {
-            Some(poll) => poll,
-            None => return Err(PollError::NotFound),
+        let key = (poll_id, caller.clone());
+        let (edit, response_idx) = match self.participants.get(&key) {
+            Some(_) if !poll.editable => return Err(PollError::AlredyAnswered),
+            Some(idx) => (true, idx),
+            None => (false, poll_results.participants_num),
         };
-        let current_timestamp = env::block_timestamp_ms();
-        if poll.starts_at > current_timestamp || poll.ends_at < current_timestamp {
-            return Err(PollError::NotActive);
+        self.ballots
+            .insert(&(poll_id, response_idx), &Ballot { ciphertext, weight });
+        let mut bond = 0;
+        if edit {
+            emit_respond_updated(poll_id, caller.clone());
+        } else {
+            bond = self.add_participant(poll_id, &poll, &mut poll_results, &caller)?;
+            self.results.insert(&poll_id, &poll_results);
         }
-        Ok(())
+
+        // storage can decrease when replacing a ballot
+        charge_storage(storage_start, caller, bond)
     }
 
-    fn assert_not_answered(&self, poll_id: PollId, caller: &AccountId) -> Result<(), PollError> {
-        if self.participants.contains(&(poll_id, caller.clone())) {
-            return Err(PollError::AlredyAnswered);
+    /// Records a new poll participant and locks the poll response bond. Returns the bond.
+    /// Returns `MaxParticipants` error if the poll reached the participants limit.
+    fn add_participant(
+        &mut self,
+        poll_id: PollId,
+        poll: &Poll,
+        results: &mut Results,
+        caller: &AccountId,
+    ) -> Result<Balance, PollError> {
+        if poll
+            .max_participants
+            .map_or(false, |m| results.participants_num >= m)
+        {
+            return Err(PollError::MaxParticipants);
+        }
+        let response_idx = results.participants_num;
+        self.participants
+            .insert(&(poll_id, caller.clone()), &response_idx);
+        self.responders.insert(&(poll_id, response_idx), caller);
+        results.participants_num += 1;
+        let bond = poll.response_bond.map_or(0, |b| b.0);
+        if bond > 0 {
+            self.bonds.insert(&(poll_id, caller.clone()), &bond);
+        }
+        emit_respond(poll_id, caller.clone());
+        Ok(bond)
+    }
+
+    /// Returns `MaxParticipants` error if the poll reached the participants limit.
+    fn assert_participants_limit(&self, poll: &Poll, poll_id: PollId) -> Result<(), PollError> {
+        if let Some(max) = poll.max_participants {
+            let participants = self.results.get(&poll_id).map_or(0, |r| r.participants_num);
+            if participants >= max {
+                return Err(PollError::MaxParticipants);
+            }
         }
         Ok(())
     }
 
-    fn initialize_results(&mut self, poll_id: PollId, questions: &[Question]) {
-        let mut index = 0;
-        let results: Vec<PollResult> = questions
-            .iter()
-            .map(|question| {
-                let result = match &question.question_type {
-                    Answer::YesNo(_) => PollResult::YesNo((0, 0)),
-                    Answer::TextChoices(choices) => PollResult::TextChoices(vec![0; choices.len()]),
-                    Answer::PictureChoices(_) => PollResult::PictureChoices(Vec::new()),
-                    Answer::OpinionRange(_) => {
-                        PollResult::OpinionRange(OpinionRangeResult { sum: 0, num: 0 })
-                    }
-                    Answer::TextAnswer(_) => PollResult::TextAnswer,
-                };
-                index += 1;
-                result
-            })
-            .collect();
+    /// Stores text answers of the `response_idx` response. Answers must be already validated.
+    fn store_text_answers(
+        &mut self,
+        poll_id: PollId,
+        answers: &[Option<Answer>],
+        response_idx: u64,
+    ) {
+        for (i, a) in answers.iter().enumerate() {
+            if let Some(Answer::TextAnswer(answer)) = a {
+                self.text_answers
+                    .insert(&(poll_id, i as u32, response_idx), answer);
+            }
+        }
+    }
+
+    /// Returns the registry queries required to verify the `caller` response: `is_human`
+    /// (only for IAH polls) followed by `sbt_tokens_by_owner` for each issuer of the poll
+    /// eligibility class set. Returns None if no verification is required.
+    fn registry_queries(&self, poll: &Poll, caller: &AccountId) -> Option<Promise> {
+        let reg = || ext_registry::ext(self.sbt_registry.clone());
+        let mut queries: Option<Promise> = None;
+        if poll.iah_only {
+            queries = Some(reg().is_human(caller.clone()));
+        }
+        for (issuer, classes) in poll.eligibility.iter().flatten() {
+            let from_class = classes.iter().min().copied();
+            let q = reg().sbt_tokens_by_owner(
+                caller.clone(),
+                Some(issuer.clone()),
+                from_class,
+                None,
+                None,
+            );
+            queries = Some(match queries {
+                None => q,
+                Some(p) => p.and(q),
+            });
+        }
+        queries
+    }
+
+    fn assert_admin(&self) {
+        require!(env::predecessor_account_id() == self.admin, "not an admin");
+    }
+
+    /// Returns the poll if it's called by the poll creator and the poll is neither
+    /// finalized nor cancelled.
+    fn creator_poll(&self, poll_id: PollId) -> Result<Poll, PollError> {
+        let poll = self.polls.get(&poll_id).ok_or(PollError::NotFound)?;
+        if poll.creator != env::predecessor_account_id() {
+            return Err(PollError::NotCreator);
+        }
+        self.assert_not_finalized(poll_id)?;
+        Ok(poll)
+    }
+
+    /// Records the `poll_id` as the last poll of the `creator` with the `tags`.
+    fn index_tags(&mut self, creator: &AccountId, tags: &[String], poll_id: PollId) {
+        for tag in tags {
+            self.creator_tags
+                .insert(&(creator.clone(), tag.clone()), &poll_id);
+        }
+    }
+
+    /// Adds the poll to the `tag_polls` index.
+    fn index_tag_polls(&mut self, tags: &[String], poll_id: PollId) {
+        for tag in tags {
+            let mut ids = self.tag_polls.get(tag).unwrap_or_default();
+            // keep the creation order, tags can be repeated
+            if let Err(pos) = ids.binary_search(&poll_id) {
+                ids.insert(pos, poll_id);
+                self.tag_polls.insert(tag, &ids);
+            }
+        }
+    }
+
+    fn polls_page(
+        &self,
+        ids: &[PollId],
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<(PollId, Poll)> {
+        ids.iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(polls_limit(limit))
+            .filter_map(|id| self.polls.get(id).map(|p| (*id, p)))
+            .collect()
+    }
+
+    /// Returns an error if the `creator` has a poll with one of the `tags`, which didn't end
+    /// and is not finalized.
+    fn assert_no_active_tag_poll(
+        &self,
+        creator: &AccountId,
+        tags: &[String],
+    ) -> Result<(), PollError> {
+        let now = env::block_timestamp_ms();
+        for tag in tags {
+            let poll_id = match self.creator_tags.get(&(creator.clone(), tag.clone())) {
+                Some(id) => id,
+                None => continue,
+            };
+            let ended = self.polls.get(&poll_id).map_or(true, |p| p.ends_at < now);
+            if !ended && self.assert_not_finalized(poll_id).is_ok() {
+                return Err(PollError::ActiveTagPoll(poll_id));
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_active(&self, poll_id: PollId) -> Result<(), PollError> {
+        let poll = match self.polls.get(&poll_id) {
+            Some(poll) => poll,
+            None => return Err(PollError::NotFound),
+        };
+        let current_timestamp = env::block_timestamp_ms();
+        if poll.starts_at > current_timestamp || poll.ends_at < current_timestamp {
+            return Err(PollError::NotActive);
+        }
+        if let Some(Results {
+            status: Status::Cancelled,
+            ..
+        }) = self.results.get(&poll_id)
+        {
+            return Err(PollError::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn assert_not_answered(&self, poll_id: PollId, caller: &AccountId) -> Result<(), PollError> {
+        if self.participants.contains_key(&(poll_id, caller.clone())) {
+            return Err(PollError::AlredyAnswered);
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the poll is not found, finalized or cancelled.
+    fn assert_not_finalized(&self, poll_id: PollId) -> Result<(), PollError> {
+        match self.results.get(&poll_id) {
+            None => Err(PollError::NotFound),
+            Some(Results {
+                status: Status::Finished,
+                ..
+            }) => Err(PollError::AlreadyFinalized),
+            Some(Results {
+                status: Status::Cancelled,
+                ..
+            }) => Err(PollError::Cancelled),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn cancel(&mut self, poll_id: PollId) -> Result<(), PollError> {
+        let mut results = self.results.get(&poll_id).ok_or(PollError::NotFound)?;
+        results.status = Status::Cancelled;
+        self.results.insert(&poll_id, &results);
+        self.open_polls.remove(&poll_id);
+        emit_cancel_poll(poll_id);
+        Ok(())
+    }
+
+    fn finalize_results(
+        &mut self,
+        poll_id: PollId,
+        humans_count: Option<u64>,
+    ) -> Result<(), PollError> {
+        self.assert_not_finalized(poll_id)?;
+        let mut results = self.results.get(&poll_id).ok_or(PollError::NotFound)?;
+        results.status = Status::Finished;
+        results.humans_count = humans_count;
+        self.results.insert(&poll_id, &results);
+        self.open_polls.remove(&poll_id);
+        emit_finalize(poll_id, humans_count);
+        Ok(())
+    }
+
+    fn initialize_results(&mut self, poll_id: PollId, questions: &[Question], weighted: bool) {
+        let init = || -> Vec<PollResult> {
+            questions
+                .iter()
+                .map(|question| match &question.question_type {
+                    Answer::YesNo(_) => PollResult::YesNo((0, 0)),
+                    Answer::TextChoices(choices) => PollResult::TextChoices(vec![0; choices.len()]),
+                    Answer::PictureChoices(_) => PollResult::PictureChoices(Vec::new()),
+                    Answer::OpinionRange(_) => {
+                        PollResult::OpinionRange(OpinionRangeResult { sum: 0, num: 0 })
+                    }
+                    Answer::TextAnswer(_) => PollResult::TextAnswer,
+                })
+                .collect()
+        };
+        let results = init();
+        let weighted = weighted.then(|| WeightedResults {
+            participants_weight: 0,
+            results: init(),
+        });
 
         self.results.insert(
             &poll_id,
             &Results {
                 status: Status::NotStarted,
                 participants_num: 0,
+                applicable: vec![0; results.len()],
+                humans_count: None,
                 results,
+                weighted,
             },
         );
     }
 }
 
+/// Validates the `answers` and adds them to the poll `results`. For weighted polls, the
+/// answers are also added to the weighted results with the `weight`.
+/// Text answers are only validated, they must be stored separately.
+fn add_answers(
+    questions: &[Question],
+    answers: &[Option<Answer>],
+    results: &mut Results,
+    weight: u32,
+) -> Result<(), PollError> {
+    let applicable = validate_answers(questions, answers, results)?;
+    for (i, a) in answers.iter().enumerate() {
+        if !applicable[i] {
+            continue;
+        }
+        results.applicable[i] += 1;
+
+        match (a, &mut results.results[i]) {
+            (Some(Answer::YesNo(response)), PollResult::YesNo((yes_count, no_count))) => {
+                if *response {
+                    *yes_count += 1;
+                } else {
+                    *no_count += 1;
+                }
+            }
+            (Some(Answer::TextChoices(choices)), PollResult::TextChoices(results))
+            | (Some(Answer::PictureChoices(choices)), PollResult::PictureChoices(results)) => {
+                for choice in choices {
+                    results[*choice as usize] += 1;
+                }
+            }
+            (Some(Answer::OpinionRange(opinion)), PollResult::OpinionRange(results)) => {
+                results.sum += *opinion as u64;
+                results.num += 1;
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(w) = results.weighted.as_mut() {
+        // weighted polls are not editable, so this is always a new response
+        w.participants_weight += weight as u64;
+        add_weighted_contribution(answers, &mut w.results, weight);
+    }
+    Ok(())
+}
+
+/// Checks the `answers` against the poll `questions` and current `results`, without
+/// updating the results. Returns the list of applicable questions.
+fn validate_answers(
+    questions: &[Question],
+    answers: &[Option<Answer>],
+    results: &Results,
+) -> Result<Vec<bool>, PollError> {
+    if questions.len() != answers.len() {
+        return Err(PollError::IncorrectAnswerVector);
+    }
+    let applicable = applicable_questions(questions, answers);
+    for (i, (q, a)) in questions.iter().zip(answers).enumerate() {
+        // not applicable questions are optional, but can't be answered
+        if !applicable[i] {
+            if a.is_some() {
+                return Err(PollError::NotApplicable(i));
+            }
+            continue;
+        }
+
+        match (a, &results.results[i]) {
+            (Some(Answer::YesNo(_)), PollResult::YesNo(_)) => (),
+            (Some(Answer::TextChoices(choices)), PollResult::TextChoices(results))
+            | (Some(Answer::PictureChoices(choices)), PollResult::PictureChoices(results)) => {
+                if choices.iter().any(|c| *c as usize >= results.len()) {
+                    return Err(PollError::WrongAnswer);
+                }
+            }
+            (Some(Answer::OpinionRange(opinion)), PollResult::OpinionRange(_)) => {
+                if *opinion < 1 || *opinion > 10 {
+                    return Err(PollError::OpinionRange);
+                }
+            }
+            (Some(Answer::TextAnswer(answer)), PollResult::TextAnswer) => {
+                if answer.len() > MAX_TEXT_ANSWER_LEN {
+                    return Err(PollError::AnswerTooLong(answer.len()));
+                }
+            }
+            // if the answer is not provided do nothing
+            (None, _) => {
+                if q.required {
+                    return Err(PollError::RequiredAnswer(i));
+                }
+            }
+            (_, _) => return Err(PollError::WrongAnswer),
+        }
+    }
+    Ok(applicable)
+}
+
+/// Verifies the registry queries results of a response (see `Contract::registry_queries`).
+/// Returns the IAH proof and the response weight.
+fn verify_proof(poll: &Poll) -> Result<(SBTs, u32), PollError> {
+    let mut idx = 0;
+    let mut iah_tokens: SBTs = vec![];
+    if poll.iah_only {
+        iah_tokens = promise_result_json(idx);
+        if iah_tokens.is_empty() {
+            return Err(PollError::NotIAH);
+        }
+        idx += 1;
+    }
+    // list of (issuer, classes of the returned tokens)
+    let mut held: Vec<(&AccountId, Vec<ClassId>)> = vec![];
+    for (issuer, classes) in poll.eligibility.iter().flatten() {
+        let tokens: Vec<(AccountId, Vec<OwnedToken>)> = promise_result_json(idx);
+        idx += 1;
+        let issuer_held: Vec<ClassId> = tokens
+            .iter()
+            .flat_map(|(_, ts)| ts)
+            .map(|t| t.metadata.class)
+            .collect();
+        if !classes.iter().all(|c| issuer_held.contains(c)) {
+            return Err(PollError::NotEligible);
+        }
+        held.push((issuer, issuer_held));
+    }
+    let weight = match &poll.weighting {
+        Some(Weighting::ClassWeights(weights)) => weights
+            .iter()
+            .filter(|(i, c, _)| held.iter().any(|(hi, hc)| *hi == i && hc.contains(c)))
+            .map(|(_, _, w)| w)
+            .sum(),
+        _ => proof_size(&iah_tokens) + held.iter().map(|(_, hc)| hc.len() as u32).sum::<u32>(),
+    };
+    Ok((iah_tokens, weight))
+}
+
+/// Returns the storage (in bytes) used by the participant records of a new responder: the
+/// participants and responders entries.
+fn participant_storage(caller: &AccountId) -> u64 {
+    let caller_len = caller.try_to_vec().unwrap().len() as u64;
+    // storage key prefix is a single byte; participants: (poll_id, caller) -> response_idx
+    let storage = 1 + 8 + caller_len + 8 + STORAGE_RECORD_OVERHEAD;
+    // responders: (poll_id, response_idx) -> caller
+    storage + 1 + 8 + 8 + caller_len + STORAGE_RECORD_OVERHEAD
+}
+
+/// Returns a lower bound of the storage (in bytes) used by a new ballot: the participant
+/// records and the ballot.
+fn min_ballot_storage(caller: &AccountId, ballot_len: usize) -> u64 {
+    // ballot key: prefix, poll_id and response_idx; value: ciphertext and weight
+    let ballot_key_len = 1 + 8 + 8;
+    let ballot_len = 4 + ballot_len as u64 + 4;
+    participant_storage(caller) + ballot_key_len + ballot_len + STORAGE_RECORD_OVERHEAD
+}
+
+/// Subtracts answers previously recorded for an editable poll from the poll results.
+fn remove_contribution(answers: &[Option<Answer>], results: &mut [PollResult]) {
+    for (a, r) in answers.iter().zip(results.iter_mut()) {
+        match (a, r) {
+            (Some(Answer::YesNo(true)), PollResult::YesNo((yes_count, _))) => *yes_count -= 1,
+            (Some(Answer::YesNo(false)), PollResult::YesNo((_, no_count))) => *no_count -= 1,
+            (Some(Answer::TextChoices(choices)), PollResult::TextChoices(results))
+            | (Some(Answer::PictureChoices(choices)), PollResult::PictureChoices(results)) => {
+                for choice in choices {
+                    results[*choice as usize] -= 1;
+                }
+            }
+            (Some(Answer::OpinionRange(opinion)), PollResult::OpinionRange(results)) => {
+                results.sum -= *opinion as u64;
+                results.num -= 1;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Adds answers of a response to the weighted results, each answer counted `weight` times.
+fn add_weighted_contribution(answers: &[Option<Answer>], results: &mut [PollResult], weight: u32) {
+    for (a, r) in answers.iter().zip(results.iter_mut()) {
+        match (a, r) {
+            (Some(Answer::YesNo(true)), PollResult::YesNo((yes_count, _))) => *yes_count += weight,
+            (Some(Answer::YesNo(false)), PollResult::YesNo((_, no_count))) => *no_count += weight,
+            (Some(Answer::TextChoices(choices)), PollResult::TextChoices(results))
+            | (Some(Answer::PictureChoices(choices)), PollResult::PictureChoices(results)) => {
+                for choice in choices {
+                    results[*choice as usize] += weight;
+                }
+            }
+            (Some(Answer::OpinionRange(opinion)), PollResult::OpinionRange(results)) => {
+                results.sum += *opinion as u64 * weight as u64;
+                results.num += weight as u64;
+            }
+            _ => (),
+        }
+    }
+}
+
+fn polls_limit(limit: Option<u64>) -> usize {
+    limit.map_or(MAX_POLLS_LIMIT, |l| std::cmp::min(l, MAX_POLLS_LIMIT)) as usize
+}
+
+/// Returns a callback returning the `deposit` to the `caller` when the response is rejected.
+fn refund_on_failure(caller: AccountId, deposit: Balance) -> Promise {
+    Contract::ext(env::current_account_id())
+        .with_static_gas(REFUND_CALLBACK_GAS)
+        .on_response_recorded(caller, U128(deposit))
+}
+
+/// Checks that the attached deposit covers the storage used since `storage_start` and the
+/// response `bond`, and refunds the surplus to the `caller`.
+fn charge_storage(storage_start: u64, caller: AccountId, bond: Balance) -> Result<(), PollError> {
+    let required_deposit = env::storage_usage().saturating_sub(storage_start) as u128
+        * env::storage_byte_cost()
+        + bond;
+    let deposit = env::attached_deposit();
+    if deposit < required_deposit {
+        return Err(PollError::InsufficientDeposit(required_deposit));
+    }
+    if deposit > required_deposit {
+        Promise::new(caller).transfer(deposit - required_deposit);
+    }
+    Ok(())
+}
+
+/// Returns number of tokens in the proof.
+fn proof_size(proof: &SBTs) -> u32 {
+    proof.iter().map(|(_, tokens)| tokens.len() as u32).sum()
+}
+
+/// Returns a lower bound of the storage (in bytes) used by a new response: the participant
+/// records, the text answers and, for editable polls, the recorded answers.
+fn min_response_storage(
+    poll_id: PollId,
+    caller: &AccountId,
+    answers: &[Option<Answer>],
+    editable: bool,
+) -> u64 {
+    let mut storage = participant_storage(caller);
+    // text answer key: prefix, poll_id, question_idx and response_idx
+    let text_key_len = 1 + 8 + 4 + 8;
+    for a in answers {
+        if let Some(Answer::TextAnswer(text)) = a {
+            storage +=
+                text_key_len + text.try_to_vec().unwrap().len() as u64 + STORAGE_RECORD_OVERHEAD;
+        }
+    }
+    if editable {
+        // text answers are not stored in the responses
+        let contribution: Vec<Option<&Answer>> = answers
+            .iter()
+            .map(|a| match a {
+                Some(Answer::TextAnswer(_)) => None,
+                a => a.as_ref(),
+            })
+            .collect();
+        // storage key prefix is a single byte
+        let key_len = 1 + (poll_id, caller).try_to_vec().unwrap().len() as u64;
+        storage +=
+            key_len + contribution.try_to_vec().unwrap().len() as u64 + STORAGE_RECORD_OVERHEAD;
+    }
+    storage
+}
+
+/// Returns JSON decoded result of the promise `idx`. Panics if the promise failed.
+fn promise_result_json<T: near_sdk::serde::de::DeserializeOwned>(idx: u64) -> T {
+    match env::promise_result(idx) {
+        PromiseResult::Successful(value) => {
+            serde_json::from_slice(&value).expect("can't decode registry response")
+        }
+        _ => env::panic_str("registry query failed"),
+    }
+}
+
+fn assert_response_limits(max_participants: Option<u64>, response_bond: Option<U128>) {
+    require!(
+        max_participants != Some(0),
+        "max_participants must be positive"
+    );
+    require!(
+        response_bond.map_or(true, |b| b.0 > 0),
+        "response bond must be positive"
+    );
+}
+
+/// Validates the poll eligibility class set.
+fn assert_eligibility(eligibility: &Option<ClassSet>) {
+    if let Some(e) = eligibility {
+        require!(
+            !e.is_empty() && e.len() <= MAX_ELIGIBILITY_ISSUERS,
+            format!(
+                "eligibility must have between 1 and {} issuers",
+                MAX_ELIGIBILITY_ISSUERS
+            )
+        );
+        require!(
+            e.iter().all(|(_, classes)| !classes.is_empty()),
+            "eligibility issuer must have at least one class"
+        );
+    }
+}
+
+/// Validates the poll weighting: weighted poll must be IAH only or have eligibility (to
+/// have proof SBTs), can't be editable, and weighted classes must be part of the eligibility.
+fn assert_weighting(
+    weighting: &Option<Weighting>,
+    iah_only: bool,
+    eligibility: &Option<ClassSet>,
+    editable: bool,
+) {
+    let weighting = match weighting {
+        None => return,
+        Some(w) => w,
+    };
+    require!(!editable, "weighted poll can't be editable");
+    require!(
+        iah_only || eligibility.is_some(),
+        "weighted poll must be IAH only or have eligibility"
+    );
+    if let Weighting::ClassWeights(weights) = weighting {
+        let eligibility = eligibility.as_ref().map_or(&[][..], |e| &e[..]);
+        require!(
+            weights.iter().all(|(issuer, class, _)| eligibility
+                .iter()
+                .any(|(i, classes)| i == issuer && classes.contains(class))),
+            "weighted class must be part of the poll eligibility"
+        );
+    }
+}
+
+/// Validates the questions localized variants and conditions.
+fn assert_questions(questions: &[Question]) {
+    for (i, q) in questions.iter().enumerate() {
+        assert_locales(q);
+        assert_condition(questions, i);
+    }
+}
+
+/// Returns applicability of each question: a question with a condition is applicable only if
+/// the referenced YesNo question was answered with the expected answer.
+fn applicable_questions(questions: &[Question], answers: &[Option<Answer>]) -> Vec<bool> {
+    questions
+        .iter()
+        .map(|q| match &q.condition {
+            None => true,
+            Some(c) => matches!(
+                answers.get(c.question as usize),
+                Some(Some(Answer::YesNo(a))) if *a == c.answer
+            ),
+        })
+        .collect()
+}
+
+fn assert_condition(questions: &[Question], index: usize) {
+    if let Some(c) = &questions[index].condition {
+        require!(
+            (c.question as usize) < index
+                && matches!(
+                    questions[c.question as usize].question_type,
+                    Answer::YesNo(_)
+                ),
+            format!(
+                "question index={} condition must refer to a previous YesNo question",
+                index
+            )
+        );
+    }
+}
+
+fn assert_locales(q: &Question) {
+    let locales = match &q.locales {
+        None => return,
+        Some(l) => l,
+    };
+    require!(
+        locales.len() <= MAX_LOCALES,
+        format!("max {} locales per question", MAX_LOCALES)
+    );
+    for (locale, text) in locales {
+        require!(
+            !locale.is_empty() && locale.len() <= MAX_LOCALE_LEN,
+            format!(
+                "locale must be a non empty string of max {} bytes",
+                MAX_LOCALE_LEN
+            )
+        );
+        require!(
+            text.title.len() <= MAX_LOCALIZED_TEXT_LEN
+                && text.description.as_ref().map_or(0, |d| d.len()) <= MAX_LOCALIZED_TEXT_LEN,
+            format!(
+                "localized title and description must be max {} bytes",
+                MAX_LOCALIZED_TEXT_LEN
+            )
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use cost::MILI_NEAR;
     use near_sdk::{
+        borsh::BorshSerialize,
+        env,
+        json_types::{Base64VecU8, U128},
+        mock::VmAction,
         test_utils::{self, VMContextBuilder},
-        testing_env, AccountId, Balance, VMContext,
+        testing_env, AccountId, Balance, PromiseError, PromiseOrValue, PromiseResult,
+        RuntimeFeesConfig, VMConfig, VMContext,
     };
+    use sbt::{OwnedToken, TokenMetadata};
+
+    use std::collections::HashMap;
 
     use crate::{
-        Answer, Contract, OpinionRangeResult, PollError, PollResult, Question, Results, Status,
+        min_response_storage, sealed, Answer, Contract, LocalizedText, OpinionRangeResult, Poll,
+        PollError, PollId, PollParams, PollResult, Question, QuestionCondition, Results, Status,
+        WeightedResults, Weighting, REVEAL_PERIOD,
     };
+    use sbt::ClassSet;
 
-    pub const RESPOND_COST: Balance = MILI_NEAR;
+    pub const RESPOND_COST: Balance = 2 * MILI_NEAR;
     const MILI_SECOND: u64 = 1000000; // nanoseconds
 
     fn alice() -> AccountId {
@@ -332,6 +1747,8 @@ mod tests {
             labels: None,
             choices: None,
             max_choices: None,
+            locales: None,
+            condition: None,
         }
     }
 
@@ -345,6 +1762,8 @@ mod tests {
             labels: None,
             choices: None,
             max_choices: None,
+            locales: None,
+            condition: None,
         }
     }
 
@@ -362,6 +1781,8 @@ mod tests {
                 String::from("no opinion"),
             ]),
             max_choices: Some(1),
+            locales: None,
+            condition: None,
         }
     }
 
@@ -375,6 +1796,31 @@ mod tests {
             labels: None,
             choices: None,
             max_choices: None,
+            locales: None,
+            condition: None,
+        }
+    }
+
+    fn poll_params(
+        iah_only: bool,
+        questions: Vec<Question>,
+        starts_at: u64,
+        ends_at: u64,
+    ) -> PollParams {
+        PollParams {
+            iah_only,
+            questions,
+            starts_at,
+            ends_at,
+            title: String::from("Hello, world!"),
+            tags: tags(),
+            description: String::from(""),
+            link: String::from(""),
+            editable: None,
+            eligibility: None,
+            weighting: None,
+            max_participants: None,
+            response_bond: None,
         }
     }
 
@@ -385,7 +1831,7 @@ mod tests {
             .is_view(false)
             .build();
         testing_env!(ctx.clone());
-        let ctr = Contract::new(registry());
+        let ctr = Contract::new(registry(), alice());
         ctx.predecessor_account_id = predecessor.clone();
         testing_env!(ctx.clone());
         return (ctx, ctr);
@@ -395,36 +1841,65 @@ mod tests {
     #[should_panic(expected = "poll start must be in the future")]
     fn create_poll_wrong_time() {
         let (_, mut ctr) = setup(&alice());
-        ctr.create_poll(
-            false,
-            vec![question_yes_no(true)],
-            1,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        ctr.create_poll(poll_params(false, vec![question_yes_no(true)], 1, 100))
+            .unwrap();
     }
 
     #[test]
     fn create_poll() {
         let (_, mut ctr) = setup(&alice());
-        ctr.create_poll(
-            false,
-            vec![question_yes_no(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        ctr.create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
         let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"create_poll","data":{"poll_id":1}}"#;
         assert!(test_utils::get_logs().len() == 1);
         assert_eq!(test_utils::get_logs()[0], expected_event);
     }
 
+    #[test]
+    fn poll_locales() {
+        let (_, mut ctr) = setup(&alice());
+        let localized = |title: &str| LocalizedText {
+            title: String::from(title),
+            description: None,
+        };
+        let mut q1 = question_yes_no(true);
+        q1.locales = Some(HashMap::from([
+            (String::from("es"), localized("Prueba de sí y no!")),
+            (String::from("de"), localized("Ja und Nein Test!")),
+        ]));
+        let mut q2 = question_opinion_range(false);
+        q2.locales = Some(HashMap::from([(String::from("es"), localized("Opinión!"))]));
+        let poll_id = ctr
+            .create_poll(poll_params(
+                false,
+                vec![q1, q2, question_text_answers(false)],
+                2,
+                100,
+            ))
+            .unwrap();
+        assert_eq!(
+            ctr.poll_locales(poll_id),
+            Some(vec![String::from("de"), String::from("es")])
+        );
+        assert_eq!(ctr.poll_locales(poll_id + 1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "locale must be a non empty string of max 10 bytes")]
+    fn create_poll_wrong_locale() {
+        let (_, mut ctr) = setup(&alice());
+        let mut q = question_yes_no(true);
+        q.locales = Some(HashMap::from([(
+            String::from("not-a-locale-code"),
+            LocalizedText {
+                title: String::from("title"),
+                description: None,
+            },
+        )]));
+        ctr.create_poll(poll_params(false, vec![q], 2, 100))
+            .unwrap();
+    }
+
     #[test]
     fn results_poll_not_found() {
         let (_, ctr) = setup(&alice());
@@ -434,21 +1909,17 @@ mod tests {
     #[test]
     fn results() {
         let (_, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_yes_no(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
         let res = ctr.results(poll_id);
         let expected = Results {
             status: Status::NotStarted,
             participants_num: 0,
             results: vec![PollResult::YesNo((0, 0))],
+            applicable: vec![0],
+            humans_count: None,
+            weighted: None,
         };
         assert_eq!(res.unwrap(), expected);
     }
@@ -456,16 +1927,9 @@ mod tests {
     #[test]
     fn respond_poll_not_active() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_yes_no(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
         ctx.attached_deposit = RESPOND_COST;
         testing_env!(ctx.clone());
         // too early
@@ -495,84 +1959,300 @@ mod tests {
     }
 
     #[test]
-    fn yes_no_flow() {
+    fn respond_conditional_question() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_yes_no(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
-        ctx.attached_deposit = RESPOND_COST;
+        let mut follow_up = question_opinion_range(true);
+        follow_up.condition = Some(QuestionCondition {
+            question: 0,
+            answer: true,
+        });
+        let poll_id = ctr
+            .create_poll(PollParams {
+                editable: Some(true),
+                ..poll_params(false, vec![question_yes_no(true), follow_up], 2, 100)
+            })
+            .unwrap();
+        ctx.attached_deposit = 3 * RESPOND_COST;
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx.clone());
-        let mut res = ctr.on_human_verifed(
-            vec![],
-            false,
-            ctx.predecessor_account_id,
+        // follow up question is applicable and required
+        match ctr.respond(poll_id, vec![Some(Answer::YesNo(true)), None]) {
+            Err(err) => assert_eq!(err, PollError::RequiredAnswer(1)),
+            Ok(_) => panic!("expected RequiredAnswer"),
+        }
+        ctr.respond(
             poll_id,
-            vec![Some(Answer::YesNo(true))],
-        );
-        assert!(res.is_ok());
-
-        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"respond","data":{"poll_id":1,"responder":"alice.near"}}"#;
-        assert!(test_utils::get_logs().len() == 1);
-        assert_eq!(test_utils::get_logs()[0], expected_event);
+            vec![Some(Answer::YesNo(true)), Some(Answer::OpinionRange(4))],
+        )
+        .unwrap();
 
+        // follow up question is not applicable: it's optional but can't be answered
         ctx.predecessor_account_id = bob();
         testing_env!(ctx.clone());
-        res = ctr.on_human_verifed(
-            vec![],
-            false,
-            ctx.predecessor_account_id,
-            poll_id,
-            vec![Some(Answer::YesNo(true))],
-        );
-        assert!(res.is_ok());
-
-        assert!(test_utils::get_logs().len() == 1);
-
-        ctx.predecessor_account_id = charlie();
-        testing_env!(ctx.clone());
-        res = ctr.on_human_verifed(
-            vec![],
-            false,
-            ctx.predecessor_account_id,
+        match ctr.respond(
             poll_id,
-            vec![Some(Answer::YesNo(false))],
-        );
-        assert!(res.is_ok());
-
-        assert!(test_utils::get_logs().len() == 1);
+            vec![Some(Answer::YesNo(false)), Some(Answer::OpinionRange(4))],
+        ) {
+            Err(err) => assert_eq!(err, PollError::NotApplicable(1)),
+            Ok(_) => panic!("expected NotApplicable"),
+        }
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(false)), None])
+            .unwrap();
+        assert_eq!(ctr.results(poll_id).unwrap().applicable, vec![2, 1]);
 
-        let results = ctr.results(poll_id);
+        // alice changes her answer, the follow up question doesn't apply to her any more
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(false)), None])
+            .unwrap();
         assert_eq!(
-            results.unwrap(),
+            ctr.results(poll_id).unwrap(),
             Results {
                 status: Status::NotStarted,
-                participants_num: 3,
-                results: vec![PollResult::YesNo((2, 1)),]
+                participants_num: 2,
+                results: vec![
+                    PollResult::YesNo((0, 2)),
+                    PollResult::OpinionRange(OpinionRangeResult { sum: 0, num: 0 }),
+                ],
+                applicable: vec![2, 0],
+                humans_count: None,
+                weighted: None,
             }
-        )
+        );
     }
 
     #[test]
-    fn opinion_range_out_of_range() {
-        let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
+    #[should_panic(expected = "question index=1 condition must refer to a previous YesNo question")]
+    fn create_poll_wrong_condition() {
+        let (_, mut ctr) = setup(&alice());
+        let mut follow_up = question_yes_no(false);
+        follow_up.condition = Some(QuestionCondition {
+            question: 0,
+            answer: true,
+        });
+        ctr.create_poll(poll_params(
             false,
-            vec![question_opinion_range(false)],
+            vec![question_opinion_range(true), follow_up],
             2,
             100,
-            String::from("Multiple questions test!"),
-            tags(),
-            String::from(""),
-            String::from(""),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn respond_editable() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(PollParams {
+                editable: Some(true),
+                ..poll_params(
+                    false,
+                    vec![
+                        question_yes_no(true),
+                        question_opinion_range(false),
+                        question_text_answers(false),
+                    ],
+                    2,
+                    100,
+                )
+            })
+            .unwrap();
+        ctx.attached_deposit = 3 * RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        let text = || Some(Answer::TextAnswer(String::from("answer")));
+        ctr.respond(
+            poll_id,
+            vec![
+                Some(Answer::YesNo(true)),
+                Some(Answer::OpinionRange(5)),
+                text(),
+            ],
+        )
+        .unwrap();
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(true)), None, None])
+            .unwrap();
+
+        // alice changes her mind
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.respond(
+            poll_id,
+            vec![
+                Some(Answer::YesNo(false)),
+                Some(Answer::OpinionRange(9)),
+                text(),
+            ],
+        )
+        .unwrap();
+        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"respond_updated","data":{"poll_id":1,"responder":"alice.near"}}"#;
+        assert_eq!(test_utils::get_logs(), vec![expected_event]);
+        assert_eq!(
+            ctr.results(poll_id).unwrap(),
+            Results {
+                status: Status::NotStarted,
+                participants_num: 2,
+                results: vec![
+                    PollResult::YesNo((1, 1)),
+                    PollResult::OpinionRange(OpinionRangeResult { sum: 9, num: 1 }),
+                    PollResult::TextAnswer,
+                ],
+                applicable: vec![2, 2, 2],
+                humans_count: None,
+                weighted: None,
+            }
+        );
+        // text answers are not recorded in the response, but the edit replaces them
+        assert_eq!(
+            ctr.text_answers(poll_id, 2, None, None),
+            Some(vec![(0, String::from("answer"))])
+        );
+        assert_eq!(
+            ctr.response(poll_id, alice()),
+            Some(vec![
+                Some(Answer::YesNo(false)),
+                Some(Answer::OpinionRange(9)),
+                None
+            ])
+        );
+        assert_eq!(ctr.response(poll_id, charlie()), None);
+
+        // editing is not possible after the poll ends
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true)), None, None]),
+            Err(PollError::NotActive)
+        );
+    }
+
+    #[test]
+    fn respond_insufficient_deposit() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(PollParams {
+                editable: Some(true),
+                ..poll_params(
+                    true,
+                    vec![question_yes_no(true), question_opinion_range(false)],
+                    2,
+                    100,
+                )
+            })
+            .unwrap();
+        let answers = vec![Some(Answer::YesNo(true)), Some(Answer::OpinionRange(5))];
+        let required = min_response_storage(poll_id, &alice(), &answers, true) as u128
+            * env::storage_byte_cost();
+        ctx.attached_deposit = required - 1;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        // fails before calling the registry
+        assert_eq!(
+            ctr.respond(poll_id, answers.clone()),
+            Err(PollError::InsufficientDeposit(required))
+        );
+
+        // the estimate is exact when no text answer is provided
+        ctx.attached_deposit = required;
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.on_human_verifed(vec![(bob(), vec![1])], true, alice(), poll_id, answers),
+            Ok(())
+        );
+        assert_eq!(ctr.results(poll_id).unwrap().participants_num, 1);
+    }
+
+    #[test]
+    fn respond_not_editable() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(true))])
+            .unwrap();
+        assert_eq!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(false))]),
+            Err(PollError::AlredyAnswered)
+        );
+        assert_eq!(ctr.response(poll_id, alice()), None);
+    }
+
+    #[test]
+    fn yes_no_flow() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        let mut res = ctr.on_human_verifed(
+            vec![],
+            false,
+            ctx.predecessor_account_id,
+            poll_id,
+            vec![Some(Answer::YesNo(true))],
+        );
+        assert!(res.is_ok());
+
+        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"respond","data":{"poll_id":1,"responder":"alice.near"}}"#;
+        assert!(test_utils::get_logs().len() == 1);
+        assert_eq!(test_utils::get_logs()[0], expected_event);
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        res = ctr.on_human_verifed(
+            vec![],
+            false,
+            ctx.predecessor_account_id,
+            poll_id,
+            vec![Some(Answer::YesNo(true))],
         );
+        assert!(res.is_ok());
+
+        assert!(test_utils::get_logs().len() == 1);
+
+        ctx.predecessor_account_id = charlie();
+        testing_env!(ctx.clone());
+        res = ctr.on_human_verifed(
+            vec![],
+            false,
+            ctx.predecessor_account_id,
+            poll_id,
+            vec![Some(Answer::YesNo(false))],
+        );
+        assert!(res.is_ok());
+
+        assert!(test_utils::get_logs().len() == 1);
+
+        let results = ctr.results(poll_id);
+        assert_eq!(
+            results.unwrap(),
+            Results {
+                status: Status::NotStarted,
+                participants_num: 3,
+                results: vec![PollResult::YesNo((2, 1)),],
+                applicable: vec![3],
+                humans_count: None,
+                weighted: None,
+            }
+        )
+    }
+
+    #[test]
+    fn opinion_range_out_of_range() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(PollParams {
+                title: String::from("Multiple questions test!"),
+                ..poll_params(false, vec![question_opinion_range(false)], 2, 100)
+            })
+            .unwrap();
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx);
         match ctr.on_human_verifed(
@@ -596,16 +2276,12 @@ mod tests {
     #[test]
     fn respond_wrong_answer_vector() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_opinion_range(false)],
-            2,
-            100,
-            String::from("Multiple questions test!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(PollParams {
+                title: String::from("Multiple questions test!"),
+                ..poll_params(false, vec![question_opinion_range(false)], 2, 100)
+            })
+            .unwrap();
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx);
         match ctr.on_human_verifed(
@@ -634,16 +2310,12 @@ mod tests {
     #[test]
     fn opinion_range_flow() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_opinion_range(false)],
-            2,
-            100,
-            String::from("Multiple questions test!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(PollParams {
+                title: String::from("Multiple questions test!"),
+                ..poll_params(false, vec![question_opinion_range(false)], 2, 100)
+            })
+            .unwrap();
         ctx.attached_deposit = RESPOND_COST;
         ctx.predecessor_account_id = alice();
         ctx.block_timestamp = MILI_SECOND * 3;
@@ -685,23 +2357,24 @@ mod tests {
                 results: vec![PollResult::OpinionRange(OpinionRangeResult {
                     sum: 17,
                     num: 3
-                }),]
+                }),],
+                applicable: vec![3],
+                humans_count: None,
+                weighted: None,
             }
         )
     }
     #[test]
     fn text_chocies_flow() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_text_choices(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(poll_params(
+                false,
+                vec![question_text_choices(true)],
+                2,
+                100,
+            ))
+            .unwrap();
         ctx.attached_deposit = RESPOND_COST;
         ctx.predecessor_account_id = alice();
         ctx.block_timestamp = MILI_SECOND * 3;
@@ -740,7 +2413,10 @@ mod tests {
             Results {
                 status: Status::NotStarted,
                 participants_num: 3,
-                results: vec![PollResult::TextChoices(vec![2, 1, 0]),]
+                results: vec![PollResult::TextChoices(vec![2, 1, 0]),],
+                applicable: vec![3],
+                humans_count: None,
+                weighted: None,
             }
         )
     }
@@ -748,17 +2424,15 @@ mod tests {
     #[test]
     fn text_answers_flow() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            false,
-            vec![question_text_answers(true)],
-            2,
-            100,
-            String::from("Hello, world!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
-        ctx.attached_deposit = RESPOND_COST;
+        let poll_id = ctr
+            .create_poll(poll_params(
+                false,
+                vec![question_text_answers(true)],
+                2,
+                100,
+            ))
+            .unwrap();
+        ctx.attached_deposit = 2 * RESPOND_COST;
         ctx.predecessor_account_id = alice();
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx.clone());
@@ -799,24 +2473,432 @@ mod tests {
             Results {
                 status: Status::NotStarted,
                 participants_num: 3,
-                results: vec![PollResult::TextAnswer]
+                results: vec![PollResult::TextAnswer],
+                applicable: vec![3],
+                humans_count: None,
+                weighted: None,
             }
         );
+        assert_eq!(
+            ctr.text_answers(poll_id, 0, None, None),
+            Some(vec![(0, answer1), (1, answer2.clone()), (2, answer3)])
+        );
+        assert_eq!(
+            ctr.text_answers(poll_id, 0, Some(1), Some(1)),
+            Some(vec![(1, answer2)])
+        );
+        assert_eq!(ctr.text_answers(poll_id, 0, Some(3), None), Some(vec![]));
+        assert_eq!(ctr.text_answers(poll_id, 1, None, None), Some(vec![]));
+        assert_eq!(ctr.text_answers(poll_id + 1, 0, None, None), None);
     }
 
     #[test]
-    fn respond_iah_only_not_human() {
+    fn finalize_iah_poll() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(true, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        for responder in [alice(), bob()] {
+            let res = ctr.on_human_verifed(
+                vec![(registry(), vec![1])],
+                true,
+                responder,
+                poll_id,
+                vec![Some(Answer::YesNo(true))],
+            );
+            assert!(res.is_ok());
+        }
+        assert!(matches!(ctr.finalize(poll_id), Err(PollError::NotEnded)));
+        assert!(matches!(ctr.finalize(10), Err(PollError::NotFound)));
+
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.finalize(poll_id),
+            Ok(PromiseOrValue::Promise(_))
+        ));
+        // not finalized until the registry responds
+        assert_eq!(ctr.results(poll_id).unwrap().status, Status::NotStarted);
+        assert_eq!(ctr.quorum(poll_id), None);
+
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        assert!(ctr.on_humans_count(poll_id, Ok(8)).is_ok());
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.status, Status::Finished);
+        assert_eq!(results.humans_count, Some(8));
+        // 2 participants out of 8 humans
+        assert_eq!(ctr.quorum(poll_id), Some(2500));
+        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"finalize","data":{"humans_count":8,"poll_id":1}}"#;
+        assert_eq!(test_utils::get_logs(), vec![expected_event]);
+
+        assert!(matches!(
+            ctr.finalize(poll_id),
+            Err(PollError::AlreadyFinalized)
+        ));
+        assert!(matches!(
+            ctr.on_humans_count(poll_id, Ok(8)),
+            Err(PollError::AlreadyFinalized)
+        ));
+    }
+
+    fn create_tagged_poll(ctr: &mut Contract, tags: Vec<String>) -> Result<PollId, PollError> {
+        ctr.create_poll(PollParams {
+            tags,
+            ..poll_params(false, vec![question_yes_no(true)], 2, 100)
+        })
+    }
+
+    #[test]
+    fn unique_active_tags() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        assert!(!ctr.unique_active_tags());
+        // disabled: a creator can have many active polls with the same tag
+        assert_eq!(create_tagged_poll(&mut ctr, tags()), Ok(1));
+        assert_eq!(create_tagged_poll(&mut ctr, tags()), Ok(2));
+
+        ctr.set_unique_active_tags(true);
+        assert!(ctr.unique_active_tags());
+        assert_eq!(
+            create_tagged_poll(&mut ctr, vec![String::from("tag3"), String::from("tag2")]),
+            Err(PollError::ActiveTagPoll(2))
+        );
+        assert_eq!(
+            create_tagged_poll(&mut ctr, vec![String::from("tag3")]),
+            Ok(3)
+        );
+        assert_eq!(create_tagged_poll(&mut ctr, vec![]), Ok(4));
+
+        // other creator is not limited by alice polls
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        assert_eq!(create_tagged_poll(&mut ctr, tags()), Ok(5));
+
+        // the tag can be used again once the poll ended
+        ctx.predecessor_account_id = alice();
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx);
+        assert!(ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 102, 200))
+            .is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn set_unique_active_tags_not_admin() {
+        let (_, mut ctr) = setup(&bob());
+        ctr.set_unique_active_tags(true);
+    }
+
+    #[test]
+    fn update_poll() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_tagged_poll(&mut ctr, tags()).unwrap();
+        assert_eq!(ctr.poll(poll_id).unwrap().creator, alice());
+        let update = |ctr: &mut Contract, title: &str| {
+            ctr.update_poll(
+                poll_id,
+                PollParams {
+                    title: String::from(title),
+                    tags: vec![String::from("updated")],
+                    editable: Some(true),
+                    ..poll_params(
+                        true,
+                        vec![question_yes_no(true), question_text_answers(false)],
+                        5,
+                        200,
+                    )
+                },
+            )
+        };
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        assert_eq!(update(&mut ctr, "bob"), Err(PollError::NotCreator));
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        update(&mut ctr, "updated").unwrap();
+        let poll = ctr.poll(poll_id).unwrap();
+        assert_eq!(poll.title, "updated");
+        assert_eq!(poll.questions.len(), 2);
+        assert_eq!((poll.starts_at, poll.ends_at), (5, 200));
+        assert!(poll.iah_only && poll.editable);
+        assert_eq!(ctr.results(poll_id).unwrap().applicable, vec![0, 0]);
+        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"update_poll","data":{"poll_id":1}}"#;
+        assert_eq!(test_utils::get_logs(), vec![expected_event]);
+
+        // old tags are released
+        ctr.set_unique_active_tags(true);
+        create_tagged_poll(&mut ctr, tags()).unwrap();
+        assert_eq!(
+            create_tagged_poll(&mut ctr, vec![String::from("updated")]),
+            Err(PollError::ActiveTagPoll(poll_id))
+        );
+
+        ctx.block_timestamp = MILI_SECOND * 5;
+        testing_env!(ctx);
+        assert_eq!(update(&mut ctr, "late"), Err(PollError::AlreadyStarted));
+    }
+
+    #[test]
+    fn cancel_poll() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_tagged_poll(&mut ctr, tags()).unwrap();
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.cancel_poll(poll_id), Err(PollError::NotCreator));
+        assert_eq!(ctr.cancel_poll(poll_id + 1), Err(PollError::NotFound));
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.cancel_poll(poll_id).unwrap();
+        assert_eq!(ctr.results(poll_id).unwrap().status, Status::Cancelled);
+        let expected_event = r#"EVENT_JSON:{"standard":"ndc-easy-poll","version":"1.0.0","event":"cancel_poll","data":{"poll_id":1}}"#;
+        assert_eq!(test_utils::get_logs(), vec![expected_event]);
+        assert_eq!(ctr.cancel_poll(poll_id), Err(PollError::Cancelled));
+
+        // cancelled poll doesn't accept responses and can't be finalized
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true))]),
+            Err(PollError::Cancelled)
+        );
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx);
+        assert!(matches!(ctr.finalize(poll_id), Err(PollError::Cancelled)));
+    }
+
+    fn issuer() -> AccountId {
+        AccountId::new_unchecked("issuer.near".to_string())
+    }
+
+    fn owned_tokens(classes: &[u64]) -> Vec<u8> {
+        let tokens: Vec<OwnedToken> = classes
+            .iter()
+            .map(|c| OwnedToken {
+                token: *c,
+                metadata: TokenMetadata {
+                    class: *c,
+                    issued_at: None,
+                    expires_at: None,
+                    reference: None,
+                    reference_hash: None,
+                },
+            })
+            .collect();
+        serde_json::to_vec(&vec![(issuer(), tokens)]).unwrap()
+    }
+
+    #[test]
+    fn respond_eligibility() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(PollParams {
+                eligibility: Some(vec![(issuer(), vec![5, 3])]),
+                ..poll_params(false, vec![question_yes_no(true)], 2, 100)
+            })
+            .unwrap();
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(true))])
+            .unwrap();
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, registry());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall {
+                function_name,
+                args,
+                ..
+            } => {
+                assert_eq!(function_name, "sbt_tokens_by_owner");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["issuer"], "issuer.near");
+                assert_eq!(args["from_class"], 3);
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+        match &receipts[1].actions[0] {
+            VmAction::FunctionCall { function_name, .. } => {
+                assert_eq!(function_name, "on_eligibility_verified")
+            }
+            a => panic!("expected function call, got {:?}", a),
+        }
+
+        // only one of the required classes
+        let with_results = |classes: &[u64]| {
+            testing_env!(
+                ctx.clone(),
+                VMConfig::test(),
+                RuntimeFeesConfig::test(),
+                HashMap::default(),
+                vec![PromiseResult::Successful(owned_tokens(classes))],
+            );
+        };
+        with_results(&[3, 4]);
+        let answers = vec![Some(Answer::YesNo(true))];
+        assert_eq!(
+            ctr.on_eligibility_verified(alice(), poll_id, answers.clone()),
+            Err(PollError::NotEligible)
+        );
+
+        with_results(&[3, 5]);
+        ctr.on_eligibility_verified(alice(), poll_id, answers)
+            .unwrap();
+        assert_eq!(ctr.results(poll_id).unwrap().participants_num, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "eligibility issuer must have at least one class")]
+    fn create_poll_empty_eligibility_class() {
+        let (_, mut ctr) = setup(&alice());
+        ctr.create_poll(PollParams {
+            eligibility: Some(vec![(issuer(), vec![])]),
+            ..poll_params(false, vec![question_yes_no(true)], 2, 100)
+        })
+        .unwrap();
+    }
+
+    fn create_weighted_poll(
+        ctr: &mut Contract,
+        iah_only: bool,
+        eligibility: Option<ClassSet>,
+        weighting: Weighting,
+    ) -> PollId {
+        ctr.create_poll(PollParams {
+            eligibility,
+            weighting: Some(weighting),
+            ..poll_params(
+                iah_only,
+                vec![question_yes_no(true), question_opinion_range(false)],
+                2,
+                100,
+            )
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn weighted_token_count() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
+        let poll_id = create_weighted_poll(&mut ctr, true, None, Weighting::TokenCount);
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        ctr.on_human_verifed(
+            vec![(issuer(), vec![1, 2, 3])],
             true,
-            vec![question_opinion_range(false)],
-            2,
-            100,
-            String::from("Multiple questions test!"),
-            tags(),
-            String::from(""),
-            String::from(""),
+            alice(),
+            poll_id,
+            vec![Some(Answer::YesNo(true)), Some(Answer::OpinionRange(4))],
+        )
+        .unwrap();
+        ctr.on_human_verifed(
+            vec![(issuer(), vec![4])],
+            true,
+            bob(),
+            poll_id,
+            vec![Some(Answer::YesNo(false)), Some(Answer::OpinionRange(8))],
+        )
+        .unwrap();
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.results[0], PollResult::YesNo((1, 1)));
+        assert_eq!(
+            results.weighted,
+            Some(WeightedResults {
+                participants_weight: 4,
+                results: vec![
+                    PollResult::YesNo((3, 1)),
+                    PollResult::OpinionRange(OpinionRangeResult { sum: 20, num: 4 }),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn weighted_class_weights() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let weights = vec![(issuer(), 3, 10), (issuer(), 4, 5)];
+        let poll_id = create_weighted_poll(
+            &mut ctr,
+            false,
+            Some(vec![(issuer(), vec![3, 4])]),
+            Weighting::ClassWeights(weights),
+        );
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(
+            ctx.clone(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(owned_tokens(&[3, 4, 7]))],
+        );
+        ctr.on_eligibility_verified(alice(), poll_id, vec![Some(Answer::YesNo(true)), None])
+            .unwrap();
+        let weighted = ctr.results(poll_id).unwrap().weighted.unwrap();
+        assert_eq!(weighted.participants_weight, 15);
+        assert_eq!(weighted.results[0], PollResult::YesNo((15, 0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "weighted poll can't be editable")]
+    fn weighted_editable() {
+        let (_, mut ctr) = setup(&alice());
+        ctr.create_poll(PollParams {
+            editable: Some(true),
+            weighting: Some(Weighting::TokenCount),
+            ..poll_params(true, vec![question_yes_no(true)], 2, 100)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "weighted class must be part of the poll eligibility")]
+    fn weighted_class_not_eligible() {
+        let (_, mut ctr) = setup(&alice());
+        create_weighted_poll(
+            &mut ctr,
+            false,
+            Some(vec![(issuer(), vec![3])]),
+            Weighting::ClassWeights(vec![(issuer(), 4, 1)]),
         );
+    }
+
+    #[test]
+    fn finalize_poll() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx);
+        assert!(matches!(
+            ctr.finalize(poll_id),
+            Ok(PromiseOrValue::Value(()))
+        ));
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.status, Status::Finished);
+        assert_eq!(results.humans_count, None);
+        assert_eq!(ctr.quorum(poll_id), None);
+    }
+
+    #[test]
+    fn respond_iah_only_not_human() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(PollParams {
+                title: String::from("Multiple questions test!"),
+                ..poll_params(true, vec![question_opinion_range(false)], 2, 100)
+            })
+            .unwrap();
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx);
         match ctr.on_human_verifed(
@@ -842,16 +2924,17 @@ mod tests {
     #[test]
     fn respond_required_answer_not_provided() {
         let (mut ctx, mut ctr) = setup(&alice());
-        let poll_id = ctr.create_poll(
-            true,
-            vec![question_opinion_range(false), question_opinion_range(true)],
-            2,
-            100,
-            String::from("Multiple questions test!"),
-            tags(),
-            String::from(""),
-            String::from(""),
-        );
+        let poll_id = ctr
+            .create_poll(PollParams {
+                title: String::from("Multiple questions test!"),
+                ..poll_params(
+                    true,
+                    vec![question_opinion_range(false), question_opinion_range(true)],
+                    2,
+                    100,
+                )
+            })
+            .unwrap();
         ctx.block_timestamp = MILI_SECOND * 3;
         testing_env!(ctx);
         match ctr.on_human_verifed(
@@ -873,4 +2956,388 @@ mod tests {
             Ok(_) => panic!("Received Ok result, but expected an error"),
         }
     }
+    #[test]
+    fn sealed_poll_flow() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(
+                false,
+                vec![question_yes_no(true), question_text_answers(false)],
+                2,
+                100,
+            ))
+            .unwrap();
+        let sk = [7u8; sealed::KEY_LEN];
+        let pk = Base64VecU8(sealed::public_key(&sk).to_vec());
+        ctr.seal_poll(poll_id, pk.clone()).unwrap();
+        assert_eq!(ctr.sealed_poll(poll_id).unwrap().public_key, pk);
+
+        ctx.attached_deposit = 5 * RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true)), None]),
+            Err(PollError::Sealed)
+        ));
+        assert!(matches!(
+            ctr.seal_poll(poll_id, pk.clone()),
+            Err(PollError::AlreadyStarted)
+        ));
+
+        let ballot = |answers: Vec<Option<Answer>>| {
+            let plaintext = answers.try_to_vec().unwrap();
+            Base64VecU8(sealed::encrypt(
+                &sealed::public_key(&sk),
+                &[9u8; 32],
+                &plaintext,
+            ))
+        };
+        // valid ballot
+        ctr.respond_encrypted(
+            poll_id,
+            ballot(vec![
+                Some(Answer::YesNo(true)),
+                Some(Answer::TextAnswer("secret".to_string())),
+            ]),
+        )
+        .unwrap();
+        // ballot with invalid answers
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        ctr.respond_encrypted(poll_id, ballot(vec![Some(Answer::YesNo(true))]))
+            .unwrap();
+        // ballot which can't be decrypted
+        ctx.predecessor_account_id = charlie();
+        testing_env!(ctx.clone());
+        ctr.respond_encrypted(poll_id, Base64VecU8(vec![1; 80]))
+            .unwrap();
+        assert!(matches!(
+            ctr.respond_encrypted(poll_id, Base64VecU8(vec![1; 80])),
+            Err(PollError::AlredyAnswered)
+        ));
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.participants_num, 3);
+        assert_eq!(results.results[0], PollResult::YesNo((0, 0)));
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.reveal_key(poll_id, Base64VecU8(sk.to_vec())),
+            Err(PollError::NotEnded)
+        ));
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.tally(poll_id, None),
+            Err(PollError::KeyNotRevealed)
+        ));
+        ctr.reveal_key(poll_id, Base64VecU8(sk.to_vec())).unwrap();
+        assert!(matches!(ctr.finalize(poll_id), Err(PollError::NotTallied)));
+
+        assert_eq!(ctr.tally(poll_id, Some(2)), Ok(1));
+        assert_eq!(ctr.tally(poll_id, None), Ok(0));
+        let sealed = ctr.sealed_poll(poll_id).unwrap();
+        assert_eq!(sealed.tallied, 3);
+        assert_eq!(sealed.invalid, 2);
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.results[0], PollResult::YesNo((1, 0)));
+        assert_eq!(results.applicable, vec![1, 1]);
+        assert_eq!(
+            ctr.text_answers(poll_id, 1, None, None),
+            Some(vec![(0, "secret".to_string())])
+        );
+        ctr.finalize(poll_id).unwrap();
+    }
+
+    #[test]
+    fn sealed_poll_key_not_revealed() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 2, 100))
+            .unwrap();
+        let sk = [7u8; sealed::KEY_LEN];
+        let pk = sealed::public_key(&sk);
+        ctr.seal_poll(poll_id, Base64VecU8(pk.to_vec())).unwrap();
+
+        ctx.attached_deposit = 5 * RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        let plaintext = vec![Some(Answer::YesNo(true))].try_to_vec().unwrap();
+        ctr.respond_encrypted(
+            poll_id,
+            Base64VecU8(sealed::encrypt(&pk, &[9u8; 32], &plaintext)),
+        )
+        .unwrap();
+
+        ctx.block_timestamp = MILI_SECOND * (100 + REVEAL_PERIOD);
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.finalize(poll_id),
+            Err(PollError::KeyNotRevealed)
+        ));
+
+        // the creator didn't reveal the key in time: the poll is cancelled
+        ctx.block_timestamp = MILI_SECOND * (101 + REVEAL_PERIOD);
+        testing_env!(ctx);
+        ctr.finalize(poll_id).unwrap();
+        let results = ctr.results(poll_id).unwrap();
+        assert_eq!(results.status, Status::Cancelled);
+        assert_eq!(results.results[0], PollResult::YesNo((0, 0)));
+        assert!(matches!(ctr.finalize(poll_id), Err(PollError::Cancelled)));
+        assert!(matches!(
+            ctr.tally(poll_id, None),
+            Err(PollError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn respond_encrypted_not_sealed() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_weighted_poll(&mut ctr, true, None, Weighting::TokenCount);
+        ctx.attached_deposit = 5 * RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx);
+        assert!(matches!(
+            ctr.respond_encrypted(poll_id, Base64VecU8(vec![1; 80])),
+            Err(PollError::NotSealed)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "secret key doesn't match the poll public key")]
+    fn reveal_wrong_key() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_weighted_poll(&mut ctr, true, None, Weighting::TokenCount);
+        let pk = sealed::public_key(&[7u8; sealed::KEY_LEN]).to_vec();
+        ctr.seal_poll(poll_id, Base64VecU8(pk)).unwrap();
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx);
+        ctr.reveal_key(poll_id, Base64VecU8(vec![8; sealed::KEY_LEN]))
+            .unwrap();
+    }
+
+    #[test]
+    fn participants() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_weighted_poll(&mut ctr, true, None, Weighting::TokenCount);
+        assert_eq!(ctr.participants(poll_id, None, None), Some(vec![]));
+        assert_eq!(ctr.participants(poll_id + 1, None, None), None);
+        assert!(!ctr.has_responded(poll_id, alice()));
+
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx);
+        for acc in [bob(), alice(), charlie()] {
+            ctr.on_human_verifed(
+                vec![(issuer(), vec![1])],
+                true,
+                acc,
+                poll_id,
+                vec![Some(Answer::YesNo(true)), None],
+            )
+            .unwrap();
+        }
+        assert!(ctr.has_responded(poll_id, alice()));
+        assert!(!ctr.has_responded(poll_id + 1, alice()));
+        assert_eq!(
+            ctr.participants(poll_id, None, None),
+            Some(vec![bob(), alice(), charlie()])
+        );
+        assert_eq!(
+            ctr.participants(poll_id, Some(1), Some(1)),
+            Some(vec![alice()])
+        );
+        assert_eq!(ctr.participants(poll_id, Some(5), None), Some(vec![]));
+    }
+
+    #[test]
+    fn polls_discovery() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let create = |ctr: &mut Contract, starts_at: u64, tags: Vec<String>| {
+            ctr.create_poll(PollParams {
+                tags,
+                ..poll_params(false, vec![question_yes_no(true)], starts_at, 100)
+            })
+            .unwrap()
+        };
+        let p1 = create(&mut ctr, 2, vec!["a".to_string(), "b".to_string()]);
+        let p2 = create(&mut ctr, 50, vec!["b".to_string()]);
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        let p3 = create(&mut ctr, 2, vec!["a".to_string()]);
+
+        let ids = |polls: Vec<(PollId, Poll)>| -> Vec<PollId> {
+            polls.into_iter().map(|p| p.0).collect()
+        };
+        assert_eq!(
+            ids(ctr.polls_by_tag("a".to_string(), None, None)),
+            vec![p1, p3]
+        );
+        assert_eq!(
+            ids(ctr.polls_by_tag("b".to_string(), Some(1), None)),
+            vec![p2]
+        );
+        assert!(ctr.polls_by_tag("c".to_string(), None, None).is_empty());
+        assert_eq!(ids(ctr.polls(None, None)), vec![p1, p2, p3]);
+        assert_eq!(ids(ctr.polls(Some(1), Some(1))), vec![p2]);
+        assert!(ctr.polls(Some(3), None).is_empty());
+        assert_eq!(ids(ctr.polls_by_creator(alice(), None, Some(1))), vec![p1]);
+        assert_eq!(ids(ctr.polls_by_creator(bob(), None, None)), vec![p3]);
+        assert!(ctr.active_polls(None, None).is_empty());
+
+        // update tags of p1: the tag index keeps the creation order
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.update_poll(
+            p1,
+            PollParams {
+                tags: vec!["b".to_string()],
+                ..poll_params(false, vec![question_yes_no(true)], 2, 100)
+            },
+        )
+        .unwrap();
+        assert_eq!(ids(ctr.polls_by_tag("a".to_string(), None, None)), vec![p3]);
+        assert_eq!(
+            ids(ctr.polls_by_tag("b".to_string(), None, None)),
+            vec![p1, p2]
+        );
+
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        assert_eq!(ids(ctr.active_polls(None, None)), vec![p1, p3]);
+        assert_eq!(ids(ctr.active_polls(Some(p1 + 1), None)), vec![p3]);
+        assert_eq!(ids(ctr.active_polls(None, Some(1))), vec![p1]);
+        ctr.cancel_poll(p1).unwrap();
+        assert_eq!(ids(ctr.active_polls(None, None)), vec![p3]);
+
+        ctx.block_timestamp = MILI_SECOND * 60;
+        testing_env!(ctx.clone());
+        assert_eq!(ids(ctr.active_polls(None, None)), vec![p2, p3]);
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx);
+        assert!(ctr.active_polls(None, None).is_empty());
+        ctr.finalize(p2).unwrap();
+        assert!(!ctr.open_polls.contains_key(&p2));
+        assert_eq!(ctr.open_polls.len(), 1);
+
+        // ended polls can be pruned by anyone and still finalized
+        let p4 = ctr
+            .create_poll(poll_params(false, vec![question_yes_no(true)], 200, 300))
+            .unwrap();
+        assert_eq!(ctr.prune_ended_polls(Some(p3 + 1), None), 0);
+        assert_eq!(ctr.prune_ended_polls(None, None), 1);
+        assert!(!ctr.open_polls.contains_key(&p3));
+        assert!(ctr.open_polls.contains_key(&p4));
+        ctr.finalize(p3).unwrap();
+        assert_eq!(ctr.results(p3).unwrap().status, Status::Finished);
+    }
+
+    #[test]
+    fn respond_deposit_refunds() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let poll_id = create_weighted_poll(&mut ctr, true, None, Weighting::TokenCount);
+        ctx.attached_deposit = 5 * RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        ctr.respond(poll_id, vec![Some(Answer::YesNo(true)), None])
+            .unwrap();
+        let receipts = test_utils::get_created_receipts();
+        let calls: Vec<&str> = receipts
+            .iter()
+            .map(|r| match &r.actions[0] {
+                VmAction::FunctionCall { function_name, .. } => function_name.as_str(),
+                a => panic!("expected function call, got {:?}", a),
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec!["is_human", "on_human_verifed", "on_response_recorded"]
+        );
+
+        // surplus above the storage cost is refunded
+        let storage_start = env::storage_usage();
+        ctr.on_human_verifed(
+            vec![(issuer(), vec![1])],
+            true,
+            alice(),
+            poll_id,
+            vec![Some(Answer::YesNo(true)), None],
+        )
+        .unwrap();
+        let cost = (env::storage_usage() - storage_start) as u128 * env::storage_byte_cost();
+        let receipts = test_utils::get_created_receipts();
+        let last = receipts.last().unwrap();
+        assert_eq!(last.receiver_id, alice());
+        assert!(
+            matches!(last.actions[0], VmAction::Transfer { deposit } if deposit == 5 * RESPOND_COST - cost)
+        );
+
+        // rejected response: the whole deposit is refunded
+        testing_env!(ctx.clone());
+        assert!(!ctr.on_response_recorded(bob(), U128(RESPOND_COST), Err(PromiseError::Failed)));
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, bob());
+        assert!(
+            matches!(receipts[0].actions[0], VmAction::Transfer { deposit } if deposit == RESPOND_COST)
+        );
+        testing_env!(ctx);
+        assert!(ctr.on_response_recorded(bob(), U128(RESPOND_COST), Ok(())));
+        assert!(test_utils::get_created_receipts().is_empty());
+    }
+
+    #[test]
+    fn max_participants_and_bond() {
+        let (mut ctx, mut ctr) = setup(&alice());
+        let bond = RESPOND_COST;
+        let poll_id = ctr
+            .create_poll(PollParams {
+                max_participants: Some(2),
+                response_bond: Some(U128(bond)),
+                ..poll_params(false, vec![question_yes_no(true)], 2, 100)
+            })
+            .unwrap();
+        ctx.attached_deposit = RESPOND_COST;
+        ctx.block_timestamp = MILI_SECOND * 3;
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true))]),
+            Err(PollError::InsufficientDeposit(_))
+        ));
+
+        ctx.attached_deposit = 2 * RESPOND_COST + bond;
+        for acc in [alice(), bob()] {
+            ctx.predecessor_account_id = acc.clone();
+            testing_env!(ctx.clone());
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true))])
+                .unwrap();
+            assert_eq!(ctr.bond(poll_id, acc), Some(U128(bond)));
+        }
+        ctx.predecessor_account_id = charlie();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.respond(poll_id, vec![Some(Answer::YesNo(true))]),
+            Err(PollError::MaxParticipants)
+        );
+        assert_eq!(ctr.bond(poll_id, charlie()), None);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert!(matches!(
+            ctr.withdraw_bond(poll_id),
+            Err(PollError::NotEnded)
+        ));
+        ctx.block_timestamp = MILI_SECOND * 101;
+        testing_env!(ctx.clone());
+        assert!(ctr.withdraw_bond(poll_id).is_ok());
+        let receipts = test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, alice());
+        assert!(
+            matches!(receipts[0].actions[0], VmAction::Transfer { deposit } if deposit == bond)
+        );
+        assert_eq!(ctr.bond(poll_id, alice()), None);
+        assert!(matches!(ctr.withdraw_bond(poll_id), Err(PollError::NoBond)));
+        assert_eq!(ctr.bond(poll_id, bob()), Some(U128(bond)));
+    }
 }
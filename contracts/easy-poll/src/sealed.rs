@@ -0,0 +1,114 @@
+//! Sealed ballots encryption.
+//!
+//! A ballot is encrypted with the poll X25519 public key, using an ephemeral X25519 key and
+//! the ChaCha20-Poly1305 AEAD:
+//! `ballot = ephemeral_public_key (32 bytes) || ChaCha20-Poly1305(key, nonce, borsh(answers))`,
+//! where:
+//! - `shared = X25519(ephemeral_secret_key, poll_public_key)`
+//! - `key = sha256(shared || ephemeral_public_key || poll_public_key)`
+//! - `nonce = sha256(ephemeral_public_key || poll_public_key)[..12]`
+//!
+//! The AEAD output includes the 16 bytes Poly1305 authentication tag.
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use near_sdk::env;
+
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// length of an encrypted empty message
+const MIN_BALLOT_LEN: usize = KEY_LEN + TAG_LEN;
+
+/// Returns the X25519 public key of the `secret_key`.
+pub fn public_key(secret_key: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    (X25519_BASEPOINT * clamp(secret_key)).to_bytes()
+}
+
+/// Decrypts the `ballot` with the poll `secret_key` and `public_key`. Returns None if the
+/// ballot is malformed or was not encrypted with the poll public key.
+pub fn decrypt(
+    secret_key: &[u8; KEY_LEN],
+    public_key: &[u8; KEY_LEN],
+    ballot: &[u8],
+) -> Option<Vec<u8>> {
+    if ballot.len() < MIN_BALLOT_LEN {
+        return None;
+    }
+    let (ephemeral_pk, ciphertext) = ballot.split_at(KEY_LEN);
+    let ephemeral_pk: [u8; KEY_LEN] = ephemeral_pk.try_into().ok()?;
+    let shared = MontgomeryPoint(ephemeral_pk) * clamp(secret_key);
+    let (cipher, nonce) = ballot_cipher(&shared.to_bytes(), &ephemeral_pk, public_key);
+    cipher.decrypt(&nonce, ciphertext).ok()
+}
+
+/// Encrypts `plaintext` for the poll `public_key` using the `ephemeral_secret_key`.
+#[cfg(test)]
+pub fn encrypt(
+    public_key: &[u8; KEY_LEN],
+    ephemeral_secret_key: &[u8; KEY_LEN],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let ephemeral_pk = self::public_key(ephemeral_secret_key);
+    let shared = MontgomeryPoint(*public_key) * clamp(ephemeral_secret_key);
+    let (cipher, nonce) = ballot_cipher(&shared.to_bytes(), &ephemeral_pk, public_key);
+    let ciphertext = cipher.encrypt(&nonce, plaintext).unwrap();
+    [&ephemeral_pk[..], &ciphertext].concat()
+}
+
+fn clamp(secret_key: &[u8; KEY_LEN]) -> Scalar {
+    let mut s = *secret_key;
+    s[0] &= 248;
+    s[31] &= 127;
+    s[31] |= 64;
+    Scalar::from_bits(s)
+}
+
+/// Derives the ballot AEAD cipher and nonce from the X25519 `shared` secret and the
+/// ephemeral and poll public keys.
+fn ballot_cipher(
+    shared: &[u8; KEY_LEN],
+    ephemeral_pk: &[u8; KEY_LEN],
+    public_key: &[u8; KEY_LEN],
+) -> (ChaCha20Poly1305, Nonce) {
+    let key = env::sha256(&[&shared[..], ephemeral_pk, public_key].concat());
+    let nonce = env::sha256(&[&ephemeral_pk[..], public_key].concat());
+    (
+        ChaCha20Poly1305::new(Key::from_slice(&key)),
+        *Nonce::from_slice(&nonce[..NONCE_LEN]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt() {
+        let sk = [7u8; KEY_LEN];
+        let pk = public_key(&sk);
+        let msg: Vec<u8> = (0..100).collect();
+        let ballot = encrypt(&pk, &[9u8; KEY_LEN], &msg);
+        assert_eq!(ballot.len(), MIN_BALLOT_LEN + msg.len());
+        assert_eq!(decrypt(&sk, &pk, &ballot), Some(msg.clone()));
+
+        // wrong key
+        let wrong_sk = [8u8; KEY_LEN];
+        assert_eq!(decrypt(&wrong_sk, &public_key(&wrong_sk), &ballot), None);
+        // tampered ciphertext
+        let mut tampered = ballot.clone();
+        tampered[KEY_LEN] ^= 1;
+        assert_eq!(decrypt(&sk, &pk, &tampered), None);
+        // tampered ephemeral key
+        let mut tampered = ballot.clone();
+        tampered[0] ^= 1;
+        assert_eq!(decrypt(&sk, &pk, &tampered), None);
+        assert_eq!(decrypt(&sk, &pk, &ballot[..MIN_BALLOT_LEN - 1]), None);
+        // empty plaintext
+        let ballot = encrypt(&pk, &[9u8; KEY_LEN], &[]);
+        assert_eq!(decrypt(&sk, &pk, &ballot), Some(vec![]));
+    }
+}
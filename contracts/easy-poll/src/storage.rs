@@ -1,6 +1,10 @@
+use std::collections::HashMap;
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::BorshStorageKey;
+use near_sdk::{AccountId, BorshStorageKey};
+use sbt::{ClassId, ClassSet};
 
 pub type PollId = u64;
 
@@ -16,7 +20,7 @@ pub enum Answer {
     TextAnswer(String),
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub enum PollResult {
@@ -24,10 +28,10 @@ pub enum PollResult {
     TextChoices(Vec<u32>),            // should respect the min_choices, max_choices
     PictureChoices(Vec<u32>),         // should respect the min_choices, max_choices
     OpinionRange(OpinionRangeResult), // mean value
-    TextAnswer, // indicates whether the question exist or not, the answers are stored in a different struct called `TextAnswers`
+    TextAnswer, // indicates whether the question exist or not, the answers are stored in `Contract.text_answers`
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
 #[serde(crate = "near_sdk::serde")]
 pub struct OpinionRangeResult {
@@ -47,6 +51,28 @@ pub struct Question {
     pub labels: Option<(String, String, String)>, // if applicable, labels for the opinion scale question
     pub choices: Option<Vec<String>>, // if applicable, choices for the text and picture choices question TODO: make sure we dont need it
     pub max_choices: Option<u32>,
+    /// optional localized variants of the question title and description: locale -> text.
+    /// Locale should be a language code (eg: "en", "pt-BR").
+    pub locales: Option<HashMap<String, LocalizedText>>,
+    /// optional branching: the question is only applicable if the referenced previous YesNo
+    /// question was answered with `answer`. Not applicable questions can't be answered.
+    pub condition: Option<QuestionCondition>,
+}
+
+/// Condition of a question applicability.
+#[derive(BorshSerialize, BorshDeserialize, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct QuestionCondition {
+    pub question: u32, // index of a previous YesNo question
+    pub answer: bool,
+}
+
+/// Localized variant of a question content.
+#[derive(BorshSerialize, BorshDeserialize, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LocalizedText {
+    pub title: String,               // required
+    pub description: Option<String>, // optional
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -61,15 +87,78 @@ pub struct Poll {
     pub description: String, // can be an empty string
     pub link: String,   // can be an empty string
     pub created_at: u64, // time in milliseconds, should be assigned by the smart contract not a user.
+    pub editable: bool,  // if true, responders can edit their answers until `ends_at`
+    pub creator: AccountId, // assigned by the smart contract, only the creator can update or cancel the poll
+    /// if set, only holders of all the SBT classes in the set can vote (checked with the
+    /// registry `sbt_tokens_by_owner`, only not expired tokens are considered)
+    pub eligibility: Option<ClassSet>,
+    /// if set, responses are additionally aggregated in the weighted results
+    pub weighting: Option<Weighting>,
+    /// if set, new responses are rejected once the poll has `max_participants`
+    pub max_participants: Option<u64>,
+    /// if set, responders must lock the bond (on top of the storage deposit) with their
+    /// first response. The bond can be withdrawn once the poll ends.
+    pub response_bond: Option<U128>,
+}
+
+/// Poll content and settings provided by the creator in `create_poll` and `update_poll`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PollParams {
+    pub iah_only: bool, // required, if true only verified humans can vote, if false anyone can vote
+    pub questions: Vec<Question>, // required, a poll can have any number of questions
+    pub starts_at: u64, // required, time in milliseconds
+    pub ends_at: u64,   // required, time in milliseconds
+    pub title: String,  // required
+    pub tags: Vec<String>, // can be an empty vector
+    pub description: String, // can be an empty string
+    pub link: String,   // can be an empty string
+    /// if true, responders can edit their answers until `ends_at`. Default: false.
+    pub editable: Option<bool>,
+    /// see `Poll.eligibility`
+    pub eligibility: Option<ClassSet>,
+    /// see `Poll.weighting`
+    pub weighting: Option<Weighting>,
+    /// see `Poll.max_participants`
+    pub max_participants: Option<u64>,
+    /// see `Poll.response_bond`
+    pub response_bond: Option<U128>,
 }
 
+/// Weight of a response in the weighted results, computed from the proof SBTs returned by
+/// the registry.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
 #[serde(crate = "near_sdk::serde")]
+pub enum Weighting {
+    /// number of the proof SBTs: the IAH SBTs (for `iah_only` polls) and the not expired
+    /// SBTs of the eligibility issuers, starting from the smallest eligibility class.
+    TokenCount,
+    /// sum of weights of the held classes: list of (issuer, class, weight). Classes must be
+    /// part of the poll eligibility.
+    ClassWeights(Vec<(AccountId, ClassId, u32)>),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
 pub struct Results {
     pub status: Status,
     pub participants_num: u64,    // number of participants
     pub results: Vec<PollResult>, // question_id, result (sum of yes etc.)
+    pub applicable: Vec<u64>,     // question_id, number of participants the question applied to
+    /// number of humans in the registry when an IAH poll was finalized
+    pub humans_count: Option<u64>,
+    /// results of a weighted poll, where each response counts with its weight
+    pub weighted: Option<WeightedResults>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct WeightedResults {
+    pub participants_weight: u64, // sum of weights of all participants
+    pub results: Vec<PollResult>, // question_id, weighted result (eg: sum of yes weights)
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
@@ -79,6 +168,8 @@ pub enum Status {
     NotStarted,
     Active,
     Finished,
+    /// the poll was cancelled by the creator, responses are not accepted anymore
+    Cancelled,
 }
 
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -86,4 +177,37 @@ pub enum StorageKey {
     Polls,
     Results,
     Participants,
+    Responses,
+    CreatorTags,
+    TextAnswers,
+    Responders,
+    SealedPolls,
+    Ballots,
+    OpenPolls,
+    TagPolls,
+    CreatorPolls,
+    Bonds,
+}
+
+/// Sealed ballot configuration and tally progress of a poll.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SealedPoll {
+    /// X25519 public key used to encrypt the ballots
+    pub public_key: Base64VecU8,
+    /// X25519 secret key, revealed by the creator once the poll ended
+    pub secret_key: Option<Base64VecU8>,
+    /// number of ballots tallied
+    pub tallied: u64,
+    /// number of tallied ballots which couldn't be decrypted or had invalid answers
+    pub invalid: u64,
+}
+
+/// Encrypted response of a sealed poll.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Ballot {
+    pub ciphertext: Vec<u8>,
+    /// response weight, used only by weighted polls
+    pub weight: u32,
 }
@@ -41,7 +41,7 @@ async fn init(worker: &Worker<Sandbox>) -> anyhow::Result<(Contract, Account, Ac
         &worker,
         "./",
         "new",
-        json!({"sbt_registry": registry_contract.id()}),
+        json!({"sbt_registry": registry_contract.id(), "admin": alice_acc.id()}),
     )
     .await?;
 
@@ -63,9 +63,9 @@ async fn flow1() -> anyhow::Result<()> {
     let now_ms = get_block_timestamp(&worker).await? / 1_000_000;
     // create a poll
     let poll_id_non_human_gated: u64 = bob.call(easy_poll_contract.id(), "create_poll")
-        .args_json(json!({"iah_only": false, "questions": [{"question_type": {"YesNo": false}, "required": true,
+        .args_json(json!({"params": {"iah_only": false, "questions": [{"question_type": {"YesNo": false}, "required": true,
             "title": "non-human gated"}], "starts_at": now_ms + 20000, "ends_at": now_ms + 300000,
-            "title": "Testing Poll 1", "tags": ["test"], "description": "poll desc", "link": "test.io"}))
+            "title": "Testing Poll 1", "tags": ["test"], "description": "poll desc", "link": "test.io"}}))
         .max_gas()
         .transact()
         .await?
@@ -73,9 +73,9 @@ async fn flow1() -> anyhow::Result<()> {
 
     // create a poll
     let poll_id_human_gated: u64 = bob.call(easy_poll_contract.id(), "create_poll")
-        .args_json(json!({"iah_only": true, "questions": [{"question_type": {"YesNo": false}, "required": true,
+        .args_json(json!({"params": {"iah_only": true, "questions": [{"question_type": {"YesNo": false}, "required": true,
             "title": "human gated"}], "starts_at": now_ms + 5000, "ends_at": now_ms + 86400000,
-            "title": "Testing Poll 1", "tags": ["test"], "description": "poll desc", "link": "test.io"}))
+            "title": "Testing Poll 1", "tags": ["test"], "description": "poll desc", "link": "test.io"}}))
         .max_gas()
         .transact()
         .await?
@@ -105,7 +105,10 @@ async fn flow1() -> anyhow::Result<()> {
         Results {
             status: Status::NotStarted,
             participants_num: 2,
-            results: vec![PollResult::YesNo((2, 0))]
+            results: vec![PollResult::YesNo((2, 0))],
+            applicable: vec![2],
+            humans_count: None,
+            weighted: None,
         }
     );
 
@@ -122,7 +125,10 @@ async fn flow1() -> anyhow::Result<()> {
         Results {
             status: Status::NotStarted,
             participants_num: 1,
-            results: vec![PollResult::YesNo((1, 0))]
+            results: vec![PollResult::YesNo((1, 0))],
+            applicable: vec![1],
+            humans_count: None,
+            weighted: None,
         }
     );
 
@@ -20,6 +20,8 @@ pub mod tests {
             external_id: "0xb4bf0f23c702efb8a9da87a94095e28de3d21cc3".to_owned(),
             timestamp: 0,
             verified_kyc: false,
+            credentials: Vec::new(),
+            attestation: None,
         };
 
         let borsh_serialized: Vec<u8> = borsh_input.try_to_vec().unwrap();
@@ -30,6 +32,41 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn claim_credentials_serialization() {
+        let mut c = Claim {
+            claimer: alice(),
+            external_id: "0x1a".to_owned(),
+            timestamp: 10,
+            verified_kyc: true,
+            credentials: Vec::new(),
+            attestation: None,
+        };
+        // claims without credentials keep the legacy encoding
+        let legacy_len = 4 + 10 + 4 + 4 + 8 + 1;
+        let bz = c.try_to_vec().unwrap();
+        assert_eq!(bz.len(), legacy_len);
+        assert_eq!(Claim::try_from_slice(&bz).unwrap(), c);
+
+        c.credentials = vec!["age".to_owned()];
+        let bz = c.try_to_vec().unwrap();
+        assert_eq!(bz.len(), legacy_len + 4 + 4 + 3);
+        assert_eq!(Claim::try_from_slice(&bz).unwrap(), c);
+
+        // attestation is appended after the credentials (even if there are none)
+        c.attestation = Some(ClaimAttestation {
+            account_created_at: 5,
+            staked_balance: 7,
+        });
+        let bz = c.try_to_vec().unwrap();
+        assert_eq!(bz.len(), legacy_len + 4 + 4 + 3 + 8 + 16);
+        assert_eq!(Claim::try_from_slice(&bz).unwrap(), c);
+        c.credentials = Vec::new();
+        let bz = c.try_to_vec().unwrap();
+        assert_eq!(bz.len(), legacy_len + 4 + 8 + 16);
+        assert_eq!(Claim::try_from_slice(&bz).unwrap(), c);
+    }
+
     #[test]
     fn claim_deserialization_check() {
         let c = deserialize_claim(
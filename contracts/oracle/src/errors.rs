@@ -1,3 +1,5 @@
+use std::fmt;
+
 use near_sdk::env::panic_str;
 use near_sdk::{base64, FunctionError};
 
@@ -15,24 +17,28 @@ pub enum CtrError {
     DuplicatedID(String),
     Signature(String),
     Registry,
+    /// claimer has an ongoing soul transfer in the registry
+    OngoingSoulTransfer,
 }
 
-impl FunctionError for CtrError {
-    fn panic(&self) -> ! {
-        // check how we can do this:
-        // panic_str(match self {
-        //     CtrError::Borsh(e) => &format!("can't borsh decode {}", e),
-        //     CtrError::B64Err { arg, .. } => &format!("can't base64 decode {}", arg),
-        //     CtrError::BadRequest(s) => s.as_ref(),
-        // });
-
+impl fmt::Display for CtrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CtrError::Borsh(e) => panic_str(&format!("can't borsh-decode {}", e)),
-            CtrError::B64Err { arg, .. } => panic_str(&format!("can't base64-decode {}", arg)),
-            CtrError::BadRequest(s) => panic_str(s.as_ref()),
-            CtrError::DuplicatedID(s) => panic_str(&format!("duplicated id: {}", s)),
-            CtrError::Signature(s) => panic_str(&format!("signature error: {}", s)),
-            CtrError::Registry => panic_str("registry operation failed"),
+            CtrError::Borsh(e) => write!(f, "can't borsh-decode {}", e),
+            CtrError::B64Err { arg, .. } => write!(f, "can't base64-decode {}", arg),
+            CtrError::BadRequest(s) => f.write_str(s),
+            CtrError::DuplicatedID(s) => write!(f, "duplicated id: {}", s),
+            CtrError::Signature(s) => write!(f, "signature error: {}", s),
+            CtrError::Registry => f.write_str("registry operation failed"),
+            CtrError::OngoingSoulTransfer => {
+                f.write_str("claimer has an ongoing soul transfer in the registry")
+            }
         }
     }
 }
+
+impl FunctionError for CtrError {
+    fn panic(&self) -> ! {
+        panic_str(&self.to_string())
+    }
+}
@@ -0,0 +1,90 @@
+use near_sdk::{env, serde::Serialize, serde_json::json, AccountId};
+use sbt::{ClassId, EventPayload, NearEvent, TokenId};
+use uint::hex;
+
+fn emit_event<T: Serialize>(event: EventPayload<T>) {
+    NearEvent {
+        standard: "oracle_sbt",
+        version: "1.0.0",
+        event,
+    }
+    .emit();
+}
+
+/// Returns hex encoded sha256 of the normalized external identity. Events expose the hash
+/// rather than the identity, so the verification backend can reconcile its records.
+fn external_id_hash(external_id: &[u8]) -> String {
+    hex::encode(env::sha256(external_id))
+}
+
+/// `external_id`: normalized external identity.
+/// `classes`: classes of the tokens requested to mint.
+pub(crate) fn emit_iah_mint_requested(
+    external_id: &[u8],
+    claimer: &AccountId,
+    classes: Vec<ClassId>,
+) {
+    emit_event(EventPayload {
+        event: "iah_mint_requested",
+        data: json!({
+            "external_id_hash": external_id_hash(external_id),
+            "claimer": claimer,
+            "classes": classes,
+        }),
+    });
+}
+
+/// `external_id`: normalized external identity.
+pub(crate) fn emit_iah_mint_failed(external_id: &[u8], claimer: &AccountId, reason: &str) {
+    emit_event(EventPayload {
+        event: "iah_mint_failed",
+        data: json!({
+            "external_id_hash": external_id_hash(external_id),
+            "claimer": claimer,
+            "reason": reason,
+        }),
+    });
+}
+
+/// `external_id`: normalized external identity.
+/// `tokens`: revoked (or burned) tokens of the `account`.
+pub(crate) fn emit_iah_revoked(external_id: &[u8], account: &AccountId, tokens: &[TokenId]) {
+    emit_event(EventPayload {
+        event: "iah_revoked",
+        data: json!({
+            "external_id_hash": external_id_hash(external_id),
+            "account": account,
+            "tokens": tokens,
+        }),
+    });
+}
+
+/// `external_id`: normalized external identity.
+/// `quarantined_until`: time (in milliseconds) until which the identity can't be used to
+/// mint SBT, or None if the identity is released without a cool-down.
+pub(crate) fn emit_release_identity(
+    external_id: &[u8],
+    account: &AccountId,
+    quarantined_until: Option<u64>,
+) {
+    emit_event(EventPayload {
+        event: "release_identity",
+        data: json!({
+            "external_id_hash": external_id_hash(external_id),
+            "account": account,
+            "quarantined_until": quarantined_until,
+        }),
+    });
+}
+
+/// Emitted once all tokens of the `admin_revoke_range` range were revoked (or burned).
+pub(crate) fn emit_revoked_range(from_token: TokenId, to_token: TokenId, burn: bool) {
+    emit_event(EventPayload {
+        event: "revoked_range",
+        data: json!({
+            "from_token": from_token,
+            "to_token": to_token,
+            "burn": burn,
+        }),
+    });
+}
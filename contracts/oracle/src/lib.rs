@@ -1,8 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::U128;
 use near_sdk::serde::Serialize;
 use near_sdk::{
     env, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseError,
+    PromiseOrValue,
 };
 
 #[allow(unused_imports)]
@@ -20,6 +22,7 @@ pub use crate::storage::*;
 pub use crate::util::*;
 
 mod errors;
+mod events;
 mod migrate;
 mod storage;
 mod util;
@@ -31,8 +34,18 @@ pub const CLASS_KYC_SBT: ClassId = 2;
 pub const MINT_TOTAL_COST: Balance = mint_deposit(1);
 pub const MINT_TOTAL_COST_WITH_KYC: Balance = mint_deposit(2);
 
-pub const ELECTIONS_START: u64 = 1693612799000; // Fri, 1 Sep 2023 23:59:59 UTC in ms
-pub const ELECTIONS_END: u64 = 1695427199000; // Fri, 22 Sep 2023 23:59:59 UTC in ms
+const SOUL_TRANSFER_CHECK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const MINT_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const TOKENS_QUERY_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+const RENEW_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const RELEASE_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const REVOKE_CALLBACK_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+const REVOKE_RANGE_CALLBACK_GAS: Gas = Gas(REVOKE_CALLBACK_GAS.0 + 5 * Gas::ONE_TERA.0);
+
+/// Maximum `limit` for paginated queries. Bigger values are clamped to `MAX_LIMIT`.
+pub const MAX_LIMIT: u32 = 1000;
+/// Maximum number of tokens revoked by a single `admin_revoke_range` call.
+pub const MAX_REVOKE_RANGE_BATCH: TokenId = 25;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -56,6 +69,59 @@ pub struct Contract {
 
     /// class metadata
     pub class_metadata: LookupMap<ClassId, ClassMetadata>,
+
+    /// (start, end) time in milliseconds of a period (eg elections) when SBT can't be minted.
+    pub mint_blackout: Option<(u64, u64)>,
+
+    /// map of external_id -> (recipient, minted tokens), recorded on successful mint.
+    pub identity_tokens: LookupMap<Vec<u8>, (AccountId, Vec<TokenId>)>,
+
+    /// map of Claim credential type -> SBT class minted for the credential.
+    pub credential_classes: UnorderedMap<String, CredentialClass>,
+
+    /// map of recipient -> external_id used to mint SBT, recorded on successful mint.
+    pub account_identities: LookupMap<AccountId, Vec<u8>>,
+
+    /// released identities in a cool-down period: external_id -> time (in milliseconds)
+    /// until which the identity can't be used to mint SBT.
+    pub quarantined_identities: LookupMap<Vec<u8>, u64>,
+
+    /// Claim gating policy (see `set_claim_gating`).
+    pub claim_gating: LazyOption<ClaimGating>,
+
+    /// progress of the ongoing `admin_revoke_range`.
+    pub revoke_range: LazyOption<RevokeRange>,
+}
+
+/// Checks the claim attestation against the Claim `gating` policy.
+fn check_claim_gating(gating: Option<ClaimGating>, claim: &Claim) -> Result<(), CtrError> {
+    let gating = match gating {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+    let a = match &claim.attestation {
+        Some(a) => a,
+        None => {
+            return match gating.legacy_claims_until {
+                Some(until) if env::block_timestamp_ms() <= until => Ok(()),
+                _ => Err(CtrError::BadRequest(
+                    "claim.attestation is required".to_owned(),
+                )),
+            }
+        }
+    };
+    let now = env::block_timestamp_ms() / 1000;
+    if a.account_created_at > now || now - a.account_created_at < gating.min_account_age {
+        return Err(CtrError::BadRequest(
+            "claim.attestation: account is too young".to_owned(),
+        ));
+    }
+    if a.staked_balance < gating.min_staked_balance.0 {
+        return Err(CtrError::BadRequest(
+            "claim.attestation: staked balance is too low".to_owned(),
+        ));
+    }
+    Ok(())
 }
 
 // Implement the contract structure
@@ -90,6 +156,13 @@ impl Contract {
             used_identities: UnorderedSet::new(StorageKey::UsedIdentities),
             admins,
             class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            mint_blackout: None,
+            identity_tokens: LookupMap::new(StorageKey::IdentityTokens),
+            credential_classes: UnorderedMap::new(StorageKey::CredentialClasses),
+            account_identities: LookupMap::new(StorageKey::AccountIdentities),
+            quarantined_identities: LookupMap::new(StorageKey::QuarantinedIdentities),
+            claim_gating: LazyOption::new(StorageKey::ClaimGating, None),
+            revoke_range: LazyOption::new(StorageKey::RevokeRange, None),
         }
     }
 
@@ -104,6 +177,8 @@ impl Contract {
         self.admins.iter().collect()
     }
 
+    /// Returns the storage deposit required to mint FV SBT and KYC SBT. Each Claim credential
+    /// requires additional `mint_deposit(1)`.
     #[inline]
     pub fn required_sbt_mint_deposit(is_verified_kyc: bool) -> Balance {
         if is_verified_kyc {
@@ -112,12 +187,82 @@ impl Contract {
         MINT_TOTAL_COST
     }
 
-    /// Checks if the given id was already used to mint an sbt
+    /// Returns the contract configuration, used by monitoring to verify deployments.
+    pub fn status(&self) -> Status {
+        Status {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            registry: self.registry.clone(),
+            claim_ttl: self.claim_ttl,
+            sbt_ttl_ms: self.sbt_ttl_ms,
+            authority_fingerprint: hex::encode(env::sha256(&self.authority_pubkey)),
+        }
+    }
+
+    /// Returns the list of Claim credential types and their SBT classes.
+    pub fn credential_classes(&self) -> Vec<(String, CredentialClass)> {
+        self.credential_classes.to_vec()
+    }
+
+    /// Returns the progress of the ongoing `admin_revoke_range`, or None if there is no
+    /// ongoing range revoke.
+    pub fn revoke_range(&self) -> Option<RevokeRange> {
+        self.revoke_range.get()
+    }
+
+    /// Returns the Claim gating policy required to mint SBT (see `set_claim_gating`).
+    pub fn claim_gating(&self) -> Option<ClaimGating> {
+        self.claim_gating.get()
+    }
+
+    /// Returns the (start, end) time in milliseconds of the mint blackout period.
+    pub fn mint_blackout(&self) -> Option<(u64, u64)> {
+        self.mint_blackout
+    }
+
+    /// Checks if the given id was already used to mint an sbt. Returns false once the
+    /// identity is released with `admin_release_identity`.
     pub fn is_used_identity(&self, external_id: String) -> bool {
         let normalised_id = normalize_external_id(external_id).expect("failed to normalize id");
         self.used_identities.contains(&normalised_id)
     }
 
+    /// Returns the external identity (hex of the normalized external id) used to mint SBT
+    /// to the `account`. Returns None if the account didn't mint SBT, the identity was
+    /// released or revoked, or SBT were minted before the index was introduced.
+    pub fn used_identity_of(&self, account: AccountId) -> Option<String> {
+        self.account_identities.get(&account).map(hex::encode)
+    }
+
+    /// Returns the list of used external identities (hex of the normalized external id),
+    /// paginated. Removing an identity moves the last one to its index, so the backend
+    /// should reconcile a snapshot taken when no identities are released.
+    /// `from_index`: default 0.
+    /// `limit`: default and max is `MAX_LIMIT`.
+    pub fn used_identities(&self, from_index: Option<u64>, limit: Option<u32>) -> Vec<String> {
+        let limit = std::cmp::min(limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+        self.used_identities
+            .as_vector()
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .map(hex::encode)
+            .collect()
+    }
+
+    /// Returns the number of used external identities.
+    pub fn used_identities_count(&self) -> u64 {
+        self.used_identities.len()
+    }
+
+    /// Returns the time (in milliseconds) until which a released identity can't be used to
+    /// mint SBT. Returns None if the identity is not in a cool-down period.
+    pub fn identity_quarantine(&self, external_id: String) -> Option<u64> {
+        let normalised_id = normalize_external_id(external_id).expect("failed to normalize id");
+        self.quarantined_identities
+            .get(&normalised_id)
+            .filter(|until| *until > env::block_timestamp_ms())
+    }
+
     /**********
      * FUNCTIONS
      **********/
@@ -127,7 +272,11 @@ impl Contract {
     /// @claim_sig: standard base64 serialized ed25519 signature.
     /// If `metadata.expires_at` is None then we set it to ` now+self.ttl`.
     /// Panics if `metadata.expires_at > now+self.ttl`.
-    /// Throws an error if trying to mint during the elections period.
+    /// Throws an error if trying to mint during the blackout period (see `set_mint_blackout`).
+    /// Additionally, a token is minted for each `claim.credentials` type, using the class and
+    /// ttl set with `set_credential_class`. Throws an error if a credential is not configured.
+    /// Throws an error if the claim attestation doesn't satisfy the Claim gating policy (see
+    /// `set_claim_gating`).
     // TODO: update result to return TokenId
     #[handle_result]
     #[payable]
@@ -138,15 +287,12 @@ impl Contract {
         memo: Option<String>,
     ) -> Result<Promise, CtrError> {
         let now_ms = env::block_timestamp_ms();
-        let this_acc = env::current_account_id();
-        // only stop in prod
-        if this_acc.as_str().ends_with("i-am-human.near")
-            && now_ms > ELECTIONS_START
-            && now_ms <= ELECTIONS_END
-        {
-            return Err(CtrError::BadRequest(
-                "IAH SBT cannot be mint during the elections period".to_owned(),
-            ));
+        if let Some((start, end)) = self.mint_blackout {
+            if now_ms > start && now_ms <= end {
+                return Err(CtrError::BadRequest(
+                    "IAH SBT cannot be mint during the elections period".to_owned(),
+                ));
+            }
         }
 
         let user = env::signer_account_id();
@@ -156,13 +302,11 @@ impl Contract {
             ));
         }
 
-        let claim_bytes = b64_decode("claim_b64", claim_b64)?;
-        let claim = Claim::try_from_slice(&claim_bytes)
-            .map_err(|_| CtrError::Borsh("claim".to_string()))?;
-        let signature = b64_decode("claim_sig", claim_sig)?;
-        verify_claim(&signature, &claim_bytes, &self.authority_pubkey)?;
+        let claim = self.decode_claim(claim_b64, claim_sig)?;
+        let credentials = self.credential_classes_of(&claim.credentials)?;
 
-        let storage_deposit = Self::required_sbt_mint_deposit(claim.verified_kyc);
+        let num_tokens = 1 + claim.verified_kyc as usize + credentials.len();
+        let storage_deposit = mint_deposit(num_tokens);
         require!(
             env::attached_deposit() >= storage_deposit,
             format!(
@@ -170,28 +314,23 @@ impl Contract {
                 storage_deposit
             )
         );
-        let num_tokens = if claim.verified_kyc { 2 } else { 1 };
 
-        let now = now_ms / 1000;
-        if claim.timestamp > now {
-            return Err(CtrError::BadRequest(
-                "claim.timestamp in the future".to_string(),
-            ));
-        }
-        if now >= claim.timestamp + self.claim_ttl {
-            return Err(CtrError::BadRequest("claim expired".to_string()));
-        }
-
-        if claim.claimer != user {
-            return Err(CtrError::BadRequest(
-                "claimer is not the transaction signer".to_string(),
-            ));
-        }
+        self.check_claim(&claim, &user)?;
+        check_claim_gating(self.claim_gating.get(), &claim)?;
         let external_id = normalize_external_id(claim.external_id)?;
 
         if self.used_identities.contains(&external_id) {
             return Err(CtrError::DuplicatedID("external_id".to_string()));
         }
+        if let Some(until) = self.quarantined_identities.get(&external_id) {
+            if until > now_ms {
+                return Err(CtrError::BadRequest(format!(
+                    "external_id is in quarantine until {}",
+                    until
+                )));
+            }
+            self.quarantined_identities.remove(&external_id);
+        }
 
         let mut tokens_metadata: Vec<TokenMetadata> = Vec::new();
         tokens_metadata.push(TokenMetadata {
@@ -211,26 +350,185 @@ impl Contract {
                 reference_hash: None,
             });
         }
+        for c in credentials {
+            tokens_metadata.push(TokenMetadata {
+                class: c.class,
+                issued_at: Some(now_ms),
+                expires_at: Some(now_ms + c.ttl_ms),
+                reference: None,
+                reference_hash: None,
+            });
+        }
 
         self.used_identities.insert(&external_id);
 
         if let Some(memo) = memo {
             env::log_str(&format!("SBT mint memo: {}", memo));
         }
+        events::emit_iah_mint_requested(
+            &external_id,
+            &claim.claimer,
+            tokens_metadata.iter().map(|m| m.class).collect(),
+        );
 
+        // Minting to an account in the middle of a soul transfer would leave the new tokens
+        // behind (or make the transfer fail), so we check the registry first.
         let result = ext_registry::ext(self.registry.clone())
-            .with_attached_deposit(storage_deposit)
-            .with_static_gas(calculate_mint_gas(num_tokens))
-            .sbt_mint(vec![(claim.claimer, tokens_metadata)])
+            .with_static_gas(SOUL_TRANSFER_CHECK_GAS)
+            .soul_transfer_ongoing(claim.claimer.clone())
             .then(
                 Self::ext(env::current_account_id())
-                    .with_static_gas(Gas::ONE_TERA * 3)
-                    .sbt_mint_callback(hex::encode(external_id)),
+                    .with_static_gas(
+                        calculate_mint_gas(num_tokens)
+                            + MINT_CALLBACK_GAS
+                            + SOUL_TRANSFER_CHECK_GAS,
+                    )
+                    .on_soul_transfer_check(
+                        claim.claimer,
+                        tokens_metadata,
+                        U128(storage_deposit),
+                        hex::encode(external_id),
+                    ),
             );
 
         Ok(result)
     }
 
+    /// Callback for the registry `soul_transfer_ongoing` query made in `sbt_mint`.
+    /// If the claimer has an ongoing soul transfer, the claim is rejected: the external
+    /// identity is released and the storage deposit is returned to the claimer.
+    /// Otherwise calls the registry to mint the tokens.
+    #[private]
+    pub fn on_soul_transfer_check(
+        &mut self,
+        claimer: AccountId,
+        tokens_metadata: Vec<TokenMetadata>,
+        storage_deposit: U128,
+        external_id: String,
+        #[callback_result] ongoing: Result<bool, PromiseError>,
+    ) -> PromiseOrValue<CallbackResult<TokenId, String>> {
+        let err = match ongoing {
+            Ok(false) => {
+                let num_tokens = tokens_metadata.len();
+                return PromiseOrValue::Promise(
+                    ext_registry::ext(self.registry.clone())
+                        .with_attached_deposit(storage_deposit.0)
+                        .with_static_gas(calculate_mint_gas(num_tokens))
+                        .sbt_mint(vec![(claimer.clone(), tokens_metadata)])
+                        .then(
+                            Self::ext(env::current_account_id())
+                                .with_static_gas(MINT_CALLBACK_GAS)
+                                .sbt_mint_callback(external_id, claimer, storage_deposit),
+                        ),
+                );
+            }
+            Ok(true) => CtrError::OngoingSoulTransfer,
+            Err(_) => CtrError::Registry,
+        };
+        // Same as in `sbt_mint_callback`: we can't panic here in order to preserve the
+        // rollback of the external identity.
+        let external_id = hex::decode(external_id).unwrap();
+        self.used_identities.remove(&external_id);
+        let err = err.to_string();
+        events::emit_iah_mint_failed(&external_id, &claimer, &err);
+        Promise::new(claimer).transfer(storage_deposit.0);
+        PromiseOrValue::Value(CallbackResult::Err(err))
+    }
+
+    /// Renews the FV SBT (and the KYC SBT when `verified_kyc` is set in the Claim) of the
+    /// transaction signer, setting `expires_at` to `now+self.ttl`.
+    /// The claim must be fresh and must be for the same external identity which was used
+    /// to mint the tokens of the claimer (claims for a new identity must use `sbt_mint`).
+    /// @claim_b64: standard base64 borsh serialized Claim (same bytes as used for the claim signature).
+    /// @claim_sig: standard base64 serialized ed25519 signature.
+    #[handle_result]
+    pub fn sbt_renew(
+        &mut self,
+        claim_b64: String,
+        claim_sig: String,
+        memo: Option<String>,
+    ) -> Result<Promise, CtrError> {
+        let user = env::signer_account_id();
+        let claim = self.decode_claim(claim_b64, claim_sig)?;
+        self.check_claim(&claim, &user)?;
+        let external_id = normalize_external_id(claim.external_id)?;
+        if !self.used_identities.contains(&external_id) {
+            return Err(CtrError::BadRequest(
+                "external_id was not used to mint SBT, use sbt_mint".to_string(),
+            ));
+        }
+        // the identity must be bound to the claimer, otherwise a claim for an identity used
+        // by another account could renew the claimer tokens.
+        let bound = self.account_identities.get(&claim.claimer).as_ref() == Some(&external_id)
+            || matches!(self.identity_tokens.get(&external_id), Some((a, _)) if a == claim.claimer);
+        if !bound {
+            return Err(CtrError::BadRequest(
+                "external_id was not used to mint SBT for the claimer".to_string(),
+            ));
+        }
+
+        if let Some(memo) = memo {
+            env::log_str(&format!("SBT renew memo: {}", memo));
+        }
+
+        let num_tokens = if claim.verified_kyc { 2 } else { 1 };
+        let result = ext_registry::ext(self.registry.clone())
+            .with_static_gas(TOKENS_QUERY_GAS)
+            .sbt_tokens_by_owner_private(
+                claim.claimer,
+                Some(env::current_account_id()),
+                None,
+                None,
+                Some(true),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(calculate_renew_gas(num_tokens) + RENEW_CALLBACK_GAS)
+                    .on_renew_tokens(claim.verified_kyc),
+            );
+        Ok(result)
+    }
+
+    /// Callback for the registry `sbt_tokens_by_owner_private` query made in `sbt_renew`.
+    /// Calls the registry to renew the claimer FV token (and KYC token if `with_kyc`).
+    /// Returns an error if the claimer doesn't have an FV token.
+    #[private]
+    pub fn on_renew_tokens(
+        &mut self,
+        with_kyc: bool,
+        #[callback_result] tokens: Result<Vec<(AccountId, Vec<OwnedToken>)>, PromiseError>,
+    ) -> PromiseOrValue<CallbackResult<(), String>> {
+        let tokens = match tokens {
+            Ok(tokens) => tokens,
+            Err(_) => {
+                return PromiseOrValue::Value(CallbackResult::Err(CtrError::Registry.to_string()))
+            }
+        };
+        let mut has_fv = false;
+        let mut to_renew: Vec<TokenId> = Vec::new();
+        for t in tokens.into_iter().flat_map(|(_, tokens)| tokens) {
+            match t.metadata.class {
+                CLASS_FV_SBT => has_fv = true,
+                CLASS_KYC_SBT if with_kyc => (),
+                _ => continue,
+            }
+            to_renew.push(t.token);
+        }
+        if !has_fv {
+            return PromiseOrValue::Value(CallbackResult::Err(
+                CtrError::BadRequest("claimer doesn't have FV SBT to renew".to_string())
+                    .to_string(),
+            ));
+        }
+
+        let num_tokens = to_renew.len();
+        PromiseOrValue::Promise(
+            ext_registry::ext(self.registry.clone())
+                .with_static_gas(calculate_renew_gas(num_tokens))
+                .sbt_renew(to_renew, env::block_timestamp_ms() + self.sbt_ttl_ms),
+        )
+    }
+
     // We use our own result type, because NEAR stopped to support standard `Result` return
     // type without `handle_result`. With `handle_result` we would need to make an ugly wrap
     // to always return Ok at the outer layer:
@@ -241,22 +539,35 @@ impl Contract {
     // Other solution (probably the right one) is to schedule another callback to "self" which
     // will panic.
     // Ideally, though, NEAR will start considering Result types again.
+    /// `claimer`: the `sbt_mint` transaction signer (`sbt_mint` checks that the claimer is the
+    /// signer). On failure the `storage_deposit` is refunded to the claimer.
     #[private]
     pub fn sbt_mint_callback(
         &mut self,
         external_id: String,
+        claimer: AccountId,
+        storage_deposit: U128,
         #[callback_result] last_result: Result<Vec<TokenId>, PromiseError>,
     ) -> CallbackResult<TokenId, &str> {
         match last_result {
-            Ok(v) => CallbackResult::Ok(v[0]),
+            Ok(v) => {
+                let id = hex::decode(external_id).unwrap();
+                self.account_identities.insert(&claimer, &id);
+                self.identity_tokens.insert(&id, &(claimer, v.clone()));
+                CallbackResult::Ok(v[0])
+            }
             Err(_) => {
                 // registry mint failed, need to rollback. We can't panic here in order to
                 // preserve state change.
                 // We are safe to remove the external identity, because we only call registry
                 // if the external_id was not used before.
-                self.used_identities
-                    .remove(&hex::decode(external_id).unwrap());
-                CallbackResult::Err("registry.sbt_mint failed")
+                let external_id = hex::decode(external_id).unwrap();
+                self.used_identities.remove(&external_id);
+                let err = "registry.sbt_mint failed";
+                events::emit_iah_mint_failed(&external_id, &claimer, err);
+                // the deposit attached to the failed registry call is refunded to the oracle
+                Promise::new(claimer).transfer(storage_deposit.0);
+                CallbackResult::Err(err)
             }
         }
     }
@@ -266,7 +577,7 @@ impl Contract {
     pub fn sbt_revoke(&mut self, tokens: Vec<TokenId>, burn: bool) -> Promise {
         self.assert_admin();
         ext_registry::ext(self.registry.clone())
-            .with_static_gas(MINT_GAS * tokens.len() as u64)
+            .with_static_gas(calculate_revoke_gas(tokens.len(), burn))
             .sbt_revoke(tokens, burn)
     }
 
@@ -274,6 +585,181 @@ impl Contract {
      * ADMIN
      **********/
 
+    /// Revokes (or burns if `burn`) the tokens minted for the `external_id` through the
+    /// registry, and frees the identity, eg when the verification provider reports the
+    /// identity as fraudulent. Tokens minted before the `external_id` index was introduced
+    /// are not indexed and must be revoked with `sbt_revoke`.
+    /// Must be called by an admin.
+    #[handle_result]
+    pub fn admin_revoke_external(
+        &mut self,
+        external_id: String,
+        burn: bool,
+    ) -> Result<Promise, CtrError> {
+        self.assert_admin();
+        let external_id = normalize_external_id(external_id)?;
+        let (_, tokens) = self.identity_tokens.get(&external_id).ok_or_else(|| {
+            CtrError::BadRequest("no tokens indexed for the external_id".to_string())
+        })?;
+        let result = ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_revoke_gas(tokens.len(), burn))
+            .sbt_revoke(tokens, burn)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(REVOKE_CALLBACK_GAS)
+                    .on_revoke_external(hex::encode(external_id)),
+            );
+        Ok(result)
+    }
+
+    /// Callback for the registry `sbt_revoke` call made in `admin_revoke_external`.
+    /// Frees the identity once the tokens are revoked.
+    /// Emits `iah_revoked` event.
+    #[private]
+    #[handle_result]
+    pub fn on_revoke_external(
+        &mut self,
+        external_id: String,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> Result<(), CtrError> {
+        result.map_err(|_| CtrError::Registry)?;
+        let external_id = hex::decode(external_id).unwrap();
+        if let Some((account, tokens)) = self.identity_tokens.get(&external_id) {
+            events::emit_iah_revoked(&external_id, &account, &tokens);
+        }
+        self.free_identity(&external_id);
+        Ok(())
+    }
+
+    /// Revokes (or burns if `burn`) all oracle tokens with IDs in the `[from_token, to_token]`
+    /// range through the registry, eg to revoke a compromised verification batch. Token IDs
+    /// which don't exist in the registry (eg already burned) are skipped. Each call
+    /// revokes up to `MAX_REVOKE_RANGE_BATCH` tokens, so the function must be called again
+    /// (with the same arguments, once the previous call finished) until the callback
+    /// returns `true`. See `revoke_range` query for the progress.
+    /// Only one range can be revoked at a time, see `admin_cancel_revoke_range`.
+    /// Emits `revoked_range` event once all tokens are revoked.
+    /// Must be called by an admin.
+    #[handle_result]
+    pub fn admin_revoke_range(
+        &mut self,
+        from_token: TokenId,
+        to_token: TokenId,
+        burn: bool,
+    ) -> Result<Promise, CtrError> {
+        self.assert_admin();
+        if from_token == 0 || from_token > to_token {
+            return Err(CtrError::BadRequest(
+                "from_token must be positive and not bigger than to_token".to_string(),
+            ));
+        }
+        let next = match self.revoke_range.get() {
+            Some(r) if r.from_token == from_token && r.to_token == to_token && r.burn == burn => {
+                r.next
+            }
+            Some(_) => {
+                return Err(CtrError::BadRequest(
+                    "another range revoke is in progress".to_string(),
+                ))
+            }
+            None => {
+                self.revoke_range.set(&RevokeRange {
+                    from_token,
+                    to_token,
+                    burn,
+                    next: from_token,
+                });
+                from_token
+            }
+        };
+        let last = std::cmp::min(next + MAX_REVOKE_RANGE_BATCH - 1, to_token);
+        let tokens: Vec<TokenId> = (next..=last).collect();
+        // registry `sbt_revoke` panics on a not existing token, so we query the tokens first
+        let result = ext_registry::ext(self.registry.clone())
+            .with_static_gas(TOKENS_QUERY_GAS)
+            .sbt_classes(env::current_account_id(), tokens.clone())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(
+                        calculate_revoke_gas(tokens.len(), burn) + REVOKE_RANGE_CALLBACK_GAS,
+                    )
+                    .on_revoke_range_classes(tokens, burn),
+            );
+        Ok(result)
+    }
+
+    /// Callback for the registry `sbt_classes` query made in `admin_revoke_range`.
+    /// Calls the registry to revoke the existing `tokens`. If none of the tokens exists,
+    /// moves the range progress directly.
+    #[private]
+    #[handle_result]
+    pub fn on_revoke_range_classes(
+        &mut self,
+        tokens: Vec<TokenId>,
+        burn: bool,
+        #[callback_result] classes: Result<Vec<Option<ClassId>>, PromiseError>,
+    ) -> Result<PromiseOrValue<bool>, CtrError> {
+        let classes = classes.map_err(|_| CtrError::Registry)?;
+        let last = *tokens.last().expect("tokens must not be empty");
+        let existing: Vec<TokenId> = tokens
+            .into_iter()
+            .zip(classes)
+            .filter_map(|(t, c)| c.map(|_| t))
+            .collect();
+        if existing.is_empty() {
+            return Ok(PromiseOrValue::Value(self.advance_revoke_range(last)));
+        }
+        let result = ext_registry::ext(self.registry.clone())
+            .with_static_gas(calculate_revoke_gas(existing.len(), burn))
+            .sbt_revoke(existing, burn)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(REVOKE_CALLBACK_GAS)
+                    .on_revoke_range(last),
+            );
+        Ok(PromiseOrValue::Promise(result))
+    }
+
+    /// Callback for the registry `sbt_revoke` call made in `admin_revoke_range`.
+    /// Moves the range progress after the `last` revoked token. Returns true if the whole
+    /// range was revoked. The progress is not changed when the registry call failed.
+    #[private]
+    #[handle_result]
+    pub fn on_revoke_range(
+        &mut self,
+        last: TokenId,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> Result<bool, CtrError> {
+        result.map_err(|_| CtrError::Registry)?;
+        Ok(self.advance_revoke_range(last))
+    }
+
+    /// Cancels the ongoing `admin_revoke_range`. Tokens already revoked stay revoked.
+    /// Returns false if there was no ongoing range revoke.
+    /// Must be called by an admin.
+    pub fn admin_cancel_revoke_range(&mut self) -> bool {
+        self.assert_admin();
+        self.revoke_range.remove()
+    }
+
+    /// Moves the `revoke_range` progress after the `last` processed token. Returns true if
+    /// the whole range was processed (or the range was cancelled in the meantime).
+    fn advance_revoke_range(&mut self, last: TokenId) -> bool {
+        let mut r = match self.revoke_range.get() {
+            Some(r) => r,
+            // the range was cancelled in the meantime
+            None => return true,
+        };
+        if last >= r.to_token {
+            self.revoke_range.remove();
+            events::emit_revoked_range(r.from_token, r.to_token, r.burn);
+            return true;
+        }
+        r.next = std::cmp::max(r.next, last + 1);
+        self.revoke_range.set(&r);
+        false
+    }
+
     /* for testing the callback
         #[payable]
         pub fn admin_mint(&mut self, recipient: AccountId, external_id: String) -> Promise {
@@ -289,15 +775,143 @@ impl Contract {
             ext_registry::ext(self.registry.clone())
                 .with_attached_deposit(MINT_COST)
                 .with_static_gas(MINT_GAS)
-                .sbt_mint(vec![(recipient, vec![metadata])])
+                .sbt_mint(vec![(recipient.clone(), vec![metadata])])
                 .then(
                     Self::ext(env::current_account_id())
                         .with_static_gas(Gas::ONE_TERA * 3)
-                        .sbt_mint_callback(hex::encode(external_id)),
+                        .sbt_mint_callback(hex::encode(external_id), recipient, U128(MINT_COST)),
                 )
         }
     */
 
+    /// Releases the `external_id` used to mint SBT to the `account`, once the account tokens
+    /// are burned (eg by the DAO for a fraud), so a legitimate user of the identity can be
+    /// verified again. The identity can't be used to mint SBT during the `cooldown_ms`
+    /// period (quarantine).
+    /// The registry is queried to confirm that the account doesn't have the oracle tokens.
+    /// Emits `release_identity` event.
+    /// Must be called by an admin.
+    #[handle_result]
+    pub fn admin_release_identity(
+        &mut self,
+        external_id: String,
+        account: AccountId,
+        cooldown_ms: u64,
+    ) -> Result<Promise, CtrError> {
+        self.assert_admin();
+        let external_id = normalize_external_id(external_id)?;
+        if !self.used_identities.contains(&external_id) {
+            return Err(CtrError::BadRequest("external_id is not used".to_string()));
+        }
+        let result = ext_registry::ext(self.registry.clone())
+            .with_static_gas(TOKENS_QUERY_GAS)
+            .sbt_tokens_by_owner_private(
+                account.clone(),
+                Some(env::current_account_id()),
+                None,
+                None,
+                Some(true),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(RELEASE_CALLBACK_GAS)
+                    .on_release_identity(hex::encode(external_id), account, cooldown_ms),
+            );
+        Ok(result)
+    }
+
+    /// Callback for the registry `sbt_tokens_by_owner_private` query made in
+    /// `admin_release_identity`. Releases the identity if the account doesn't have the
+    /// oracle tokens.
+    #[private]
+    #[handle_result]
+    pub fn on_release_identity(
+        &mut self,
+        external_id: String,
+        account: AccountId,
+        cooldown_ms: u64,
+        #[callback_result] tokens: Result<Vec<(AccountId, Vec<OwnedToken>)>, PromiseError>,
+    ) -> Result<(), CtrError> {
+        let tokens = tokens.map_err(|_| CtrError::Registry)?;
+        if tokens.iter().any(|(_, tokens)| !tokens.is_empty()) {
+            return Err(CtrError::BadRequest(
+                "account tokens must be burned before releasing the identity".to_string(),
+            ));
+        }
+        let id = hex::decode(&external_id).unwrap();
+        self.free_identity(&id);
+        let quarantined_until = if cooldown_ms > 0 {
+            let until = env::block_timestamp_ms() + cooldown_ms;
+            self.quarantined_identities.insert(&id, &until);
+            Some(until)
+        } else {
+            None
+        };
+        events::emit_release_identity(&id, &account, quarantined_until);
+        Ok(())
+    }
+
+    /// Sets a period (eg elections) when `sbt_mint` is rejected: `start_ms < now <= end_ms`
+    /// (time in milliseconds). Overwrites the previous period.
+    /// Must be called by an admin.
+    pub fn set_mint_blackout(&mut self, start_ms: u64, end_ms: u64) {
+        self.assert_admin();
+        require!(start_ms < end_ms, "start_ms must be before end_ms");
+        self.mint_blackout = Some((start_ms, end_ms));
+    }
+
+    /// Removes the mint blackout period.
+    /// Must be called by an admin.
+    pub fn clear_mint_blackout(&mut self) {
+        self.assert_admin();
+        self.mint_blackout = None;
+    }
+
+    /// Sets the Claim gating policy: `sbt_mint` requires a claim attestation matching the
+    /// policy. Claims without the attestation are accepted until
+    /// `gating.legacy_claims_until`. If `gating` is None, the policy is removed.
+    /// Must be called by an admin.
+    pub fn set_claim_gating(&mut self, gating: Option<ClaimGating>) {
+        self.assert_admin();
+        match gating {
+            Some(g) => {
+                self.claim_gating.set(&g);
+            }
+            None => {
+                self.claim_gating.remove();
+            }
+        }
+    }
+
+    /// Sets the SBT `class` minted for the Claim `credential` type, with the given ttl (in
+    /// milliseconds). Overwrites the previous class of the credential.
+    /// Classes 1 (FV) and 2 (KYC) are reserved, and a class can be used only by one credential.
+    /// Must be called by an admin.
+    pub fn set_credential_class(&mut self, credential: String, class: ClassId, ttl_ms: u64) {
+        self.assert_admin();
+        require!(
+            class > CLASS_KYC_SBT,
+            "class must be bigger than 2: 1 (FV) and 2 (KYC) are reserved"
+        );
+        require!(ttl_ms > 0, "ttl_ms must be positive");
+        require!(
+            !self
+                .credential_classes
+                .iter()
+                .any(|(cr, cc)| cc.class == class && cr != credential),
+            "class is already used by another credential"
+        );
+        self.credential_classes
+            .insert(&credential, &CredentialClass { class, ttl_ms });
+    }
+
+    /// Removes the Claim `credential` type: claims with the credential are rejected.
+    /// Must be called by an admin.
+    pub fn remove_credential_class(&mut self, credential: String) {
+        self.assert_admin();
+        self.credential_classes.remove(&credential);
+    }
+
     /// @authority: pubkey used to verify claim signature
     pub fn admin_change_authority(&mut self, authority: String) {
         self.assert_admin();
@@ -314,6 +928,74 @@ impl Contract {
         self.admins.remove(&admin);
     }
 
+    /// Decodes the claim and verifies its signature.
+    fn decode_claim(&self, claim_b64: String, claim_sig: String) -> Result<Claim, CtrError> {
+        let claim_bytes = b64_decode("claim_b64", claim_b64)?;
+        let claim = Claim::try_from_slice(&claim_bytes)
+            .map_err(|_| CtrError::Borsh("claim".to_string()))?;
+        let signature = b64_decode("claim_sig", claim_sig)?;
+        verify_claim(&signature, &claim_bytes, &self.authority_pubkey)?;
+        Ok(claim)
+    }
+
+    /// Checks the claim timestamp and that the claim was made for the transaction signer.
+    fn check_claim(&self, claim: &Claim, user: &AccountId) -> Result<(), CtrError> {
+        let now = env::block_timestamp_ms() / 1000;
+        if claim.timestamp > now {
+            return Err(CtrError::BadRequest(
+                "claim.timestamp in the future".to_string(),
+            ));
+        }
+        if now >= claim.timestamp + self.claim_ttl {
+            return Err(CtrError::BadRequest("claim expired".to_string()));
+        }
+        if &claim.claimer != user {
+            return Err(CtrError::BadRequest(
+                "claimer is not the transaction signer".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Removes the identity from the used identities, and its tokens and account indexes.
+    fn free_identity(&mut self, id: &Vec<u8>) {
+        self.used_identities.remove(id);
+        if let Some((account, _)) = self.identity_tokens.remove(id) {
+            if self.account_identities.get(&account).as_ref() == Some(id) {
+                self.account_identities.remove(&account);
+            }
+        }
+    }
+
+    /// Returns the classes of the Claim credentials.
+    /// Returns an error if a credential is not configured or is duplicated.
+    fn credential_classes_of(
+        &self,
+        credentials: &[String],
+    ) -> Result<Vec<CredentialClass>, CtrError> {
+        let mut classes: Vec<CredentialClass> = Vec::with_capacity(credentials.len());
+        for cr in credentials {
+            let cc = self.credential_classes.get(cr).ok_or_else(|| {
+                CtrError::BadRequest(format!("claim.credentials: unknown credential {}", cr))
+            })?;
+            if classes.iter().any(|c| c.class == cc.class) {
+                return Err(CtrError::BadRequest(format!(
+                    "claim.credentials: duplicated credential {}",
+                    cr
+                )));
+            }
+            classes.push(cc);
+        }
+        Ok(classes)
+    }
+
+    /// Returns true if the class is FV, KYC or a credential class.
+    fn is_oracle_class(&self, class: ClassId) -> bool {
+        class == CLASS_FV_SBT
+            || class == CLASS_KYC_SBT
+            || self.credential_classes.values().any(|cc| cc.class == class)
+    }
+
     #[inline]
     fn assert_admin(&self) {
         require!(
@@ -323,7 +1005,8 @@ impl Contract {
     }
 
     /// Allows admin to update class metadata.
-    /// Panics if not admin or the class is not found (Currently oracle only supports classes: [1,2])
+    /// Panics if not admin or the class is not found (oracle supports classes 1 (FV), 2 (KYC)
+    /// and the credential classes).
     #[handle_result]
     pub fn set_class_metadata(
         &mut self,
@@ -331,7 +1014,7 @@ impl Contract {
         metadata: ClassMetadata,
     ) -> Result<(), CtrError> {
         self.assert_admin();
-        if class != 1 && class != 2 {
+        if !self.is_oracle_class(class) {
             return Err(CtrError::BadRequest("class not found".to_string()));
         }
         self.class_metadata.insert(&class, &metadata);
@@ -358,8 +1041,8 @@ impl Contract {
             format!("Requires min {}yoctoNEAR storage deposit", required_deposit)
         );
         require!(
-            class == CLASS_FV_SBT || class == CLASS_KYC_SBT,
-            "wrong request, class must be either 1 (FV) or 2 (KYC)"
+            self.is_oracle_class(class),
+            "wrong request, class must be 1 (FV), 2 (KYC) or a credential class"
         );
 
         if deposit > required_deposit {
@@ -391,9 +1074,6 @@ impl Contract {
             .with_static_gas(calculate_mint_gas(num_tokens))
             .sbt_mint(tokens_metadata)
     }
-
-    // TODO:
-    // - fn sbt_renew
 }
 
 #[near_bindgen]
@@ -418,6 +1098,22 @@ pub enum CallbackResult<T, E> {
     Err(E),
 }
 
+/// Contract configuration returned by the `status` query.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct Status {
+    /// contract version
+    pub version: String,
+    pub registry: AccountId,
+    /// max duration (in seconds) a claim is valid for processing
+    pub claim_ttl: u64,
+    /// SBT ttl until expire in miliseconds
+    pub sbt_ttl_ms: u64,
+    /// hex encoded sha256 hash of the authority public key
+    pub authority_fingerprint: String,
+}
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod checks;
 
@@ -429,7 +1125,12 @@ pub mod tests {
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::{testing_env, VMContext};
 
-    use crate::util::tests::{acc_claimer, b64_encode, gen_key, mk_claim_sign};
+    use crate::util::tests::{
+        acc_claimer, b64_encode, gen_key, mk_claim, mk_claim_sign, sign_claim,
+    };
+
+    const ELECTIONS_START: u64 = 1693612799000; // Fri, 1 Sep 2023 23:59:59 UTC in ms
+    const ELECTIONS_END: u64 = 1695427199000; // Fri, 22 Sep 2023 23:59:59 UTC in ms
 
     fn acc_u1() -> AccountId {
         "user2.near".parse().unwrap()
@@ -529,6 +1230,44 @@ pub mod tests {
     }
     */
 
+    #[test]
+    fn status() {
+        let (_, ctr, k) = setup(&acc_claimer(), &acc_admin());
+        let s = ctr.status();
+        assert_eq!(s.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(s.registry, acc_registry());
+        assert_eq!(s.claim_ttl, CLAIM_TTL);
+        assert_eq!(s.sbt_ttl_ms, ctr.sbt_ttl_ms);
+        assert_eq!(
+            s.authority_fingerprint,
+            hex::encode(env::sha256(&k.public.to_bytes()))
+        );
+    }
+
+    #[test]
+    fn used_identities() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        assert_eq!(ctr.used_identities_count(), 0);
+        assert_eq!(ctr.used_identities(None, None), Vec::<String>::new());
+
+        ctx.block_timestamp = start() + SECOND;
+        for (i, id) in ["0x1a", "0x1b", "0x1c"].iter().enumerate() {
+            ctx.signer_account_id = format!("user{}.near", i).parse().unwrap();
+            testing_env!(ctx.clone());
+            let mut c = mk_claim(start() / SECOND, id, false);
+            c.claimer = ctx.signer_account_id.clone();
+            let (c_str, sig) = sign_claim(&c, &k);
+            assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+        }
+
+        assert_eq!(ctr.used_identities_count(), 3);
+        assert_eq!(ctr.used_identities(None, None), vec!["1a", "1b", "1c"]);
+        assert_eq!(ctr.used_identities(Some(1), Some(1)), vec!["1b"]);
+        assert_eq!(ctr.used_identities(Some(2), Some(10)), vec!["1c"]);
+        assert_eq!(ctr.used_identities(Some(3), None), Vec::<String>::new());
+    }
+
     #[test]
     fn add_admin() {
         let (_, mut ctr, _) = setup(&acc_claimer(), &acc_admin());
@@ -701,10 +1440,397 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn sbt_mint_callback_refund() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        ctx.block_timestamp = start() + SECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, false);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+        let id_hash = hex::encode(env::sha256(
+            &normalize_external_id("0x1a".to_owned()).unwrap(),
+        ));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![format!(
+                r#"EVENT_JSON:{{"standard":"oracle_sbt","version":"1.0.0","event":"iah_mint_requested","data":{{"claimer":"{}","classes":[1],"external_id_hash":"{}"}}}}"#,
+                signer, id_hash
+            )]
+        );
+
+        // registry mint failed: the identity is released and the deposit refunded
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx);
+        let external_id = hex::encode(normalize_external_id("0x1a".to_owned()).unwrap());
+        match ctr.sbt_mint_callback(
+            external_id,
+            signer.clone(),
+            U128(MINT_TOTAL_COST),
+            Err(PromiseError::Failed),
+        ) {
+            CallbackResult::Err(e) => assert_eq!(e, "registry.sbt_mint failed"),
+            CallbackResult::Ok(_) => panic!("expected Err, got: Ok"),
+        }
+        assert!(!ctr.is_used_identity("0x1a".to_owned()));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![format!(
+                r#"EVENT_JSON:{{"standard":"oracle_sbt","version":"1.0.0","event":"iah_mint_failed","data":{{"claimer":"{}","external_id_hash":"{}","reason":"registry.sbt_mint failed"}}}}"#,
+                signer, id_hash
+            )]
+        );
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, signer);
+        assert_eq!(
+            receipts[0].actions,
+            vec![near_sdk::mock::VmAction::Transfer {
+                deposit: MINT_TOTAL_COST
+            }]
+        );
+    }
+
+    #[test]
+    fn sbt_mint_ongoing_soul_transfer() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        ctx.block_timestamp = start() + SECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, false);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+
+        let external_id = hex::encode(normalize_external_id("0x1a".to_owned()).unwrap());
+        let tokens_metadata = vec![TokenMetadata {
+            class: CLASS_FV_SBT,
+            issued_at: Some(start()),
+            expires_at: None,
+            reference: None,
+            reference_hash: None,
+        }];
+
+        // claimer is not in the middle of a soul transfer: continue with the registry mint
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx);
+        match ctr.on_soul_transfer_check(
+            signer.clone(),
+            tokens_metadata.clone(),
+            U128(MINT_TOTAL_COST),
+            external_id.clone(),
+            Ok(false),
+        ) {
+            PromiseOrValue::Promise(_) => (),
+            PromiseOrValue::Value(_) => panic!("expected registry mint promise"),
+        }
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+
+        // ongoing soul transfer: the claim is rejected and the identity released
+        match ctr.on_soul_transfer_check(
+            signer,
+            tokens_metadata,
+            U128(MINT_TOTAL_COST),
+            external_id,
+            Ok(true),
+        ) {
+            PromiseOrValue::Value(CallbackResult::Err(e)) => {
+                assert_eq!(e, CtrError::OngoingSoulTransfer.to_string())
+            }
+            _ => panic!("expected OngoingSoulTransfer error"),
+        }
+        assert!(!ctr.is_used_identity("0x1a".to_owned()));
+    }
+
+    #[test]
+    fn sbt_renew() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        ctx.block_timestamp = start() + SECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, false);
+
+        // fail: the identity was not used to mint SBT
+        assert_bad_request(
+            ctr.sbt_renew(c_str.clone(), sig.clone(), None),
+            "external_id was not used to mint SBT, use sbt_mint",
+        );
+
+        assert!(ctr.sbt_mint(c_str.clone(), sig.clone(), None).is_ok());
+        // fail: the registry mint is not finished, the identity is not bound to the claimer
+        assert_bad_request(
+            ctr.sbt_renew(c_str.clone(), sig.clone(), None),
+            "external_id was not used to mint SBT for the claimer",
+        );
+        let _ = ctr.sbt_mint_callback(
+            "1a".to_owned(),
+            signer.clone(),
+            U128(MINT_COST),
+            Ok(vec![1]),
+        );
+        assert!(ctr.sbt_renew(c_str.clone(), sig.clone(), None).is_ok());
+
+        // fail: the identity is bound to another account
+        let (_, c_other, sig_other) = mk_claim_sign(start() / SECOND, "0x1b", &k, false);
+        let _ = ctr.sbt_mint_callback("1b".to_owned(), acc_u1(), U128(MINT_COST), Ok(vec![2]));
+        ctr.used_identities.insert(&vec![0x1b]);
+        assert_bad_request(
+            ctr.sbt_renew(c_other, sig_other, None),
+            "external_id was not used to mint SBT for the claimer",
+        );
+
+        // fail: claim must be fresh
+        ctx.block_timestamp = start() + CLAIM_TTL * SECOND;
+        testing_env!(ctx.clone());
+        assert_bad_request(
+            ctr.sbt_renew(c_str.clone(), sig.clone(), None),
+            "claim expired",
+        );
+
+        // fail: tx signer is not claimer
+        ctx.block_timestamp = start() + SECOND;
+        ctx.signer_account_id = acc_u1();
+        testing_env!(ctx);
+        assert_bad_request(
+            ctr.sbt_renew(c_str, sig, None),
+            "claimer is not the transaction signer",
+        );
+    }
+
+    #[test]
+    fn on_renew_tokens() {
+        let (mut ctx, mut ctr, _) = setup(&acc_claimer(), &acc_u1());
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+
+        let mk_token = |token, class| OwnedToken {
+            token,
+            metadata: TokenMetadata {
+                class,
+                issued_at: Some(start()),
+                expires_at: Some(start()),
+                reference: None,
+                reference_hash: None,
+            },
+        };
+        let expect_err = |r: PromiseOrValue<CallbackResult<(), String>>, msg: &str| match r {
+            PromiseOrValue::Value(CallbackResult::Err(e)) => assert_eq!(e, msg),
+            _ => panic!("expected error: {}", msg),
+        };
+
+        expect_err(
+            ctr.on_renew_tokens(false, Ok(vec![])),
+            "claimer doesn't have FV SBT to renew",
+        );
+        expect_err(
+            ctr.on_renew_tokens(
+                true,
+                Ok(vec![(
+                    ctx.current_account_id.clone(),
+                    vec![mk_token(2, CLASS_KYC_SBT)],
+                )]),
+            ),
+            "claimer doesn't have FV SBT to renew",
+        );
+        expect_err(
+            ctr.on_renew_tokens(false, Err(PromiseError::Failed)),
+            &CtrError::Registry.to_string(),
+        );
+
+        let tokens = vec![(
+            ctx.current_account_id.clone(),
+            vec![mk_token(1, CLASS_FV_SBT), mk_token(2, CLASS_KYC_SBT)],
+        )];
+        match ctr.on_renew_tokens(false, Ok(tokens.clone())) {
+            PromiseOrValue::Promise(_) => (),
+            PromiseOrValue::Value(_) => panic!("expected registry renew promise"),
+        }
+        match ctr.on_renew_tokens(true, Ok(tokens)) {
+            PromiseOrValue::Promise(_) => (),
+            PromiseOrValue::Value(_) => panic!("expected registry renew promise"),
+        }
+    }
+
+    #[test]
+    fn release_identity() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        ctx.block_timestamp = start() + SECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, false);
+        assert!(ctr.sbt_mint(c_str.clone(), sig.clone(), None).is_ok());
+
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx.clone());
+        assert_bad_request(
+            ctr.admin_release_identity("0x1b".to_owned(), signer.clone(), 0),
+            "external_id is not used",
+        );
+        assert!(ctr
+            .admin_release_identity("0x1a".to_owned(), signer.clone(), 0)
+            .is_ok());
+
+        let external_id = hex::encode(normalize_external_id("0x1a".to_owned()).unwrap());
+        let token = OwnedToken {
+            token: 1,
+            metadata: TokenMetadata {
+                class: CLASS_FV_SBT,
+                issued_at: Some(start()),
+                expires_at: None,
+                reference: None,
+                reference_hash: None,
+            },
+        };
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        // fail: the account tokens are not burned
+        match ctr.on_release_identity(
+            external_id.clone(),
+            signer.clone(),
+            SECOND / 1_000_000,
+            Ok(vec![(ctx.current_account_id.clone(), vec![token])]),
+        ) {
+            Err(CtrError::BadRequest(s)) => assert_eq!(
+                s,
+                "account tokens must be burned before releasing the identity"
+            ),
+            r => panic!("expected BadRequest, got: {:?}", r),
+        }
+        assert_eq!(
+            ctr.on_release_identity(
+                external_id.clone(),
+                signer.clone(),
+                1000,
+                Err(PromiseError::Failed)
+            ),
+            Err(CtrError::Registry)
+        );
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+
+        let _ = ctr.sbt_mint_callback(
+            external_id.clone(),
+            signer.clone(),
+            U128(MINT_TOTAL_COST),
+            Ok(vec![1]),
+        );
+        assert_eq!(ctr.used_identity_of(signer.clone()), Some("1a".to_owned()));
+
+        // release with 1s cool-down
+        let now_ms = (start() + SECOND) / 1_000_000;
+        assert_eq!(
+            ctr.on_release_identity(external_id, signer.clone(), 1000, Ok(vec![])),
+            Ok(())
+        );
+        assert!(!ctr.is_used_identity("0x1a".to_owned()));
+        assert_eq!(ctr.used_identity_of(signer.clone()), None);
+        assert_eq!(
+            ctr.identity_quarantine("0x1a".to_owned()),
+            Some(now_ms + 1000)
+        );
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![format!(
+                r#"EVENT_JSON:{{"standard":"oracle_sbt","version":"1.0.0","event":"release_identity","data":{{"account":"{}","external_id_hash":"{}","quarantined_until":{}}}}}"#,
+                signer,
+                hex::encode(env::sha256(
+                    &normalize_external_id("0x1a".to_owned()).unwrap()
+                )),
+                now_ms + 1000
+            )]
+        );
+
+        // fail: the identity is in quarantine
+        ctx.predecessor_account_id = signer.clone();
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND + 1, "0x1a", &k, false);
+        assert_bad_request(
+            ctr.sbt_mint(c_str.clone(), sig.clone(), None),
+            &format!("external_id is in quarantine until {}", now_ms + 1000),
+        );
+
+        // the identity can be used after the cool-down
+        ctx.block_timestamp = start() + 2 * SECOND;
+        testing_env!(ctx);
+        assert_eq!(ctr.identity_quarantine("0x1a".to_owned()), None);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+    }
+
+    #[test]
+    fn admin_revoke_external() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &signer);
+        ctx.block_timestamp = start() + SECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, true);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+
+        let external_id = normalize_external_id("0x1a".to_owned()).unwrap();
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        match ctr.sbt_mint_callback(
+            hex::encode(&external_id),
+            signer.clone(),
+            U128(MINT_TOTAL_COST_WITH_KYC),
+            Ok(vec![3, 4]),
+        ) {
+            CallbackResult::Ok(token) => assert_eq!(token, 3),
+            CallbackResult::Err(e) => panic!("expected Ok, got: {}", e),
+        }
+        assert_eq!(
+            ctr.identity_tokens.get(&external_id),
+            Some((signer.clone(), vec![3, 4]))
+        );
+        assert_eq!(ctr.used_identity_of(signer.clone()), Some("1a".to_owned()));
+        assert_eq!(ctr.used_identity_of(acc_u1()), None);
+
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx.clone());
+        assert_bad_request(
+            ctr.admin_revoke_external("0x1b".to_owned(), true),
+            "no tokens indexed for the external_id",
+        );
+        assert!(ctr.admin_revoke_external("0x1a".to_owned(), true).is_ok());
+
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx);
+        // registry revoke failed: the identity is not freed
+        assert_eq!(
+            ctr.on_revoke_external(hex::encode(&external_id), Err(PromiseError::Failed)),
+            Err(CtrError::Registry)
+        );
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+
+        assert_eq!(
+            ctr.on_revoke_external(hex::encode(&external_id), Ok(())),
+            Ok(())
+        );
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![format!(
+                r#"EVENT_JSON:{{"standard":"oracle_sbt","version":"1.0.0","event":"iah_revoked","data":{{"account":"{}","external_id_hash":"{}","tokens":[3,4]}}}}"#,
+                signer,
+                hex::encode(env::sha256(&external_id))
+            )]
+        );
+        assert!(!ctr.is_used_identity("0x1a".to_owned()));
+        assert_eq!(ctr.identity_tokens.get(&external_id), None);
+        assert_eq!(ctr.used_identity_of(signer), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn set_mint_blackout_not_admin() {
+        let (_, mut ctr, _) = setup(&alice(), &alice());
+        ctr.set_mint_blackout(ELECTIONS_START, ELECTIONS_END);
+    }
+
     #[test]
     fn mint_during_elections() {
         let signer = acc_claimer();
-        let (mut ctx, mut ctr, k) = setup(&signer, &acc_u1());
+        let (mut ctx, mut ctr, k) = setup(&signer, &acc_admin());
+        assert_eq!(ctr.mint_blackout(), None);
+        ctr.set_mint_blackout(ELECTIONS_START, ELECTIONS_END);
+        assert_eq!(ctr.mint_blackout(), Some((ELECTIONS_START, ELECTIONS_END)));
 
         ctx.block_timestamp = (ELECTIONS_START + 1) * 1_000_000;
         ctx.current_account_id = "fractal.i-am-human.near".parse().unwrap();
@@ -715,11 +1841,236 @@ pub mod tests {
         assert_bad_request(res, "IAH SBT cannot be mint during the elections period");
 
         ctx.block_timestamp = ELECTIONS_END * 1_000_000;
-        testing_env!(ctx);
+        testing_env!(ctx.clone());
         let (_, c_str, sig) = mk_claim_sign(start() / SECOND, "0x1a", &k, false);
-        let res = ctr.sbt_mint(c_str, sig, None);
+        let res = ctr.sbt_mint(c_str.clone(), sig.clone(), None);
         assert!(res.is_err());
         assert_bad_request(res, "IAH SBT cannot be mint during the elections period");
+
+        // the claim is processed once the blackout is cleared
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx);
+        ctr.clear_mint_blackout();
+        assert_eq!(ctr.mint_blackout(), None);
+        assert_bad_request(ctr.sbt_mint(c_str, sig, None), "claim expired");
+    }
+
+    #[test]
+    fn mint_with_credentials() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &acc_admin());
+        ctr.set_credential_class("age".to_owned(), 3, 1000);
+        ctr.set_credential_class("residency".to_owned(), 4, 2000);
+        assert_eq!(
+            ctr.credential_classes(),
+            vec![
+                (
+                    "age".to_owned(),
+                    CredentialClass {
+                        class: 3,
+                        ttl_ms: 1000
+                    }
+                ),
+                (
+                    "residency".to_owned(),
+                    CredentialClass {
+                        class: 4,
+                        ttl_ms: 2000
+                    }
+                )
+            ]
+        );
+        assert!(ctr.set_class_metadata(3, class_metadata()).is_ok());
+
+        ctx.predecessor_account_id = signer;
+        ctx.attached_deposit = mint_deposit(2);
+        testing_env!(ctx.clone());
+        let mut c = mk_claim(start() / SECOND, "0x1a", false);
+        c.credentials = vec!["age".to_owned(), "unknown".to_owned()];
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert_bad_request(
+            ctr.sbt_mint(c_str, sig, None),
+            "claim.credentials: unknown credential unknown",
+        );
+
+        c.credentials = vec!["age".to_owned(), "age".to_owned()];
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert_bad_request(
+            ctr.sbt_mint(c_str, sig, None),
+            "claim.credentials: duplicated credential age",
+        );
+
+        c.credentials = vec!["age".to_owned()];
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+        assert!(ctr.is_used_identity("0x1a".to_owned()));
+
+        // removed credential can't be used
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx);
+        ctr.remove_credential_class("age".to_owned());
+        assert_eq!(ctr.credential_classes().len(), 1);
+        match ctr.set_class_metadata(3, class_metadata()) {
+            Err(CtrError::BadRequest(_)) => (),
+            Err(error) => panic!("expected BadRequest, got: {:?}", error),
+            Ok(_) => panic!("expected BadRequest, got: Ok"),
+        }
+    }
+
+    #[test]
+    fn mint_with_claim_gating() {
+        let signer = acc_claimer();
+        let (mut ctx, mut ctr, k) = setup(&signer, &acc_admin());
+        let now_s = start() / SECOND;
+        let gating = ClaimGating {
+            min_account_age: 5,
+            min_staked_balance: U128(10),
+            legacy_claims_until: Some(start() / MILLISECOND + 1000),
+        };
+        ctr.set_claim_gating(Some(gating.clone()));
+        assert_eq!(ctr.claim_gating(), Some(gating));
+
+        ctx.predecessor_account_id = signer.clone();
+        testing_env!(ctx.clone());
+        let mut c = mk_claim(now_s, "0x1a", false);
+        c.attestation = Some(ClaimAttestation {
+            account_created_at: now_s - 3,
+            staked_balance: 10,
+        });
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert_bad_request(
+            ctr.sbt_mint(c_str, sig, None),
+            "claim.attestation: account is too young",
+        );
+
+        c.attestation = Some(ClaimAttestation {
+            account_created_at: now_s - 5,
+            staked_balance: 9,
+        });
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert_bad_request(
+            ctr.sbt_mint(c_str, sig, None),
+            "claim.attestation: staked balance is too low",
+        );
+
+        c.attestation = Some(ClaimAttestation {
+            account_created_at: now_s - 5,
+            staked_balance: 10,
+        });
+        let (c_str, sig) = sign_claim(&c, &k);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+
+        // claims without attestation are accepted during the transition period
+        let (_, c_str, sig) = mk_claim_sign(now_s, "0x2b", &k, false);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+
+        ctx.block_timestamp = start() + 1001 * MILLISECOND;
+        testing_env!(ctx.clone());
+        let (_, c_str, sig) = mk_claim_sign(now_s + 1, "0x3c", &k, false);
+        assert_bad_request(
+            ctr.sbt_mint(c_str.clone(), sig.clone(), None),
+            "claim.attestation is required",
+        );
+
+        // without the gating policy any claim is accepted
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx.clone());
+        ctr.set_claim_gating(None);
+        assert_eq!(ctr.claim_gating(), None);
+        ctx.predecessor_account_id = signer;
+        testing_env!(ctx);
+        assert!(ctr.sbt_mint(c_str, sig, None).is_ok());
+    }
+
+    #[test]
+    fn admin_revoke_range() {
+        let (mut ctx, mut ctr, _) = setup(&acc_admin(), &acc_admin());
+        assert_bad_request(
+            ctr.admin_revoke_range(5, 4, true),
+            "from_token must be positive and not bigger than to_token",
+        );
+        assert!(ctr.admin_revoke_range(1, 60, true).is_ok());
+        let mut range = RevokeRange {
+            from_token: 1,
+            to_token: 60,
+            burn: true,
+            next: 1,
+        };
+        assert_eq!(ctr.revoke_range(), Some(range.clone()));
+        assert_bad_request(
+            ctr.admin_revoke_range(1, 60, false),
+            "another range revoke is in progress",
+        );
+
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        // registry query failed
+        assert!(matches!(
+            ctr.on_revoke_range_classes(vec![1, 2], true, Err(PromiseError::Failed)),
+            Err(CtrError::Registry)
+        ));
+        // existing tokens are revoked
+        assert!(matches!(
+            ctr.on_revoke_range_classes(vec![1, 2], true, Ok(vec![None, Some(1)])),
+            Ok(PromiseOrValue::Promise(_))
+        ));
+        assert_eq!(ctr.revoke_range(), Some(range.clone()));
+        // registry revoke failed: the progress is not changed
+        assert_eq!(
+            ctr.on_revoke_range(MAX_REVOKE_RANGE_BATCH, Err(PromiseError::Failed)),
+            Err(CtrError::Registry)
+        );
+        assert_eq!(ctr.revoke_range(), Some(range.clone()));
+        assert_eq!(
+            ctr.on_revoke_range(MAX_REVOKE_RANGE_BATCH, Ok(())),
+            Ok(false)
+        );
+        range.next = MAX_REVOKE_RANGE_BATCH + 1;
+        assert_eq!(ctr.revoke_range(), Some(range));
+
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_revoke_range(1, 60, true).is_ok());
+        ctx.predecessor_account_id = ctx.current_account_id.clone();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.on_revoke_range(50, Ok(())), Ok(false));
+        // none of the tokens exists (eg burned): the progress is moved without revoke
+        let tokens: Vec<TokenId> = (51..=60).collect();
+        assert!(matches!(
+            ctr.on_revoke_range_classes(tokens, true, Ok(vec![None; 10])),
+            Ok(PromiseOrValue::Value(true))
+        ));
+        assert_eq!(ctr.revoke_range(), None);
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"oracle_sbt","version":"1.0.0","event":"revoked_range","data":{"burn":true,"from_token":1,"to_token":60}}"#
+            ]
+        );
+
+        ctx.predecessor_account_id = acc_admin();
+        testing_env!(ctx);
+        assert!(!ctr.admin_cancel_revoke_range());
+        assert!(ctr.admin_revoke_range(3, 3, false).is_ok());
+        assert!(ctr.admin_cancel_revoke_range());
+        assert_eq!(ctr.revoke_range(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "class must be bigger than 2: 1 (FV) and 2 (KYC) are reserved")]
+    fn set_credential_class_reserved() {
+        let (_, mut ctr, _) = setup(&alice(), &acc_admin());
+        ctr.set_credential_class("age".to_owned(), CLASS_KYC_SBT, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "class is already used by another credential")]
+    fn set_credential_class_duplicated() {
+        let (_, mut ctr, _) = setup(&alice(), &acc_admin());
+        ctr.set_credential_class("age".to_owned(), 3, 1000);
+        // update of the same credential is fine
+        ctr.set_credential_class("age".to_owned(), 3, 2000);
+        ctr.set_credential_class("residency".to_owned(), 3, 1000);
     }
 
     #[test]
@@ -771,4 +2122,32 @@ pub mod tests {
         let _ = ctr.admin_mint(vec![(bob(), 100), (alice(), 100)], CLASS_KYC_SBT, None);
         let _ = ctr.admin_mint(vec![(bob(), 100), (alice(), 100)], CLASS_FV_SBT, None);
     }
+
+    #[test]
+    fn migrate_v1_2_0() {
+        let (_, ctr, _) = setup(&alice(), &acc_admin());
+        let mut class_md = LookupMap::new(StorageKey::ClassMetadata);
+        class_md.insert(&CLASS_FV_SBT, &class_metadata());
+        let old_state = migrate::OldState {
+            metadata: ctr.metadata,
+            registry: acc_registry(),
+            claim_ttl: CLAIM_TTL,
+            sbt_ttl_ms: 100,
+            authority_pubkey: ctr.authority_pubkey,
+            used_identities: ctr.used_identities,
+            admins: ctr.admins,
+            class_metadata: class_md,
+        };
+        env::state_write(&old_state);
+
+        let ctr = Contract::migrate();
+        assert_eq!(ctr.registry, acc_registry());
+        assert_eq!(ctr.sbt_ttl_ms, 100);
+        assert_eq!(ctr.get_admins(), vec![acc_admin()]);
+        assert_eq!(ctr.sbt_class_metadata(CLASS_FV_SBT), Some(class_metadata()));
+        assert_eq!(ctr.sbt_class_metadata(CLASS_KYC_SBT), None);
+        assert_eq!(ctr.mint_blackout(), None);
+        assert_eq!(ctr.claim_gating(), None);
+        assert_eq!(ctr.revoke_range(), None);
+    }
 }
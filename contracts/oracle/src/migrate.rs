@@ -1,7 +1,7 @@
 use crate::*;
 
-// registry/v1.3.0
-#[derive(BorshDeserialize, PanicOnDefault)]
+// oracle/v1.2.0
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct OldState {
     pub metadata: LazyOption<ContractMetadata>,
     pub registry: AccountId,
@@ -10,6 +10,7 @@ pub struct OldState {
     pub authority_pubkey: [u8; PUBLIC_KEY_LEN],
     pub used_identities: UnorderedSet<Vec<u8>>,
     pub admins: UnorderedSet<AccountId>,
+    pub class_metadata: LookupMap<ClassId, ClassMetadata>,
 }
 
 #[near_bindgen]
@@ -17,15 +18,17 @@ impl Contract {
     #[private]
     #[init(ignore_state)]
     /* pub  */
-    pub fn migrate(class_metadata: Vec<(ClassId, ClassMetadata)>) -> Self {
+    pub fn migrate() -> Self {
         let old_state: OldState = env::state_read().expect("failed");
         // new field in the smart contract :
-        // + class_metadata: LookupMap<ClassId, ClassMetadata>
-
-        let mut c_metadata = LookupMap::new(StorageKey::ClassMetadata);
-        for (class_id, class_metadata) in class_metadata {
-            c_metadata.insert(&class_id, &class_metadata);
-        }
+        // + mint_blackout: Option<(u64, u64)>
+        // + identity_tokens: LookupMap<Vec<u8>, (AccountId, Vec<TokenId>)>
+        // + credential_classes: UnorderedMap<String, CredentialClass>
+        // + account_identities: LookupMap<AccountId, Vec<u8>>
+        // + quarantined_identities: LookupMap<Vec<u8>, u64>
+        // + claim_gating: LazyOption<ClaimGating>
+        // + revoke_range: LazyOption<RevokeRange>
+        // NOTE: tokens and accounts minted before the migration are not indexed.
 
         Self {
             metadata: old_state.metadata,
@@ -35,7 +38,14 @@ impl Contract {
             authority_pubkey: old_state.authority_pubkey,
             used_identities: old_state.used_identities,
             admins: old_state.admins,
-            class_metadata: c_metadata,
+            class_metadata: old_state.class_metadata,
+            mint_blackout: None,
+            identity_tokens: LookupMap::new(StorageKey::IdentityTokens),
+            credential_classes: UnorderedMap::new(StorageKey::CredentialClasses),
+            account_identities: LookupMap::new(StorageKey::AccountIdentities),
+            quarantined_identities: LookupMap::new(StorageKey::QuarantinedIdentities),
+            claim_gating: LazyOption::new(StorageKey::ClaimGating, None),
+            revoke_range: LazyOption::new(StorageKey::RevokeRange, None),
         }
     }
 }
@@ -1,5 +1,8 @@
-use near_sdk::borsh::{self, BorshSerialize};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::BorshStorageKey;
+use sbt::{ClassId, TokenId};
 
 /// Helper structure for keys of the persistent collections.
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -10,4 +13,47 @@ pub enum StorageKey {
     UsedIdentities,
     Admins,
     ClassMetadata,
+    QuarantinedIdentities,
+    IdentityTokens,
+    CredentialClasses,
+    AccountIdentities,
+    ClaimGating,
+    RevokeRange,
+}
+
+/// SBT class minted for a verified credential type of the Claim.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq, Clone))]
+pub struct CredentialClass {
+    pub class: ClassId,
+    /// SBT ttl until expire in miliseconds
+    pub ttl_ms: u64,
+}
+
+/// Constraints attested in the Claim (`Claim.attestation`) required to mint SBT.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq, Clone))]
+pub struct ClaimGating {
+    /// minimum claimer account age (in seconds) at the time of minting.
+    pub min_account_age: u64,
+    /// minimum claimer staked balance (yoctoNEAR).
+    pub min_staked_balance: U128,
+    /// time (in milliseconds) until which claims without the attestation are accepted
+    /// without the checks (transition period for the backend). If None, such claims are
+    /// rejected.
+    pub legacy_claims_until: Option<u64>,
+}
+
+/// Progress of the `admin_revoke_range` process.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq, Clone))]
+pub struct RevokeRange {
+    pub from_token: TokenId,
+    pub to_token: TokenId,
+    pub burn: bool,
+    /// next token to revoke.
+    pub next: TokenId,
 }
@@ -11,7 +11,6 @@ pub const SIGNATURE_LEN: usize = 64;
 
 type CtrResult<T> = Result<T, CtrError>;
 
-#[derive(BorshSerialize, BorshDeserialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 pub struct Claim {
     pub claimer: AccountId,
@@ -21,6 +20,70 @@ pub struct Claim {
     pub timestamp: u64,
     /// indicates whether the user has passed a KYC or not
     pub verified_kyc: bool,
+    /// additional verified credential types (eg proof-of-age), each one is minted as a token
+    /// of the class configured with `set_credential_class`.
+    pub credentials: Vec<String>,
+    /// account facts attested by the backend, checked against the `ClaimGating` policy.
+    pub attestation: Option<ClaimAttestation>,
+}
+
+/// Account facts attested by the backend in the Claim.
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq, Clone))]
+pub struct ClaimAttestation {
+    /// unix time (seconds) when the claimer account was created
+    pub account_created_at: u64,
+    /// claimer staked balance (yoctoNEAR)
+    pub staked_balance: u128,
+}
+
+// The Claim schema is versioned by trailing fields:
+// * v1: claimer, external_id, timestamp, verified_kyc;
+// * v2: v1 + credentials;
+// * v3: v2 + attestation.
+// Claims are serialized with the lowest version able to represent them, so they have the
+// same bytes (and signature) as the claims created before the new fields were introduced.
+impl BorshSerialize for Claim {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.claimer.serialize(writer)?;
+        self.external_id.serialize(writer)?;
+        self.timestamp.serialize(writer)?;
+        self.verified_kyc.serialize(writer)?;
+        if !self.credentials.is_empty() || self.attestation.is_some() {
+            self.credentials.serialize(writer)?;
+        }
+        if let Some(a) = &self.attestation {
+            a.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for Claim {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let claimer = BorshDeserialize::deserialize(buf)?;
+        let external_id = BorshDeserialize::deserialize(buf)?;
+        let timestamp = BorshDeserialize::deserialize(buf)?;
+        let verified_kyc = BorshDeserialize::deserialize(buf)?;
+        let credentials = if buf.is_empty() {
+            Vec::new()
+        } else {
+            BorshDeserialize::deserialize(buf)?
+        };
+        let attestation = if buf.is_empty() {
+            None
+        } else {
+            Some(BorshDeserialize::deserialize(buf)?)
+        };
+        Ok(Claim {
+            claimer,
+            external_id,
+            timestamp,
+            verified_kyc,
+            credentials,
+            attestation,
+        })
+    }
 }
 
 pub(crate) fn normalize_external_id(id: String) -> Result<Vec<u8>, CtrError> {
@@ -154,6 +217,8 @@ pub mod tests {
             external_id: external_id.to_string(),
             timestamp,
             verified_kyc: is_verified_kyc,
+            credentials: Vec::new(),
+            attestation: None,
         }
     }
 
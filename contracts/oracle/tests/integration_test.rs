@@ -13,7 +13,7 @@ use test_util::{
 };
 
 use near_sdk::borsh::BorshSerialize;
-use oracle_sbt::{Claim, MINT_TOTAL_COST, CLASS_KYC_SBT};
+use oracle_sbt::{Claim, CLASS_KYC_SBT, MINT_TOTAL_COST};
 use sbt::{ClassMetadata, ContractMetadata};
 
 const AUTHORITY_KEY: &str = "zqMwV9fTRoBOLXwt1mHxBAF3d0Rh9E9xwSAXR3/KL5E=";
@@ -331,6 +331,8 @@ pub fn build_signed_claim(
         external_id: external_id.to_string(),
         verified_kyc,
         timestamp: Utc::now().timestamp() as u64,
+        credentials: Vec::new(),
+        attestation: None,
     }
     .try_to_vec()?;
 
@@ -0,0 +1,32 @@
+use near_sdk::env::panic_str;
+use near_sdk::FunctionError;
+
+/// Contract errors
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+pub enum Error {
+    RequiredDeposit(u128),
+    EventNotFound,
+    /// claim is only possible between the event `start` and `end` (the values carried by
+    /// the error).
+    OutsideClaimWindow(u64, u64),
+    WrongCode,
+    /// the caller already claimed the event token.
+    AlreadyClaimed,
+}
+
+impl FunctionError for Error {
+    fn panic(&self) -> ! {
+        match self {
+            Error::RequiredDeposit(min_deposit) => {
+                panic_str(&format!("deposit must be at least {}yN", min_deposit))
+            }
+            Error::EventNotFound => panic_str("event not found"),
+            Error::OutsideClaimWindow(start, end) => panic_str(&format!(
+                "claim is only possible between {} and {}",
+                start, end
+            )),
+            Error::WrongCode => panic_str("wrong claim code"),
+            Error::AlreadyClaimed => panic_str("caller already claimed the event token"),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::{
+    env, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseError,
+    ONE_NEAR,
+};
+
+use cost::{calculate_mint_gas, mint_deposit};
+use sbt::*;
+
+pub use crate::errors::*;
+pub use crate::storage::*;
+
+mod errors;
+mod storage;
+
+const MIN_TTL: u64 = 86_400_000; // 24 hours in miliseconds
+const MILI_NEAR: Balance = ONE_NEAR / 1000;
+/// Deposit required to create an event, covers the storage and prevents spam.
+pub const REGISTRATION_COST: Balance = 100 * MILI_NEAR;
+const CLAIM_CALLBACK_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    /// SBT registry.
+    pub registry: AccountId,
+    /// contract metadata
+    pub metadata: LazyOption<ContractMetadata>,
+    /// map of classId -> event
+    pub events: LookupMap<ClassId, PoapEvent>,
+    pub class_metadata: LookupMap<ClassId, ClassMetadata>,
+    pub next_class: ClassId,
+    /// set of (class, account) pairs of accounts which already claimed the event token.
+    pub claimed: LookupSet<(ClassId, AccountId)>,
+}
+
+// Implement the contract structure
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(registry: AccountId, metadata: ContractMetadata) -> Self {
+        Self {
+            registry,
+            metadata: LazyOption::new(StorageKey::ContractMetadata, Some(&metadata)),
+            events: LookupMap::new(StorageKey::Events),
+            class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            next_class: 1,
+            claimed: LookupSet::new(StorageKey::Claimed),
+        }
+    }
+
+    /**********
+     * QUERIES
+     **********/
+
+    /// Returns the event by class. Returns none if the event is not found.
+    pub fn event(&self, class: ClassId) -> Option<PoapEvent> {
+        self.events.get(&class)
+    }
+
+    /// Returns true if the `account` already claimed the `class` token.
+    pub fn claimed(&self, class: ClassId, account: AccountId) -> bool {
+        self.claimed.contains(&(class, account))
+    }
+
+    /// Returns registry address.
+    pub fn registry(&self) -> AccountId {
+        self.registry.clone()
+    }
+
+    /**********
+     * Transactions
+     **********/
+
+    /// Creates a new event and returns its class ID. The caller becomes the event organizer.
+    /// * `code_hash`: base64 encoded sha256 hash of the claim code, which will be shared
+    ///   with the attendees.
+    /// * `start`, `end`: claim window in ms. Tokens can be claimed only when
+    ///   `start <= now < end`.
+    /// * `ttl`: time to live in ms of the claimed tokens, must be at least `MIN_TTL`.
+    /// Must attach at least `REGISTRATION_COST` yNEAR.
+    #[payable]
+    #[handle_result]
+    pub fn create_event(
+        &mut self,
+        code_hash: Base64VecU8,
+        start: u64,
+        end: u64,
+        ttl: u64,
+        metadata: ClassMetadata,
+    ) -> Result<ClassId, Error> {
+        if env::attached_deposit() < REGISTRATION_COST {
+            return Err(Error::RequiredDeposit(REGISTRATION_COST));
+        }
+        require!(code_hash.0.len() == 32, "code_hash must be a sha256 hash");
+        require!(
+            start < end && end > env::block_timestamp_ms(),
+            "claim window must end in the future and after the start"
+        );
+        require!(
+            MIN_TTL <= ttl,
+            format!("ttl must be at least {}ms", MIN_TTL)
+        );
+        let cls = self.next_class;
+        self.next_class += 1;
+        self.events.insert(
+            &cls,
+            &PoapEvent {
+                organizer: env::predecessor_account_id(),
+                code_hash,
+                start,
+                end,
+                ttl,
+            },
+        );
+        self.class_metadata.insert(&cls, &metadata);
+        Ok(cls)
+    }
+
+    /// Claims the event token. Must be called through `registry.is_human_call`, so only
+    /// verified humans can claim tokens:
+    ///
+    ///    registry.is_human_call({ctr: POAP_ADDRESS, function: "sbt_claim",
+    ///                            payload: "{\"class\": 1, \"code\": \"CLAIM_CODE\"}"})
+    ///
+    /// Every account can claim at most one token of an event, within the event claim window.
+    /// Must attach `cost.mint_deposit(1)` deposit. The deposit is returned if the mint fails.
+    #[payable]
+    #[handle_result]
+    pub fn sbt_claim(
+        &mut self,
+        caller: AccountId,
+        #[allow(unused_variables)] iah_proof: SBTs,
+        payload: ClaimPayload,
+    ) -> Result<Promise, Error> {
+        require!(
+            env::predecessor_account_id() == self.registry,
+            "must be called by registry"
+        );
+        let ev = self
+            .events
+            .get(&payload.class)
+            .ok_or(Error::EventNotFound)?;
+        let now_ms = env::block_timestamp_ms();
+        if now_ms < ev.start || now_ms >= ev.end {
+            return Err(Error::OutsideClaimWindow(ev.start, ev.end));
+        }
+        if env::sha256(payload.code.as_bytes()) != ev.code_hash.0 {
+            return Err(Error::WrongCode);
+        }
+        let required_deposit = mint_deposit(1);
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < required_deposit {
+            return Err(Error::RequiredDeposit(required_deposit));
+        }
+        if !self.claimed.insert(&(payload.class, caller.clone())) {
+            return Err(Error::AlreadyClaimed);
+        }
+
+        let metadata = TokenMetadata {
+            class: payload.class,
+            issued_at: Some(now_ms),
+            expires_at: Some(now_ms + ev.ttl),
+            reference: None,
+            reference_hash: None,
+        };
+        let promise = ext_registry::ext(self.registry.clone())
+            .with_attached_deposit(attached_deposit)
+            .with_static_gas(calculate_mint_gas(1))
+            .sbt_mint(vec![(caller.clone(), vec![metadata])])
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CLAIM_CALLBACK_GAS)
+                    .on_claim_callback(payload.class, caller, U128(attached_deposit)),
+            );
+        Ok(promise)
+    }
+
+    /// Callback for `sbt_claim`. If the registry mint failed, allows the caller to claim
+    /// again and returns the deposit. Returns the minted token ID or None if the mint failed.
+    #[private]
+    pub fn on_claim_callback(
+        &mut self,
+        class: ClassId,
+        caller: AccountId,
+        deposit: U128,
+        #[callback_result] minted: Result<Vec<TokenId>, PromiseError>,
+    ) -> Option<TokenId> {
+        match minted {
+            Ok(tokens) => Some(tokens[0]),
+            Err(_) => {
+                // we can't panic here in order to preserve the state change.
+                self.claimed.remove(&(class, caller.clone()));
+                Promise::new(caller).transfer(deposit.0);
+                None
+            }
+        }
+    }
+
+    /// Allows the event organizer to update the class metadata.
+    pub fn set_sbt_class_metadata(&mut self, class: ClassId, metadata: ClassMetadata) {
+        let ev = self.events.get(&class).expect("event not found");
+        require!(
+            ev.organizer == env::predecessor_account_id(),
+            "not authorized: required organizer"
+        );
+        self.class_metadata.insert(&class, &metadata);
+    }
+}
+
+#[near_bindgen]
+impl SBTIssuer for Contract {
+    fn sbt_metadata(&self) -> ContractMetadata {
+        self.metadata.get().unwrap()
+    }
+
+    /// Returns `ClassMetadata` by class. Returns none if the class is not found.
+    fn sbt_class_metadata(&self, class: ClassId) -> Option<ClassMetadata> {
+        self.class_metadata.get(&class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cost::mint_deposit;
+    use near_sdk::{
+        env,
+        json_types::{Base64VecU8, U128},
+        test_utils::{
+            test_env::{alice, bob},
+            VMContextBuilder,
+        },
+        testing_env, AccountId, PromiseError, VMContext,
+    };
+    use sbt::{ClassId, ClassMetadata, ContractMetadata, SBTIssuer};
+
+    use crate::{ClaimPayload, Contract, Error, PoapEvent, MIN_TTL, REGISTRATION_COST};
+
+    const START: u64 = 1_000_000;
+    const MSECOND: u64 = 1_000_000;
+    const CODE: &str = "nearcon-2024";
+
+    fn registry() -> AccountId {
+        AccountId::new_unchecked("registry.near".to_string())
+    }
+
+    fn organizer() -> AccountId {
+        AccountId::new_unchecked("organizer.near".to_string())
+    }
+
+    fn contract_metadata() -> ContractMetadata {
+        ContractMetadata {
+            spec: "poap-sbt-0.0.1".to_string(),
+            name: "poap-sbt".to_string(),
+            symbol: "POAP".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn class_metadata(c: ClassId) -> ClassMetadata {
+        ClassMetadata {
+            name: format!("event-{}", c),
+            symbol: None,
+            icon: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn code_hash(code: &str) -> Base64VecU8 {
+        Base64VecU8(env::sha256(code.as_bytes()))
+    }
+
+    fn payload(class: ClassId, code: &str) -> ClaimPayload {
+        ClaimPayload {
+            class,
+            code: code.to_owned(),
+        }
+    }
+
+    /// creates an event with a claim window [START+1000, START+2000) ms, and sets the
+    /// predecessor to the registry with the time inside the claim window.
+    fn setup() -> (VMContext, Contract) {
+        let mut ctx = VMContextBuilder::new()
+            .predecessor_account_id(organizer())
+            .block_timestamp(START * MSECOND)
+            .attached_deposit(REGISTRATION_COST)
+            .is_view(false)
+            .build();
+        testing_env!(ctx.clone());
+        let mut ctr = Contract::new(registry(), contract_metadata());
+        let cls = ctr
+            .create_event(
+                code_hash(CODE),
+                START + 1000,
+                START + 2000,
+                MIN_TTL,
+                class_metadata(1),
+            )
+            .unwrap();
+        assert_eq!(cls, 1);
+
+        ctx.predecessor_account_id = registry();
+        ctx.attached_deposit = mint_deposit(1);
+        ctx.block_timestamp = (START + 1500) * MSECOND;
+        testing_env!(ctx.clone());
+        (ctx, ctr)
+    }
+
+    #[test]
+    fn create_event() {
+        let (mut ctx, mut ctr) = setup();
+        assert_eq!(
+            ctr.event(1),
+            Some(PoapEvent {
+                organizer: organizer(),
+                code_hash: code_hash(CODE),
+                start: START + 1000,
+                end: START + 2000,
+                ttl: MIN_TTL,
+            })
+        );
+        assert_eq!(ctr.sbt_class_metadata(1), Some(class_metadata(1)));
+        assert_eq!(ctr.event(2), None);
+
+        ctx.predecessor_account_id = organizer();
+        ctx.attached_deposit = REGISTRATION_COST - 1;
+        testing_env!(ctx);
+        match ctr.create_event(
+            code_hash(CODE),
+            START + 2000,
+            START + 3000,
+            MIN_TTL,
+            class_metadata(2),
+        ) {
+            Err(Error::RequiredDeposit(REGISTRATION_COST)) => (),
+            x => panic!("expected RequiredDeposit, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn sbt_claim() -> Result<(), Error> {
+        let (_, mut ctr) = setup();
+        assert!(!ctr.claimed(1, alice()));
+        ctr.sbt_claim(alice(), vec![], payload(1, CODE))?;
+        assert!(ctr.claimed(1, alice()));
+        assert!(!ctr.claimed(1, bob()));
+
+        match ctr.sbt_claim(alice(), vec![], payload(1, CODE)) {
+            Err(Error::AlreadyClaimed) => (),
+            x => panic!("expected AlreadyClaimed, got: {:?}", x.is_ok()),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sbt_claim_errors() {
+        let (mut ctx, mut ctr) = setup();
+        assert_eq!(
+            ctr.sbt_claim(alice(), vec![], payload(2, CODE)).err(),
+            Some(Error::EventNotFound)
+        );
+        assert_eq!(
+            ctr.sbt_claim(alice(), vec![], payload(1, "wrong")).err(),
+            Some(Error::WrongCode)
+        );
+
+        ctx.attached_deposit = mint_deposit(1) - 1;
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.sbt_claim(alice(), vec![], payload(1, CODE)).err(),
+            Some(Error::RequiredDeposit(mint_deposit(1)))
+        );
+
+        // before and after the claim window
+        ctx.attached_deposit = mint_deposit(1);
+        for t in [START + 999, START + 2000] {
+            ctx.block_timestamp = t * MSECOND;
+            testing_env!(ctx.clone());
+            assert_eq!(
+                ctr.sbt_claim(alice(), vec![], payload(1, CODE)).err(),
+                Some(Error::OutsideClaimWindow(START + 1000, START + 2000))
+            );
+        }
+        assert!(!ctr.claimed(1, alice()));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be called by registry")]
+    fn sbt_claim_not_registry() {
+        let (mut ctx, mut ctr) = setup();
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        let _ = ctr.sbt_claim(alice(), vec![], payload(1, CODE));
+    }
+
+    #[test]
+    fn on_claim_callback() -> Result<(), Error> {
+        let (_, mut ctr) = setup();
+        ctr.sbt_claim(alice(), vec![], payload(1, CODE))?;
+        assert_eq!(
+            ctr.on_claim_callback(1, alice(), U128(mint_deposit(1)), Ok(vec![3])),
+            Some(3)
+        );
+        assert!(ctr.claimed(1, alice()));
+
+        // failed mint: alice can claim again
+        assert_eq!(
+            ctr.on_claim_callback(1, alice(), U128(mint_deposit(1)), Err(PromiseError::Failed)),
+            None
+        );
+        assert!(!ctr.claimed(1, alice()));
+        ctr.sbt_claim(alice(), vec![], payload(1, CODE))?;
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "not authorized: required organizer")]
+    fn set_sbt_class_metadata_not_organizer() {
+        let (mut ctx, mut ctr) = setup();
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.set_sbt_class_metadata(1, class_metadata(5));
+    }
+}
@@ -0,0 +1,41 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, BorshStorageKey};
+use sbt::ClassId;
+
+/// Helper structure for keys of the persistent collections.
+#[derive(BorshSerialize, BorshStorageKey)]
+pub enum StorageKey {
+    ContractMetadata,
+    Events,
+    ClassMetadata,
+    Claimed,
+}
+
+/// Proof of attendance event. Every event is a separate SBT class.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoapEvent {
+    /// account which created the event.
+    pub organizer: AccountId,
+    /// sha256 hash of the claim code.
+    pub code_hash: Base64VecU8,
+    /// claim window start time in ms.
+    pub start: u64,
+    /// claim window end time in ms (exclusive).
+    pub end: u64,
+    /// time to live in ms of the claimed tokens.
+    pub ttl: u64,
+}
+
+/// Payload of the `sbt_claim` method, passed through `registry.is_human_call`.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClaimPayload {
+    pub class: ClassId,
+    /// claim code: the sha256 pre-image of the event `code_hash`.
+    pub code: String,
+}
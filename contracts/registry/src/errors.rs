@@ -1,15 +1,39 @@
 use near_sdk::env::panic_str;
-use near_sdk::FunctionError;
+use near_sdk::{AccountId, FunctionError};
 
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
 pub enum IsHumanCallErr {
     NotHuman,
+    /// `is_human_call_batch` calls list is empty or longer than the limit (the param).
+    WrongBatchSize(usize),
+    /// attached deposit doesn't match the total deposit of the `is_human_call_batch`
+    /// calls (the param).
+    WrongDeposit(u128),
+    /// caller is flagged as `GovBan` and the target is a governance contract.
+    GovBanned,
+    /// `is_human_call` forward deposit is bigger than the attached deposit (the param).
+    ForwardDepositTooBig(u128),
 }
 
 impl FunctionError for IsHumanCallErr {
     fn panic(&self) -> ! {
         match self {
             IsHumanCallErr::NotHuman => panic_str("caller is not a human"),
+            IsHumanCallErr::WrongBatchSize(max) => panic_str(&format!(
+                "calls must be a non empty list of at most {} elements",
+                max
+            )),
+            IsHumanCallErr::WrongDeposit(total) => panic_str(&format!(
+                "attached deposit must equal the total calls deposit: {}",
+                total
+            )),
+            IsHumanCallErr::GovBanned => {
+                panic_str("caller is banned from calling governance contracts")
+            }
+            IsHumanCallErr::ForwardDepositTooBig(attached) => panic_str(&format!(
+                "forward deposit can't be bigger than the attached deposit: {}",
+                attached
+            )),
         }
     }
 }
@@ -17,6 +41,8 @@ impl FunctionError for IsHumanCallErr {
 #[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
 pub enum SoulTransferErr {
     TransferLocked,
+    NotAccepted,
+    OtherTransfer,
 }
 
 impl FunctionError for SoulTransferErr {
@@ -25,6 +51,55 @@ impl FunctionError for SoulTransferErr {
             SoulTransferErr::TransferLocked => {
                 panic_str("soul transfer not possible: owner has a transfer lock")
             }
+            SoulTransferErr::NotAccepted => {
+                panic_str("soul transfer not possible: recipient didn't accept the transfer")
+            }
+            SoulTransferErr::OtherTransfer => panic_str(
+                "soul transfer not possible: owner has another ongoing transfer or recipient",
+            ),
+        }
+    }
+}
+
+/// Errors returned by `sbt_mint` and `sbt_mint_iah`.
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+pub enum MintErr {
+    /// the recipient (value) is the source of an unfinished soul transfer or recovery.
+    /// Minting to it would interleave with the transfer continuation.
+    OngoingTransfer(AccountId),
+}
+
+impl FunctionError for MintErr {
+    fn panic(&self) -> ! {
+        match self {
+            MintErr::OngoingTransfer(account) => panic_str(&format!(
+                "can't mint to {}: the account has an ongoing soul transfer or recovery",
+                account
+            )),
+        }
+    }
+}
+
+/// Errors returned when a configured registry limit (see `Limits`) is exceeded.
+#[cfg_attr(not(target_arch = "wasm32"), derive(PartialEq, Debug))]
+pub enum LimitErr {
+    /// IAH class set has more classes than the limit (value).
+    TooManyIahClasses(u32),
+    /// mint batch has more tokens than the limit (value).
+    MintBatchTooLarge(u32),
+}
+
+impl FunctionError for LimitErr {
+    fn panic(&self) -> ! {
+        match self {
+            LimitErr::TooManyIahClasses(max) => panic_str(&format!(
+                "IAH class set can't have more than {} classes",
+                max
+            )),
+            LimitErr::MintBatchTooLarge(max) => panic_str(&format!(
+                "can't mint more than {} tokens in a single call",
+                max
+            )),
         }
     }
 }
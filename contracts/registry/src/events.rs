@@ -1,42 +1,139 @@
+use near_sdk::json_types::Base64VecU8;
 use near_sdk::{serde::Serialize, serde_json::json, AccountId};
-use sbt::{EventPayload, NearEvent};
+use sbt::{ClassId, EventPayload, NearEvent, SeqEvent, TokenId};
 
 use crate::storage::AccountFlag;
 
-fn emit_iah_event<T: Serialize>(event: EventPayload<T>) {
+/// `seq`: the registry event sequence number (see `Contract::next_event_seq`).
+fn emit_iah_event<T: Serialize>(event: EventPayload<T>, seq: u64) {
     NearEvent {
         standard: "i_am_human",
         version: "1.0.0",
-        event,
+        event: SeqEvent { event, seq },
     }
     .emit();
 }
 
-pub(crate) fn emit_iah_flag_accounts(flag: crate::AccountFlag, accounts: Vec<AccountId>) {
+pub(crate) fn emit_iah_flag_accounts(flag: crate::AccountFlag, accounts: Vec<AccountId>, seq: u64) {
     let event = match flag {
         AccountFlag::Blacklisted => "flag_blacklisted",
         AccountFlag::Verified => "flag_verified",
         AccountFlag::GovBan => "flag_govban",
     };
-    emit_iah_event(EventPayload {
-        event,
-        data: accounts, // data is a simple list of accounts to flag (Verify or Blacklist)
-    });
+    emit_iah_event(
+        EventPayload {
+            event,
+            data: accounts, // data is a simple list of accounts to flag (Verify or Blacklist)
+        },
+        seq,
+    );
 }
 
-pub(crate) fn emit_iah_unflag_accounts(accounts: Vec<AccountId>) {
-    emit_iah_event(EventPayload {
-        event: "unflag",
-        data: accounts, // data is a simple list of accounts to unflag
-    });
+/// `expires_at`: time in milliseconds after which the flag of the `accounts` is ignored.
+pub(crate) fn emit_iah_flag_expires_at(expires_at: u64, accounts: &[AccountId], seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event: "flag_expires_at",
+            data: json!({ "accounts": accounts, "expires_at": expires_at }),
+        },
+        seq,
+    );
+}
+
+pub(crate) fn emit_iah_unflag_accounts(accounts: Vec<AccountId>, seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event: "unflag",
+            data: accounts, // data is a simple list of accounts to unflag
+        },
+        seq,
+    );
 }
 
 /// `locked_until`: time in milliseconds until when the new account lock is established.
-pub(crate) fn emit_transfer_lock(account: AccountId, locked_until: u64) {
-    emit_iah_event(EventPayload {
-        event: "transfer_lock",
-        data: json!({ "account": account, "locked_until": locked_until}),
-    });
+pub(crate) fn emit_transfer_lock(account: AccountId, locked_until: u64, seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event: "transfer_lock",
+            data: json!({ "account": account, "locked_until": locked_until}),
+        },
+        seq,
+    );
+}
+
+/// `unban`: true if the account was removed from the banlist.
+pub(crate) fn emit_cancel_soul_transfer(account: AccountId, unban: bool, seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event: "cancel_soul_transfer",
+            data: json!({ "account": account, "unban": unban }),
+        },
+        seq,
+    );
+}
+
+/// Emitted once all the `from` tokens were merged into `to` (see `sbt_merge`).
+pub(crate) fn emit_merge(from: &AccountId, to: &AccountId, seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event: "merge",
+            data: json!({ "from": from, "to": to }),
+        },
+        seq,
+    );
+}
+
+/// `event`: "propose_upgrade" or "apply_upgrade".
+pub(crate) fn emit_upgrade(event: &'static str, code_hash: &Base64VecU8, seq: u64) {
+    emit_iah_event(
+        EventPayload {
+            event,
+            data: json!({ "code_hash": code_hash }),
+        },
+        seq,
+    );
+}
+
+/// `blocked`: true if the class was blocked, false if unblocked.
+pub(crate) fn emit_block_class(
+    account: AccountId,
+    issuer: AccountId,
+    class: ClassId,
+    blocked: bool,
+    seq: u64,
+) {
+    let event = if blocked {
+        "block_class"
+    } else {
+        "unblock_class"
+    };
+    emit_iah_event(
+        EventPayload {
+            event,
+            data: json!({ "account": account, "issuer": issuer, "class": class }),
+        },
+        seq,
+    );
+}
+
+/// `changes`: list of (token ID, old class, new class) of the tokens moved by
+/// `sbt_change_class`.
+pub(crate) fn emit_change_class(
+    issuer: &AccountId,
+    changes: &[(TokenId, ClassId, ClassId)],
+    seq: u64,
+) {
+    let tokens: Vec<_> = changes
+        .iter()
+        .map(|(token, from, to)| json!({ "token": token, "from_class": from, "to_class": to }))
+        .collect();
+    emit_iah_event(
+        EventPayload {
+            event: "change_class",
+            data: json!({ "issuer": issuer, "tokens": tokens }),
+        },
+        seq,
+    );
 }
 
 #[cfg(test)]
@@ -51,16 +148,16 @@ mod tests {
 
     #[test]
     fn log_flag_account() {
-        let expected1 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_blacklisted","data":["user-1.near"]}"#;
-        emit_iah_flag_accounts(AccountFlag::Blacklisted, vec![acc(1)]);
+        let expected1 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_blacklisted","data":["user-1.near"],"seq":1}"#;
+        emit_iah_flag_accounts(AccountFlag::Blacklisted, vec![acc(1)], 1);
         assert_eq!(vec![expected1], test_utils::get_logs());
 
-        let expected2 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_verified","data":["user-4.near","user-2.near"]}"#;
-        emit_iah_flag_accounts(AccountFlag::Verified, vec![acc(4), acc(2)]);
+        let expected2 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_verified","data":["user-4.near","user-2.near"],"seq":2}"#;
+        emit_iah_flag_accounts(AccountFlag::Verified, vec![acc(4), acc(2)], 2);
         assert_eq!(vec![expected1, expected2], test_utils::get_logs());
 
-        let expected3 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"unflag","data":["user-4.near","user-3.near"]}"#;
-        emit_iah_unflag_accounts(vec![acc(4), acc(3)]);
+        let expected3 = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"unflag","data":["user-4.near","user-3.near"],"seq":3}"#;
+        emit_iah_unflag_accounts(vec![acc(4), acc(3)], 3);
         assert_eq!(
             vec![expected1, expected2, expected3],
             test_utils::get_logs()
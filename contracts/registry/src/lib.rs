@@ -1,13 +1,20 @@
 use std::collections::{HashMap, HashSet};
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{
+    LazyOption, LookupMap, LookupSet, TreeMap, UnorderedMap, UnorderedSet,
+};
+use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde_json::value::RawValue;
-use near_sdk::{env, near_bindgen, require, serde_json, AccountId, Gas, PanicOnDefault, Promise};
+use near_sdk::{
+    env, near_bindgen, require, serde_json, serde_json::json, AccountId, FunctionError, Gas,
+    PanicOnDefault, Promise,
+};
 
 use sbt::*;
 
 use crate::errors::*;
+use crate::registry::{clamp_limit, flatten_minted, MAX_LIMIT, MAX_REVOKE_PER_CALL};
 use crate::storage::*;
 
 pub mod errors;
@@ -15,8 +22,36 @@ pub mod events;
 pub mod migrate;
 pub mod registry;
 pub mod storage;
+pub mod upgrade;
 
 const IS_HUMAN_GAS: Gas = Gas(12 * Gas::ONE_TERA.0);
+/// Gas reserved for scheduling each `is_human_call_batch` call.
+const IS_HUMAN_BATCH_CALL_GAS: Gas = Gas(3 * Gas::ONE_TERA.0);
+/// Maximum number of calls made by a single `is_human_call_batch`.
+pub const MAX_IS_HUMAN_CALL_BATCH: usize = 5;
+/// Maximum number of accounts accepted by `is_human_batch`. Each account check costs up to
+/// ~1.5 TGas per IAH class, so the limit keeps the call well below the 200 TGas view limit.
+pub const MAX_IS_HUMAN_BATCH: usize = 100;
+/// Default maximum number of classes in the IAH class set (see `Limits`).
+pub const DEFAULT_MAX_IAH_CLASSES: u32 = 20;
+/// Default maximum number of tokens minted in a single call (see `Limits`).
+pub const DEFAULT_MAX_MINT_BATCH: u32 = 200;
+/// Maximum number of token IDs processed by a single `admin_purge_issuer_tokens` call.
+pub const MAX_PURGE_PER_CALL: u32 = 50;
+/// Maximum number of accounts indexed by a single `admin_backfill_counters` call.
+pub const MAX_BACKFILL_PER_CALL: u32 = 50;
+/// Maximum number of changes accepted by a single `sbt_change_class` call.
+pub const MAX_CHANGE_CLASS_BATCH: usize = 100;
+/// Maximum number of entries in the `humanity_score` weights list.
+pub const MAX_SCORE_WEIGHTS: usize = 50;
+/// Maximum number of classes tracked by `humans_count_by_extra_class`.
+pub const MAX_HUMAN_CLASSES: usize = 10;
+/// Maximum number of listener contracts (see `admin_add_listener`).
+pub const MAX_LISTENERS: usize = 5;
+/// Gas attached to each listener notification.
+pub const LISTENER_NOTIFY_GAS: Gas = Gas(5 * Gas::ONE_TERA.0);
+/// Gas left for the rest of the call when scheduling listener notifications.
+const LISTENER_RESERVE_GAS: Gas = Gas(10 * Gas::ONE_TERA.0);
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -37,7 +72,7 @@ pub struct Contract {
     pub(crate) banlist: UnorderedSet<AccountId>,
     /// Map of accounts that are marked by a committee to have a special status (eg: blacklist,
     /// whitelist).
-    pub(crate) flagged: LookupMap<AccountId, AccountFlag>,
+    pub(crate) flagged: LookupMap<AccountId, FlagEntry>,
     /// list of admins that can manage flagged accounts map.
     pub(crate) authorized_flaggers: LazyOption<Vec<AccountId>>,
 
@@ -53,9 +88,115 @@ pub struct Contract {
     pub(crate) next_token_ids: LookupMap<IssuerId, TokenId>,
     pub(crate) next_issuer_id: IssuerId,
 
-    /// tuple of (required issuer, [required list of classes]) that represents mandatory
+    /// list of (required issuer, [required list of classes]) that represents mandatory
     /// requirements to be verified as human for `is_human` and `is_human_call` methods.
-    pub(crate) iah_sbts: (AccountId, Vec<ClassId>),
+    /// Entries are combined according to `iah_mode`.
+    pub(crate) iah_sbts: ClassSet,
+
+    /// Grace period in milliseconds: an account flagged as `Verified` is still considered
+    /// a human if its IAH tokens expired no longer than `verified_grace_ms` ago.
+    /// Zero disables the fast path.
+    pub(crate) verified_grace_ms: u64,
+
+    /// map of issuer -> burn, revoke and renew counters.
+    pub(crate) issuer_stats: LookupMap<IssuerId, IssuerStats>,
+
+    /// iterable index of the `flagged` map keys.
+    pub(crate) flagged_index: UnorderedSet<AccountId>,
+
+    /// defines how the `iah_sbts` entries are combined in `is_human`.
+    pub(crate) iah_mode: ClassSetMode,
+
+    /// map of contract -> number of calls made through `is_human_call` and
+    /// `is_human_call_lock`.
+    pub(crate) consumer_stats: LookupMap<AccountId, ConsumerStats>,
+
+    /// map of soul transfer requests by "old owner". A soul transfer can only start once the
+    /// recipient accepted the request.
+    pub(crate) soul_transfer_requests: LookupMap<AccountId, SoulTransferRequest>,
+
+    /// recipient and kind of the transfers stored in `ongoing_soul_tx`.
+    pub(crate) ongoing_tx_info: LookupMap<AccountId, (AccountId, TransferKind)>,
+
+    /// issuers paused by the authority: they can't mint, renew nor revoke tokens.
+    pub(crate) paused_issuers: UnorderedSet<AccountId>,
+
+    /// maximum IAH class set size and mint batch size.
+    pub(crate) limits: Limits,
+
+    /// issuers removed by the authority, with tokens not yet purged.
+    pub(crate) removed_issuers: LookupMap<AccountId, RemovedIssuer>,
+
+    /// map of issuer -> number of accounts holding the issuer tokens.
+    pub(crate) holders_by_issuer: LookupMap<IssuerId, u64>,
+    /// map of issuer -> sorted list of minted classes.
+    pub(crate) issuer_classes: LookupMap<IssuerId, Vec<ClassId>>,
+
+    /// sequence number of the last emitted nep393 event.
+    pub(crate) event_seq: u64,
+
+    /// map of issuer -> token provenance recording enabled. Contains only issuers which
+    /// enabled the recording at least once: burned tokens provenance is removed only for them.
+    pub(crate) provenance_issuers: LookupMap<IssuerId, bool>,
+    /// minting context of tokens minted by `provenance_issuers`.
+    pub(crate) token_provenance: LookupMap<IssuerTokenId, TokenProvenance>,
+
+    /// list of (issuer, class, weight) used to compute the `humanity_score`.
+    pub(crate) score_weights: Vec<ScoreWeight>,
+
+    /// number of accounts holding (not burned) IAH class set tokens, including expired ones.
+    pub(crate) humans_count: u64,
+    /// list of (issuer, class) for which the number of humans holding the class is tracked.
+    pub(crate) human_classes: Vec<(IssuerId, ClassId)>,
+    /// map of tracked (issuer, class) -> number of humans holding the class.
+    pub(crate) humans_by_class: LookupMap<(IssuerId, ClassId), u64>,
+
+    /// set of (account, issuer, class) blocked by the authority: the account tokens of the
+    /// class are ignored by `is_human` and `sbt_tokens_by_owner`.
+    pub(crate) blocked_classes: LookupSet<BalanceKey>,
+
+    /// sha256 hash of the contract code approved by the authority for the upgrade.
+    pub(crate) upgrade_code_hash: Option<Vec<u8>>,
+
+    /// governance contracts: `is_human_call*` to these contracts is rejected for `GovBan`
+    /// flagged accounts.
+    pub(crate) gov_contracts: UnorderedSet<AccountId>,
+
+    /// map of issuer -> burned tokens tombstone policy. Tombstones are recorded only for
+    /// issuers with a policy.
+    pub(crate) tombstone_policies: LookupMap<IssuerId, TombstonePolicy>,
+    /// burned tokens of `tombstone_policies` issuers.
+    pub(crate) tombstones: LookupMap<IssuerTokenId, TokenTombstone>,
+
+    /// accounts which hid their tokens from the public `sbt_tokens_by_owner` queries (see
+    /// `set_portfolio_privacy`).
+    pub(crate) hidden_portfolios: LookupSet<AccountId>,
+
+    /// map of account merge requests by the merged ("from") account (see `sbt_merge_init`).
+    pub(crate) merge_requests: LookupMap<AccountId, SoulTransferRequest>,
+
+    /// ban time (unix timestamp in milliseconds) of the `banlist` accounts. Accounts banned
+    /// before this map was introduced don't have an entry.
+    pub(crate) ban_timestamps: LookupMap<AccountId, u64>,
+
+    /// contracts notified about bans and flags (see `admin_add_listener`).
+    pub(crate) listeners: UnorderedSet<AccountId>,
+
+    /// total number of tokens of an account across all issuers (sum of `supply_by_owner`).
+    /// Accounts which didn't change their supply since the counter was introduced don't
+    /// have an entry (see `_total_supply_by_owner`).
+    pub(crate) total_supply_by_owner: LookupMap<AccountId, u64>,
+
+    /// progress of the counters backfill. When set, the counters (`humans_count`,
+    /// `humans_by_class` and, if `supply`, `holders_by_issuer` and `issuer_classes`) are
+    /// not indexed yet and can't be queried.
+    pub(crate) counters_backfill: Option<CountersBackfill>,
+
+    /// class catalog: (issuer, class) -> class metadata set by the issuer (see
+    /// `set_class_metadata`).
+    pub(crate) class_metadata: LookupMap<(IssuerId, ClassId), ClassMetadata>,
+    /// map of issuer -> sorted list of classes with metadata in the class catalog.
+    pub(crate) catalog_classes: LookupMap<IssuerId, Vec<ClassId>>,
 }
 
 // Implement the contract structure
@@ -75,6 +216,13 @@ impl Contract {
             !iah_classes.is_empty(),
             "iah_classes must be a non empty list"
         );
+        require!(
+            iah_classes.len() <= DEFAULT_MAX_IAH_CLASSES as usize,
+            format!(
+                "iah_classes can't have more than {} classes",
+                DEFAULT_MAX_IAH_CLASSES
+            )
+        );
         let mut contract = Self {
             authority,
             sbt_issuers: UnorderedMap::new(StorageKey::SbtIssuers),
@@ -89,12 +237,44 @@ impl Contract {
             next_token_ids: LookupMap::new(StorageKey::NextTokenId),
             next_issuer_id: 1,
             ongoing_soul_tx: LookupMap::new(StorageKey::OngoingSoultTx),
-            iah_sbts: (iah_issuer.clone(), iah_classes),
-            flagged: LookupMap::new(StorageKey::Flagged),
+            iah_sbts: vec![(iah_issuer.clone(), iah_classes)],
+            flagged: LookupMap::new(StorageKey::FlagEntries),
             authorized_flaggers: LazyOption::new(
                 StorageKey::AdminsFlagged,
                 Some(&authorized_flaggers),
             ),
+            verified_grace_ms: 0,
+            issuer_stats: LookupMap::new(StorageKey::IssuerStats),
+            flagged_index: UnorderedSet::new(StorageKey::FlaggedIndex),
+            iah_mode: ClassSetMode::All,
+            consumer_stats: LookupMap::new(StorageKey::ConsumerStats),
+            soul_transfer_requests: LookupMap::new(StorageKey::SoulTransferRequests),
+            ongoing_tx_info: LookupMap::new(StorageKey::OngoingTxInfo),
+            paused_issuers: UnorderedSet::new(StorageKey::PausedIssuers),
+            limits: Limits::default(),
+            removed_issuers: LookupMap::new(StorageKey::RemovedIssuers),
+            holders_by_issuer: LookupMap::new(StorageKey::HoldersByIssuer),
+            issuer_classes: LookupMap::new(StorageKey::IssuerClasses),
+            event_seq: 0,
+            provenance_issuers: LookupMap::new(StorageKey::ProvenanceIssuers),
+            token_provenance: LookupMap::new(StorageKey::TokenProvenance),
+            score_weights: Vec::new(),
+            humans_count: 0,
+            human_classes: Vec::new(),
+            humans_by_class: LookupMap::new(StorageKey::HumansByClass),
+            blocked_classes: LookupSet::new(StorageKey::BlockedClasses),
+            upgrade_code_hash: None,
+            gov_contracts: UnorderedSet::new(StorageKey::GovContracts),
+            tombstone_policies: LookupMap::new(StorageKey::TombstonePolicies),
+            tombstones: LookupMap::new(StorageKey::Tombstones),
+            hidden_portfolios: LookupSet::new(StorageKey::HiddenPortfolios),
+            merge_requests: LookupMap::new(StorageKey::MergeRequests),
+            ban_timestamps: LookupMap::new(StorageKey::BanTimestamps),
+            listeners: UnorderedSet::new(StorageKey::Listeners),
+            total_supply_by_owner: LookupMap::new(StorageKey::TotalSupplyByOwner),
+            counters_backfill: None,
+            class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            catalog_classes: LookupMap::new(StorageKey::CatalogClasses),
         };
         contract._add_sbt_issuer(&iah_issuer);
         contract
@@ -108,10 +288,31 @@ impl Contract {
         self.sbt_issuers.keys().collect()
     }
 
+    /// Returns list of governance contracts (see `admin_add_gov_contract`).
+    pub fn gov_contracts(&self) -> Vec<AccountId> {
+        self.gov_contracts.to_vec()
+    }
+
+    /// Returns list of listener contracts (see `admin_add_listener`).
+    pub fn listeners(&self) -> Vec<AccountId> {
+        self.listeners.to_vec()
+    }
+
+    /// Returns list of paused issuers (see `admin_pause_issuer`).
+    pub fn paused_issuers(&self) -> Vec<AccountId> {
+        self.paused_issuers.to_vec()
+    }
+
     /// Returns IAH class set: required token classes to be approved as a human by the
     /// `is_human`.
     pub fn iah_class_set(&self) -> ClassSet {
-        vec![self.iah_sbts.clone()]
+        self.iah_sbts.clone()
+    }
+
+    /// Returns how the IAH class set entries are combined by `is_human`: `All` - account
+    /// must have required tokens from every issuer, `Any` - from at least one issuer.
+    pub fn iah_class_set_mode(&self) -> ClassSetMode {
+        self.iah_mode
     }
 
     #[inline]
@@ -119,18 +320,177 @@ impl Contract {
         self.banlist.contains(account)
     }
 
-    /// Returns account status if it was flagged. Returns None if the account was not flagged.
+    /// Returns account status if it was flagged. Returns None if the account was not flagged
+    /// or the flag expired.
     pub fn account_flagged(&self, account: AccountId) -> Option<AccountFlag> {
-        self.flagged.get(&account)
+        self._account_flag(&account)
+    }
+
+    /// Returns the expiration time (unix time in milliseconds) of the account flag.
+    /// Returns None if the account is not flagged or the flag doesn't expire.
+    pub fn account_flag_expires_at(&self, account: AccountId) -> Option<u64> {
+        match self.flag_entry(&account) {
+            Some(FlagEntry(_, Some(expires_at))) if expires_at > MsTimestamp::now().ms() => {
+                Some(expires_at)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the account flag entry. Flags recorded before the v1.8.0 migration, which
+    /// were not moved by `admin_index_flagged` yet, are read from the old map.
+    fn flag_entry(&self, account: &AccountId) -> Option<FlagEntry> {
+        self.flagged
+            .get(account)
+            .or_else(|| legacy_flagged().get(account).map(|f| FlagEntry(f, None)))
+    }
+
+    /// Returns the account flag, ignoring expired flags.
+    fn _account_flag(&self, account: &AccountId) -> Option<AccountFlag> {
+        let FlagEntry(flag, expires_at) = self.flag_entry(account)?;
+        match expires_at {
+            Some(t) if t <= MsTimestamp::now().ms() => None,
+            _ => Some(flag),
+        }
+    }
+
+    /// Returns a list of flagged accounts with their flags, paginated. Expired flags are skipped.
+    /// If `flag` is set, only accounts with the given flag are returned.
+    /// The pagination is applied to the index of all flagged accounts before filtering, so a
+    /// page can have less than `limit` elements; the next page starts at `from_index + limit`.
+    /// `from_index`: default 0.
+    /// `limit`: default and max is `MAX_LIMIT`.
+    pub fn flagged_accounts(
+        &self,
+        flag: Option<AccountFlag>,
+        from_index: Option<u64>,
+        limit: Option<u32>,
+    ) -> Vec<(AccountId, AccountFlag)> {
+        let limit = clamp_limit(limit);
+        self.flagged_index
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .filter_map(|a| {
+                let f = self._account_flag(&a)?;
+                match &flag {
+                    Some(flag) if *flag != f => None,
+                    _ => Some((a, f)),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the total number of SBTs of the `account` across all issuers, including
+    /// expired tokens. Returns 0 if the account has an ongoing soul transfer.
+    pub fn sbt_total_supply_by_owner(&self, account: AccountId) -> u64 {
+        if self.ongoing_soul_tx.contains_key(&account) {
+            return 0;
+        }
+        self._total_supply_by_owner(&account)
+    }
+
+    /// Returns banned accounts (see `is_banned`) with the ban time (unix timestamp in
+    /// milliseconds). The ban time is None for accounts banned before it was recorded.
+    pub fn banned_accounts(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u32>,
+    ) -> Vec<(AccountId, Option<u64>)> {
+        let limit = clamp_limit(limit);
+        self.banlist
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .map(|a| {
+                let ts = self.ban_timestamps.get(&a);
+                (a, ts)
+            })
+            .collect()
+    }
+
+    /// Returns the number of banned accounts.
+    pub fn banned_count(&self) -> u64 {
+        self.banlist.len()
+    }
+
+    /// Returns unix timestamp in milliseconds until when soul transfers of the `account` are
+    /// locked (see `is_human_call_lock`). Returns None if the account is not locked.
+    /// Contracts receiving `is_human_call_lock` calls can use it to confirm that the proof
+    /// is still stable in later blocks of a multi-block workflow.
+    pub fn account_transfer_lock(&self, account: AccountId) -> Option<u64> {
+        self.transfer_lock
+            .get(&account)
+            .filter(|lock| *lock >= MsTimestamp::now().ms())
+    }
+
+    /// Returns true if the `account` has started a soul transfer which didn't complete yet
+    /// (`sbt_soul_transfer` must be called again to finish it).
+    pub fn soul_transfer_ongoing(&self, account: AccountId) -> bool {
+        self.ongoing_soul_tx.contains_key(&account)
+    }
+
+    /// Returns the state of an unfinished soul transfer (`sbt_soul_transfer`) or recovery
+    /// (`sbt_recover`) of the `account`, or None if there is no ongoing transfer. Such
+    /// account can't receive, nor burn tokens until the transfer is completed.
+    pub fn ongoing_transfer(&self, account: AccountId) -> Option<TransferState> {
+        let cursor = self.ongoing_soul_tx.get(&account)?;
+        let (recipient, kind) = match self.ongoing_tx_info.get(&account) {
+            Some((recipient, kind)) => (Some(recipient), Some(kind)),
+            None => (None, None),
+        };
+        Some(TransferState {
+            recipient,
+            kind,
+            issuer_id: cursor.issuer_id,
+            class_id: cursor.token,
+        })
+    }
+
+    /// Returns the account merge request created by `from` with `sbt_merge_init`, or None
+    /// if there is no pending request.
+    pub fn merge_request(&self, from: AccountId) -> Option<SoulTransferRequest> {
+        self.merge_requests.get(&from)
+    }
+
+    /// Returns the soul transfer request created by `from` with `sbt_soul_transfer_init`,
+    /// or None if there is no pending request.
+    pub fn soul_transfer_request(&self, from: AccountId) -> Option<SoulTransferRequest> {
+        self.soul_transfer_requests.get(&from)
+    }
+
+    /// Returns the grace period (in milliseconds) during which an account flagged as `Verified`
+    /// is still considered a human after its IAH tokens expired. Zero means the fast path is
+    /// disabled.
+    pub fn verified_grace(&self) -> u64 {
+        self.verified_grace_ms
     }
 
     /// Returns empty list if the account is NOT a human according to the IAH protocol.
     /// Otherwise returns list of SBTs (identifed by issuer and list of token IDs) proving
-    /// the `account` humanity.
+    /// the `account` humanity. The proof is in the canonical form (see `sbt::normalize_proof`).
     pub fn is_human(&self, account: AccountId) -> SBTs {
         self._is_human(&account)
     }
 
+    /// Batch version of `is_human`: returns a list of `(account, is_human(account))` pairs
+    /// in the same order as the `accounts` input.
+    /// At most `MAX_IS_HUMAN_BATCH` accounts can be checked in a single call: longer lists
+    /// must be split by the caller and queried page by page.
+    pub fn is_human_batch(&self, accounts: Vec<AccountId>) -> Vec<(AccountId, SBTs)> {
+        require!(
+            accounts.len() <= MAX_IS_HUMAN_BATCH,
+            format!("can't check more than {} accounts", MAX_IS_HUMAN_BATCH)
+        );
+        accounts
+            .into_iter()
+            .map(|a| {
+                let proof = self._is_human(&a);
+                (a, proof)
+            })
+            .collect()
+    }
+
     /// Returns `true` if an account is considered human, and `false` otherwise.
     /// We DO NOT RECOMMEND using this function. You SHOULD use `is_human` instead. Returning
     /// bool may create wrong practices. Humanity will be a metric, not a true/false.
@@ -140,29 +500,415 @@ impl Contract {
         !self._is_human(&account).is_empty()
     }
 
+    /// Returns the `account` humanity proof (same as `is_human`), ban status and flag in a
+    /// single call.
+    pub fn account_status(&self, account: AccountId) -> AccountStatus {
+        let proof = self._is_human(&account);
+        AccountStatus {
+            is_human: !proof.is_empty(),
+            banned: self._is_banned(&account),
+            flag: self._account_flag(&account),
+            proof,
+        }
+    }
+
+    /// Returns all data needed to render the `account` SBT profile in a single call: humanity
+    /// status, ban status, flag and a page of the account tokens. Token pagination parameters
+    /// are the same as in `sbt_tokens_by_owner`, the applied limit is returned in the
+    /// response.
+    pub fn account_profile(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> AccountProfile {
+        let limit = clamp_limit(limit);
+        AccountProfile {
+            is_human: !self._is_human(&account).is_empty(),
+            banned: self._is_banned(&account),
+            flag: self._account_flag(&account),
+            sbts: self.sbt_tokens_by_owner(account, issuer, from_class, Some(limit), with_expired),
+            limit,
+        }
+    }
+
     fn _is_human(&self, account: &AccountId) -> SBTs {
-        if self.flagged.get(account) == Some(AccountFlag::Blacklisted) || self._is_banned(account) {
+        let flag = self._account_flag(account);
+        if flag == Some(AccountFlag::Blacklisted) || self._is_banned(account) {
             return vec![];
         }
-        let issuer = Some(self.iah_sbts.0.clone());
+        // Verified accounts are considered human also when their tokens expired recently
+        // (within the grace period), to not break access during re-verification.
+        let grace = if flag == Some(AccountFlag::Verified) {
+            self.verified_grace_ms
+        } else {
+            0
+        };
+        let mut proof: SBTs = Vec::new();
+        for (issuer, classes) in &self.iah_sbts {
+            match self._iah_issuer_proof(account, issuer, classes, grace) {
+                Some(tokens) => proof.push((issuer.clone(), tokens)),
+                None if self.iah_mode == ClassSetMode::All => return vec![],
+                None => (),
+            }
+        }
+        normalize_proof(proof)
+    }
+
+    /// Returns the humanity score of the `account`: sum of the `score_weights` of the
+    /// classes for which the account has a valid (not expired) token. Unlike the binary
+    /// `is_human`, allows to weight the verification providers. Returns 0 if the account
+    /// is banned or blacklisted. Tokens of `Verified` accounts are considered valid within
+    /// the `verified_grace` period after expiration, same as in `is_human`.
+    pub fn humanity_score(&self, account: AccountId) -> u32 {
+        let flag = self._account_flag(&account);
+        if flag == Some(AccountFlag::Blacklisted) || self._is_banned(&account) {
+            return 0;
+        }
+        let grace = if flag == Some(AccountFlag::Verified) {
+            self.verified_grace_ms
+        } else {
+            0
+        };
+        let now = MsTimestamp::now();
+        let mut score: u32 = 0;
+        for sw in &self.score_weights {
+            let issuer_id = match self.sbt_issuers.get(&sw.issuer) {
+                Some(id) => id,
+                None => continue,
+            };
+            let key = balance_key(account.clone(), issuer_id, sw.class);
+            let token = match self.balances.get(&key) {
+                Some(t) => t,
+                None => continue,
+            };
+            if self.blocked_classes.contains(&key) {
+                continue;
+            }
+            let t = self.get_token(issuer_id, token);
+            if !now.is_expired_with_grace(t.metadata.expires_at(), grace) {
+                score = score.saturating_add(sw.weight);
+            }
+        }
+        score
+    }
+
+    /// Returns the list of weights used by `humanity_score`.
+    pub fn score_weights(&self) -> Vec<ScoreWeight> {
+        self.score_weights.clone()
+    }
+
+    /// Returns list of tokens (one per class) proving that the `account` has tokens of all
+    /// `classes` issued by the `issuer`. Returns None if any of the tokens is missing.
+    fn _iah_issuer_proof(
+        &self,
+        account: &AccountId,
+        issuer: &AccountId,
+        classes: &[ClassId],
+        grace: u64,
+    ) -> Option<Vec<TokenId>> {
+        let now = MsTimestamp::now();
         let mut proof: Vec<TokenId> = Vec::new();
         // check if user has tokens from all classes
-        for cls in &self.iah_sbts.1 {
-            let tokens = self.sbt_tokens_by_owner(
+        for cls in classes {
+            let tokens = self._sbt_tokens_by_owner(
                 account.clone(),
-                issuer.clone(),
+                Some(issuer.clone()),
                 Some(*cls),
                 Some(1),
-                None,
+                Some(grace > 0),
+                false,
+                true,
             );
             // we need to check class, because the query can return a "next" token if a user
             // doesn't have the token of requested class.
             if tokens.is_empty() || tokens[0].1[0].metadata.class != *cls {
-                return vec![];
+                return None;
+            }
+            let t = &tokens[0].1[0];
+            if grace > 0 && now.is_expired_with_grace(t.metadata.expires_at, grace) {
+                return None;
+            }
+            proof.push(t.token)
+        }
+        Some(proof)
+    }
+
+    /// Returns number of calls made to the `ctr` contract through `is_human_call` and
+    /// `is_human_call_lock`. Only calls to consumers registered with `admin_add_consumer`
+    /// are counted.
+    pub fn consumer_stats(&self, ctr: AccountId) -> ConsumerStats {
+        self.consumer_stats.get(&ctr).unwrap_or_default()
+    }
+
+    /// Returns total number of tokens burned, revoked and renewed by the given issuer.
+    pub fn issuer_stats(&self, issuer: AccountId) -> IssuerStats {
+        self.sbt_issuers
+            .get(&issuer)
+            .and_then(|id| self.issuer_stats.get(&id))
+            .unwrap_or_default()
+    }
+
+    /// Returns the issuer total supply, supply by class and the number of token holders in
+    /// a single call. Returns zero values if the issuer is not registered.
+    /// Panics if the supply stats are not indexed yet (see `admin_backfill_counters`).
+    pub fn sbt_stats(&self, issuer: AccountId) -> SupplyStats {
+        self.assert_counters_indexed(true);
+        let issuer_id = match self.sbt_issuers.get(&issuer) {
+            None => {
+                return SupplyStats {
+                    supply: 0,
+                    classes: vec![],
+                    holders: 0,
+                }
             }
-            proof.push(tokens[0].1[0].token)
+            Some(id) => id,
+        };
+        let classes = self
+            .issuer_classes
+            .get(&issuer_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|c| {
+                let s = self.supply_by_class.get(&(issuer_id, c)).unwrap_or(0);
+                (s > 0).then(|| (c, s))
+            })
+            .collect();
+        SupplyStats {
+            supply: self.supply_by_issuer.get(&issuer_id).unwrap_or(0),
+            classes,
+            holders: self.holders_by_issuer.get(&issuer_id).unwrap_or(0),
+        }
+    }
+
+    /// Returns the number of accounts holding (minted and not burned) IAH class set tokens.
+    /// The counter is updated only when tokens are minted, burned or moved, so it ignores
+    /// token expiration: accounts with expired IAH tokens are still counted (use `is_human`
+    /// to check the current status of an account).
+    /// Panics if the counter is not indexed yet: after the migration, or after the IAH class
+    /// set or the tracked classes changed (see `admin_backfill_counters`).
+    pub fn humans_count(&self) -> u64 {
+        self.assert_counters_indexed(false);
+        self.humans_count
+    }
+
+    /// Returns the number of humans (see `humans_count`) holding a token of the `issuer`
+    /// `class`. Returns None if the class is not tracked (see `admin_set_human_classes`).
+    /// Panics if the counter is not indexed yet (see `humans_count`).
+    pub fn humans_count_by_extra_class(&self, issuer: AccountId, class: ClassId) -> Option<u64> {
+        self.assert_counters_indexed(false);
+        let key = (self.sbt_issuers.get(&issuer)?, class);
+        if !self.human_classes.contains(&key) {
+            return None;
+        }
+        Some(self.humans_by_class.get(&key).unwrap_or(0))
+    }
+
+    /// Returns the progress of the counters backfill, or None if all counters are indexed
+    /// (see `admin_backfill_counters`).
+    pub fn counters_backfill(&self) -> Option<CountersBackfill> {
+        self.counters_backfill.clone()
+    }
+
+    /// Returns the list of (issuer, class) tracked by `humans_count_by_extra_class`.
+    pub fn human_classes(&self) -> Vec<(AccountId, ClassId)> {
+        self.human_classes
+            .iter()
+            .map(|(id, c)| (self.issuer_by_id(*id), *c))
+            .collect()
+    }
+
+    /// Returns true if the `issuer` `class` is blocked for the `account`.
+    pub fn is_class_blocked(&self, account: AccountId, issuer: AccountId, class: ClassId) -> bool {
+        match self.sbt_issuers.get(&issuer) {
+            None => false,
+            Some(issuer_id) => self
+                .blocked_classes
+                .contains(&balance_key(account, issuer_id, class)),
+        }
+    }
+
+    /// Returns true if the `account` hid its tokens with `set_portfolio_privacy`.
+    pub fn portfolio_hidden(&self, account: AccountId) -> bool {
+        self.hidden_portfolios.contains(&account)
+    }
+
+    /// Same as `sbt_tokens_by_owner`, but also returns tokens hidden by the `account`
+    /// portfolio privacy (see `set_portfolio_privacy`).
+    /// Must be called by the `account` or by a registered issuer. Issuers can only query
+    /// their own tokens: `issuer` defaults to the caller and panics if it's another issuer.
+    /// NOTE: the caller is required, so the method can't be called as a view function.
+    pub fn sbt_tokens_by_owner_private(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)> {
+        let caller = env::predecessor_account_id();
+        let issuer = if caller == account {
+            issuer
+        } else {
+            self.assert_issuer(&caller);
+            require!(
+                issuer.is_none() || issuer.as_ref() == Some(&caller),
+                "issuer can only query its own tokens"
+            );
+            Some(caller)
+        };
+        self._sbt_tokens_by_owner(
+            account,
+            issuer,
+            from_class,
+            limit,
+            with_expired,
+            false,
+            true,
+        )
+    }
+
+    /// Returns the sequence number of the last emitted event. Every event emitted by the
+    /// registry (nep393 and `i_am_human`) has a `seq` field with a monotonically increasing
+    /// sequence number, so indexers can detect missing events.
+    pub fn last_event_seq(&self) -> u64 {
+        self.event_seq
+    }
+
+    /// Returns the minting context of the issuer token. Returns None if the token doesn't
+    /// exist or provenance recording was not enabled for the issuer when the token was minted.
+    pub fn token_provenance(&self, issuer: AccountId, token: TokenId) -> Option<TokenProvenance> {
+        let issuer_id = self.sbt_issuers.get(&issuer)?;
+        self.token_provenance
+            .get(&IssuerTokenId { issuer_id, token })
+    }
+
+    /// Returns true if provenance recording is enabled for the `issuer`.
+    pub fn token_provenance_enabled(&self, issuer: AccountId) -> bool {
+        self.sbt_issuers
+            .get(&issuer)
+            .and_then(|id| self.provenance_issuers.get(&id))
+            .unwrap_or(false)
+    }
+
+    /// Returns the record of a burned issuer token. Returns None if the token was not burned,
+    /// the issuer had no tombstone policy when the token was burned, or the tombstone was
+    /// pruned.
+    pub fn token_tombstone(&self, issuer: AccountId, token: TokenId) -> Option<TokenTombstone> {
+        let issuer_id = self.sbt_issuers.get(&issuer)?;
+        self.tombstones.get(&IssuerTokenId { issuer_id, token })
+    }
+
+    /// Returns the `issuer` class metadata from the registry class catalog (see
+    /// `set_class_metadata`). Returns None if the issuer didn't set the class metadata.
+    pub fn class_metadata(&self, issuer: AccountId, class: ClassId) -> Option<ClassMetadata> {
+        let issuer_id = self.sbt_issuers.get(&issuer)?;
+        self.class_metadata.get(&(issuer_id, class))
+    }
+
+    /// Returns the `issuer` classes with metadata in the registry class catalog, ordered by
+    /// class, starting from `from_class` (inclusive).
+    /// `from_class`: default 0.
+    /// `limit`: default and max is `MAX_LIMIT`.
+    pub fn classes_by_issuer(
+        &self,
+        issuer: AccountId,
+        from_class: Option<ClassId>,
+        limit: Option<u32>,
+    ) -> Vec<(ClassId, ClassMetadata)> {
+        let limit = clamp_limit(limit);
+        let issuer_id = match self.sbt_issuers.get(&issuer) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+        let classes = self.catalog_classes.get(&issuer_id).unwrap_or_default();
+        let start = classes.partition_point(|c| *c < from_class.unwrap_or(0));
+        classes[start..]
+            .iter()
+            .take(limit as usize)
+            .map(|c| (*c, self.class_metadata.get(&(issuer_id, *c)).unwrap()))
+            .collect()
+    }
+
+    /// Returns the `issuer` tombstone policy (see `sbt_set_tombstone_policy`).
+    pub fn tombstone_policy(&self, issuer: AccountId) -> Option<TombstonePolicy> {
+        self.tombstone_policies.get(&self.sbt_issuers.get(&issuer)?)
+    }
+
+    /// Returns the maximum IAH class set size and mint batch size.
+    pub fn limits(&self) -> Limits {
+        self.limits.clone()
+    }
+
+    /// Returns an upper bound of the storage deposit required by `sbt_mint` to mint
+    /// `token_spec_len` tokens (in total) to `recipients` accounts. See
+    /// `cost::required_mint_deposit` for the meaning of `metadata_size_hint`.
+    pub fn required_mint_deposit(
+        &self,
+        token_spec_len: u32,
+        recipients: u32,
+        metadata_size_hint: Option<u32>,
+    ) -> U128 {
+        U128(cost::required_mint_deposit(
+            token_spec_len,
+            recipients,
+            metadata_size_hint,
+        ))
+    }
+
+    /// Returns the maximum `limit` accepted by paginated queries. Bigger values are clamped.
+    pub fn max_limit(&self) -> u32 {
+        MAX_LIMIT
+    }
+
+    /// Same as `sbt_tokens`, but additionally returns the limit applied to the query.
+    pub fn sbt_tokens_v2(
+        &self,
+        issuer: AccountId,
+        from_token: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Page<Vec<Token>> {
+        let limit = clamp_limit(limit);
+        Page {
+            data: self.sbt_tokens(issuer, from_token, Some(limit), with_expired),
+            limit,
+        }
+    }
+
+    /// Returns the `account` tokens of all issuers, ordered by (issuer ID, class), as
+    /// `sbt_tokens_by_owner`, but paginated with an opaque `cursor` rather than a per issuer
+    /// `from_class`. `next_cursor` must be passed to the subsequent query to continue the
+    /// enumeration, it's None when there are no more tokens.
+    /// `limit`: maximum number of returned tokens, default and max is `MAX_LIMIT`.
+    /// `with_expired`: default false.
+    /// Panics if the `cursor` is malformed.
+    pub fn sbt_tokens_by_owner_cursor(
+        &self,
+        account: AccountId,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> CursorPage<Vec<(AccountId, Vec<OwnedToken>)>> {
+        self._sbt_tokens_by_owner_cursor(account, cursor, limit, with_expired)
+    }
+
+    /// Same as `sbt_tokens_by_owner`, but additionally returns the limit applied to the query.
+    pub fn sbt_tokens_by_owner_v2(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Page<Vec<(AccountId, Vec<OwnedToken>)>> {
+        let limit = clamp_limit(limit);
+        Page {
+            data: self.sbt_tokens_by_owner(account, issuer, from_class, Some(limit), with_expired),
+            limit,
         }
-        vec![(self.iah_sbts.0.clone(), proof)]
     }
 
     pub fn get_authority(self) -> AccountId {
@@ -191,71 +937,505 @@ impl Contract {
                 format!("{} is not a human", &ts.0)
             );
         }
-        self._sbt_mint(issuer, token_spec)
+        flatten_minted(self._sbt_mint(issuer, token_spec, MintMethod::SbtMintIah, false))
     }
 
-    /// Transfers atomically all SBT tokens from one account to another account.
-    /// The caller must be an SBT holder and the `recipient` must not be a banned account.
-    /// Transfers the account flag from the owner to the recipient.
-    /// Fails when:
-    /// + `recipient` is banned;
-    /// + there is a potential conflict between the caller's and recipient's flag,
-    ///   specifically when one account is `Blacklisted` and the other is `Verified`;
-    /// Bans the caller account. NOTE: call can try to do soul_transfer to himself. This
-    /// sounds irrationally, and allows to ban himself/herself.
-    /// Emits:
-    /// + `Ban` event for the caller at the beginning of the process.
-    /// + `SoulTransfer` event only once all the tokens from the caller were transferred
-    ///    and at least one token was transferred (caller had at least 1 sbt).
-    /// Returns the amount of tokens transferred and a boolean: `true` if the whole
-    /// process has finished, `false` when the process has not finished and should be
-    /// continued by a subsequent call.
-    /// + User must keep calling the `sbt_soul_transfer` until `true` is returned.
-    /// + If caller does not have any tokens, nothing will be transfered, the caller
-    ///   will be banned and `Ban` event will be emitted.
-    /// See https://github.com/near/NEPs/pull/393 for more details and rationality about
-    /// soul transfer.
+    /// Same as `sbt_mint`, but returns the minted tokens per recipient: for each `token_spec`
+    /// entry, the recipient and the (class, token ID) pairs, in the `token_spec` order.
+    /// Must be called by an SBT issuer.
     #[payable]
-    #[handle_result]
-    pub fn sbt_soul_transfer(
+    pub fn sbt_mint_v2(
         &mut self,
-        recipient: AccountId,
-        #[allow(unused_variables)] memo: Option<String>,
-    ) -> Result<(u32, bool), SoulTransferErr> {
-        // TODO: test what is the max safe amount of updates
-        self._sbt_soul_transfer(recipient, 20)
+        token_spec: Vec<(AccountId, Vec<TokenMetadata>)>,
+    ) -> Vec<(AccountId, Vec<(ClassId, TokenId)>)> {
+        let issuer = &env::predecessor_account_id();
+        self._sbt_mint(issuer, token_spec, MintMethod::SbtMint, false)
     }
 
-    pub(crate) fn _transfer_flag(&mut self, from: &AccountId, recipient: &AccountId) {
-        if let Some(flag_from) = self.flagged.get(from) {
-            if let Some(flag_to) = self.flagged.get(recipient) {
-                require!(
-                    flag_from == flag_to,
-                    "can't transfer soul when there is a flag conflict"
-                )
-            } else {
-                self.flagged.insert(recipient, &flag_from);
-            }
-        }
+    /// Same as `sbt_mint_v2`, but an expired token of the recipient with the same class is
+    /// burned and replaced by the new token, instead of failing the mint, so issuers don't
+    /// need to revoke expired tokens first. `Burn` and `Revoke` events listing the replaced
+    /// tokens are emitted before the `Mint` event. Minting a class for which the recipient has a valid
+    /// token still fails, as does minting the same class twice to a recipient in one batch.
+    /// Must be called by an SBT issuer.
+    #[payable]
+    pub fn sbt_mint_replace_expired(
+        &mut self,
+        token_spec: Vec<(AccountId, Vec<TokenMetadata>)>,
+    ) -> Vec<(AccountId, Vec<(ClassId, TokenId)>)> {
+        let issuer = &env::predecessor_account_id();
+        self._sbt_mint(issuer, token_spec, MintMethod::SbtMint, true)
     }
 
-    // execution of the sbt_soul_transfer in this function to parametrize `max_updates` in
-    // order to facilitate tests.
-    #[handle_result]
-    pub(crate) fn _sbt_soul_transfer(
+    /// Same as `sbt_renew`, but each token is renewed with its own `expires_at` (unix
+    /// timestamp in milliseconds): `tokens` is a list of (token ID, expires_at) pairs.
+    /// The batch is validated before any update, and a single `Renew` event listing all the
+    /// tokens is emitted. Use `cost::calculate_renew_gas` to calculate the required gas.
+    /// Must be called by an SBT issuer.
+    pub fn sbt_renew_many(&mut self, tokens: Vec<(TokenId, u64)>) {
+        let issuer = env::predecessor_account_id();
+        self._sbt_renew_many(issuer, tokens);
+    }
+
+    /// Same as `sbt_revoke_by_owner`, but only tokens of the listed `classes` are revoked
+    /// (or burned if `burn`). The list must have at most `MAX_REVOKE_PER_CALL` elements, and
+    /// all tokens are revoked in a single call, so the function always returns true.
+    /// Must be called by an SBT issuer.
+    /// Emits `Revoke` event, and also `Burn` event if the tokens are burned.
+    pub fn sbt_revoke_by_owner_classes(
         &mut self,
-        recipient: AccountId,
-        limit: usize,
-    ) -> Result<(u32, bool), SoulTransferErr> {
-        let owner = env::predecessor_account_id();
-        let transfer_lock = self.transfer_lock.get(&owner).unwrap_or(0);
-        if transfer_lock >= env::block_timestamp_ms() {
-            return Err(SoulTransferErr::TransferLocked);
-        }
+        owner: AccountId,
+        classes: Vec<ClassId>,
+        burn: bool,
+    ) -> bool {
+        self._sbt_revoke_by_owner(owner, burn, Some(classes))
+    }
 
-        let (resumed, start) = self.transfer_continuation(&owner, &recipient, true);
+    /// Moves the caller tokens to new classes without burning them: `changes` is a list of
+    /// (token ID, new class) pairs. Updates the owner balances and the class supply and
+    /// human counters. A token can't be moved to a class already held by its owner.
+    /// The whole batch is reverted if any change fails.
+    /// Emits `change_class` event, unless no token was changed.
+    /// Must be called by an SBT issuer. Panics if more than `MAX_CHANGE_CLASS_BATCH` changes
+    /// are provided.
+    pub fn sbt_change_class(&mut self, changes: Vec<(TokenId, ClassId)>) {
+        require!(
+            changes.len() <= MAX_CHANGE_CLASS_BATCH,
+            format!("at most {} changes can be made", MAX_CHANGE_CLASS_BATCH)
+        );
+        let issuer = env::predecessor_account_id();
+        let issuer_id = self.assert_active_issuer(&issuer);
+        let mut token_ids = HashSet::new();
+        let mut human_before: HashMap<AccountId, HumanState> = HashMap::new();
+        let mut changed = Vec::with_capacity(changes.len());
+        for (token, class) in changes {
+            require!(class > 0, "Class must be > 0");
+            require!(
+                token_ids.insert(token),
+                format!("duplicated token_id in changes: {}", token)
+            );
+            let key = IssuerTokenId { issuer_id, token };
+            let mut t = self.get_token(issuer_id, token);
+            require!(
+                !self.ongoing_soul_tx.contains_key(&t.owner),
+                format!("owner of token {} has an ongoing soul transfer", token)
+            );
+            let mut m = t.metadata.v1();
+            let old_class = m.class;
+            if old_class == class {
+                continue;
+            }
+            let new_balance_key = balance_key(t.owner.clone(), issuer_id, class);
+            require!(
+                !self.balances.contains_key(&new_balance_key),
+                format!(
+                    "owner of token {} already has a token of class {}",
+                    token, class
+                )
+            );
+            human_before
+                .entry(t.owner.clone())
+                .or_insert_with(|| self.human_state(&t.owner));
+
+            let old_balance_key = balance_key(t.owner.clone(), issuer_id, old_class);
+            self.balances.remove(&old_balance_key);
+            self.balances.insert(&new_balance_key, &token);
+            if self.blocked_classes.remove(&old_balance_key) {
+                self.blocked_classes.insert(&new_balance_key);
+            }
+            m.class = class;
+            t.metadata = m.into();
+            self.issuer_tokens.insert(&key, &t);
+
+            let old_key = (issuer_id, old_class);
+            let supply = self.supply_by_class.get(&old_key).unwrap_or(1) - 1;
+            if supply == 0 {
+                self.supply_by_class.remove(&old_key);
+            } else {
+                self.supply_by_class.insert(&old_key, &supply);
+            }
+            let new_key = (issuer_id, class);
+            let supply = self.supply_by_class.get(&new_key).unwrap_or(0) + 1;
+            self.supply_by_class.insert(&new_key, &supply);
+            changed.push((token, old_class, class));
+        }
+        if changed.is_empty() {
+            return;
+        }
+        for (owner, before) in human_before {
+            self.update_human_counters(&owner, before);
+        }
+
+        let mut classes = self.issuer_classes.get(&issuer_id).unwrap_or_default();
+        let num_classes = classes.len();
+        for (_, _, class) in &changed {
+            if !classes.contains(class) {
+                classes.push(*class);
+            }
+        }
+        if classes.len() != num_classes {
+            classes.sort();
+            self.issuer_classes.insert(&issuer_id, &classes);
+        }
+        events::emit_change_class(&issuer, &changed, self.next_event_seq());
+    }
+
+    /// Hides (or reveals if `hidden=false`) the caller tokens from the public
+    /// `sbt_tokens_by_owner` queries (also used by `sbt_tokens_by_owner_v2`,
+    /// `sbt_tokens_by_owner_cursor` and `account_profile`). For a hidden portfolio these queries return only tokens of the
+    /// IAH class set, so the account remains verifiable as a human (`is_human` is not
+    /// affected). The owner and issuers can list all the tokens with
+    /// `sbt_tokens_by_owner_private`.
+    /// NOTE: this is not a confidentiality guarantee: the contract state and the mint
+    /// events are public, so the tokens can still be reconstructed by an indexer. The
+    /// setting only stops the registry from enumerating the tokens.
+    pub fn set_portfolio_privacy(&mut self, hidden: bool) {
+        let caller = env::predecessor_account_id();
+        if hidden {
+            self.hidden_portfolios.insert(&caller);
+        } else {
+            self.hidden_portfolios.remove(&caller);
+        }
+    }
+
+    /// Sets the tombstone policy of the caller. With a policy, the registry keeps a compact
+    /// record of every burned caller token (see `token_tombstone`), for audits of the past
+    /// humanity proofs. `None` disables recording, already recorded tombstones are kept
+    /// until pruned.
+    /// Must be called by a registered issuer.
+    pub fn sbt_set_tombstone_policy(&mut self, policy: Option<TombstonePolicy>) {
+        let issuer_id = self.assert_issuer(&env::predecessor_account_id());
+        match policy {
+            Some(p) => self.tombstone_policies.insert(&issuer_id, &p),
+            None => self.tombstone_policies.remove(&issuer_id),
+        };
+    }
+
+    /// Sets the caller class metadata in the registry class catalog, or removes it when
+    /// `metadata` is None. The catalog allows wallets to query class metadata of all issuers
+    /// from the registry (see `class_metadata` and `classes_by_issuer`), instead of calling
+    /// `sbt_class_metadata` of each issuer.
+    /// The attached deposit must cover the storage increase.
+    /// Must be called by a registered issuer.
+    #[payable]
+    pub fn set_class_metadata(&mut self, class: ClassId, metadata: Option<ClassMetadata>) {
+        require!(class > 0, "Class must be > 0");
+        let issuer_id = self.assert_issuer(&env::predecessor_account_id());
+        let storage_start = env::storage_usage();
+        let key = (issuer_id, class);
+        let mut classes = self.catalog_classes.get(&issuer_id).unwrap_or_default();
+        match metadata {
+            Some(m) => {
+                if let Err(idx) = classes.binary_search(&class) {
+                    classes.insert(idx, class);
+                }
+                self.class_metadata.insert(&key, &m);
+            }
+            None => {
+                if let Ok(idx) = classes.binary_search(&class) {
+                    classes.remove(idx);
+                }
+                self.class_metadata.remove(&key);
+            }
+        }
+        if classes.is_empty() {
+            self.catalog_classes.remove(&issuer_id);
+        } else {
+            self.catalog_classes.insert(&issuer_id, &classes);
+        }
+
+        let storage_end = env::storage_usage();
+        if storage_end > storage_start {
+            let required_deposit = (storage_end - storage_start) as u128 * env::storage_byte_cost();
+            require!(
+                env::attached_deposit() >= required_deposit,
+                format!(
+                    "not enough NEAR storage deposit, required: {}",
+                    required_deposit
+                )
+            );
+        }
+    }
+
+    /// Removes tombstones of the `issuer` burned `tokens` and returns the number of removed
+    /// tombstones. The issuer can prune any of its tombstones, other accounts only the ones
+    /// older than the issuer policy `retention_ms`.
+    /// Panics if more than `MAX_PURGE_PER_CALL` tokens are provided.
+    pub fn prune_tombstones(&mut self, issuer: AccountId, tokens: Vec<TokenId>) -> u32 {
+        require!(
+            tokens.len() <= MAX_PURGE_PER_CALL as usize,
+            format!("at most {} tokens can be pruned", MAX_PURGE_PER_CALL)
+        );
+        let issuer_id = self.assert_issuer(&issuer);
+        let pruned_before = if env::predecessor_account_id() == issuer {
+            u64::MAX
+        } else {
+            match self
+                .tombstone_policies
+                .get(&issuer_id)
+                .and_then(|p| p.retention_ms)
+            {
+                None => return 0,
+                Some(retention) => MsTimestamp::now().sub_ms(retention).ms(),
+            }
+        };
+        let mut pruned = 0;
+        for token in tokens {
+            let key = IssuerTokenId { issuer_id, token };
+            match self.tombstones.get(&key) {
+                Some(t) if t.burned_at <= pruned_before => {
+                    self.tombstones.remove(&key);
+                    pruned += 1;
+                }
+                _ => (),
+            }
+        }
+        pruned
+    }
+
+    /// Transfers atomically all SBT tokens from one account to another account.
+    /// The caller must be an SBT holder and the `recipient` must not be a banned account.
+    /// The transfer must be firstly requested by the caller (`sbt_soul_transfer_init`) and
+    /// accepted by the recipient (`sbt_soul_transfer_accept`).
+    /// Transfers the account flag from the owner to the recipient.
+    /// Fails when:
+    /// + `recipient` didn't accept the soul transfer from the caller;
+    /// + `recipient` is banned;
+    /// + there is a potential conflict between the caller's and recipient's flag,
+    ///   specifically when one account is `Blacklisted` and the other is `Verified`;
+    /// Bans the caller account. NOTE: call can try to do soul_transfer to himself. This
+    /// sounds irrationally, and allows to ban himself/herself.
+    /// When both accounts have a token of the same (issuer, class), the token with the newest
+    /// expiry is kept (recipient token on a tie), and the other one is burned.
+    /// Emits:
+    /// + `Ban` event for the caller at the beginning of the process.
+    /// + `Burn` event for tokens burned due to (issuer, class) conflicts.
+    /// + `SoulTransfer` event only once all the tokens from the caller were transferred
+    ///    and at least one token was transferred (caller had at least 1 sbt).
+    /// Returns the amount of tokens transferred and a boolean: `true` if the whole
+    /// process has finished, `false` when the process has not finished and should be
+    /// continued by a subsequent call.
+    /// + User must keep calling the `sbt_soul_transfer` until `true` is returned.
+    /// + If caller does not have any tokens, nothing will be transfered, the caller
+    ///   will be banned and `Ban` event will be emitted.
+    /// See https://github.com/near/NEPs/pull/393 for more details and rationality about
+    /// soul transfer.
+    #[payable]
+    #[handle_result]
+    pub fn sbt_soul_transfer(
+        &mut self,
+        recipient: AccountId,
+        #[allow(unused_variables)] memo: Option<String>,
+    ) -> Result<(u32, bool), SoulTransferErr> {
+        // TODO: test what is the max safe amount of updates
+        self._sbt_soul_transfer(recipient, 20)
+    }
+
+    /// Starts the soul transfer process: creates a request to transfer the caller soul (all
+    /// SBTs and the account flag) to the `recipient`. The transfer can be executed with
+    /// `sbt_soul_transfer` once the recipient accepts it with `sbt_soul_transfer_accept`.
+    /// Overwrites the previous caller request.
+    /// Panics if the caller or the `recipient` is banned or the caller has an ongoing
+    /// soul transfer.
+    pub fn sbt_soul_transfer_init(&mut self, recipient: AccountId) {
+        let owner = env::predecessor_account_id();
+        self.assert_not_banned(&owner);
+        self.assert_not_banned(&recipient);
+        require!(
+            !self.ongoing_soul_tx.contains_key(&owner),
+            "soul transfer in progress"
+        );
+        self.soul_transfer_requests.insert(
+            &owner,
+            &SoulTransferRequest {
+                recipient,
+                accepted: false,
+            },
+        );
+    }
+
+    /// Accepts the soul transfer requested by `from` to the caller (see
+    /// `sbt_soul_transfer_init`). Once accepted, `from` can transfer all his SBTs and the
+    /// account flag to the caller using `sbt_soul_transfer`.
+    /// Panics if there is no soul transfer request from `from` to the caller.
+    pub fn sbt_soul_transfer_accept(&mut self, from: AccountId) {
+        let recipient = env::predecessor_account_id();
+        match self.soul_transfer_requests.get(&from) {
+            Some(mut req) if req.recipient == recipient => {
+                req.accepted = true;
+                self.soul_transfer_requests.insert(&from, &req);
+            }
+            _ => env::panic_str(&format!("no soul transfer request from {}", from)),
+        }
+    }
+
+    /// Starts the account merge: creates a request to move all the caller SBTs and the
+    /// account flag to the `to` account, without banning the caller (unlike the soul
+    /// transfer). The merge can be executed with `sbt_merge` once `to` accepts it with
+    /// `sbt_merge_accept`. Overwrites the previous caller request.
+    /// Panics if the caller or `to` is banned or the caller has an ongoing transfer.
+    pub fn sbt_merge_init(&mut self, to: AccountId) {
+        let from = env::predecessor_account_id();
+        self.assert_not_banned(&from);
+        self.assert_not_banned(&to);
+        require!(from != to, "can't merge an account into itself");
+        require!(
+            !self.ongoing_soul_tx.contains_key(&from),
+            "soul transfer in progress"
+        );
+        self.merge_requests.insert(
+            &from,
+            &SoulTransferRequest {
+                recipient: to,
+                accepted: false,
+            },
+        );
+    }
+
+    /// Accepts the account merge requested by `from` to the caller (see `sbt_merge_init`).
+    /// Panics if there is no merge request from `from` to the caller.
+    pub fn sbt_merge_accept(&mut self, from: AccountId) {
+        let to = env::predecessor_account_id();
+        match self.merge_requests.get(&from) {
+            Some(mut req) if req.recipient == to => {
+                req.accepted = true;
+                self.merge_requests.insert(&from, &req);
+            }
+            _ => env::panic_str(&format!("no merge request from {}", from)),
+        }
+    }
+
+    /// Merges the caller account into `to`: transfers all the caller SBTs and moves the
+    /// account flag to `to`, like `sbt_soul_transfer`, but the caller is not banned, so it can
+    /// still be used (and receive new tokens). The caller flag is removed. The merge must be requested by the caller
+    /// (`sbt_merge_init`) and accepted by `to` (`sbt_merge_accept`).
+    /// Token conflicts are resolved as in `sbt_soul_transfer`.
+    /// Returns the amount of tokens transferred and a boolean: `true` if the whole
+    /// process has finished, `false` when the process has not finished and `sbt_merge`
+    /// must be called again.
+    /// Emits `merge` event once all the caller tokens were transferred.
+    #[handle_result]
+    pub fn sbt_merge(&mut self, to: AccountId) -> Result<(u32, bool), SoulTransferErr> {
+        self._sbt_merge(to, 20)
+    }
+
+    // execution of the sbt_merge with `limit` parameter in order to facilitate tests.
+    #[handle_result]
+    pub(crate) fn _sbt_merge(
+        &mut self,
+        to: AccountId,
+        limit: usize,
+    ) -> Result<(u32, bool), SoulTransferErr> {
+        let from = env::predecessor_account_id();
+        self.transfer_soul(from, to, limit, TransferKind::Merge)
+    }
+
+    /// Copies the `from` account flag to the `recipient`. On merge (`kind=Merge`) the flag is
+    /// moved: the source account stays usable and must not keep the flag.
+    /// Counts an `is_human_call*` call to the `ctr` contract. Only registered consumers are
+    /// counted, so callers can't create stats entries (paid by the registry) for arbitrary
+    /// accounts.
+    fn count_consumer_call(&mut self, ctr: &AccountId, lock: bool) {
+        if let Some(mut stats) = self.consumer_stats.get(ctr) {
+            if lock {
+                stats.lock_calls += 1;
+            } else {
+                stats.calls += 1;
+            }
+            self.consumer_stats.insert(ctr, &stats);
+        }
+    }
+
+    pub(crate) fn _transfer_flag(
+        &mut self,
+        from: &AccountId,
+        recipient: &AccountId,
+        kind: &TransferKind,
+    ) {
+        if let Some(flag_from) = self._account_flag(from) {
+            if let Some(flag_to) = self._account_flag(recipient) {
+                require!(
+                    flag_from == flag_to,
+                    "can't transfer soul when there is a flag conflict"
+                )
+            } else {
+                // flag is transferred together with its expiration
+                let entry = self.flag_entry(from).unwrap();
+                self.flagged.insert(recipient, &entry);
+                self.flagged_index.insert(recipient);
+                self.notify_listeners(
+                    "on_iah_flag",
+                    json!({ "account": recipient, "flag": entry.0 }),
+                );
+                if let Some(t) = entry.1 {
+                    events::emit_iah_flag_expires_at(
+                        t,
+                        std::slice::from_ref(recipient),
+                        self.next_event_seq(),
+                    );
+                }
+                events::emit_iah_flag_accounts(
+                    entry.0,
+                    vec![recipient.clone()],
+                    self.next_event_seq(),
+                );
+            }
+        }
+        if *kind == TransferKind::Merge {
+            let legacy = legacy_flagged().remove(from).is_some();
+            if self.flagged.remove(from).is_some() || legacy {
+                self.flagged_index.remove(from);
+                events::emit_iah_unflag_accounts(vec![from.clone()], self.next_event_seq());
+            }
+        }
+    }
+
+    // execution of the sbt_soul_transfer in this function to parametrize `max_updates` in
+    // order to facilitate tests.
+    #[handle_result]
+    pub(crate) fn _sbt_soul_transfer(
+        &mut self,
+        recipient: AccountId,
+        limit: usize,
+    ) -> Result<(u32, bool), SoulTransferErr> {
+        let owner = env::predecessor_account_id();
+        self.transfer_soul(owner, recipient, limit, TransferKind::SoulTransfer)
+    }
+
+    /// Moves the `owner` tokens (and the flag) to the `recipient`: executes the soul transfer
+    /// (`kind=SoulTransfer`, the owner is banned) or the account merge (`kind=Merge`).
+    fn transfer_soul(
+        &mut self,
+        owner: AccountId,
+        recipient: AccountId,
+        limit: usize,
+        kind: TransferKind,
+    ) -> Result<(u32, bool), SoulTransferErr> {
+        let transfer_lock = self.transfer_lock.get(&owner).unwrap_or(0);
+        if transfer_lock >= MsTimestamp::now().ms() {
+            return Err(SoulTransferErr::TransferLocked);
+        }
+        let requests = match kind {
+            TransferKind::Merge => &self.merge_requests,
+            _ => &self.soul_transfer_requests,
+        };
+        // consent is only checked when starting the process, a resumed transfer was already
+        // accepted.
+        if !self.ongoing_soul_tx.contains_key(&owner) {
+            match requests.get(&owner) {
+                Some(req) if req.recipient == recipient && req.accepted => (),
+                _ => return Err(SoulTransferErr::NotAccepted),
+            }
+        } else if let Some((r, k)) = self.ongoing_tx_info.get(&owner) {
+            if r != recipient || k != kind {
+                return Err(SoulTransferErr::OtherTransfer);
+            }
+        }
+
+        let ban_owner = kind == TransferKind::SoulTransfer;
+        let (resumed, start) = self.transfer_continuation(&owner, &recipient, ban_owner);
         if !resumed {
-            self._transfer_flag(&owner, &recipient);
+            self._transfer_flag(&owner, &recipient, &kind);
         }
 
         let batch: Vec<(BalanceKey, TokenId)> = self
@@ -268,6 +1448,8 @@ impl Contract {
             .take(limit)
             .collect();
 
+        let owner_before = self.human_state(&owner);
+        let recipient_before = self.human_state(&recipient);
         let mut key_new = BalanceKey {
             owner: recipient.clone(),
             issuer_id: 0,
@@ -275,6 +1457,7 @@ impl Contract {
         };
         let mut prev_issuer: IssuerId = 0;
         let mut token_counter = 0;
+        let mut burned: Vec<IssuerTokenId> = Vec::new();
         for (key, token_id) in &batch {
             if key.owner != owner {
                 break;
@@ -284,48 +1467,84 @@ impl Contract {
             if prev_issuer != key.issuer_id {
                 prev_issuer = key.issuer_id;
                 // update user token supply map
-                if let Some(s) = self.supply_by_owner.remove(&(owner.clone(), prev_issuer)) {
+                let s = self
+                    .supply_by_owner
+                    .get(&(owner.clone(), prev_issuer))
+                    .unwrap_or(0);
+                if s > 0 {
+                    self.set_supply_by_owner(&owner, prev_issuer, 0);
                     let key = &(recipient.clone(), prev_issuer);
                     let supply_to = self.supply_by_owner.get(key).unwrap_or(0);
-                    self.supply_by_owner.insert(key, &(s + supply_to));
+                    self.set_supply_by_owner(&recipient, prev_issuer, s + supply_to);
                 }
             }
 
             key_new.issuer_id = key.issuer_id;
             key_new.class_id = key.class_id;
-            // One use can have max one toke of a (issuer, class) pair. We don't allow users
-            // to overwrite each other tokens. Recipient or sender should firstly burn his SBT
-            // to avoid conflicts.
-            if self.balances.insert(&key_new, token_id).is_some() {
-                env::panic_str(&format!(
-                    "recipient already has an SBT of issuer={}, class={}; source_token_id={}",
-                    self.issuer_by_id(key.issuer_id),
-                    key.class_id,
-                    token_id
-                ));
-            }
             self.balances.remove(key);
+            // class block follows the token, and is kept on conflicts
+            if self.blocked_classes.remove(key) {
+                self.blocked_classes.insert(&key_new);
+            }
 
             let i_key = IssuerTokenId {
                 issuer_id: key.issuer_id,
                 token: *token_id,
             };
             let mut td = self.issuer_tokens.get(&i_key).unwrap();
+            // One user can have max one token of a (issuer, class) pair. On conflict we keep
+            // the token with the newest expiry (recipient token on a tie) and burn the other.
+            if let Some(recipient_token) = self.balances.get(&key_new) {
+                let r_key = IssuerTokenId {
+                    issuer_id: key.issuer_id,
+                    token: recipient_token,
+                };
+                let r_td = self.issuer_tokens.get(&r_key).unwrap();
+                let expiry = |t: &TokenData| t.metadata.expires_at().unwrap_or(u64::MAX);
+                if expiry(&td) > expiry(&r_td) {
+                    self.burn_replaced_token(&r_key, &recipient, key.class_id);
+                } else {
+                    self.burn_replaced_token(&i_key, &owner, key.class_id);
+                    burned.push(i_key);
+                    continue;
+                }
+                burned.push(r_key);
+            }
+            self.balances.insert(&key_new, token_id);
             td.owner = recipient.clone();
             self.issuer_tokens.insert(&i_key, &td);
         }
+        if !burned.is_empty() {
+            self.emit_conflict_burns(&recipient, burned);
+        }
+
+        self.update_human_counters(&owner, owner_before);
+        self.update_human_counters(&recipient, recipient_before);
 
         let completed = token_counter != limit;
         if completed {
+            if kind == TransferKind::Merge {
+                self.merge_requests.remove(&owner);
+            } else {
+                self.soul_transfer_requests.remove(&owner);
+            }
             if resumed {
                 // insert is happening when we need to continue, so don't need to remove if
                 // the process finishes in the same transaction.
                 self.ongoing_soul_tx.remove(&owner);
+                self.ongoing_tx_info.remove(&owner);
             }
             // we emit the event only once the operation is completed and only if some tokens were
             // transferred
             if resumed || token_counter > 0 {
-                emit_soul_transfer(&owner, &recipient);
+                if kind == TransferKind::Merge {
+                    events::emit_merge(&owner, &recipient, self.next_event_seq());
+                } else {
+                    self.emit_nep393(Nep393Event::SoulTransfer(SoulTransfer {
+                        from: &owner,
+                        to: &recipient,
+                    }));
+                }
             }
         } else {
             let last = &batch[token_counter - 1];
@@ -336,6 +1555,7 @@ impl Contract {
                     token: last.0.class_id, // we reuse IssuerTokenId type here (to not generate new code), but we store class_id instead of token here.
                 },
             );
+            self.ongoing_tx_info.insert(&owner, &(recipient, kind));
         }
 
         Ok((token_counter as u32, completed))
@@ -350,7 +1570,12 @@ impl Contract {
     ///
     /// `payload` must be a JSON string, and it will be passed through the default interface,
     /// hence it will be JSON deserialized when using SDK.
-    /// Panics if the predecessor is not a human.
+    /// `forward_deposit`: the deposit passed to `ctr.function`, useful for contracts requiring
+    /// an exact deposit. The surplus of the attached deposit is refunded to the caller. If not
+    /// set, the whole attached deposit is forwarded.
+    /// Returns `NotHuman` error if the predecessor is not a human, `GovBanned` if the
+    /// predecessor is flagged as `GovBan` and `ctr` is a governance contract,
+    /// `ForwardDepositTooBig` if `forward_deposit` is bigger than the attached deposit.
     #[payable]
     #[handle_result]
     pub fn is_human_call(
@@ -358,13 +1583,25 @@ impl Contract {
         ctr: AccountId,
         function: String,
         payload: String,
+        forward_deposit: Option<U128>,
     ) -> Result<Promise, IsHumanCallErr> {
+        let attached = env::attached_deposit();
+        let deposit = forward_deposit.map_or(attached, |d| d.0);
+        if deposit > attached {
+            return Err(IsHumanCallErr::ForwardDepositTooBig(attached));
+        }
         let caller = env::predecessor_account_id();
         let iah_proof = self._is_human(&caller);
         if iah_proof.is_empty() {
             return Err(IsHumanCallErr::NotHuman);
         }
+        self.assert_not_gov_banned(&caller, &ctr)?;
+
+        self.count_consumer_call(&ctr, false);
 
+        if attached > deposit {
+            Promise::new(caller.clone()).transfer(attached - deposit);
+        }
         let args = IsHumanCallbackArgs {
             caller,
             iah_proof,
@@ -373,11 +1610,73 @@ impl Contract {
         Ok(Promise::new(ctr).function_call(
             function,
             serde_json::to_vec(&args).unwrap(),
-            env::attached_deposit(),
+            deposit,
             env::prepaid_gas() - IS_HUMAN_GAS,
         ))
     }
 
+    /// Same as `is_human_call`, but calls multiple contracts with the same `iah_proof`, so
+    /// dapps composing several IAH gated contracts (eg register + vote) don't require the
+    /// user to sign multiple transactions. Each call is a tuple of
+    /// (contract, function, payload, deposit) and results in:
+    ///
+    ///    contract.function({caller: predecessor_account_id(),
+    ///                       iah_proof: SBTs,
+    ///                       payload: payload})
+    ///
+    /// The calls are independent (executed in parallel), one failing call doesn't revert the
+    /// other ones. The remaining gas is split equally between the calls.
+    /// The attached deposit must be equal to the sum of the calls deposits.
+    /// Returns `NotHuman` error if the predecessor is not a human, `WrongBatchSize` if
+    /// `calls` is empty or has more than `MAX_IS_HUMAN_CALL_BATCH` elements, `GovBanned`
+    /// if the predecessor is flagged as `GovBan` and any call targets a governance contract.
+    #[payable]
+    #[handle_result]
+    pub fn is_human_call_batch(
+        &mut self,
+        calls: Vec<(AccountId, String, String, U128)>,
+    ) -> Result<Promise, IsHumanCallErr> {
+        if calls.is_empty() || calls.len() > MAX_IS_HUMAN_CALL_BATCH {
+            return Err(IsHumanCallErr::WrongBatchSize(MAX_IS_HUMAN_CALL_BATCH));
+        }
+        let total_deposit: u128 = calls.iter().map(|c| c.3 .0).sum();
+        if total_deposit != env::attached_deposit() {
+            return Err(IsHumanCallErr::WrongDeposit(total_deposit));
+        }
+        let caller = env::predecessor_account_id();
+        let iah_proof = self._is_human(&caller);
+        if iah_proof.is_empty() {
+            return Err(IsHumanCallErr::NotHuman);
+        }
+        for c in &calls {
+            self.assert_not_gov_banned(&caller, &c.0)?;
+        }
+
+        let n = calls.len() as u64;
+        let gas = Gas((env::prepaid_gas() - IS_HUMAN_GAS).0 / n - IS_HUMAN_BATCH_CALL_GAS.0);
+        let mut promise: Option<Promise> = None;
+        for (ctr, function, payload, deposit) in calls {
+            self.count_consumer_call(&ctr, false);
+
+            let args = IsHumanCallbackArgs {
+                caller: caller.clone(),
+                iah_proof: iah_proof.clone(),
+                payload: &RawValue::from_string(payload).unwrap(),
+            };
+            let p = Promise::new(ctr).function_call(
+                function,
+                serde_json::to_vec(&args).unwrap(),
+                deposit.0,
+                gas,
+            );
+            promise = Some(match promise {
+                None => p,
+                Some(prev) => prev.and(p),
+            });
+        }
+        Ok(promise.unwrap())
+    }
+
     /// Apps should use this function to ask a user to lock his account for soul transfer.
     /// This is useful when a dapp relays on user account ID (rather set of potential SBTs)
     /// being a unique human over a period of time (there is no soul transfer in between).
@@ -403,7 +1702,8 @@ impl Contract {
     ///    soul transfers. Can be zero, if no lock is needed.
     /// * `with_proof`: when false - doesn't send iah_proof (SBTs) to the contract call.
     /// Emits transfer_lock if the transfer_lock is extended.
-    /// Panics if the predecessor is not a human.
+    /// Returns `NotHuman` error if the predecessor is not a human, `GovBanned` if the
+    /// predecessor is flagged as `GovBan` and `ctr` is a governance contract.
     #[payable]
     #[handle_result]
     pub fn is_human_call_lock(
@@ -419,14 +1719,17 @@ impl Contract {
         if proof.is_empty() {
             return Err(IsHumanCallErr::NotHuman);
         }
+        self.assert_not_gov_banned(&caller, &ctr)?;
 
-        let now = env::block_timestamp_ms();
-        let mut lock = self.transfer_lock.get(&caller).unwrap_or(now);
-        if lock_duration > 0 && lock < now + lock_duration {
-            lock = now + lock_duration;
+        let now = MsTimestamp::now();
+        let mut lock = self.transfer_lock.get(&caller).unwrap_or(now.ms());
+        let until = now.add_ms(lock_duration).ms();
+        if lock_duration > 0 && lock < until {
+            lock = until;
             self.transfer_lock.insert(&caller, &lock);
-            events::emit_transfer_lock(caller.clone(), lock)
+            events::emit_transfer_lock(caller.clone(), lock, self.next_event_seq())
         }
+        self.count_consumer_call(&ctr, true);
 
         let args = IsHumanLockCallbackArgs {
             caller,
@@ -460,7 +1763,9 @@ impl Contract {
                 self.banlist.insert(owner),
                 "from account is banned. Cannot start the transfer"
             );
-            Nep393Event::Ban(vec![owner]).emit();
+            self.ban_timestamps.insert(owner, &MsTimestamp::now().ms());
+            self.emit_nep393(Nep393Event::Ban(vec![owner]));
+            self.notify_listeners("on_iah_ban", json!({ "account": owner }));
         }
 
         IssuerTokenId {
@@ -492,7 +1797,7 @@ impl Contract {
     fn _sbt_recover(&mut self, from: AccountId, to: AccountId, limit: usize) -> (u32, bool) {
         let storage_start = env::storage_usage();
         let issuer = env::predecessor_account_id();
-        let issuer_id = self.assert_issuer(&issuer);
+        let issuer_id = self.assert_active_issuer(&issuer);
         self.assert_not_banned(&to);
         // get the last transfered token and don't ban the owner.
         let (resumed, start) = self.transfer_continuation(&from, &to, false);
@@ -526,6 +1831,8 @@ impl Contract {
         }
 
         // update user balances
+        let from_before = self.human_state(&from);
+        let to_before = self.human_state(&to);
         let mut old_balance_key = balance_key(from.clone(), issuer_id, 0);
         let mut new_balance_key = balance_key(to.clone(), issuer_id, 0);
         for class_id in class_ids {
@@ -533,22 +1840,21 @@ impl Contract {
             let token_id = self.balances.remove(&old_balance_key).unwrap();
             new_balance_key.class_id = class_id;
             self.balances.insert(&new_balance_key, &token_id);
+            if self.blocked_classes.remove(&old_balance_key) {
+                self.blocked_classes.insert(&new_balance_key);
+            }
         }
+        self.update_human_counters(&from, from_before);
+        self.update_human_counters(&to, to_before);
 
         // update supply_by_owner map. We can't do it in the loop above becuse we can't modify
         // self.balances while iterating over it
         let supply_key = &(from.clone(), issuer_id);
-        let old_supply_from = self.supply_by_owner.remove(supply_key).unwrap_or(0);
-        if old_supply_from != tokens_recovered {
-            self.supply_by_owner.insert(
-                &(from.clone(), issuer_id),
-                &(old_supply_from - tokens_recovered),
-            );
-        }
+        let old_supply_from = self.supply_by_owner.get(supply_key).unwrap_or(0);
+        self.set_supply_by_owner(&from, issuer_id, old_supply_from - tokens_recovered);
         let supply_key = &(to.clone(), issuer_id);
         let old_supply_to = self.supply_by_owner.get(supply_key).unwrap_or(0);
-        self.supply_by_owner
-            .insert(supply_key, &(old_supply_to + tokens_recovered));
+        self.set_supply_by_owner(&to, issuer_id, old_supply_to + tokens_recovered);
 
         let completed = tokens_recovered != limit as u64;
         if completed {
@@ -556,17 +1862,17 @@ impl Contract {
                 // insert is happening when we need to continue, so don't need to remove if
                 // the process finishes in the same transaction.
                 self.ongoing_soul_tx.remove(&from);
+                self.ongoing_tx_info.remove(&from);
             }
             // we emit the event only once the operation is completed and only if some tokens were
             // recovered
             if resumed || tokens_recovered > 0 {
                 // emit Recover event
-                SbtRecover {
+                self.emit_nep393(Nep393Event::Recover(SbtRecover {
                     issuer: &issuer,
                     old_owner: &from,
                     new_owner: &to,
-                }
-                .emit();
+                }));
             }
         } else {
             self.ongoing_soul_tx.insert(
@@ -576,6 +1882,8 @@ impl Contract {
                     token: last_token_transfered.class_id, // we reuse IssuerTokenId type here (to not generate new code), but we store class_id instead of token here.
                 },
             );
+            self.ongoing_tx_info
+                .insert(&from, &(to.clone(), TransferKind::Recover));
         }
         // storage check
         // we are using checked_sub, since the storage can decrease and we are running of risk of underflow
@@ -595,11 +1903,13 @@ impl Contract {
     }
 
     /// Method to burn all caller tokens (from all issuers).
-    /// The method must be called repeatedly until true is returned.
-    /// Not all tokens may be burned in a single call due to the gas limitation - in that case
-    /// `false` is returned.
+    /// The method must be called repeatedly until `done` is returned.
+    /// Not all tokens may be burned in a single call due to the gas limitation.
+    /// Returns `(burned_this_call, remaining_estimate, done)`: number of tokens burned in
+    /// this call, an estimate of the number of tokens left to burn (based on the owner
+    /// supply counters), and true if all tokens have been burned.
     /// The burn event is emitted for all the tokens burned.
-    pub fn sbt_burn_all(&mut self) -> bool {
+    pub fn sbt_burn_all(&mut self) -> (u32, u32, bool) {
         self._sbt_burn_all(25)
     }
 
@@ -620,6 +1930,9 @@ impl Contract {
         );
 
         let issuer_id = self.assert_issuer(&issuer);
+        let has_provenance = self.provenance_issuers.contains_key(&issuer_id);
+        let has_tombstones = self.tombstone_policies.contains_key(&issuer_id);
+        let human_before = self.human_state(&owner);
         let token_len = tokens.len() as u64;
         let mut token_ids = HashSet::new();
         for tid in tokens.iter() {
@@ -643,7 +1956,13 @@ impl Contract {
             );
 
             self.issuer_tokens.remove(ct_key);
+            if has_provenance {
+                self.token_provenance.remove(ct_key);
+            }
             let class_id = t.metadata.v1().class;
+            if has_tombstones {
+                self.add_tombstone(ct_key, &owner, class_id);
+            }
             self.balances
                 .remove(&balance_key(owner.clone(), issuer_id, class_id));
 
@@ -655,17 +1974,26 @@ impl Contract {
         }
 
         // update supply by owner
-        let key = (owner, issuer_id);
-        let mut supply = self.supply_by_owner.get(&key).unwrap();
-        supply -= token_len;
-        self.supply_by_owner.insert(&key, &supply);
+        let supply = self
+            .supply_by_owner
+            .get(&(owner.clone(), issuer_id))
+            .unwrap();
+        self.set_supply_by_owner(&owner, issuer_id, supply - token_len);
+        self.update_human_counters(&owner, human_before);
 
         // update total supply by issuer
         let mut supply = self.supply_by_issuer.get(&issuer_id).unwrap();
         supply -= token_len;
         self.supply_by_issuer.insert(&issuer_id, &supply);
+        self.add_issuer_stats(
+            issuer_id,
+            IssuerStats {
+                total_burned: token_len,
+                ..Default::default()
+            },
+        );
 
-        SbtTokensEvent { issuer, tokens }.emit_burn();
+        self.emit_nep393(Nep393Event::Burn(SbtTokensEvent { issuer, tokens }));
     }
 
     //
@@ -673,23 +2001,475 @@ impl Contract {
     //
 
     /// returns false if the `issuer` contract was already registered.
+    /// Panics if the `issuer` account is flagged.
     pub fn admin_add_sbt_issuer(&mut self, issuer: AccountId) -> bool {
         self.assert_authority();
+        require!(
+            self._account_flag(&issuer).is_none(),
+            format!("account {} is flagged and can't be an issuer", issuer)
+        );
+        require!(
+            !self.removed_issuers.contains_key(&issuer),
+            "issuer tokens must be purged before adding it again"
+        );
         self._add_sbt_issuer(&issuer)
     }
 
-    pub fn change_admin(&mut self, new_admin: AccountId) {
+    /// Indexes the counters of accounts holding tokens minted before the counters were
+    /// introduced (after the migration: `sbt_stats` holders and classes, and the human
+    /// counters), or after the IAH class set or the tracked human classes changed (human
+    /// counters only). Accounts are processed in the `balances` order, at most `limit`
+    /// (default and max `MAX_BACKFILL_PER_CALL`) accounts per call. Until the backfill is
+    /// completed, the counters can't be queried, and token changes of accounts not indexed
+    /// yet are counted once the backfill reaches them.
+    /// Returns true when all the accounts are indexed (or there is nothing to index), false
+    /// if the method must be called again.
+    pub fn admin_backfill_counters(&mut self, limit: Option<u32>) -> bool {
         self.assert_authority();
-        self.authority = new_admin;
+        let mut backfill = match self.counters_backfill.take() {
+            None => return true,
+            Some(b) => b,
+        };
+        let limit = limit
+            .unwrap_or(MAX_BACKFILL_PER_CALL)
+            .clamp(1, MAX_BACKFILL_PER_CALL) as usize;
+        let iter: Box<dyn Iterator<Item = (BalanceKey, TokenId)>> = match &backfill.last_owner {
+            None => Box::new(self.balances.iter()),
+            // iter_from skips the start key, so we start after the last owner keys
+            Some(o) => Box::new(self.balances.iter_from(balance_key(
+                o.clone(),
+                IssuerId::MAX,
+                ClassId::MAX,
+            ))),
+        };
+        let mut accounts: Vec<(AccountId, Vec<(IssuerId, ClassId)>)> = Vec::new();
+        let mut done = true;
+        for (key, _) in iter {
+            match accounts.last_mut() {
+                Some((owner, classes)) if *owner == key.owner => {
+                    classes.push((key.issuer_id, key.class_id))
+                }
+                _ => {
+                    if accounts.len() == limit {
+                        done = false;
+                        break;
+                    }
+                    accounts.push((key.owner, vec![(key.issuer_id, key.class_id)]));
+                }
+            }
+        }
+        for (owner, classes) in &accounts {
+            self.backfill_account(owner, classes, backfill.supply);
+        }
+        if done {
+            return true;
+        }
+        backfill.last_owner = accounts.pop().map(|(owner, _)| owner);
+        self.counters_backfill = Some(backfill);
+        false
     }
 
-    pub fn admin_set_authorized_flaggers(&mut self, authorized_flaggers: Vec<AccountId>) {
+    /// Blocks the `issuer` `class` for the `account`: the account token of that class is
+    /// ignored by `is_human`, `humanity_score` and `sbt_tokens_by_owner` until unblocked, while
+    /// other tokens of the account stay valid. Allows to suspend a single token usage
+    /// (eg KYC) without banning the whole account. The block applies also to tokens minted
+    /// later. Emits `block_class` event.
+    /// Returns false if the class was already blocked.
+    /// Panics if the `issuer` is not registered.
+    pub fn admin_block_class(
+        &mut self,
+        account: AccountId,
+        issuer: AccountId,
+        class: ClassId,
+    ) -> bool {
         self.assert_authority();
-        self.authorized_flaggers.set(&authorized_flaggers);
+        let issuer_id = self.assert_issuer(&issuer);
+        if !self
+            .blocked_classes
+            .insert(&balance_key(account.clone(), issuer_id, class))
+        {
+            return false;
+        }
+        events::emit_block_class(account, issuer, class, true, self.next_event_seq());
+        true
     }
 
-    /// Returns true if account was added. Returns false if account was already authorized.
-    pub fn admin_add_authorized_flagger(&mut self, account: AccountId) -> bool {
+    /// Removes the block created with `admin_block_class`. Emits `unblock_class` event.
+    /// Returns false if the class was not blocked.
+    /// Panics if the `issuer` is not registered.
+    pub fn admin_unblock_class(
+        &mut self,
+        account: AccountId,
+        issuer: AccountId,
+        class: ClassId,
+    ) -> bool {
+        self.assert_authority();
+        let issuer_id = self.assert_issuer(&issuer);
+        if !self
+            .blocked_classes
+            .remove(&balance_key(account.clone(), issuer_id, class))
+        {
+            return false;
+        }
+        events::emit_block_class(account, issuer, class, false, self.next_event_seq());
+        true
+    }
+
+    /// Sets the list of (issuer, class) for which the number of humans holding the class is
+    /// tracked (eg KYC classes), see `humans_count_by_extra_class`. Counters of classes
+    /// removed from the list are dropped. When a new class is added, the human counters
+    /// are reset and must be indexed with `admin_backfill_counters`.
+    /// Panics if the list has more than `MAX_HUMAN_CLASSES` entries, any issuer is not
+    /// registered or the supply stats backfill (after the migration) is in progress.
+    pub fn admin_set_human_classes(&mut self, classes: Vec<(AccountId, ClassId)>) {
+        self.assert_authority();
+        require!(
+            classes.len() <= MAX_HUMAN_CLASSES,
+            format!("max {} human classes are allowed", MAX_HUMAN_CLASSES)
+        );
+        let mut human_classes = Vec::with_capacity(classes.len());
+        for (issuer, class) in classes {
+            let issuer_id = self
+                .sbt_issuers
+                .get(&issuer)
+                .unwrap_or_else(|| panic!("issuer {} is not registered", issuer));
+            if !human_classes.contains(&(issuer_id, class)) {
+                human_classes.push((issuer_id, class));
+            }
+        }
+        for key in &self.human_classes {
+            if !human_classes.contains(key) {
+                self.humans_by_class.remove(key);
+            }
+        }
+        let added = human_classes
+            .iter()
+            .any(|key| !self.human_classes.contains(key));
+        self.human_classes = human_classes;
+        if added {
+            self.reset_human_counters();
+        }
+    }
+
+    /// Deregisters the `issuer`: all further calls from the issuer are rejected. Issuer
+    /// tokens must be then burned with `admin_purge_issuer_tokens`.
+    /// Returns false if the issuer was not registered.
+    /// Panics if the issuer is a part of the IAH class set.
+    pub fn admin_remove_sbt_issuer(&mut self, issuer: AccountId) -> bool {
+        self.assert_authority();
+        require!(
+            self.iah_sbts.iter().all(|(i, _)| i != &issuer),
+            "can't remove an issuer used in the IAH class set"
+        );
+        let issuer_id = match self.sbt_issuers.remove(&issuer) {
+            None => return false,
+            Some(id) => id,
+        };
+        self.paused_issuers.remove(&issuer);
+        self.removed_issuers.insert(
+            &issuer,
+            &RemovedIssuer {
+                issuer_id,
+                next_token: 1,
+            },
+        );
+        true
+    }
+
+    /// Burns tokens of an issuer removed with `admin_remove_sbt_issuer` and reclaims their
+    /// storage (including the tokens blocked classes and tombstones). At most `limit` token
+    /// IDs (default and max `MAX_PURGE_PER_CALL`) are processed in a single call. Once all
+    /// tokens are purged, all the issuer data (supply, stats, classes, policies) is removed.
+    /// Emits the burn and revoke events for the purged tokens.
+    /// Returns true when all the issuer tokens are purged, false if the method must be
+    /// called again.
+    /// Panics if the issuer was not removed or its tokens were already purged.
+    pub fn admin_purge_issuer_tokens(&mut self, issuer: AccountId, limit: Option<u32>) -> bool {
+        self.assert_authority();
+        let mut ri = self
+            .removed_issuers
+            .get(&issuer)
+            .expect("issuer is not removed");
+        let issuer_id = ri.issuer_id;
+        let limit = std::cmp::min(limit.unwrap_or(MAX_PURGE_PER_CALL), MAX_PURGE_PER_CALL);
+        let last_token = self.next_token_ids.get(&issuer_id).unwrap_or(0);
+        let end = std::cmp::min(ri.next_token + limit as u64, last_token + 1);
+        let has_provenance = self.provenance_issuers.contains_key(&issuer_id);
+
+        let mut tokens = Vec::new();
+        let mut supply_by_owner: HashMap<AccountId, u64> = HashMap::new();
+        let mut human_before: HashMap<AccountId, HumanState> = HashMap::new();
+        let mut supply_by_class: HashMap<ClassId, u64> = HashMap::new();
+        for token in ri.next_token..end {
+            let key = IssuerTokenId { issuer_id, token };
+            self.tombstones.remove(&key);
+            let t = match self.issuer_tokens.remove(&key) {
+                None => continue, // already burned
+                Some(t) => t,
+            };
+            if has_provenance {
+                self.token_provenance.remove(&key);
+            }
+            let class_id = t.metadata.v1().class;
+            human_before
+                .entry(t.owner.clone())
+                .or_insert_with(|| self.human_state(&t.owner));
+            let bkey = balance_key(t.owner.clone(), issuer_id, class_id);
+            self.balances.remove(&bkey);
+            self.blocked_classes.remove(&bkey);
+            *supply_by_owner.entry(t.owner).or_default() += 1;
+            *supply_by_class.entry(class_id).or_default() += 1;
+            tokens.push(token);
+        }
+
+        for (owner, burned) in supply_by_owner {
+            let supply = self
+                .supply_by_owner
+                .get(&(owner.clone(), issuer_id))
+                .unwrap();
+            self.set_supply_by_owner(&owner, issuer_id, supply - burned);
+        }
+        for (owner, before) in human_before {
+            self.update_human_counters(&owner, before);
+        }
+        for (class_id, burned) in supply_by_class {
+            let key = (issuer_id, class_id);
+            let supply = self.supply_by_class.get(&key).unwrap() - burned;
+            if supply == 0 {
+                self.supply_by_class.remove(&key);
+            } else {
+                self.supply_by_class.insert(&key, &supply);
+            }
+        }
+        if !tokens.is_empty() {
+            let burned = tokens.len() as u64;
+            let supply = self.supply_by_issuer.get(&issuer_id).unwrap() - burned;
+            self.supply_by_issuer.insert(&issuer_id, &supply);
+            self.add_issuer_stats(
+                issuer_id,
+                IssuerStats {
+                    total_burned: burned,
+                    ..Default::default()
+                },
+            );
+            self.emit_burn_revoke(issuer.clone(), tokens);
+        }
+
+        if end <= last_token {
+            ri.next_token = end;
+            self.removed_issuers.insert(&issuer, &ri);
+            return false;
+        }
+        // all tokens are purged
+        self.removed_issuers.remove(&issuer);
+        self.issuer_id_map.remove(&issuer_id);
+        self.next_token_ids.remove(&issuer_id);
+        self.supply_by_issuer.remove(&issuer_id);
+        self.holders_by_issuer.remove(&issuer_id);
+        self.issuer_classes.remove(&issuer_id);
+        self.provenance_issuers.remove(&issuer_id);
+        self.tombstone_policies.remove(&issuer_id);
+        self.issuer_stats.remove(&issuer_id);
+        self.score_weights.retain(|sw| sw.issuer != issuer);
+        for key in &self.human_classes {
+            if key.0 == issuer_id {
+                self.humans_by_class.remove(key);
+            }
+        }
+        self.human_classes.retain(|(id, _)| *id != issuer_id);
+        true
+    }
+
+    /// Enables or disables recording of the minting context (`token_provenance`) of the
+    /// `issuer` tokens. Provenance is stored per token, so it's disabled by default to
+    /// limit the storage cost (covered by the mint deposit). Tokens minted before enabling
+    /// have no provenance. Returns false if the setting was not changed.
+    /// Panics if the `issuer` is not registered.
+    pub fn admin_set_token_provenance(&mut self, issuer: AccountId, enabled: bool) -> bool {
+        self.assert_authority();
+        let issuer_id = self.assert_issuer(&issuer);
+        match self.provenance_issuers.get(&issuer_id) {
+            None if !enabled => false,
+            Some(prev) if prev == enabled => false,
+            _ => {
+                self.provenance_issuers.insert(&issuer_id, &enabled);
+                true
+            }
+        }
+    }
+
+    /// Adds `ctr` to the governance contracts: accounts flagged as `GovBan` stay human for
+    /// `is_human` and other contracts, but `is_human_call`, `is_human_call_lock` and
+    /// `is_human_call_batch` targeting a governance contract fail with `GovBanned` error.
+    /// Returns false if the contract was already added.
+    pub fn admin_add_gov_contract(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        self.gov_contracts.insert(&ctr)
+    }
+
+    /// Removes `ctr` from the governance contracts. Returns false if the contract was not
+    /// a governance contract.
+    pub fn admin_remove_gov_contract(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        self.gov_contracts.remove(&ctr)
+    }
+
+    /// Registers `ctr` as a consumer: calls made to `ctr` through `is_human_call`,
+    /// `is_human_call_batch` and `is_human_call_lock` are counted in `consumer_stats`.
+    /// Returns false if the contract was already registered.
+    /// Must be called by the authority.
+    pub fn admin_add_consumer(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        if self.consumer_stats.contains_key(&ctr) {
+            return false;
+        }
+        self.consumer_stats.insert(&ctr, &ConsumerStats::default());
+        true
+    }
+
+    /// Unregisters the `ctr` consumer and removes its stats. Returns false if the contract
+    /// was not registered.
+    /// Must be called by the authority.
+    pub fn admin_remove_consumer(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        self.consumer_stats.remove(&ctr).is_some()
+    }
+
+    /// Adds `ctr` to the listener contracts. Listeners are notified with a best-effort cross
+    /// contract call whenever an account is banned (`on_iah_ban(account: AccountId)`) or
+    /// flagged (`on_iah_flag(account: AccountId, flag: AccountFlag)`). Each notification has
+    /// `LISTENER_NOTIFY_GAS` attached and its result is ignored; notifications are skipped
+    /// when the transaction doesn't have enough gas left.
+    /// Returns false if the contract was already added.
+    /// Panics if there are already `MAX_LISTENERS` listeners.
+    pub fn admin_add_listener(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        if self.listeners.contains(&ctr) {
+            return false;
+        }
+        require!(
+            (self.listeners.len() as usize) < MAX_LISTENERS,
+            format!("max amount of listeners is {}", MAX_LISTENERS)
+        );
+        self.listeners.insert(&ctr)
+    }
+
+    /// Removes `ctr` from the listener contracts. Returns false if the contract was not
+    /// a listener.
+    pub fn admin_remove_listener(&mut self, ctr: AccountId) -> bool {
+        self.assert_authority();
+        self.listeners.remove(&ctr)
+    }
+
+    /// Pauses the `issuer`: mint, renew, revoke, recover and token references update calls
+    /// from the issuer are rejected until it's unpaused. Existing tokens stay queryable.
+    /// Returns false if the issuer was already paused.
+    /// Panics if the `issuer` is not registered.
+    pub fn admin_pause_issuer(&mut self, issuer: AccountId) -> bool {
+        self.assert_authority();
+        self.assert_issuer(&issuer);
+        self.paused_issuers.insert(&issuer)
+    }
+
+    /// Unpauses the `issuer`. Returns false if the issuer was not paused.
+    pub fn admin_unpause_issuer(&mut self, issuer: AccountId) -> bool {
+        self.assert_authority();
+        self.paused_issuers.remove(&issuer)
+    }
+
+    /// Sets the IAH class set used by `is_human`: list of (issuer, [required classes]) and
+    /// the `mode` defining how the entries are combined (`All` or `Any` issuer).
+    /// The human counters are reset and must be indexed with `admin_backfill_counters`.
+    /// Panics if the class set is empty, any issuer has an empty list of classes, any
+    /// issuer is not registered or the supply stats backfill (after the migration) is in
+    /// progress. Returns `TooManyIahClasses` error if the total number of
+    /// classes exceeds `limits.max_iah_classes`.
+    #[handle_result]
+    pub fn admin_set_iah_class_set(
+        &mut self,
+        class_set: ClassSet,
+        mode: ClassSetMode,
+    ) -> Result<(), LimitErr> {
+        self.assert_authority();
+        self.limits.check_iah_classes(&class_set)?;
+        require!(!class_set.is_empty(), "class_set must be a non empty list");
+        for (issuer, classes) in &class_set {
+            require!(
+                !classes.is_empty(),
+                format!("classes of issuer {} must be a non empty list", issuer)
+            );
+            require!(
+                self.sbt_issuers.get(issuer).is_some(),
+                format!("issuer {} is not registered", issuer)
+            );
+        }
+        self.iah_sbts = class_set;
+        self.iah_mode = mode;
+        self.reset_human_counters();
+        Ok(())
+    }
+
+    /// Updates the registry limits. Both limits must be positive. Returns
+    /// `TooManyIahClasses` error if the current IAH class set exceeds the new limit.
+    #[handle_result]
+    pub fn admin_set_limits(&mut self, limits: Limits) -> Result<(), LimitErr> {
+        self.assert_authority();
+        require!(
+            limits.max_iah_classes > 0 && limits.max_mint_batch > 0,
+            "limits must be positive"
+        );
+        limits.check_iah_classes(&self.iah_sbts)?;
+        self.limits = limits;
+        Ok(())
+    }
+
+    /// Sets the list of (issuer, class, weight) used by `humanity_score`. Replaces the
+    /// previous list.
+    /// Panics if the list has more than `MAX_SCORE_WEIGHTS` entries, any issuer is not
+    /// registered, any class is zero or an (issuer, class) pair is duplicated.
+    pub fn admin_set_score_weights(&mut self, weights: Vec<ScoreWeight>) {
+        self.assert_authority();
+        require!(
+            weights.len() <= MAX_SCORE_WEIGHTS,
+            format!("max {} score weights are allowed", MAX_SCORE_WEIGHTS)
+        );
+        let mut seen = HashSet::new();
+        for sw in &weights {
+            require!(sw.class > 0, "class must be > 0");
+            require!(
+                self.sbt_issuers.get(&sw.issuer).is_some(),
+                format!("issuer {} is not registered", sw.issuer)
+            );
+            require!(
+                seen.insert((&sw.issuer, sw.class)),
+                format!(
+                    "duplicated weight for issuer {} class {}",
+                    sw.issuer, sw.class
+                )
+            );
+        }
+        self.score_weights = weights;
+    }
+
+    pub fn change_admin(&mut self, new_admin: AccountId) {
+        self.assert_authority();
+        self.authority = new_admin;
+    }
+
+    /// Sets the grace period (in milliseconds) for the `Verified` accounts fast path in
+    /// `is_human`. Set to zero to disable it.
+    pub fn admin_set_verified_grace(&mut self, grace_ms: u64) {
+        self.assert_authority();
+        self.verified_grace_ms = grace_ms;
+    }
+
+    pub fn admin_set_authorized_flaggers(&mut self, authorized_flaggers: Vec<AccountId>) {
+        self.assert_authority();
+        self.authorized_flaggers.set(&authorized_flaggers);
+    }
+
+    /// Returns true if account was added. Returns false if account was already authorized.
+    pub fn admin_add_authorized_flagger(&mut self, account: AccountId) -> bool {
         self.assert_authority();
         match self.authorized_flaggers.get() {
             None => {
@@ -709,24 +2489,47 @@ impl Contract {
     }
 
     /// Sets a flag for every account in the `accounts` list, overwriting if needed.
+    /// `expires_at`: optional time (unix time in milliseconds) after which the flag is
+    /// ignored (eg a temporary blacklist during an investigation). If not set, the flag
+    /// is permanent. Emits `flag_expires_at` event in addition to the flag event when set.
     /// Panics if a caller is not flagged.
     /// Panics if any of the account is blacklisted.
+    /// Panics if any of the account is a registered issuer.
+    /// Panics if `expires_at` is not in the future.
     pub fn admin_flag_accounts(
         &mut self,
         flag: AccountFlag,
         accounts: Vec<AccountId>,
         #[allow(unused_variables)] memo: String,
+        expires_at: Option<u64>,
     ) {
         self.assert_authorized_flagger();
+        if let Some(t) = expires_at {
+            require!(
+                t > MsTimestamp::now().ms(),
+                "expires_at must be in the future"
+            );
+        }
+        let entry = FlagEntry(flag, expires_at);
         for a in &accounts {
             self.assert_not_banned(a);
-            self.flagged.insert(a, &flag);
+            require!(
+                self.sbt_issuers.get(a).is_none(),
+                format!("account {} is an issuer and can't be flagged", a)
+            );
+            legacy_flagged().remove(a);
+            self.flagged.insert(a, &entry);
+            self.flagged_index.insert(a);
+            self.notify_listeners("on_iah_flag", json!({ "account": a, "flag": entry.0 }));
+        }
+        if let Some(t) = expires_at {
+            events::emit_iah_flag_expires_at(t, &accounts, self.next_event_seq());
         }
-        events::emit_iah_flag_accounts(flag, accounts);
+        events::emit_iah_flag_accounts(entry.0, accounts, self.next_event_seq());
     }
 
     /// removes flag from the provided account list.
-    /// Panics if an account is not currently flagged.
+    /// Panics if an account is not currently flagged (an expired flag can be removed).
     pub fn admin_unflag_accounts(
         &mut self,
         accounts: Vec<AccountId>,
@@ -734,15 +2537,92 @@ impl Contract {
     ) {
         self.assert_authorized_flagger();
         for a in &accounts {
-            require!(self.flagged.remove(a).is_some());
+            let legacy = legacy_flagged().remove(a).is_some();
+            require!(self.flagged.remove(a).is_some() || legacy);
+            self.flagged_index.remove(a);
+        }
+        events::emit_iah_unflag_accounts(accounts, self.next_event_seq());
+    }
+
+    /// Moves flags of accounts flagged before the v1.8.0 migration (stored without the
+    /// expiration) to the current flags map and adds the accounts to the flagged accounts
+    /// index. Accounts which are not flagged are ignored.
+    /// Must be called by the authority.
+    pub fn admin_index_flagged(&mut self, accounts: Vec<AccountId>) {
+        self.assert_authority();
+        let mut legacy = legacy_flagged();
+        for a in &accounts {
+            if let Some(flag) = legacy.remove(a) {
+                self.flagged.insert(a, &FlagEntry(flag, None));
+            }
+            if self.flagged.contains_key(a) {
+                self.flagged_index.insert(a);
+            }
+        }
+    }
+
+    /// Cancels an unfinished soul transfer (or recovery, or merge) of the `account`: clears the
+    /// continuation state, so the account is not blocked by the ongoing transfer any more.
+    /// Tokens (and the flag) already transferred stay with the recipient.
+    /// If `unban` is true, the account is also removed from the banlist (soul transfer bans
+    /// the source account).
+    /// Must be called by the authority. Panics if the account has no ongoing transfer.
+    /// Emits `cancel_soul_transfer` event.
+    pub fn admin_cancel_soul_transfer(&mut self, account: AccountId, unban: bool) {
+        self.assert_authority();
+        let cursor = self.ongoing_soul_tx.remove(&account);
+        require!(cursor.is_some(), "account has no ongoing soul transfer");
+        let cursor = cursor.unwrap();
+        // soul transfer moves the whole owner supply of an issuer once it starts transferring
+        // the issuer tokens, so we give back the supply of the tokens not transferred yet.
+        if let Some((recipient, TransferKind::SoulTransfer | TransferKind::Merge)) =
+            self.ongoing_tx_info.remove(&account)
+        {
+            let remaining = self
+                .balances
+                .iter_from(BalanceKey {
+                    owner: account.clone(),
+                    issuer_id: cursor.issuer_id,
+                    class_id: cursor.token,
+                })
+                .take_while(|(key, _)| key.owner == account && key.issuer_id == cursor.issuer_id)
+                .count() as u64;
+            if remaining > 0 {
+                let key = &(recipient.clone(), cursor.issuer_id);
+                let supply_to = self.supply_by_owner.get(key).unwrap_or(0);
+                self.set_supply_by_owner(&recipient, cursor.issuer_id, supply_to - remaining);
+                self.set_supply_by_owner(&account, cursor.issuer_id, remaining);
+            }
+        }
+        self.soul_transfer_requests.remove(&account);
+        self.merge_requests.remove(&account);
+        if unban {
+            self.banlist.remove(&account);
+            self.ban_timestamps.remove(&account);
         }
-        events::emit_iah_unflag_accounts(accounts);
+        events::emit_cancel_soul_transfer(account, unban, self.next_event_seq());
     }
 
     //
     // Internal
     //
 
+    /// Schedules `method` call with `args` to every listener contract. The promises are not
+    /// awaited, so a failing listener doesn't affect the registry. Stops when there is not
+    /// enough gas left.
+    fn notify_listeners(&self, method: &str, args: serde_json::Value) {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let args = serde_json::to_vec(&args).unwrap();
+        for l in self.listeners.iter() {
+            if env::prepaid_gas() - env::used_gas() < LISTENER_NOTIFY_GAS + LISTENER_RESERVE_GAS {
+                return;
+            }
+            Promise::new(l).function_call(method.to_owned(), args.clone(), 0, LISTENER_NOTIFY_GAS);
+        }
+    }
+
     /// Queries a given token. Panics if token doesn't exist
     pub(crate) fn get_token(&self, issuer_id: IssuerId, token: TokenId) -> TokenData {
         self.issuer_tokens
@@ -775,6 +2655,188 @@ impl Contract {
         );
     }
 
+    /// Implementation of `sbt_tokens_by_owner`. If `with_blocked` is true, then tokens of
+    /// classes blocked for the account are also returned. If `with_hidden` is false and the
+    /// account portfolio is hidden, then only tokens of the IAH class set are returned.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn _sbt_tokens_by_owner(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+        with_blocked: bool,
+        with_hidden: bool,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)> {
+        if from_class.is_some() {
+            require!(
+                issuer.is_some(),
+                "issuer must be defined if from_class is defined"
+            );
+        }
+        // we don't check banlist because we should still enable banned accounts to query their tokens
+        if self.ongoing_soul_tx.contains_key(&account) {
+            return vec![];
+        }
+
+        let issuer_id = match &issuer {
+            None => 0,
+            Some(addr) => self.assert_issuer(addr),
+        };
+        let from_class = from_class.unwrap_or(0);
+        // iter_from starts from exclusive "left end". We need to iteretare from one before.
+        let first_key = balance_key(account.clone(), issuer_id, from_class.saturating_sub(1));
+        let now = MsTimestamp::now();
+        let with_expired = with_expired.unwrap_or(false);
+        let visible_classes = if !with_hidden && self.hidden_portfolios.contains(&account) {
+            Some(self.iah_class_keys())
+        } else {
+            None
+        };
+
+        let mut limit = clamp_limit(limit);
+
+        let mut resp = Vec::new();
+        let mut tokens = Vec::new();
+        let mut prev_issuer = issuer_id;
+
+        for (key, token_id) in self.balances.iter_from(first_key) {
+            if key.owner != account {
+                break;
+            }
+            if prev_issuer != key.issuer_id {
+                if issuer_id != 0 {
+                    break;
+                }
+                if !tokens.is_empty() {
+                    let issuer = self.issuer_by_id(prev_issuer);
+                    resp.push((issuer, tokens));
+                    tokens = Vec::new();
+                }
+                prev_issuer = key.issuer_id;
+            }
+            let token = match self.visible_token(
+                &key,
+                token_id,
+                now,
+                with_expired,
+                with_blocked,
+                &visible_classes,
+            ) {
+                Some(t) => t,
+                None => continue,
+            };
+            tokens.push(token);
+            limit -= 1;
+            if limit == 0 {
+                break;
+            }
+        }
+        if prev_issuer != 0 && !tokens.is_empty() {
+            let issuer = self.issuer_by_id(prev_issuer);
+            resp.push((issuer, tokens));
+        }
+        resp
+    }
+
+    /// Cursor based `_sbt_tokens_by_owner`: returns the `account` tokens of all issuers
+    /// after the `cursor` position (see `sbt_tokens_by_owner_cursor`).
+    pub(crate) fn _sbt_tokens_by_owner_cursor(
+        &self,
+        account: AccountId,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> CursorPage<Vec<(AccountId, Vec<OwnedToken>)>> {
+        let limit = clamp_limit(limit);
+        let (issuer_id, class_id) = match &cursor {
+            None => (0, 0),
+            Some(c) => decode_cursor(c).expect("invalid cursor"),
+        };
+        let mut page = CursorPage {
+            data: Vec::new(),
+            next_cursor: None,
+        };
+        if self.ongoing_soul_tx.contains_key(&account) {
+            return page;
+        }
+        let now = MsTimestamp::now();
+        let with_expired = with_expired.unwrap_or(false);
+        let visible_classes = if self.hidden_portfolios.contains(&account) {
+            Some(self.iah_class_keys())
+        } else {
+            None
+        };
+
+        let mut num_tokens = 0;
+        let mut last_issuer: IssuerId = 0;
+        let mut last_key = None;
+        // iter_from starts from exclusive "left end": the cursor is the last visited key.
+        for (key, token_id) in
+            self.balances
+                .iter_from(balance_key(account.clone(), issuer_id, class_id))
+        {
+            if key.owner != account {
+                break;
+            }
+            if num_tokens == limit {
+                // there are more tokens to visit
+                page.next_cursor = last_key;
+                break;
+            }
+            last_key = Some(encode_cursor(key.issuer_id, key.class_id));
+            let token = match self.visible_token(
+                &key,
+                token_id,
+                now,
+                with_expired,
+                false,
+                &visible_classes,
+            ) {
+                Some(t) => t,
+                None => continue,
+            };
+            if last_issuer != key.issuer_id {
+                last_issuer = key.issuer_id;
+                page.data
+                    .push((self.issuer_by_id(key.issuer_id), Vec::new()));
+            }
+            page.data.last_mut().unwrap().1.push(token);
+            num_tokens += 1;
+        }
+        page
+    }
+
+    /// Returns the token stored under the balance `key`, if it passes the
+    /// `sbt_tokens_by_owner` filters.
+    fn visible_token(
+        &self,
+        key: &BalanceKey,
+        token_id: TokenId,
+        now: MsTimestamp,
+        with_expired: bool,
+        with_blocked: bool,
+        visible_classes: &Option<Vec<(IssuerId, ClassId)>>,
+    ) -> Option<OwnedToken> {
+        if let Some(vc) = visible_classes {
+            if !vc.contains(&(key.issuer_id, key.class_id)) {
+                return None;
+            }
+        }
+        let t: TokenData = self.get_token(key.issuer_id, token_id);
+        if !with_expired && now.is_expired(t.metadata.expires_at()) {
+            return None;
+        }
+        if !with_blocked && self.blocked_classes.contains(key) {
+            return None;
+        }
+        Some(OwnedToken {
+            token: token_id,
+            metadata: t.metadata.v1(),
+        })
+    }
+
     /// note: use issuer_id() if you need issuer_id
     pub(crate) fn assert_issuer(&self, issuer: &AccountId) -> IssuerId {
         // TODO: use Result rather than panic
@@ -783,12 +2845,117 @@ impl Contract {
             .expect("must be called by a registered SBT Issuer")
     }
 
+    /// Returns the ID of the `issuer`, also when the issuer was removed with
+    /// `admin_remove_sbt_issuer` and its tokens are not purged yet.
+    pub(crate) fn issuer_id_with_removed(&self, issuer: &AccountId) -> Option<IssuerId> {
+        self.sbt_issuers
+            .get(issuer)
+            .or_else(|| self.removed_issuers.get(issuer).map(|ri| ri.issuer_id))
+    }
+
+    /// Same as `assert_issuer`, but additionally panics if the issuer is paused.
+    pub(crate) fn assert_active_issuer(&self, issuer: &AccountId) -> IssuerId {
+        let issuer_id = self.assert_issuer(issuer);
+        require!(!self.paused_issuers.contains(issuer), "issuer is paused");
+        issuer_id
+    }
+
+    /// Burns a token of the `owner` replaced by another token of the same (issuer, class)
+    /// pair: a soul transferred token (conflict) or an expired token replaced by a new mint.
+    /// Only the storage and supply records of the token are updated, the balance entry and
+    /// the owner supply are handled by the caller.
+    fn burn_replaced_token(&mut self, key: &IssuerTokenId, owner: &AccountId, class: ClassId) {
+        let issuer_id = key.issuer_id;
+        self.issuer_tokens.remove(key);
+        if self.provenance_issuers.contains_key(&issuer_id) {
+            self.token_provenance.remove(key);
+        }
+        if self.tombstone_policies.contains_key(&issuer_id) {
+            self.add_tombstone(key, owner, class);
+        }
+        let c_key = (issuer_id, class);
+        let supply = self.supply_by_class.get(&c_key).unwrap();
+        self.supply_by_class.insert(&c_key, &(supply - 1));
+        let supply = self.supply_by_issuer.get(&issuer_id).unwrap();
+        self.supply_by_issuer.insert(&issuer_id, &(supply - 1));
+        self.add_issuer_stats(
+            issuer_id,
+            IssuerStats {
+                total_burned: 1,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Decrements the `recipient` supply by the tokens `burned` in soul transfer conflicts
+    /// and emits the `burn` events (one per issuer).
+    fn emit_conflict_burns(&mut self, recipient: &AccountId, burned: Vec<IssuerTokenId>) {
+        let mut per_issuer: Vec<(IssuerId, Vec<TokenId>)> = Vec::new();
+        for k in burned {
+            match per_issuer.last_mut() {
+                Some((issuer_id, tokens)) if *issuer_id == k.issuer_id => tokens.push(k.token),
+                _ => per_issuer.push((k.issuer_id, vec![k.token])),
+            }
+        }
+        for (issuer_id, tokens) in per_issuer {
+            let supply = self
+                .supply_by_owner
+                .get(&(recipient.clone(), issuer_id))
+                .unwrap_or(0);
+            self.set_supply_by_owner(
+                recipient,
+                issuer_id,
+                supply
+                    .checked_sub(tokens.len() as u64)
+                    .expect("supply_by_owner underflow"),
+            );
+            self.emit_nep393(Nep393Event::Burn(SbtTokensEvent {
+                issuer: self.issuer_by_id(issuer_id),
+                tokens,
+            }));
+        }
+    }
+
+    /// Records the tombstone of a burned token.
+    pub(crate) fn add_tombstone(&mut self, key: &IssuerTokenId, owner: &AccountId, class: ClassId) {
+        self.tombstones.insert(
+            key,
+            &TokenTombstone {
+                owner_hash: Base64VecU8(env::sha256(owner.as_bytes())),
+                class,
+                burned_at: MsTimestamp::now().ms(),
+            },
+        );
+    }
+
     pub(crate) fn issuer_by_id(&self, id: IssuerId) -> AccountId {
         self.issuer_id_map
             .get(&id)
             .expect("internal error: inconsistent sbt issuer map")
     }
 
+    fn assert_not_gov_banned(
+        &self,
+        caller: &AccountId,
+        ctr: &AccountId,
+    ) -> Result<(), IsHumanCallErr> {
+        if self._account_flag(caller) == Some(AccountFlag::GovBan)
+            && self.gov_contracts.contains(ctr)
+        {
+            return Err(IsHumanCallErr::GovBanned);
+        }
+        Ok(())
+    }
+
+    /// Returns `OngoingTransfer` error if the `account` is the source of an unfinished soul
+    /// transfer or recovery.
+    pub(crate) fn check_no_ongoing_transfer(&self, account: &AccountId) -> Result<(), MintErr> {
+        if self.ongoing_soul_tx.contains_key(account) {
+            return Err(MintErr::OngoingTransfer(account.clone()));
+        }
+        Ok(())
+    }
+
     pub(crate) fn assert_authority(&self) {
         require!(
             self.authority == env::predecessor_account_id(),
@@ -807,48 +2974,93 @@ impl Contract {
     }
 
     fn _sbt_renew(&mut self, issuer: AccountId, tokens: Vec<TokenId>, expires_at: u64) {
-        let issuer_id = self.assert_issuer(&issuer);
-        for token in &tokens {
-            let token = *token;
-            let mut t = self.get_token(issuer_id, token);
-            self.assert_not_banned(&t.owner);
+        let tokens = tokens.into_iter().map(|t| (t, expires_at)).collect();
+        self._sbt_renew_many(issuer, tokens);
+    }
+
+    /// Renews each token with its own `expires_at`.
+    fn _sbt_renew_many(&mut self, issuer: AccountId, tokens: Vec<(TokenId, u64)>) {
+        let issuer_id = self.assert_active_issuer(&issuer);
+        // validate the whole batch before updating any token, so the renew is all or nothing.
+        let batch: Vec<TokenData> = tokens
+            .iter()
+            .map(|(token, _)| {
+                let t = self.get_token(issuer_id, *token);
+                self.assert_not_banned(&t.owner);
+                t
+            })
+            .collect();
+        for ((token, expires_at), mut t) in tokens.iter().copied().zip(batch) {
             let mut m = t.metadata.v1();
             m.expires_at = Some(expires_at);
             t.metadata = m.into();
             self.issuer_tokens
                 .insert(&IssuerTokenId { issuer_id, token }, &t);
         }
-        SbtTokensEvent { issuer, tokens }.emit_renew();
+        self.add_issuer_stats(
+            issuer_id,
+            IssuerStats {
+                total_renewed: tokens.len() as u64,
+                ..Default::default()
+            },
+        );
+        let tokens = tokens.into_iter().map(|(t, _)| t).collect();
+        self.emit_nep393(Nep393Event::Renew(SbtTokensEvent { issuer, tokens }));
     }
 
     fn _sbt_mint(
         &mut self,
         issuer: &AccountId,
         token_spec: Vec<(AccountId, Vec<TokenMetadata>)>,
-    ) -> Vec<TokenId> {
+        method: MintMethod,
+        replace_expired: bool,
+    ) -> Vec<(AccountId, Vec<(ClassId, TokenId)>)> {
         let storage_start = env::storage_usage();
         let storage_deposit = env::attached_deposit();
 
-        let issuer_id = self.assert_issuer(issuer);
+        let issuer_id = self.assert_active_issuer(issuer);
         let mut num_tokens = 0;
         for el in token_spec.iter() {
             num_tokens += el.1.len() as u64;
         }
+        if let Err(err) = self.limits.check_mint_batch(num_tokens) {
+            err.panic();
+        }
         let mut token = self.next_token_id(issuer_id, num_tokens);
-        let ret_token_ids = (token..token + num_tokens).collect();
+        // only tokens minted before this call can be replaced: a duplicated class in the
+        // batch must not burn a token minted by the same batch.
+        let first_token = token;
+        let provenance = self
+            .provenance_issuers
+            .get(&issuer_id)
+            .unwrap_or(false)
+            .then(|| TokenProvenance {
+                method,
+                batch: token,
+            });
+        let mut ret_tokens = Vec::with_capacity(token_spec.len());
         let mut supply_by_class = HashMap::new();
         let mut per_recipient: HashMap<AccountId, Vec<TokenId>> = HashMap::new();
-        let now = env::block_timestamp_ms();
+        let now = MsTimestamp::now().ms();
+        let mut replaced = Vec::new();
 
         for (owner, metadatas) in token_spec {
-            // no need to check ongoing_soult_tx, because it will automatically ban the source account
+            // soul transfer bans the source account, but recovery doesn't, so we need to
+            // check the continuation explicitly.
+            if let Err(err) = self.check_no_ongoing_transfer(&owner) {
+                err.panic();
+            }
             self.assert_not_banned(&owner);
+            let human_before = self.human_state(&owner);
 
             let recipient_tokens = per_recipient.entry(owner.clone()).or_default();
             let metadatas_len = metadatas.len();
+            let mut spec_tokens = Vec::with_capacity(metadatas_len);
+            let replaced_before = replaced.len();
 
             for mut metadata in metadatas {
-                require!(metadata.class > 0, "Class must be > 0");
+                let class = metadata.class;
+                require!(class > 0, "Class must be > 0");
                 if metadata.issued_at.is_none() {
                     metadata.issued_at = Some(now);
                 }
@@ -856,10 +3068,26 @@ impl Contract {
                     &balance_key(owner.clone(), issuer_id, metadata.class),
                     &token,
                 );
-                require!(
-                    prev.is_none(),
-                    format! {"{} already has SBT of class {}", owner, metadata.class}
-                );
+                if let Some(prev) = prev {
+                    let prev_key = IssuerTokenId {
+                        issuer_id,
+                        token: prev,
+                    };
+                    require!(
+                        replace_expired
+                            && prev < first_token
+                            && MsTimestamp(now).is_expired(
+                                self.issuer_tokens
+                                    .get(&prev_key)
+                                    .unwrap()
+                                    .metadata
+                                    .expires_at()
+                            ),
+                        format! {"{} already has SBT of class {}", owner, metadata.class}
+                    );
+                    self.burn_replaced_token(&prev_key, &owner, class);
+                    replaced.push(prev);
+                }
 
                 // update supply by class
                 match supply_by_class.get_mut(&metadata.class) {
@@ -876,33 +3104,52 @@ impl Contract {
                         metadata: metadata.into(),
                     },
                 );
+                if let Some(p) = &provenance {
+                    self.token_provenance
+                        .insert(&IssuerTokenId { issuer_id, token }, p);
+                }
                 recipient_tokens.push(token);
+                spec_tokens.push((class, token));
 
                 token += 1;
             }
+            ret_tokens.push((owner.clone(), spec_tokens));
 
             // update supply by owner
-            let skey = (owner, issuer_id);
-            let sowner = self.supply_by_owner.get(&skey).unwrap_or(0) + metadatas_len as u64;
-            self.supply_by_owner.insert(&skey, &sowner);
+            let skey = (owner.clone(), issuer_id);
+            let sowner = self.supply_by_owner.get(&skey).unwrap_or(0) + metadatas_len as u64
+                - (replaced.len() - replaced_before) as u64;
+            self.set_supply_by_owner(&owner, issuer_id, sowner);
+            self.update_human_counters(&owner, human_before);
         }
 
+        let mut classes = self.issuer_classes.get(&issuer_id).unwrap_or_default();
+        let num_classes = classes.len();
         for (cls, new_supply) in supply_by_class {
             let key = (issuer_id, cls);
             let s = self.supply_by_class.get(&key).unwrap_or(0) + new_supply;
             self.supply_by_class.insert(&key, &s);
+            if !classes.contains(&cls) {
+                classes.push(cls);
+            }
+        }
+        if classes.len() != num_classes {
+            classes.sort();
+            self.issuer_classes.insert(&issuer_id, &classes);
         }
 
         let new_supply = self.supply_by_issuer.get(&issuer_id).unwrap_or(0) + num_tokens;
         self.supply_by_issuer.insert(&issuer_id, &new_supply);
 
+        if !replaced.is_empty() {
+            self.emit_burn_revoke(issuer.clone(), replaced);
+        }
         let mut minted: Vec<(&AccountId, &Vec<TokenId>)> = per_recipient.iter().collect();
         minted.sort_by(|a, b| a.0.cmp(b.0));
-        SbtMint {
+        self.emit_nep393(Nep393Event::Mint(SbtMint {
             issuer,
             tokens: minted,
-        }
-        .emit();
+        }));
 
         let required_deposit =
             (env::storage_usage() - storage_start) as u128 * env::storage_byte_cost();
@@ -914,32 +3161,54 @@ impl Contract {
             )
         );
 
-        ret_token_ids
+        ret_tokens
     }
 
     /// Method to help parametrize the sbt_burn_all.
     /// limit indicates the number of tokens that will be burned in one call
-    pub(crate) fn _sbt_burn_all(&mut self, limit: u32) -> bool {
+    pub(crate) fn _sbt_burn_all(&mut self, limit: u32) -> (u32, u32, bool) {
         let owner = env::predecessor_account_id();
         require!(
             !self.ongoing_soul_tx.contains_key(&owner),
             "can't burn tokens while in soul_transfer"
         );
         let mut tokens_burned: u32 = 0;
-
-        let issuer_token_pair_vec =
-            self.sbt_tokens_by_owner(owner.clone(), None, None, Some(limit), Some(true));
+        let owner_supply = self._total_supply_by_owner(&owner);
+
+        let issuer_token_pair_vec = self._sbt_tokens_by_owner(
+            owner.clone(),
+            None,
+            None,
+            Some(limit),
+            Some(true),
+            true,
+            true,
+        );
+        let human_before = self.human_state(&owner);
+        let mut completed = true;
         for (issuer, tokens) in issuer_token_pair_vec.iter() {
             let mut token_ids = Vec::new();
-            let issuer_id = self.assert_issuer(issuer);
+            // owners can burn tokens of removed issuers which are not purged yet
+            let issuer_id = self
+                .issuer_id_with_removed(issuer)
+                .expect("issuer not found");
+            let has_provenance = self.provenance_issuers.contains_key(&issuer_id);
+            let has_tombstones = self.tombstone_policies.contains_key(&issuer_id);
             let mut tokens_burned_per_issuer: u64 = 0;
             for t in tokens.iter() {
                 token_ids.push(t.token);
-                self.issuer_tokens.remove(&IssuerTokenId {
+                let key = IssuerTokenId {
                     issuer_id,
                     token: t.token,
-                });
+                };
+                self.issuer_tokens.remove(&key);
+                if has_provenance {
+                    self.token_provenance.remove(&key);
+                }
                 let class_id = t.metadata.class;
+                if has_tombstones {
+                    self.add_tombstone(&key, &owner, class_id);
+                }
                 self.balances
                     .remove(&balance_key(owner.clone(), issuer_id, class_id));
 
@@ -956,39 +3225,489 @@ impl Contract {
             }
 
             // update supply by owner
-            let key = (owner.clone(), issuer_id);
-            let mut supply = self.supply_by_owner.get(&key).unwrap();
-            supply -= tokens_burned_per_issuer;
-            self.supply_by_owner.insert(&key, &supply);
+            let supply = self
+                .supply_by_owner
+                .get(&(owner.clone(), issuer_id))
+                .unwrap();
+            self.set_supply_by_owner(&owner, issuer_id, supply - tokens_burned_per_issuer);
 
             // update total supply by issuer
             let mut supply = self.supply_by_issuer.get(&issuer_id).unwrap();
             supply -= tokens_burned_per_issuer;
             self.supply_by_issuer.insert(&issuer_id, &supply);
+            self.add_issuer_stats(
+                issuer_id,
+                IssuerStats {
+                    total_burned: tokens_burned_per_issuer,
+                    ..Default::default()
+                },
+            );
 
-            SbtTokensEvent {
+            self.emit_nep393(Nep393Event::Burn(SbtTokensEvent {
                 issuer: issuer.to_owned(),
                 tokens: token_ids.clone(),
-            }
-            .emit_burn();
+            }));
             if tokens_burned >= limit {
-                return false;
+                completed = false;
+                break;
             }
         }
-        true
+        self.update_human_counters(&owner, human_before);
+        let remaining = owner_supply.saturating_sub(tokens_burned as u64);
+        (
+            tokens_burned,
+            remaining.try_into().unwrap_or(u32::MAX),
+            completed,
+        )
+    }
+
+    /// Returns the next event sequence number. Must be used for every event emitted by the
+    /// registry (nep393 and `i_am_human` events).
+    pub(crate) fn next_event_seq(&mut self) -> u64 {
+        self.event_seq += 1;
+        self.event_seq
+    }
+
+    /// Emits a nep393 `event` with the next event sequence number.
+    pub(crate) fn emit_nep393(&mut self, event: Nep393Event) {
+        let seq = self.next_event_seq();
+        event.emit_seq(seq);
+    }
+
+    /// Emits nep393 `burn` followed by `revoke` event. Must be used for all tokens burned
+    /// by the issuer or the authority. Tokens burned by the owner only emit `burn`.
+    pub(crate) fn emit_burn_revoke(&mut self, issuer: AccountId, tokens: Vec<TokenId>) {
+        self.emit_nep393(Nep393Event::Burn(SbtTokensEvent {
+            issuer: issuer.clone(),
+            tokens: tokens.clone(),
+        }));
+        self.emit_nep393(Nep393Event::Revoke(SbtTokensEvent { issuer, tokens }));
+    }
+
+    /// Returns list of (issuer ID, class) pairs of the IAH class set.
+    pub(crate) fn iah_class_keys(&self) -> Vec<(IssuerId, ClassId)> {
+        self.iah_sbts
+            .iter()
+            .filter_map(|(issuer, classes)| Some((self.sbt_issuers.get(issuer)?, classes)))
+            .flat_map(|(id, classes)| classes.iter().map(move |c| (id, *c)))
+            .collect()
+    }
+
+    /// Returns the `HumanState` of the `account`. Must be called before changing the account
+    /// balances, and passed to `update_human_counters` after the change.
+    pub(crate) fn human_state(&self, account: &AccountId) -> HumanState {
+        let has = |issuer_id: IssuerId, class: ClassId| {
+            self.balances
+                .contains_key(&balance_key(account.clone(), issuer_id, class))
+        };
+        let mut holds = self.iah_sbts.iter().map(|(issuer, classes)| {
+            self.sbt_issuers
+                .get(issuer)
+                .map_or(false, |id| classes.iter().all(|c| has(id, *c)))
+        });
+        let human = match self.iah_mode {
+            ClassSetMode::All => holds.all(|h| h),
+            ClassSetMode::Any => holds.any(|h| h),
+        };
+        let classes = if human {
+            self.human_classes
+                .iter()
+                .map(|(i, c)| has(*i, *c))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        HumanState { human, classes }
+    }
+
+    /// Updates the human counters based on the account state `before` and the current
+    /// account state. Accounts not indexed yet by `admin_backfill_counters` are skipped.
+    pub(crate) fn update_human_counters(&mut self, account: &AccountId, before: HumanState) {
+        if !self.counters_indexed(account, false) {
+            return;
+        }
+        let after = self.human_state(account);
+        if before.human != after.human {
+            self.humans_count = if after.human {
+                self.humans_count + 1
+            } else {
+                self.humans_count
+                    .checked_sub(1)
+                    .expect("humans_count underflow")
+            };
+        }
+        for i in 0..self.human_classes.len() {
+            let b = before.classes.get(i).copied().unwrap_or(false);
+            let a = after.classes.get(i).copied().unwrap_or(false);
+            if a != b {
+                let key = self.human_classes[i];
+                let count = self.humans_by_class.get(&key).unwrap_or(0);
+                let count = if a {
+                    count + 1
+                } else {
+                    count.checked_sub(1).expect("humans_by_class underflow")
+                };
+                self.humans_by_class.insert(&key, &count);
+            }
+        }
+    }
+
+    /// Sets the `owner` supply of the issuer tokens (removing the entry when zero) and
+    /// updates the owner total supply and the issuer holders counter.
+    pub(crate) fn set_supply_by_owner(
+        &mut self,
+        owner: &AccountId,
+        issuer_id: IssuerId,
+        supply: u64,
+    ) {
+        // must be read before the update: the total may be computed from `supply_by_owner`.
+        let total = self._total_supply_by_owner(owner);
+        let key = (owner.clone(), issuer_id);
+        let prev = if supply == 0 {
+            self.supply_by_owner.remove(&key)
+        } else {
+            self.supply_by_owner.insert(&key, &supply)
+        }
+        .unwrap_or(0);
+        let total = (total + supply)
+            .checked_sub(prev)
+            .expect("total_supply_by_owner underflow");
+        if total == 0 {
+            self.total_supply_by_owner.remove(owner);
+        } else {
+            self.total_supply_by_owner.insert(owner, &total);
+        }
+        // holders not indexed yet are counted by `admin_backfill_counters`.
+        if (prev == 0) != (supply == 0) && self.counters_indexed(owner, true) {
+            let holders = self.holders_by_issuer.get(&issuer_id).unwrap_or(0);
+            let holders = if supply == 0 {
+                holders.checked_sub(1).expect("holders_by_issuer underflow")
+            } else {
+                holders + 1
+            };
+            self.holders_by_issuer.insert(&issuer_id, &holders);
+        }
+    }
+
+    /// Returns false if the `owner` counters (the supply stats if `supply`, otherwise the
+    /// human counters) are not indexed yet by the ongoing `admin_backfill_counters`.
+    pub(crate) fn counters_indexed(&self, owner: &AccountId, supply: bool) -> bool {
+        match &self.counters_backfill {
+            None => true,
+            Some(b) if supply && !b.supply => true,
+            Some(b) => b.last_owner.as_ref().map_or(false, |last| owner <= last),
+        }
+    }
+
+    /// Panics if the counters (the supply stats if `supply`, otherwise the human counters)
+    /// are not indexed yet.
+    fn assert_counters_indexed(&self, supply: bool) {
+        require!(
+            self.counters_backfill
+                .as_ref()
+                .map_or(true, |b| supply && !b.supply),
+            "counters are not indexed yet, see admin_backfill_counters"
+        );
+    }
+
+    /// Resets the human counters, which must be then indexed with `admin_backfill_counters`.
+    /// An ongoing backfill of the human counters is restarted.
+    fn reset_human_counters(&mut self) {
+        require!(
+            self.counters_backfill.as_ref().map_or(true, |b| !b.supply),
+            "supply stats backfill is in progress"
+        );
+        self.humans_count = 0;
+        for key in &self.human_classes {
+            self.humans_by_class.remove(key);
+        }
+        self.counters_backfill = Some(CountersBackfill {
+            supply: false,
+            last_owner: None,
+        });
+    }
+
+    /// Adds the `owner` to the counters: `owner_classes` is the list of the owner
+    /// (issuer, class) balances, sorted.
+    fn backfill_account(
+        &mut self,
+        owner: &AccountId,
+        owner_classes: &[(IssuerId, ClassId)],
+        supply: bool,
+    ) {
+        if supply {
+            let mut prev_issuer = None;
+            for &(issuer_id, class) in owner_classes {
+                if prev_issuer != Some(issuer_id) {
+                    prev_issuer = Some(issuer_id);
+                    let holders = self.holders_by_issuer.get(&issuer_id).unwrap_or(0);
+                    self.holders_by_issuer.insert(&issuer_id, &(holders + 1));
+                }
+                let mut classes = self.issuer_classes.get(&issuer_id).unwrap_or_default();
+                if !classes.contains(&class) {
+                    classes.push(class);
+                    classes.sort();
+                    self.issuer_classes.insert(&issuer_id, &classes);
+                }
+            }
+        }
+        let state = self.human_state(owner);
+        if state.human {
+            self.humans_count += 1;
+            for (i, has) in state.classes.iter().enumerate() {
+                if *has {
+                    let key = self.human_classes[i];
+                    let count = self.humans_by_class.get(&key).unwrap_or(0);
+                    self.humans_by_class.insert(&key, &(count + 1));
+                }
+            }
+        }
+    }
+
+    /// Returns the total number of `owner` tokens across all issuers. Computes the total
+    /// from the supply of all issuer IDs (including removed, not yet purged issuers) if the
+    /// owner doesn't have the total counter yet.
+    pub(crate) fn _total_supply_by_owner(&self, owner: &AccountId) -> u64 {
+        self.total_supply_by_owner.get(owner).unwrap_or_else(|| {
+            (1..self.next_issuer_id)
+                .filter_map(|issuer_id| self.supply_by_owner.get(&(owner.clone(), issuer_id)))
+                .sum()
+        })
+    }
+
+    /// Increments the issuer stats counters by the `delta` values.
+    pub(crate) fn add_issuer_stats(&mut self, issuer_id: IssuerId, delta: IssuerStats) {
+        let mut stats = self.issuer_stats.get(&issuer_id).unwrap_or_default();
+        stats.total_burned += delta.total_burned;
+        stats.total_revoked += delta.total_revoked;
+        stats.total_renewed += delta.total_renewed;
+        self.issuer_stats.insert(&issuer_id, &stats);
+    }
+
+    /// Implementation of `sbt_revoke_by_owner` and `sbt_revoke_by_owner_classes`.
+    /// If `classes` is set, only tokens of the listed classes are revoked.
+    pub(crate) fn _sbt_revoke_by_owner(
+        &mut self,
+        owner: AccountId,
+        burn: bool,
+        classes: Option<Vec<ClassId>>,
+    ) -> bool {
+        let issuer = env::predecessor_account_id();
+        let issuer_id = self.assert_active_issuer(&issuer);
+        let has_provenance = self.provenance_issuers.contains_key(&issuer_id);
+        let has_tombstones = self.tombstone_policies.contains_key(&issuer_id);
+        if let Some(classes) = &classes {
+            require!(
+                !classes.is_empty() && classes.len() <= MAX_REVOKE_PER_CALL as usize,
+                format!(
+                    "classes must be a non empty list of at most {} elements",
+                    MAX_REVOKE_PER_CALL
+                )
+            );
+        }
+
+        if burn {
+            let tokens_by_owner = self.sbt_token_ids_by_owner(
+                owner.clone(),
+                issuer_id,
+                classes.as_deref(),
+                MAX_REVOKE_PER_CALL,
+            );
+
+            if tokens_by_owner.is_empty() {
+                return true;
+            }
+            let mut burned_per_class: HashMap<u64, u64> = HashMap::new();
+            let human_before = self.human_state(&owner);
+
+            // Batch updates for balances and issuer_tokens
+            for (token_id, class_id) in &tokens_by_owner {
+                let balance_key = BalanceKey {
+                    issuer_id,
+                    owner: owner.clone(),
+                    class_id: *class_id,
+                };
+
+                self.balances.remove(&balance_key);
+
+                // Collect info about tokens revoked per class to update the balance accordingly
+                burned_per_class
+                    .entry(*class_id)
+                    .and_modify(|key_value| *key_value += 1)
+                    .or_insert(1);
+
+                let key = IssuerTokenId {
+                    issuer_id,
+                    token: *token_id,
+                };
+                self.issuer_tokens.remove(&key);
+                if has_provenance {
+                    self.token_provenance.remove(&key);
+                }
+                if has_tombstones {
+                    self.add_tombstone(&key, &owner, *class_id);
+                }
+            }
+
+            // Batch updates for supply values
+            let supply_update = tokens_by_owner.len() as u64;
+
+            // Update supply_by_owner
+            let owner_key = &(owner.clone(), issuer_id);
+            let supply_owner = self.supply_by_owner.get(owner_key).unwrap_or(0);
+            self.set_supply_by_owner(&owner, issuer_id, supply_owner - supply_update);
+            self.update_human_counters(&owner, human_before);
+
+            // Update supply_by_issuer
+            let supply_issuer = self.supply_by_issuer.get(&issuer_id).unwrap_or(0);
+            let new_supply_issuer = supply_issuer - supply_update;
+            self.supply_by_issuer.insert(&issuer_id, &new_supply_issuer);
+            self.add_issuer_stats(
+                issuer_id,
+                IssuerStats {
+                    total_burned: supply_update,
+                    total_revoked: supply_update,
+                    ..Default::default()
+                },
+            );
+
+            // Update supply_by_class
+            for (class_id, tokens_revoked) in burned_per_class {
+                let class_key = &(issuer_id, class_id);
+                let supply_class = self.supply_by_class.get(class_key).unwrap_or(0);
+                let new_supply_class = supply_class - tokens_revoked;
+                self.supply_by_class.insert(class_key, &new_supply_class);
+            }
+
+            let token_ids_burned: Vec<TokenId> = tokens_by_owner
+                .iter()
+                .map(|(token_id, _)| *token_id)
+                .collect();
+
+            self.emit_burn_revoke(issuer.clone(), token_ids_burned);
+
+            // Check if all tokens were burned. Tokens of the listed classes are all burned
+            // in a single call.
+            return classes.is_some() || self.sbt_supply_by_owner(owner.clone(), issuer, None) == 0;
+        }
+
+        if let Some(classes) = classes {
+            let now = MsTimestamp::now();
+            let mut token_ids: Vec<TokenId> = Vec::new();
+            for (token, _) in self.sbt_token_ids_by_owner(
+                owner.clone(),
+                issuer_id,
+                Some(&classes),
+                MAX_REVOKE_PER_CALL,
+            ) {
+                let key = IssuerTokenId { issuer_id, token };
+                let mut t = self.get_token(issuer_id, token);
+                if now.is_expired(t.metadata.expires_at()) {
+                    continue;
+                }
+                let mut m = t.metadata.v1();
+                m.expires_at = Some(now.ms());
+                t.metadata = m.into();
+                self.issuer_tokens.insert(&key, &t);
+                token_ids.push(token);
+            }
+            if !token_ids.is_empty() {
+                self.add_issuer_stats(
+                    issuer_id,
+                    IssuerStats {
+                        total_revoked: token_ids.len() as u64,
+                        ..Default::default()
+                    },
+                );
+                self.emit_nep393(Nep393Event::Revoke(SbtTokensEvent {
+                    issuer,
+                    tokens: token_ids,
+                }));
+            }
+            return true;
+        }
+
+        let (_, non_expired_tokens) = self
+            ._sbt_tokens_by_owner(
+                owner.clone(),
+                Some(issuer.clone()),
+                None,
+                Some(MAX_REVOKE_PER_CALL),
+                Some(false),
+                true,
+                true,
+            )
+            .pop()
+            .unwrap();
+
+        if non_expired_tokens.is_empty() {
+            return true;
+        }
+
+        let is_finished = non_expired_tokens.len() < MAX_REVOKE_PER_CALL as usize;
+
+        let mut token_ids: Vec<TokenId> = Vec::new();
+
+        // Revoke: Update expire date for all tokens to current_timestamp
+        let now = MsTimestamp::now().ms();
+        for mut t in non_expired_tokens {
+            token_ids.push(t.token);
+            t.metadata.expires_at = Some(now);
+            let token_data = TokenData {
+                owner: owner.clone(),
+                metadata: t.metadata.into(),
+            };
+            self.issuer_tokens.insert(
+                &IssuerTokenId {
+                    issuer_id,
+                    token: t.token,
+                },
+                &token_data,
+            );
+        }
+
+        self.add_issuer_stats(
+            issuer_id,
+            IssuerStats {
+                total_revoked: token_ids.len() as u64,
+                ..Default::default()
+            },
+        );
+        self.emit_nep393(Nep393Event::Revoke(SbtTokensEvent {
+            issuer,
+            tokens: token_ids,
+        }));
+
+        // Check if all tokens were revoked
+        is_finished
     }
 
-    /// Helper function for `sbt_revoke_by_owner`
+    /// Helper function for `sbt_revoke_by_owner`.
+    /// If `classes` is set, returns only tokens of the listed classes.
     fn sbt_token_ids_by_owner(
         &self,
         account: AccountId,
         issuer_id: u32,
+        classes: Option<&[ClassId]>,
         limit: u32,
     ) -> Vec<(TokenId, ClassId)> {
-        let first_key = balance_key(account.clone(), issuer_id, 0);
-
         assert!(limit > 0, "limit must be bigger than 0");
 
+        if let Some(classes) = classes {
+            // one account can have at most one token of a given class, so we can query the
+            // balances directly.
+            return classes
+                .iter()
+                .filter_map(|c| {
+                    self.balances
+                        .get(&balance_key(account.clone(), issuer_id, *c))
+                        .map(|token_id| (token_id, *c))
+                })
+                .take(limit as usize)
+                .collect();
+        }
+
+        let first_key = balance_key(account.clone(), issuer_id, 0);
         self.balances
             .iter_from(first_key)
             .take_while(|(key, _)| key.owner == account && key.issuer_id == issuer_id)
@@ -1005,7 +3724,7 @@ mod tests {
     use cost::MILI_NEAR;
     use near_sdk::json_types::Base64VecU8;
     use near_sdk::test_utils::{self, VMContextBuilder};
-    use near_sdk::{testing_env, Balance, Gas, VMContext};
+    use near_sdk::{mock::VmAction, testing_env, Balance, Gas, VMContext};
     use sbt::*;
 
     use pretty_assertions::assert_eq;
@@ -1137,7 +3856,9 @@ mod tests {
     #[test]
     fn iah_class_set() {
         let (_, ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
-        assert_eq!(ctr.iah_class_set(), vec![ctr.iah_sbts]);
+        assert_eq!(ctr.iah_class_set(), ctr.iah_sbts);
+        assert_eq!(ctr.iah_class_set(), vec![(fractal_mainnet(), vec![1])]);
+        assert_eq!(ctr.iah_class_set_mode(), ClassSetMode::All);
     }
 
     #[test]
@@ -1242,7 +3963,8 @@ mod tests {
                     r#"{{"issuer":"{}","tokens":[["{}",[1]]]}}"#,
                     issuer1(),
                     alice2()
-                )
+                ),
+                1
             )
         );
 
@@ -1267,7 +3989,8 @@ mod tests {
                     alice2(),
                     alice(),
                     bob()
-                )
+                ),
+                2
             )
         );
 
@@ -1417,7 +4140,7 @@ mod tests {
         ctr.sbt_burn(issuer2(), vec![1, 5], Some("alice burning".to_owned()));
         assert_eq!(
             test_utils::get_logs(),
-            mk_log_str("burn", r#"{"issuer":"sbt.ne","tokens":[1,5]}"#)
+            mk_log_str("burn", r#"{"issuer":"sbt.ne","tokens":[1,5]}"#, 5)
         );
 
         supply_by_issuer[1] -= 2;
@@ -1467,79 +4190,463 @@ mod tests {
     }
 
     #[test]
-    fn soul_transfer1() {
-        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
-
-        // test1: simple case: alice has one token and she owns alice2 account as well. She
-        // will do transfer from alice -> alice2
-        let m1_1 = mk_metadata(1, Some(START + 10));
-        let m2_1 = mk_metadata(2, Some(START + 10));
-        ctr.sbt_mint(vec![(alice(), vec![m1_1.clone(), m2_1.clone()])]);
-
+    fn sbt_tokens_by_owner_cursor() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 20 * MINT_DEPOSIT);
+        let batch_metadata = mk_batch_metadata(10);
+        ctr.sbt_mint(vec![(alice(), batch_metadata[..5].to_vec())]);
+        ctr.sbt_mint(vec![(bob(), batch_metadata[..2].to_vec())]);
         ctx.predecessor_account_id = issuer2();
         testing_env!(ctx.clone());
-        ctr.sbt_mint(vec![(alice(), vec![m1_1.clone()])]);
+        ctr.sbt_mint(vec![(alice(), batch_metadata[..3].to_vec())]);
+        ctx.predecessor_account_id = issuer3();
+        testing_env!(ctx.clone());
+        // expired token
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START - 1))])]);
+        ctr.sbt_mint(vec![(alice(), batch_metadata[1..3].to_vec())]);
+
+        let flatten = |r: Vec<(AccountId, Vec<OwnedToken>)>| -> Vec<(AccountId, TokenId)> {
+            r.into_iter()
+                .flat_map(|(i, ts)| ts.into_iter().map(move |t| (i.clone(), t.token)))
+                .collect()
+        };
+        let stream = |limit: u32, with_expired: Option<bool>| {
+            let mut all = Vec::new();
+            let mut cursor = None;
+            loop {
+                let p = ctr.sbt_tokens_by_owner_cursor(alice(), cursor, Some(limit), with_expired);
+                assert!(p.data.iter().map(|(_, ts)| ts.len()).sum::<usize>() <= limit as usize);
+                all.extend(flatten(p.data));
+                cursor = p.next_cursor;
+                if cursor.is_none() {
+                    return all;
+                }
+            }
+        };
+        let expected = flatten(ctr.sbt_tokens_by_owner(alice(), None, None, None, None));
+        assert_eq!(expected.len(), 10);
+        for limit in [1, 3, 4, 10, 100] {
+            assert_eq!(stream(limit, None), expected, "limit: {}", limit);
+        }
+        let expected = flatten(ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(true)));
+        assert_eq!(expected.len(), 11);
+        assert_eq!(stream(3, Some(true)), expected);
+
+        // the issuer tokens are grouped in a single page
+        let p = ctr.sbt_tokens_by_owner_cursor(alice(), None, Some(7), None);
+        assert_eq!(p.data.len(), 2);
+        assert_eq!(p.data[0].1.len(), 5);
+        assert_eq!(p.data[1].1.len(), 2);
+        // cursor of the issuer2 class 2 token
+        assert_eq!(
+            p.next_cursor,
+            Some(format!("{}:2", ctr.sbt_issuers.get(&issuer2()).unwrap()))
+        );
+        let p = ctr.sbt_tokens_by_owner_cursor(alice(), p.next_cursor, Some(2), None);
+        let classes: Vec<(AccountId, ClassId)> = p
+            .data
+            .iter()
+            .flat_map(|(i, ts)| ts.iter().map(move |t| (i.clone(), t.metadata.class)))
+            .collect();
+        // issuer3 class 1 token is expired
+        assert_eq!(classes, vec![(issuer2(), 3), (issuer3(), 2)]);
 
-        // make soul transfer
-        ctx.predecessor_account_id = alice();
-        testing_env!(ctx);
-        assert_eq!(ctr.sbt_soul_transfer(alice2(), None).unwrap(), (3, true));
+        // no tokens
+        let p = ctr.sbt_tokens_by_owner_cursor(carol(), None, None, None);
+        assert!(p.data.is_empty());
+        assert_eq!(p.next_cursor, None);
+    }
 
-        let log1 = mk_log_str("ban", &format!(r#"["{}"]"#, alice()));
-        let log2 = mk_log_str(
-            "soul_transfer",
-            &format!(r#"{{"from":"{}","to":"{}"}}"#, alice(), alice2()),
-        );
-        assert_eq!(test_utils::get_logs(), vec![log1, log2].concat());
-        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
-        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 2);
-        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer2(), None), 1);
+    #[test]
+    #[should_panic(expected = "invalid cursor")]
+    fn sbt_tokens_by_owner_cursor_invalid() {
+        let (_, ctr) = setup(&issuer1(), 0);
+        ctr.sbt_tokens_by_owner_cursor(alice(), Some("1-2".to_owned()), None, None);
+    }
 
-        assert!(ctr.is_banned(alice()));
-        assert!(!ctr.is_banned(alice2()));
+    fn mk_class_metadata(name: &str) -> ClassMetadata {
+        ClassMetadata {
+            name: name.to_owned(),
+            symbol: None,
+            icon: Some("https://example.com/icon.svg".to_owned()),
+            reference: None,
+            reference_hash: None,
+        }
+    }
 
+    #[test]
+    fn class_metadata_catalog() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), MINT_DEPOSIT);
+        assert_eq!(ctr.class_metadata(issuer1(), 1), None);
+        assert!(ctr.classes_by_issuer(issuer1(), None, None).is_empty());
+
+        ctr.set_class_metadata(3, Some(mk_class_metadata("c3")));
+        ctr.set_class_metadata(1, Some(mk_class_metadata("c1")));
+        ctr.set_class_metadata(2, Some(mk_class_metadata("c2")));
+        // overwrite
+        ctr.set_class_metadata(1, Some(mk_class_metadata("c1 v2")));
         assert_eq!(
-            ctr.sbt_tokens_by_owner(alice(), None, None, None, None),
-            vec![]
+            ctr.class_metadata(issuer1(), 1),
+            Some(mk_class_metadata("c1 v2"))
         );
+        assert_eq!(ctr.class_metadata(issuer2(), 1), None);
+        assert_eq!(ctr.class_metadata(alice(), 1), None);
+
+        let classes = |r: Vec<(ClassId, ClassMetadata)>| -> Vec<ClassId> {
+            r.into_iter().map(|(c, _)| c).collect()
+        };
         assert_eq!(
-            ctr.sbt_tokens_by_owner(alice2(), None, None, None, None),
+            ctr.classes_by_issuer(issuer1(), None, None),
             vec![
-                (
-                    issuer1(),
-                    vec![mk_owned_token(1, m1_1.clone()), mk_owned_token(2, m2_1)]
-                ),
-                (issuer2(), vec![mk_owned_token(1, m1_1)]),
+                (1, mk_class_metadata("c1 v2")),
+                (2, mk_class_metadata("c2")),
+                (3, mk_class_metadata("c3"))
             ]
         );
-    }
+        assert_eq!(
+            classes(ctr.classes_by_issuer(issuer1(), None, Some(2))),
+            vec![1, 2]
+        );
+        assert_eq!(
+            classes(ctr.classes_by_issuer(issuer1(), Some(2), Some(2))),
+            vec![2, 3]
+        );
+        assert!(ctr.classes_by_issuer(issuer1(), Some(4), None).is_empty());
+        assert!(ctr.classes_by_issuer(alice(), None, None).is_empty());
 
-    #[test]
-    fn soul_transfer_with_continuation() {
-        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
-        // test1: simple case: alice has one token and she owns alice2 account as well. She
-        // will do transfer from alice -> alice2
-        let m1_1 = mk_metadata(1, Some(START + 10));
-        let m2_1 = mk_metadata(2, Some(START + 11));
-        let m3_1 = mk_metadata(3, Some(START + 12));
-        let m4_1 = mk_metadata(4, Some(START + 13));
-        ctr.sbt_mint(vec![(alice(), vec![m1_1, m2_1])]);
+        // remove
+        ctr.set_class_metadata(2, None);
+        ctr.set_class_metadata(5, None);
+        assert_eq!(ctr.class_metadata(issuer1(), 2), None);
+        assert_eq!(
+            classes(ctr.classes_by_issuer(issuer1(), None, None)),
+            vec![1, 3]
+        );
 
+        // other issuers have a separate catalog
         ctx.predecessor_account_id = issuer2();
-        testing_env!(ctx.clone());
-        ctr.sbt_mint(vec![(alice(), vec![m3_1, m4_1])]);
+        testing_env!(ctx);
+        ctr.set_class_metadata(1, Some(mk_class_metadata("i2")));
+        assert_eq!(
+            ctr.class_metadata(issuer2(), 1),
+            Some(mk_class_metadata("i2"))
+        );
+        assert_eq!(
+            classes(ctr.classes_by_issuer(issuer2(), None, None)),
+            vec![1]
+        );
+        assert_eq!(
+            classes(ctr.classes_by_issuer(issuer1(), None, None)),
+            vec![1, 3]
+        );
+    }
 
-        // make soul transfer
+    #[test]
+    #[should_panic(expected = "must be called by a registered SBT Issuer")]
+    fn set_class_metadata_not_issuer() {
+        let (mut ctx, mut ctr) = setup(&alice(), MINT_DEPOSIT);
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
-        assert_eq!(ctr._sbt_soul_transfer(alice2(), 3).unwrap(), (3, false));
-        assert!(test_utils::get_logs().len() == 1);
-        assert_eq!(ctr._sbt_soul_transfer(alice2(), 3).unwrap(), (1, true));
-        assert!(test_utils::get_logs().len() == 2);
+        ctr.set_class_metadata(1, Some(mk_class_metadata("c1")));
+    }
 
-        let log_soul_transfer = mk_log_str(
-            "soul_transfer",
+    #[test]
+    #[should_panic(expected = "not enough NEAR storage deposit")]
+    fn set_class_metadata_no_deposit() {
+        let (_, mut ctr) = setup(&issuer1(), 0);
+        ctr.set_class_metadata(1, Some(mk_class_metadata("c1")));
+    }
+
+    #[test]
+    fn required_mint_deposit() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 0);
+        let long = |c: char| AccountId::new_unchecked(c.to_string().repeat(64));
+        let mut m = mk_metadata(1, Some(START));
+        m.reference = Some("https://example.com/".repeat(5));
+        m.reference_hash = Some(vec![1; 32].into());
+        let mut m2 = m.clone();
+        m2.class = 2;
+        let spec = vec![
+            (long('a'), vec![m.clone(), m2.clone()]),
+            (long('b'), vec![m, m2]),
+        ];
+        let required = ctr.required_mint_deposit(4, 2, Some(100 + 32)).0;
+        ctx.attached_deposit = required;
+        testing_env!(ctx);
+        let storage_start = env::storage_usage();
+        ctr.sbt_mint(spec);
+        let used = (env::storage_usage() - storage_start) as u128 * env::storage_byte_cost();
+        assert!(used <= required, "used: {}, required: {}", used, required);
+        // the estimate is close to the used storage
+        assert!(required - used < MINT_DEPOSIT);
+    }
+
+    #[test]
+    fn total_supply_by_owner() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 0);
+        ctr.sbt_mint(vec![
+            (alice(), mk_batch_metadata(2)),
+            (bob(), mk_batch_metadata(1)),
+        ]);
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), mk_batch_metadata(1))]);
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 3);
+        assert_eq!(ctr.sbt_total_supply_by_owner(bob()), 1);
+
+        // accounts without the counter (minted before it was introduced)
+        ctr.total_supply_by_owner.remove(&alice());
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 3);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(issuer1(), vec![1], None);
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 2);
+        assert_eq!(ctr.total_supply_by_owner.get(&alice()), Some(2));
+
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        ctr.sbt_soul_transfer(alice2(), None).unwrap();
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 0);
+        assert_eq!(ctr.total_supply_by_owner.get(&alice()), None);
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice2()), 2);
+    }
+
+    #[test]
+    fn sbt_change_class() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
+        let m2 = mk_metadata(2, Some(START));
+        let m5 = mk_metadata(5, Some(START));
+        ctr.sbt_mint(vec![
+            (alice(), vec![m2.clone()]),
+            (bob(), vec![m2.clone(), m5]),
+        ]);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_block_class(alice(), issuer1(), 2);
+
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        // unchanged class is skipped
+        ctr.sbt_change_class(vec![(1, 5), (2, 2)]);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 2), 1);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 5), 2);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), Some(2)), 0);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), Some(5)), 1);
+        assert_eq!(ctr.sbt(issuer1(), 1).unwrap().metadata.class, 5);
+        assert!(ctr.is_class_blocked(alice(), issuer1(), 5));
+        assert!(!ctr.is_class_blocked(alice(), issuer1(), 2));
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"change_class","data":{"issuer":"sbt.n","tokens":[{"from_class":2,"to_class":5,"token":1}]},"seq":3}"#;
+        assert_eq!(test_utils::get_logs(), vec![exp]);
+
+        let issuer_id = ctr.sbt_issuers.get(&issuer1()).unwrap();
+        assert_eq!(ctr.issuer_classes.get(&issuer_id), Some(vec![2, 5]));
+
+        ctr.sbt_change_class(vec![(2, 7)]);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 2), 0);
+        assert_eq!(ctr.supply_by_class.get(&(1, 2)), None);
+        assert_eq!(ctr.issuer_classes.get(&issuer_id), Some(vec![2, 5, 7]));
+        assert_eq!(test_utils::get_logs().len(), 2);
+
+        // no event when nothing changed
+        ctr.sbt_change_class(vec![(2, 7), (3, 5)]);
+        assert_eq!(test_utils::get_logs().len(), 2);
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(bob(), None, None, None, None),
+            vec![(
+                issuer1(),
+                vec![
+                    mk_owned_token(3, mk_metadata(5, Some(START))),
+                    mk_owned_token(2, mk_metadata(7, Some(START))),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "owner of token 1 already has a token of class 5")]
+    fn sbt_change_class_conflict() {
+        let (_, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            bob(),
+            vec![mk_metadata(2, Some(START)), mk_metadata(5, Some(START))],
+        )]);
+        ctr.sbt_change_class(vec![(1, 5)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 100 changes can be made")]
+    fn sbt_change_class_too_many() {
+        let (_, mut ctr) = setup(&issuer1(), 0);
+        let changes = (1..=MAX_CHANGE_CLASS_BATCH as u64 + 1)
+            .map(|t| (t, 2))
+            .collect();
+        ctr.sbt_change_class(changes);
+    }
+
+    #[test]
+    fn mint_replace_expired() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
+        let m1 = mk_metadata(1, Some(START + 10));
+        let m2 = mk_metadata(2, Some(START + 100));
+        ctr.sbt_mint(vec![(alice(), vec![m1, m2])]);
+
+        ctx.block_timestamp = (START + 50) * MSECOND;
+        testing_env!(ctx);
+        let m1_new = mk_metadata(1, Some(START + 1000));
+        let minted = ctr.sbt_mint_replace_expired(vec![
+            (alice(), vec![m1_new.clone()]),
+            (bob(), vec![m1_new.clone()]),
+        ]);
+        assert_eq!(minted, vec![(alice(), vec![(1, 3)]), (bob(), vec![(1, 4)])]);
+        assert_eq!(ctr.sbt(issuer1(), 1), None);
+        assert_eq!(ctr.sbt(issuer1(), 3).unwrap().owner, alice());
+        assert_eq!(ctr.sbt_supply(issuer1()), 3);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 1), 2);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 2);
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 2);
+        assert_eq!(ctr.issuer_stats(issuer1()).total_burned, 1);
+        let logs = test_utils::get_logs();
+        let data = format!(r#"{{"issuer":"{}","tokens":[1]}}"#, issuer1());
+        assert_eq!(
+            logs[..2],
+            [mk_log_str("burn", &data, 2), mk_log_str("revoke", &data, 3)].concat()
+        );
+        assert_eq!(logs.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "alice.near already has SBT of class 2")]
+    fn mint_replace_expired_valid_token() {
+        let (_, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(2, Some(START + 100))])]);
+        ctr.sbt_mint_replace_expired(vec![(alice(), vec![mk_metadata(2, Some(START + 200))])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "alice.near already has SBT of class 1")]
+    fn mint_replace_expired_duplicated_class() {
+        let (_, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        // the first token is already expired, but it's minted by the same batch
+        ctr.sbt_mint_replace_expired(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START - 10)),
+                mk_metadata(1, Some(START + 100)),
+            ],
+        )]);
+    }
+
+    #[test]
+    fn mint_v2() {
+        let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        let m1 = mk_metadata(1, Some(START));
+        let m2 = mk_metadata(2, Some(START));
+        let minted = ctr.sbt_mint_v2(vec![
+            (alice(), vec![m2.clone(), m1.clone()]),
+            (bob(), vec![m2]),
+        ]);
+        assert_eq!(
+            minted,
+            vec![(alice(), vec![(2, 1), (1, 2)]), (bob(), vec![(2, 3)])]
+        );
+        assert_eq!(ctr.sbt_supply(issuer1()), 3);
+        // the flat result of sbt_mint follows the same order
+        assert_eq!(ctr.sbt_mint(vec![(alice2(), vec![m1])]), vec![4]);
+    }
+
+    #[test]
+    fn soul_transfer1() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+
+        // test1: simple case: alice has one token and she owns alice2 account as well. She
+        // will do transfer from alice -> alice2
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        let m2_1 = mk_metadata(2, Some(START + 10));
+        ctr.sbt_mint(vec![(alice(), vec![m1_1.clone(), m2_1.clone()])]);
+
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![m1_1.clone()])]);
+
+        // make soul transfer
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        assert_eq!(ctr.sbt_soul_transfer(alice2(), None).unwrap(), (3, true));
+
+        let log1 = mk_log_str("ban", &format!(r#"["{}"]"#, alice()), 3);
+        let log2 = mk_log_str(
+            "soul_transfer",
+            &format!(r#"{{"from":"{}","to":"{}"}}"#, alice(), alice2()),
+            4,
+        );
+        assert_eq!(test_utils::get_logs(), vec![log1, log2].concat());
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 2);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer2(), None), 1);
+
+        assert!(ctr.is_banned(alice()));
+        assert!(!ctr.is_banned(alice2()));
+
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None),
+            vec![]
+        );
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice2(), None, None, None, None),
+            vec![
+                (
+                    issuer1(),
+                    vec![mk_owned_token(1, m1_1.clone()), mk_owned_token(2, m2_1)]
+                ),
+                (issuer2(), vec![mk_owned_token(1, m1_1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn soul_transfer_with_continuation() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        // test1: simple case: alice has one token and she owns alice2 account as well. She
+        // will do transfer from alice -> alice2
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        let m2_1 = mk_metadata(2, Some(START + 11));
+        let m3_1 = mk_metadata(3, Some(START + 12));
+        let m4_1 = mk_metadata(4, Some(START + 13));
+        ctr.sbt_mint(vec![(alice(), vec![m1_1, m2_1])]);
+
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![m3_1, m4_1])]);
+
+        // make soul transfer
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        assert!(!ctr.soul_transfer_ongoing(alice()));
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 3).unwrap(), (3, false));
+        assert!(test_utils::get_logs().len() == 1);
+        assert!(ctr.soul_transfer_ongoing(alice()));
+        assert!(!ctr.soul_transfer_ongoing(alice2()));
+        assert_eq!(
+            ctr.ongoing_transfer(alice()),
+            Some(TransferState {
+                recipient: Some(alice2()),
+                kind: Some(TransferKind::SoulTransfer),
+                issuer_id: 3,
+                class_id: 3,
+            })
+        );
+        assert_eq!(ctr.ongoing_transfer(alice2()), None);
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 3).unwrap(), (1, true));
+        assert!(test_utils::get_logs().len() == 2);
+        assert!(!ctr.soul_transfer_ongoing(alice()));
+        assert_eq!(ctr.ongoing_transfer(alice()), None);
+
+        let log_soul_transfer = mk_log_str(
+            "soul_transfer",
             &format!(r#"{{"from":"{}","to":"{}"}}"#, alice(), alice2()),
+            4,
         );
         assert_eq!(test_utils::get_logs()[1], log_soul_transfer[0]);
         assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
@@ -1550,6 +4657,60 @@ mod tests {
         assert!(!ctr.is_banned(alice2()));
     }
 
+    #[test]
+    fn soul_transfer_opt_in() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.sbt_soul_transfer(alice2(), None),
+            Err(SoulTransferErr::NotAccepted)
+        );
+
+        ctr.sbt_soul_transfer_init(alice2());
+        assert_eq!(
+            ctr.soul_transfer_request(alice()),
+            Some(SoulTransferRequest {
+                recipient: alice2(),
+                accepted: false
+            })
+        );
+        assert_eq!(
+            ctr.sbt_soul_transfer(alice2(), None),
+            Err(SoulTransferErr::NotAccepted)
+        );
+
+        ctx.predecessor_account_id = alice2();
+        testing_env!(ctx.clone());
+        ctr.sbt_soul_transfer_accept(alice());
+        assert!(ctr.soul_transfer_request(alice()).unwrap().accepted);
+
+        // only the accepted recipient can receive the soul
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        assert_eq!(
+            ctr.sbt_soul_transfer(bob(), None),
+            Err(SoulTransferErr::NotAccepted)
+        );
+        assert!(!ctr.is_banned(alice()));
+        assert_eq!(ctr.sbt_soul_transfer(alice2(), None), Ok((1, true)));
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 1);
+        assert_eq!(ctr.soul_transfer_request(alice()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no soul transfer request from alice.near")]
+    fn soul_transfer_accept_other_recipient() {
+        let (mut ctx, mut ctr) = setup(&alice(), 0);
+        ctr.sbt_soul_transfer_init(alice2());
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx);
+        ctr.sbt_soul_transfer_accept(alice());
+    }
+
     #[test]
     fn soul_transfer_no_tokens_from_caller() {
         let (mut ctx, mut ctr) = setup(&issuer1(), MINT_DEPOSIT);
@@ -1557,14 +4718,27 @@ mod tests {
         testing_env!(ctx);
         assert!(!ctr.is_banned(alice()));
         assert!(!ctr.is_banned(alice2()));
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
         assert!(ctr.is_banned(alice()));
         assert!(!ctr.is_banned(alice2()));
         // assert ban even is being emited after the caller with zero tokens has invoked the soul_transfer
-        let log_ban = mk_log_str("ban", &format!("[\"{}\"]", alice()));
+        let log_ban = mk_log_str("ban", &format!("[\"{}\"]", alice()), 1);
         assert_eq!(test_utils::get_logs(), log_ban);
     }
 
+    /// Creates an accepted soul transfer request (`sbt_soul_transfer_init` +
+    /// `sbt_soul_transfer_accept`).
+    fn accept_soul_transfer(ctr: &mut Contract, from: &AccountId, to: &AccountId) {
+        ctr.soul_transfer_requests.insert(
+            from,
+            &SoulTransferRequest {
+                recipient: to.clone(),
+                accepted: true,
+            },
+        );
+    }
+
     fn soul_transfer_prepare() -> (VMContext, Contract) {
         let (mut ctx, mut ctr) = setup(&issuer1(), 150 * MINT_DEPOSIT);
         let batch_metadata = mk_batch_metadata(110);
@@ -1611,6 +4785,7 @@ mod tests {
         testing_env!(ctx.clone());
         let limit: u32 = 20; //anything above this limit will fail due to exceeding maximum gas usage per call
 
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         let mut result = ctr._sbt_soul_transfer(alice2(), limit as usize).unwrap();
         while !result.1 {
             testing_env!(ctx.clone()); // reset gas
@@ -1637,27 +4812,70 @@ mod tests {
         ctx.prepaid_gas = max_gas();
         testing_env!(ctx);
         let limit: u32 = 30;
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr._sbt_soul_transfer(alice2(), limit as usize).unwrap();
     }
 
     #[test]
-    #[should_panic(
-        expected = "recipient already has an SBT of issuer=sbt.n, class=1; source_token_id=1"
-    )]
     fn soul_transfer_conflict() {
         let (mut ctx, mut ctr) = soul_transfer_prepare();
 
-        // alice1 already has SBT of (issuer1, 1), so let's mint it to alice2 and try soul_transfer
-        let batch_metadata = mk_batch_metadata(1);
+        // alice already has SBTs of (issuer1, 1..3), so let's mint them to alice2 and try
+        // soul_transfer. alice tokens expire at START + class.
         ctx.predecessor_account_id = issuer1();
         testing_env!(ctx.clone());
-        ctr.sbt_mint(vec![(alice2(), batch_metadata)]);
+        let t = ctr.sbt_mint(vec![(
+            alice2(),
+            vec![
+                mk_metadata(1, Some(START + 1)), // tie: recipient token is kept
+                mk_metadata(2, None),            // newer: recipient token is kept
+                mk_metadata(3, Some(START)),     // older: alice token is kept
+            ],
+        )]);
+        let supply = ctr.sbt_supply(issuer1());
+        let supply_class_1 = ctr.sbt_supply_by_class(issuer1(), 1);
+        let supply_class_3 = ctr.sbt_supply_by_class(issuer1(), 3);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 13);
 
         ctx.predecessor_account_id = alice();
         ctx.prepaid_gas = max_gas();
-        testing_env!(ctx);
-        let limit: u32 = 30;
-        ctr._sbt_soul_transfer(alice2(), limit as usize).unwrap();
+        testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 20), Ok((20, false)));
+        let log_burn = mk_log_str(
+            "burn",
+            &format!(r#"{{"issuer":"{}","tokens":[1,2,{}]}}"#, issuer1(), t[2]),
+            ctr.event_seq,
+        );
+        assert_eq!(test_utils::get_logs().last(), Some(&log_burn[0]));
+        loop {
+            testing_env!(ctx.clone());
+            if ctr._sbt_soul_transfer(alice2(), 20).unwrap().1 {
+                break;
+            }
+        }
+
+        assert_eq!(ctr.sbt(issuer1(), 1), None);
+        assert_eq!(ctr.sbt(issuer1(), 2), None);
+        assert_eq!(ctr.sbt(issuer1(), t[2]), None);
+        assert_eq!(ctr.sbt(issuer1(), t[0]).unwrap().owner, alice2());
+        assert_eq!(ctr.sbt(issuer1(), 3).unwrap().owner, alice2());
+        let tokens: Vec<TokenId> =
+            ctr.sbt_tokens_by_owner(alice2(), Some(issuer1()), None, Some(3), None)[0]
+                .1
+                .iter()
+                .map(|t| t.token)
+                .collect();
+        assert_eq!(tokens, vec![t[0], t[1], 3]);
+
+        // supply counters are consistent
+        assert_eq!(ctr.sbt_supply(issuer1()), supply - 3);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 1), supply_class_1 - 1);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 3), supply_class_3 - 1);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 60);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer2(), None), 50);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
+        assert_eq!(ctr.issuer_stats(issuer1()).total_burned, 3);
     }
 
     #[test]
@@ -1682,6 +4900,7 @@ mod tests {
         testing_env!(ctx.clone());
 
         let limit: usize = 10;
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         assert_eq!(
             ctr._sbt_soul_transfer(alice2(), limit).unwrap(),
             (limit as u32, false)
@@ -1724,19 +4943,20 @@ mod tests {
 
     #[test]
     fn test_mk_log() {
-        let l = mk_log_str("abc", "[1,2,3]");
+        let l = mk_log_str("abc", "[1,2,3]", 4);
         assert_eq!(
             l,
             vec![
-                r#"EVENT_JSON:{"standard":"nep393","version":"1.0.0","event":"abc","data":[1,2,3]}"#
+                r#"EVENT_JSON:{"standard":"nep393","version":"1.0.0","event":"abc","data":[1,2,3],"seq":4}"#
             ],
         )
     }
 
-    fn mk_log_str(event: &str, data: &str) -> Vec<String> {
+    /// `seq`: expected registry event sequence number.
+    fn mk_log_str(event: &str, data: &str, seq: u64) -> Vec<String> {
         vec![format!(
-            "EVENT_JSON:{{\"standard\":\"nep393\",\"version\":\"1.0.0\",\"event\":\"{}\",\"data\":{}}}",
-            event,data
+            "EVENT_JSON:{{\"standard\":\"nep393\",\"version\":\"1.0.0\",\"event\":\"{}\",\"data\":{},\"seq\":{}}}",
+            event, data, seq
         )]
     }
 
@@ -1838,6 +5058,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn registry_renew_many() {
+        let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        let m2_1 = mk_metadata(2, Some(START + 11));
+        let tokens = ctr.sbt_mint(vec![(alice(), vec![m1_1]), (bob(), vec![m2_1])]);
+
+        ctr.sbt_renew_many(vec![(tokens[1], START + 200), (tokens[0], START + 100)]);
+        assert_eq!(
+            ctr.sbt(issuer1(), tokens[0]).unwrap().metadata.expires_at,
+            Some(START + 100)
+        );
+        assert_eq!(
+            ctr.sbt(issuer1(), tokens[1]).unwrap().metadata.expires_at,
+            Some(START + 200)
+        );
+        let log_renew = mk_log_str(
+            "renew",
+            &format!(
+                r#"{{"issuer":"{}","tokens":[{},{}]}}"#,
+                issuer1(),
+                tokens[1],
+                tokens[0]
+            ),
+            2,
+        );
+        assert_eq!(test_utils::get_logs().last(), Some(&log_renew[0]));
+        assert_eq!(ctr.issuer_stats(issuer1()).total_renewed, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "account bob.near is banned")]
+    fn registry_renew_many_banned_owner() {
+        let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        let tokens = ctr.sbt_mint(vec![(alice(), vec![m1_1.clone()]), (bob(), vec![m1_1])]);
+        ctr.banlist.insert(&bob());
+        ctr.sbt_renew_many(vec![(tokens[0], START + 100), (tokens[1], START + 100)]);
+    }
+
     #[test]
     #[should_panic]
     fn registry_renew_basics() {
@@ -1854,6 +5114,17 @@ mod tests {
         ctr.sbt_renew(tokens, START + 100);
     }
 
+    #[test]
+    #[should_panic(expected = "account bob.near is banned")]
+    fn registry_renew_banned_owner() {
+        let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        let tokens = ctr.sbt_mint(vec![(alice(), vec![m1_1.clone()]), (bob(), vec![m1_1])]);
+        ctr.banlist.insert(&bob());
+        // alice token is valid, but the whole batch must be rejected
+        ctr.sbt_renew(tokens, START + 100);
+    }
+
     #[test]
     fn registry_renew_event() {
         let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
@@ -1869,10 +5140,12 @@ mod tests {
                 issuer1(),
                 alice()
             ),
+            1,
         );
         let log_renew = mk_log_str(
             "renew",
             &format!(r#"{{"issuer":"{}","tokens":[{}]}}"#, issuer1(), tokens[0]),
+            2,
         );
         assert_eq!(test_utils::get_logs(), vec![log_mint, log_renew].concat());
     }
@@ -1900,6 +5173,7 @@ mod tests {
                 alice(),
                 bob()
             ),
+            3,
         );
         assert_eq!(test_utils::get_logs().len(), 2);
         assert_eq!(test_utils::get_logs()[1], recover_log[0]);
@@ -1993,18 +5267,77 @@ mod tests {
         assert_eq!((3, false), result);
         assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 3);
         assert!(test_utils::get_logs().len() == 1);
+        assert_eq!(
+            ctr.ongoing_transfer(alice()),
+            Some(TransferState {
+                recipient: Some(alice2()),
+                kind: Some(TransferKind::Recover),
+                issuer_id: 2,
+                class_id: 3,
+            })
+        );
         result = ctr._sbt_recover(alice(), alice2(), 3);
         assert_eq!((1, true), result);
         assert!(test_utils::get_logs().len() == 2);
+        assert_eq!(ctr.ongoing_transfer(alice()), None);
 
         assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
         assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 4);
     }
 
     #[test]
-    fn sbt_revoke() {
-        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
-
+    fn sbt_mint_ongoing_recovery() {
+        let (_, mut ctr) = setup(&issuer1(), 6 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 10)),
+                mk_metadata(2, Some(START + 10)),
+                mk_metadata(3, Some(START + 10)),
+            ],
+        )]);
+
+        assert_eq!(ctr._sbt_recover(alice(), alice2(), 2), (2, false));
+        assert_eq!(
+            ctr.check_no_ongoing_transfer(&alice()),
+            Err(MintErr::OngoingTransfer(alice()))
+        );
+        // mint to the recipient doesn't conflict with the continuation
+        assert_eq!(ctr.check_no_ongoing_transfer(&alice2()), Ok(()));
+        ctr.sbt_mint(vec![(alice2(), vec![mk_metadata(5, Some(START + 10))])]);
+
+        assert_eq!(ctr._sbt_recover(alice(), alice2(), 2), (1, true));
+        assert_eq!(ctr.check_no_ongoing_transfer(&alice()), Ok(()));
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 4);
+
+        // the source can receive tokens once the recovery is completed
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "can't mint to alice.near: the account has an ongoing soul transfer or recovery"
+    )]
+    fn sbt_mint_ongoing_recovery_fail() {
+        let (_, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 10)),
+                mk_metadata(2, Some(START + 10)),
+            ],
+        )]);
+        assert_eq!(ctr._sbt_recover(alice(), alice2(), 1), (1, false));
+        // class 1 was already recovered, so without the check the mint would succeed
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+    }
+
+    #[test]
+    fn sbt_revoke() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+
         let m1_1 = mk_metadata(1, Some(START + 10));
         let m2_1 = mk_metadata(2, Some(START + 11));
         let m3_1 = mk_metadata(3, Some(START + 21));
@@ -2036,6 +5369,7 @@ mod tests {
         let log_revoke = mk_log_str(
             "revoke",
             &format!(r#"{{"issuer":"{}","tokens":[1,2,3]}}"#, issuer1()),
+            4,
         );
         assert_eq!(test_utils::get_logs().len(), 1);
         assert_eq!(test_utils::get_logs()[0], log_revoke[0]);
@@ -2106,9 +5440,16 @@ mod tests {
         let log_burn = mk_log_str(
             "burn",
             &format!(r#"{{"issuer":"{}","tokens":[1,2,3]}}"#, issuer1()),
+            5,
+        );
+        let log_revoke = mk_log_str(
+            "revoke",
+            &format!(r#"{{"issuer":"{}","tokens":[1,2,3]}}"#, issuer1()),
+            6,
         );
         assert_eq!(test_utils::get_logs().len(), 2);
         assert_eq!(test_utils::get_logs()[0], log_burn[0]);
+        assert_eq!(test_utils::get_logs()[1], log_revoke[0]);
         assert_eq!(ctr.sbt_supply(issuer1()), 1);
         assert_eq!(ctr.sbt_supply(issuer2()), 3);
         assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
@@ -2145,6 +5486,7 @@ mod tests {
 
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
 
         assert!(ctr.is_banned(alice()));
@@ -2278,6 +5620,7 @@ mod tests {
 
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
     }
 
@@ -2295,6 +5638,7 @@ mod tests {
 
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
     }
 
@@ -2307,6 +5651,7 @@ mod tests {
 
         ctx.predecessor_account_id = alice();
         testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         assert!(!ctr.sbt_soul_transfer(alice2(), None).unwrap().1);
 
         // assert the from account is banned after the first soul transfer execution
@@ -2330,69 +5675,791 @@ mod tests {
         ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
         assert!(!ctr.is_banned(alice()));
 
-        ctx.predecessor_account_id = issuer1();
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_recover(alice(), alice2());
+        // sbt_recover should not ban the source account
+        assert!(!ctr.is_banned(alice()));
+        assert!(!ctr.is_banned(alice2()));
+    }
+
+    #[test]
+    #[should_panic(expected = "account alice.near is banned")]
+    fn sbt_mint_to_banned_account() {
+        let (_, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        let m1_1 = mk_metadata(1, Some(START + 10));
+
+        //ban alice account
+        ctr.banlist.insert(&alice());
+        assert!(ctr.is_banned(alice()));
+
+        //try to mint to a banned account
+        ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
+    }
+
+    #[test]
+    fn sbt_tokens_by_owner_non_expired() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
+        ctx.block_timestamp = START * MSECOND; // 11 seconds
+        testing_env!(ctx.clone());
+
+        let m1_1 = mk_metadata(1, Some(START));
+        let m1_2 = mk_metadata(2, Some(START));
+        let m1_3 = mk_metadata(3, Some(START + 100));
+        let m1_4 = mk_metadata(4, Some(START + 100));
+        ctr.sbt_mint(vec![(alice(), vec![m1_1, m1_2, m1_3, m1_4])]);
+
+        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(true));
+        assert_eq!(res[0].1.len(), 4);
+        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
+        assert_eq!(res[0].1.len(), 4);
+
+        let res = ctr.sbt_tokens(issuer1(), None, None, Some(true));
+        assert_eq!(res.len(), 4);
+        let res = ctr.sbt_tokens(issuer1(), None, None, Some(false));
+        assert_eq!(res.len(), 4);
+        let res = ctr.sbt_tokens(issuer1(), None, None, None);
+        assert_eq!(res.len(), 4);
+
+        // fast forward so the first two sbts are expired
+        ctx.block_timestamp = (START + 50) * MSECOND;
+        testing_env!(ctx);
+
+        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(true));
+        assert_eq!(res[0].1.len(), 4);
+        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(false));
+        assert_eq!(res[0].1.len(), 2);
+        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
+        assert_eq!(res[0].1.len(), 2);
+
+        let res = ctr.sbt_tokens(issuer1(), None, None, Some(true));
+        assert_eq!(res.len(), 4);
+        let res = ctr.sbt_tokens(issuer1(), None, None, Some(false));
+        assert_eq!(res.len(), 2);
+        let res = ctr.sbt_tokens(issuer1(), None, None, None);
+        assert_eq!(res.len(), 2);
+    }
+
+    #[test]
+    fn paginated_queries_limit() {
+        let (_, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, None),
+                mk_metadata(2, None),
+                mk_metadata(3, None),
+            ],
+        )]);
+        assert_eq!(ctr.max_limit(), 1000);
+
+        let res = ctr.sbt_tokens_v2(issuer1(), None, Some(u32::MAX), None);
+        assert_eq!(res.limit, 1000);
+        assert_eq!(res.data.len(), 3);
+        let res = ctr.sbt_tokens_v2(issuer1(), None, Some(2), None);
+        assert_eq!(res.limit, 2);
+        assert_eq!(res.data.len(), 2);
+
+        let res = ctr.sbt_tokens_by_owner_v2(alice(), None, None, None, None);
+        assert_eq!(res.limit, 1000);
+        assert_eq!(res.data[0].1.len(), 3);
+        let res = ctr.sbt_tokens_by_owner_v2(alice(), None, None, Some(1), None);
+        assert_eq!(res.limit, 1);
+        assert_eq!(res.data[0].1.len(), 1);
+
+        // v1 queries are clamped as well
+        assert_eq!(
+            ctr.sbt_tokens(issuer1(), None, Some(u32::MAX), None).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn sbt_stats() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 20 * MINT_DEPOSIT);
+        let stats = |supply, classes, holders| SupplyStats {
+            supply,
+            classes,
+            holders,
+        };
+        assert_eq!(ctr.sbt_stats(issuer1()), stats(0, vec![], 0));
+
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(2, None), mk_metadata(1, None)]),
+            (bob(), vec![mk_metadata(1, None)]),
+        ]);
+        ctr.sbt_mint(vec![(carol(), vec![mk_metadata(3, None)])]);
+        assert_eq!(
+            ctr.sbt_stats(issuer1()),
+            stats(4, vec![(1, 2), (2, 1), (3, 1)], 3)
+        );
+        assert_eq!(ctr.sbt_stats(issuer2()), stats(0, vec![], 0));
+
+        // bob burns his only token
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(issuer1(), vec![3], None);
+        assert_eq!(
+            ctr.sbt_stats(issuer1()),
+            stats(3, vec![(1, 1), (2, 1), (3, 1)], 2)
+        );
+
+        // issuer revokes (burns) carol token
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_revoke(vec![4], true);
+        assert_eq!(ctr.sbt_stats(issuer1()), stats(2, vec![(1, 1), (2, 1)], 1));
+
+        // soul transfer moves the holder
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &dan());
+        assert_eq!(ctr.sbt_soul_transfer(dan(), None).unwrap(), (2, true));
+        assert_eq!(ctr.sbt_stats(issuer1()), stats(2, vec![(1, 1), (2, 1)], 1));
+        assert_eq!(ctr.supply_by_owner.get(&(alice(), 2)), None);
+    }
+
+    #[test]
+    fn humanity_score() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![
+            (
+                alice(),
+                vec![
+                    mk_metadata(1, Some(START + 10)),
+                    mk_metadata(2, Some(START + 100)),
+                ],
+            ),
+            (bob(), vec![mk_metadata(1, Some(START + 10))]),
+        ]);
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 100))])]);
+        assert_eq!(ctr.humanity_score(alice()), 0);
+
+        let sw = |issuer, class, weight| ScoreWeight {
+            issuer,
+            class,
+            weight,
+        };
+        let weights = vec![
+            sw(issuer1(), 1, 10),
+            sw(issuer1(), 2, 5),
+            sw(issuer2(), 1, 3),
+            sw(issuer3(), 1, 100),
+        ];
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_set_score_weights(weights.clone());
+        assert_eq!(ctr.score_weights(), weights);
+        assert_eq!(ctr.humanity_score(alice()), 18);
+        assert_eq!(ctr.humanity_score(bob()), 10);
+        assert_eq!(ctr.humanity_score(carol()), 0);
+
+        // expired tokens are not counted
+        ctx.block_timestamp = (START + 50) * MSECOND;
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        assert_eq!(ctr.humanity_score(alice()), 8);
+        assert_eq!(ctr.humanity_score(bob()), 0);
+
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+        assert_eq!(ctr.humanity_score(alice()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicated weight for issuer sbt.n class 1")]
+    fn admin_set_score_weights_duplicated() {
+        let (_, mut ctr) = setup(&admin(), 0);
+        let sw = ScoreWeight {
+            issuer: issuer1(),
+            class: 1,
+            weight: 1,
+        };
+        ctr.admin_set_score_weights(vec![sw.clone(), sw]);
+    }
+
+    #[test]
+    fn humans_count() {
+        let (mut ctx, mut ctr) = setup(&admin(), 3 * MINT_DEPOSIT);
+        ctr.admin_set_human_classes(vec![(issuer1(), 2), (issuer1(), 2)]);
+        assert_eq!(ctr.human_classes(), vec![(issuer1(), 2)]);
+        assert!(ctr.admin_backfill_counters(None));
+
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(1, Some(START + 10))]),
+            (bob(), vec![mk_metadata(1, Some(START + 10))]),
+        ]);
+        assert_eq!(ctr.humans_count(), 2);
+
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(2, Some(START + 10))]),
+            (carol(), vec![mk_metadata(2, Some(START + 10))]),
+        ]);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(1));
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 3), None);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer2(), 2), None);
+
+        // carol becomes a human
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        let carol_tokens = ctr.sbt_mint(vec![(carol(), vec![mk_metadata(1, Some(START + 10))])]);
+        assert_eq!(ctr.humans_count(), 3);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(2));
+
+        ctr.sbt_revoke(carol_tokens, true);
+        assert_eq!(ctr.humans_count(), 2);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(1));
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(issuer1(), vec![1], None);
+        assert_eq!(ctr.humans_count(), 2);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(0));
+        ctr.sbt_burn(fractal_mainnet(), vec![1], None);
+        assert_eq!(ctr.humans_count(), 1);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx);
+        // a new tracked class resets the counters
+        ctr.admin_set_human_classes(vec![(issuer2(), 1)]);
+        assert!(ctr.admin_backfill_counters(None));
+        assert_eq!(ctr.humans_count(), 1);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), None);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer2(), 1), Some(0));
+    }
+
+    #[test]
+    fn admin_backfill_counters() {
+        let (mut ctx, mut ctr) = setup(&admin(), 10 * MINT_DEPOSIT);
+        ctr.admin_set_human_classes(vec![(issuer1(), 2)]);
+        assert!(ctr.admin_backfill_counters(None));
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(1, None)]),
+            (bob(), vec![mk_metadata(1, None)]),
+        ]);
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(1, None), mk_metadata(2, None)]),
+            (carol(), vec![mk_metadata(2, None)]),
+        ]);
+        let issuer_id = ctr.sbt_issuers.get(&issuer1()).unwrap();
+
+        // simulate the migration: the counters are not indexed
+        for id in [1, issuer_id] {
+            ctr.holders_by_issuer.remove(&id);
+            ctr.issuer_classes.remove(&id);
+        }
+        ctr.humans_count = 0;
+        ctr.humans_by_class.remove(&(issuer_id, 2));
+        ctr.counters_backfill = Some(CountersBackfill {
+            supply: true,
+            last_owner: None,
+        });
+        // accounts not indexed yet are counted by the backfill
+        ctr.sbt_mint(vec![(dan(), vec![mk_metadata(1, None)])]);
+        assert_eq!(ctr.holders_by_issuer.get(&issuer_id), None);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(!ctr.admin_backfill_counters(Some(2)));
+        assert_eq!(
+            ctr.counters_backfill(),
+            Some(CountersBackfill {
+                supply: true,
+                last_owner: Some(bob()),
+            })
+        );
+        assert_eq!(ctr.holders_by_issuer.get(&issuer_id), Some(1));
+        assert_eq!(ctr.humans_count, 2);
+
+        // indexed accounts are updated incrementally
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(bob(), vec![mk_metadata(1, None)])]);
+        assert_eq!(ctr.holders_by_issuer.get(&issuer_id), Some(2));
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_backfill_counters(None));
+        assert_eq!(ctr.counters_backfill(), None);
+        assert!(ctr.admin_backfill_counters(None));
+        assert_eq!(
+            ctr.sbt_stats(issuer1()),
+            SupplyStats {
+                supply: 5,
+                classes: vec![(1, 3), (2, 2)],
+                holders: 4,
+            }
+        );
+        assert_eq!(ctr.sbt_stats(fractal_mainnet()).holders, 2);
+        assert_eq!(ctr.humans_count(), 2);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(1));
+
+        // changing the IAH class set resets the human counters
+        ctr.admin_set_iah_class_set(vec![(issuer1(), vec![1])], ClassSetMode::All)
+            .unwrap();
+        assert_eq!(
+            ctr.counters_backfill(),
+            Some(CountersBackfill {
+                supply: false,
+                last_owner: None,
+            })
+        );
+        // supply stats are still available
+        assert_eq!(ctr.sbt_stats(issuer1()).holders, 4);
+        assert!(ctr.admin_backfill_counters(None));
+        assert_eq!(ctr.humans_count(), 3);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 2), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "counters are not indexed yet, see admin_backfill_counters")]
+    fn humans_count_not_indexed() {
+        let (_, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+        ctr.admin_set_iah_class_set(vec![(issuer1(), vec![1])], ClassSetMode::All)
+            .unwrap();
+        ctr.humans_count();
+    }
+
+    #[test]
+    fn humans_count_soul_transfer() {
+        let (mut ctx, mut ctr) = setup(&admin(), 2 * MINT_DEPOSIT);
+        ctr.admin_set_human_classes(vec![(issuer1(), 1)]);
+        assert!(ctr.admin_backfill_counters(None));
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        assert_eq!(ctr.humans_count(), 1);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 1), Some(1));
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        // fractal token is transferred first
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 1).unwrap(), (1, false));
+        assert_eq!(ctr.humans_count(), 1);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 1), Some(0));
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 5).unwrap(), (1, true));
+        assert_eq!(ctr.humans_count(), 1);
+        assert_eq!(ctr.humans_count_by_extra_class(issuer1(), 1), Some(1));
+    }
+
+    #[test]
+    fn portfolio_privacy() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 3 * MINT_DEPOSIT);
+        let t1 = ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 10)),
+                mk_metadata(2, Some(START + 10)),
+            ],
+        )]);
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        let t2 = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        let all = vec![
+            (
+                fractal_mainnet(),
+                vec![
+                    mk_owned_token(t1[0], mk_metadata(1, Some(START + 10))),
+                    mk_owned_token(t1[1], mk_metadata(2, Some(START + 10))),
+                ],
+            ),
+            (
+                issuer1(),
+                vec![mk_owned_token(t2[0], mk_metadata(1, Some(START + 10)))],
+            ),
+        ];
+        let iah_only = vec![(
+            fractal_mainnet(),
+            vec![mk_owned_token(t1[0], mk_metadata(1, Some(START + 10)))],
+        )];
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert!(!ctr.portfolio_hidden(alice()));
+        ctr.set_portfolio_privacy(true);
+        assert!(ctr.portfolio_hidden(alice()));
+        assert!(!ctr.portfolio_hidden(bob()));
+
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None),
+            iah_only
+        );
+        assert!(ctr
+            .sbt_tokens_by_owner(alice(), Some(issuer1()), None, None, None)
+            .is_empty());
+        assert_eq!(
+            ctr.account_profile(alice(), None, None, None, None).sbts,
+            iah_only
+        );
+        // humanity proof is not affected
+        assert_eq!(
+            ctr.is_human(alice()),
+            vec![(fractal_mainnet(), vec![t1[0]])]
+        );
+
+        // owner can list all tokens
+        assert_eq!(
+            ctr.sbt_tokens_by_owner_private(alice(), None, None, None, None),
+            all
+        );
+        // issuer can list only own tokens
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.sbt_tokens_by_owner_private(alice(), None, None, None, None),
+            all[1..]
+        );
+        assert_eq!(
+            ctr.sbt_tokens_by_owner_private(alice(), Some(issuer1()), None, None, None),
+            all[1..]
+        );
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.set_portfolio_privacy(false);
+        assert!(!ctr.portfolio_hidden(alice()));
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None),
+            all
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be called by a registered SBT Issuer")]
+    fn sbt_tokens_by_owner_private_not_issuer() {
+        let (_, ctr) = setup(&bob(), 0);
+        ctr.sbt_tokens_by_owner_private(alice(), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "issuer can only query its own tokens")]
+    fn sbt_tokens_by_owner_private_other_issuer() {
+        let (_, ctr) = setup(&issuer1(), 0);
+        ctr.sbt_tokens_by_owner_private(alice(), Some(issuer2()), None, None, None);
+    }
+
+    #[test]
+    fn block_class() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 2 * MINT_DEPOSIT);
+        let t1 = ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 10)),
+                mk_metadata(2, Some(START + 10)),
+            ],
+        )]);
+        assert_eq!(
+            ctr.is_human(alice()),
+            vec![(fractal_mainnet(), vec![t1[0]])]
+        );
+        assert!(!ctr.is_class_blocked(alice(), fractal_mainnet(), 1));
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_block_class(alice(), fractal_mainnet(), 1));
+        assert!(!ctr.admin_block_class(alice(), fractal_mainnet(), 1));
+        assert!(ctr.is_class_blocked(alice(), fractal_mainnet(), 1));
+        assert!(!ctr.is_class_blocked(alice(), fractal_mainnet(), 2));
+        assert!(!ctr.is_class_blocked(bob(), fractal_mainnet(), 1));
+        assert!(ctr.is_human(alice()).is_empty());
+        let tokens = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].1.len(), 1);
+        assert_eq!(tokens[0].1[0].token, t1[1]);
+        // the token is still owned
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), fractal_mainnet(), None), 2);
+
+        assert!(ctr.admin_unblock_class(alice(), fractal_mainnet(), 1));
+        assert!(!ctr.admin_unblock_class(alice(), fractal_mainnet(), 1));
+        assert!(!ctr.is_class_blocked(alice(), fractal_mainnet(), 1));
+        assert_eq!(
+            ctr.is_human(alice()),
+            vec![(fractal_mainnet(), vec![t1[0]])]
+        );
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None)[0]
+                .1
+                .len(),
+            2
+        );
+
+        // block follows the token in the soul transfer
+        assert!(ctr.admin_block_class(alice(), fractal_mainnet(), 2));
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        ctr._sbt_soul_transfer(alice2(), 10).unwrap();
+        assert!(!ctr.is_class_blocked(alice(), fractal_mainnet(), 2));
+        assert!(ctr.is_class_blocked(alice2(), fractal_mainnet(), 2));
+        assert!(!ctr.is_class_blocked(alice2(), fractal_mainnet(), 1));
+        assert_eq!(
+            ctr.is_human(alice2()),
+            vec![(fractal_mainnet(), vec![t1[0]])]
+        );
+    }
+
+    #[test]
+    fn upgrade() {
+        let (mut ctx, mut ctr) = setup(&admin(), 0);
+        let code = b"new contract code".to_vec();
+        ctr.propose_upgrade(Base64VecU8(env::sha256(&code)));
+        assert_eq!(
+            ctr.proposed_upgrade(),
+            Some(Base64VecU8(env::sha256(&code)))
+        );
+        assert!(!ctr.upgrade_code_stored());
+
+        ctx.attached_deposit = MILI_NEAR;
+        testing_env!(ctx);
+        ctr._store_code(code.clone());
+        assert!(ctr.upgrade_code_stored());
+
+        ctr.apply_upgrade();
+        assert_eq!(ctr.proposed_upgrade(), None);
+        assert!(!ctr.upgrade_code_stored());
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"apply_upgrade","data":{"code_hash":"#;
+        assert!(test_utils::get_logs()[0].starts_with(exp));
+
+        // a new proposal removes the stored code
+        ctr.propose_upgrade(Base64VecU8(env::sha256(&code)));
+        ctr._store_code(code);
+        ctr.propose_upgrade(Base64VecU8(env::sha256(b"other code")));
+        assert!(!ctr.upgrade_code_stored());
+    }
+
+    #[test]
+    #[should_panic(expected = "code doesn't match the approved hash")]
+    fn upgrade_store_wrong_code() {
+        let (_, mut ctr) = setup(&admin(), MILI_NEAR);
+        ctr.propose_upgrade(Base64VecU8(env::sha256(b"new contract code")));
+        ctr._store_code(b"other code".to_vec());
+    }
+
+    #[test]
+    fn token_provenance() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 4 * MINT_DEPOSIT);
+        let t1 = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        assert_eq!(ctr.token_provenance(fractal_mainnet(), t1[0]), None);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(!ctr.token_provenance_enabled(fractal_mainnet()));
+        assert!(ctr.admin_set_token_provenance(fractal_mainnet(), true));
+        assert!(!ctr.admin_set_token_provenance(fractal_mainnet(), true));
+        assert!(ctr.token_provenance_enabled(fractal_mainnet()));
+        assert!(!ctr.token_provenance_enabled(issuer1()));
+
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        let t2 = ctr.sbt_mint(vec![(bob(), vec![mk_metadata(1, Some(START + 10))])]);
+        let t3 = ctr.sbt_mint_iah(vec![(
+            alice(),
+            vec![
+                mk_metadata(2, Some(START + 10)),
+                mk_metadata(3, Some(START + 10)),
+            ],
+        )]);
+        assert_eq!(t3, vec![3, 4]);
+        // provenance is not recorded for tokens minted before enabling it
+        assert_eq!(ctr.token_provenance(fractal_mainnet(), t1[0]), None);
+        assert_eq!(
+            ctr.token_provenance(fractal_mainnet(), t2[0]),
+            Some(TokenProvenance {
+                method: MintMethod::SbtMint,
+                batch: 2
+            })
+        );
+        for t in t3.iter() {
+            assert_eq!(
+                ctr.token_provenance(fractal_mainnet(), *t),
+                Some(TokenProvenance {
+                    method: MintMethod::SbtMintIah,
+                    batch: 3
+                })
+            );
+        }
+        assert_eq!(ctr.token_provenance(issuer1(), 3), None);
+
+        // burned token provenance is removed
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(fractal_mainnet(), vec![3], None);
+        assert_eq!(ctr.token_provenance(fractal_mainnet(), 3), None);
+        assert!(ctr.token_provenance(fractal_mainnet(), 4).is_some());
+
+        // tokens minted after disabling have no provenance
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_set_token_provenance(fractal_mainnet(), false));
+        ctx.predecessor_account_id = fractal_mainnet();
         testing_env!(ctx);
-        ctr.sbt_recover(alice(), alice2());
-        // sbt_recover should not ban the source account
-        assert!(!ctr.is_banned(alice()));
-        assert!(!ctr.is_banned(alice2()));
+        let t5 = ctr.sbt_mint(vec![(bob(), vec![mk_metadata(2, Some(START + 10))])]);
+        assert_eq!(ctr.token_provenance(fractal_mainnet(), t5[0]), None);
+        assert!(ctr.token_provenance(fractal_mainnet(), 4).is_some());
     }
 
     #[test]
-    #[should_panic(expected = "account alice.near is banned")]
-    fn sbt_mint_to_banned_account() {
-        let (_, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
-        let m1_1 = mk_metadata(1, Some(START + 10));
+    fn token_tombstone() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 4 * MINT_DEPOSIT);
+        let t1 = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        assert_eq!(ctr.tombstone_policy(fractal_mainnet()), None);
+        ctr.sbt_set_tombstone_policy(Some(TombstonePolicy {
+            retention_ms: Some(100),
+        }));
+        assert_eq!(
+            ctr.tombstone_policy(fractal_mainnet()),
+            Some(TombstonePolicy {
+                retention_ms: Some(100)
+            })
+        );
+        assert_eq!(ctr.tombstone_policy(issuer1()), None);
+        let t2 = ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(2, Some(START + 10))]),
+            (bob(), vec![mk_metadata(1, Some(START + 10))]),
+        ]);
+        let t3 = ctr.sbt_mint(vec![(carol(), vec![mk_metadata(1, Some(START + 10))])]);
 
-        //ban alice account
-        ctr.banlist.insert(&alice());
-        assert!(ctr.is_banned(alice()));
+        // burned by the owner
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(fractal_mainnet(), vec![t1[0]], None);
+        let tombstone = TokenTombstone {
+            owner_hash: Base64VecU8(env::sha256(alice().as_bytes())),
+            class: 1,
+            burned_at: START,
+        };
+        assert_eq!(
+            ctr.token_tombstone(fractal_mainnet(), t1[0]),
+            Some(tombstone)
+        );
+        assert_eq!(ctr.token_tombstone(fractal_mainnet(), t2[0]), None);
+        assert_eq!(ctr.token_tombstone(issuer1(), t1[0]), None);
 
-        //try to mint to a banned account
-        ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
-    }
+        // burned by the owner: sbt_burn_all
+        ctr.sbt_burn_all();
+        assert_eq!(
+            ctr.token_tombstone(fractal_mainnet(), t2[0]).unwrap().class,
+            2
+        );
 
-    #[test]
-    fn sbt_tokens_by_owner_non_expired() {
-        let (mut ctx, mut ctr) = setup(&issuer1(), 4 * MINT_DEPOSIT);
-        ctx.block_timestamp = START * MSECOND; // 11 seconds
+        // burned by the issuer
+        ctx.predecessor_account_id = fractal_mainnet();
+        ctx.block_timestamp = (START + 50) * MSECOND;
         testing_env!(ctx.clone());
+        ctr.sbt_revoke(vec![t2[1]], true);
+        ctr.sbt_revoke_by_owner(carol(), true);
+        assert_eq!(
+            ctr.token_tombstone(fractal_mainnet(), t2[1]),
+            Some(TokenTombstone {
+                owner_hash: Base64VecU8(env::sha256(bob().as_bytes())),
+                class: 1,
+                burned_at: START + 50,
+            })
+        );
+        assert!(ctr.token_tombstone(fractal_mainnet(), t3[0]).is_some());
 
-        let m1_1 = mk_metadata(1, Some(START));
-        let m1_2 = mk_metadata(2, Some(START));
-        let m1_3 = mk_metadata(3, Some(START + 100));
-        let m1_4 = mk_metadata(4, Some(START + 100));
-        ctr.sbt_mint(vec![(alice(), vec![m1_1, m1_2, m1_3, m1_4])]);
+        // other accounts can only prune tombstones older than the retention period
+        ctx.predecessor_account_id = dan();
+        ctx.block_timestamp = (START + 100) * MSECOND;
+        testing_env!(ctx.clone());
+        let all = vec![t1[0], t2[0], t2[1], t3[0], 100];
+        assert_eq!(ctr.prune_tombstones(fractal_mainnet(), all.clone()), 2);
+        assert_eq!(ctr.token_tombstone(fractal_mainnet(), t1[0]), None);
+        assert_eq!(ctr.token_tombstone(fractal_mainnet(), t2[0]), None);
+        assert!(ctr.token_tombstone(fractal_mainnet(), t2[1]).is_some());
+
+        // the issuer can prune any tombstone
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.prune_tombstones(fractal_mainnet(), vec![t2[1]]), 1);
+        assert_eq!(ctr.token_tombstone(fractal_mainnet(), t2[1]), None);
 
-        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(true));
-        assert_eq!(res[0].1.len(), 4);
-        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
-        assert_eq!(res[0].1.len(), 4);
+        // without a retention period only the issuer can prune
+        ctr.sbt_set_tombstone_policy(Some(TombstonePolicy { retention_ms: None }));
+        ctx.predecessor_account_id = dan();
+        ctx.block_timestamp = (START + 1000) * MSECOND;
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.prune_tombstones(fractal_mainnet(), all), 0);
+        assert!(ctr.token_tombstone(fractal_mainnet(), t3[0]).is_some());
 
-        let res = ctr.sbt_tokens(issuer1(), None, None, Some(true));
-        assert_eq!(res.len(), 4);
-        let res = ctr.sbt_tokens(issuer1(), None, None, Some(false));
-        assert_eq!(res.len(), 4);
-        let res = ctr.sbt_tokens(issuer1(), None, None, None);
-        assert_eq!(res.len(), 4);
+        // tombstones are not recorded after disabling the policy
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx.clone());
+        ctr.sbt_set_tombstone_policy(None);
+        assert_eq!(ctr.tombstone_policy(fractal_mainnet()), None);
+        let t4 = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        ctr.sbt_revoke(t4.clone(), true);
+        assert_eq!(ctr.token_tombstone(fractal_mainnet(), t4[0]), None);
+        assert!(ctr.token_tombstone(fractal_mainnet(), t3[0]).is_some());
+    }
 
-        // fast forward so the first two sbts are expired
-        ctx.block_timestamp = (START + 50) * MSECOND;
-        testing_env!(ctx);
+    #[test]
+    fn issuer_stats() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 6 * MINT_DEPOSIT);
+        let zero = IssuerStats::default();
+        assert_eq!(ctr.issuer_stats(issuer1()), zero);
+        assert_eq!(ctr.issuer_stats(alice()), zero);
 
-        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(true));
-        assert_eq!(res[0].1.len(), 4);
-        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, Some(false));
-        assert_eq!(res[0].1.len(), 2);
-        let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
-        assert_eq!(res[0].1.len(), 2);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, None),
+                mk_metadata(2, None),
+                mk_metadata(3, None),
+                mk_metadata(4, None),
+            ],
+        )]);
+        ctr.sbt_mint(vec![(
+            bob(),
+            vec![mk_metadata(1, None), mk_metadata(2, None)],
+        )]);
 
-        let res = ctr.sbt_tokens(issuer1(), None, None, Some(true));
-        assert_eq!(res.len(), 4);
-        let res = ctr.sbt_tokens(issuer1(), None, None, Some(false));
-        assert_eq!(res.len(), 2);
-        let res = ctr.sbt_tokens(issuer1(), None, None, None);
-        assert_eq!(res.len(), 2);
+        ctr.sbt_renew(vec![1, 2, 3], START + 100);
+        ctr.sbt_revoke(vec![1], false);
+        ctr.sbt_revoke(vec![2], true);
+        assert_eq!(
+            ctr.issuer_stats(issuer1()),
+            IssuerStats {
+                total_burned: 1,
+                total_revoked: 2,
+                total_renewed: 3,
+            }
+        );
+
+        ctr.sbt_revoke_by_owner(bob(), false);
+        ctr.sbt_revoke_by_owner(bob(), true);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        ctr.sbt_burn(issuer1(), vec![3], None);
+        ctr.sbt_burn_all();
+        assert_eq!(
+            ctr.issuer_stats(issuer1()),
+            IssuerStats {
+                total_burned: 6,
+                total_revoked: 6,
+                total_renewed: 3,
+            }
+        );
+        assert_eq!(ctr.issuer_stats(issuer2()), zero);
     }
 
     #[test]
@@ -2417,10 +6484,12 @@ mod tests {
         let log_burn = mk_log_str(
             "burn",
             &format!(r#"{{"issuer":"{}","tokens":[1,2]}}"#, issuer2()),
+            3,
         );
         let log_revoke = mk_log_str(
             "revoke",
             &format!(r#"{{"issuer":"{}","tokens":[1,2]}}"#, issuer2()),
+            4,
         );
         assert_eq!(test_utils::get_logs().len(), 3);
         assert_eq!(test_utils::get_logs()[1], log_burn[0]);
@@ -2453,6 +6522,7 @@ mod tests {
         let log_revoke = mk_log_str(
             "revoke",
             &format!(r#"{{"issuer":"{}","tokens":[1,2]}}"#, issuer1()),
+            5,
         );
         assert_eq!(test_utils::get_logs().len(), 1);
         assert_eq!(test_utils::get_logs()[0], log_revoke[0]);
@@ -2485,6 +6555,78 @@ mod tests {
         assert!(ctr.sbt_tokens(issuer2(), None, None, None).is_empty());
     }
 
+    #[test]
+    fn sbt_revoke_by_owner_classes() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 100)),
+                mk_metadata(2, Some(START + 100)),
+                mk_metadata(3, Some(START + 100)),
+            ],
+        )]);
+        ctr.sbt_mint(vec![(bob(), vec![mk_metadata(1, Some(START + 100))])]);
+
+        // burn classes 1 and 3, class 5 is not owned by alice
+        testing_env!(ctx.clone());
+        assert!(ctr.sbt_revoke_by_owner_classes(alice(), vec![1, 3, 5], true));
+        let log_burn = mk_log_str(
+            "burn",
+            &format!(r#"{{"issuer":"{}","tokens":[1,3]}}"#, issuer1()),
+            3,
+        );
+        let log_revoke = mk_log_str(
+            "revoke",
+            &format!(r#"{{"issuer":"{}","tokens":[1,3]}}"#, issuer1()),
+            4,
+        );
+        assert_eq!(
+            test_utils::get_logs(),
+            vec![log_burn[0].clone(), log_revoke[0].clone()]
+        );
+        assert_eq!(ctr.sbt_supply(issuer1()), 2);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 1), 1);
+        assert_eq!(ctr.sbt_supply_by_class(issuer1(), 3), 0);
+        assert_eq!(ctr.sbt_supply_by_owner(bob(), issuer1(), Some(1)), 1);
+
+        // revoke (not burn) class 2
+        testing_env!(ctx.clone());
+        assert!(ctr.sbt_revoke_by_owner_classes(alice(), vec![2], false));
+        let log_revoke = mk_log_str(
+            "revoke",
+            &format!(r#"{{"issuer":"{}","tokens":[2]}}"#, issuer1()),
+            5,
+        );
+        assert_eq!(test_utils::get_logs(), log_revoke);
+        ctx.block_timestamp = (START + 1) * MSECOND;
+        testing_env!(ctx.clone());
+        assert!(ctr
+            .sbt_tokens_by_owner(alice(), None, None, None, None)
+            .is_empty());
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+
+        // already revoked tokens are skipped
+        assert!(ctr.sbt_revoke_by_owner_classes(alice(), vec![2], false));
+        assert!(test_utils::get_logs().is_empty());
+        assert_eq!(
+            ctr.issuer_stats(issuer1()),
+            IssuerStats {
+                total_burned: 2,
+                total_revoked: 3,
+                total_renewed: 0,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "classes must be a non empty list of at most 25 elements")]
+    fn sbt_revoke_by_owner_empty_classes() {
+        let (_, mut ctr) = setup(&issuer1(), MINT_DEPOSIT);
+        ctr.sbt_revoke_by_owner_classes(alice(), vec![], true);
+    }
+
     #[test]
     fn sbt_revoke_by_owner_batch() {
         let (mut ctx, mut ctr) = setup(&issuer1(), 20 * MINT_DEPOSIT);
@@ -2562,11 +6704,12 @@ mod tests {
         // revoke (burn) tokens minted for alice from issuer2
         ctx.predecessor_account_id = issuer2();
         ctx.prepaid_gas = Gas::ONE_TERA.mul(110);
-        testing_env!(ctx);
+        testing_env!(ctx.clone());
         let res = ctr.sbt_revoke_by_owner(alice(), true);
         assert!(res);
 
         // make sure the balances are updated correctly
+        testing_env!(ctx); // reset gas for queries
         let res = ctr.sbt_tokens_by_owner(alice(), Some(issuer2()), None, None, None);
         assert_eq!(res.len(), 0);
     }
@@ -2663,6 +6806,256 @@ mod tests {
         assert_eq!(ctr.is_human(bob()), vec![]);
     }
 
+    #[test]
+    fn is_human_batch() {
+        let (_, mut ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        ctr.sbt_mint(vec![(bob(), vec![mk_metadata(2, None)])]);
+        ctr.sbt_mint(vec![(carol(), vec![mk_metadata(1, None)])]);
+
+        assert_eq!(ctr.is_human_batch(vec![]), vec![]);
+        assert_eq!(
+            ctr.is_human_batch(vec![carol(), bob(), alice(), dan()]),
+            vec![
+                (carol(), vec![(fractal_mainnet(), vec![3])]),
+                (bob(), vec![]),
+                (alice(), vec![(fractal_mainnet(), vec![1])]),
+                (dan(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn account_profile() {
+        let (_, mut ctr) = setup(&fractal_mainnet(), 2 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(
+            alice(),
+            vec![
+                mk_metadata(1, Some(START + 10)),
+                mk_metadata(2, Some(START + 10)),
+            ],
+        )]);
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+
+        let p = ctr.account_profile(alice(), None, None, None, None);
+        assert!(p.is_human);
+        assert!(!p.banned);
+        assert_eq!(p.flag, Some(AccountFlag::Verified));
+        assert_eq!(p.limit, MAX_LIMIT);
+        assert_eq!(
+            p.sbts,
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None)
+        );
+        assert_eq!(p.sbts[0].1.len(), 2);
+
+        let p = ctr.account_profile(alice(), Some(fractal_mainnet()), Some(2), Some(1), None);
+        assert_eq!(p.limit, 1);
+        assert_eq!(p.sbts[0].1.len(), 1);
+        assert_eq!(p.sbts[0].1[0].token, 2);
+
+        ctr.banlist.insert(&carol());
+        assert_eq!(
+            ctr.account_profile(carol(), None, None, None, None),
+            AccountProfile {
+                is_human: false,
+                banned: true,
+                flag: None,
+                sbts: vec![],
+                limit: MAX_LIMIT,
+            }
+        );
+    }
+
+    #[test]
+    fn account_status() {
+        let (_, mut ctr) = setup(&fractal_mainnet(), 2 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        ctr.sbt_mint(vec![(bob(), vec![mk_metadata(1, None)])]);
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![bob()],
+            "memo".to_owned(),
+            None,
+        );
+        ctr.banlist.insert(&carol());
+
+        assert_eq!(
+            ctr.account_status(alice()),
+            AccountStatus {
+                is_human: true,
+                banned: false,
+                flag: Some(AccountFlag::Verified),
+                proof: vec![(fractal_mainnet(), vec![1])],
+            }
+        );
+        assert_eq!(
+            ctr.account_status(bob()),
+            AccountStatus {
+                is_human: false,
+                banned: false,
+                flag: Some(AccountFlag::Blacklisted),
+                proof: vec![],
+            }
+        );
+        assert_eq!(
+            ctr.account_status(carol()),
+            AccountStatus {
+                is_human: false,
+                banned: true,
+                flag: None,
+                proof: vec![],
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "can't check more than 100 accounts")]
+    fn is_human_batch_too_many_accounts() {
+        let (_, ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
+        ctr.is_human_batch(vec![alice(); MAX_IS_HUMAN_BATCH + 1]);
+    }
+
+    #[test]
+    fn is_human_class_set() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        ctr.sbt_mint(vec![(bob(), vec![mk_metadata(1, None)])]);
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(
+            bob(),
+            vec![mk_metadata(2, None), mk_metadata(3, None)],
+        )]);
+        ctr.sbt_mint(vec![(
+            carol(),
+            vec![mk_metadata(2, None), mk_metadata(3, None)],
+        )]);
+        ctr.sbt_mint(vec![(dan(), vec![mk_metadata(2, None)])]);
+
+        let class_set = vec![(fractal_mainnet(), vec![1]), (issuer1(), vec![2, 3])];
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_set_iah_class_set(class_set.clone(), ClassSetMode::All)
+            .unwrap();
+        assert_eq!(ctr.iah_class_set(), class_set);
+        assert_eq!(ctr.iah_class_set_mode(), ClassSetMode::All);
+
+        assert_eq!(ctr.is_human(alice()), vec![]);
+        assert_eq!(
+            ctr.is_human(bob()),
+            vec![(fractal_mainnet(), vec![2]), (issuer1(), vec![1, 2])]
+        );
+        assert_eq!(ctr.is_human(carol()), vec![]);
+        assert_eq!(ctr.is_human(dan()), vec![]);
+
+        ctr.admin_set_iah_class_set(class_set, ClassSetMode::Any)
+            .unwrap();
+        assert_eq!(ctr.iah_class_set_mode(), ClassSetMode::Any);
+        assert_eq!(ctr.is_human(alice()), vec![(fractal_mainnet(), vec![1])]);
+        assert_eq!(
+            ctr.is_human(bob()),
+            vec![(fractal_mainnet(), vec![2]), (issuer1(), vec![1, 2])]
+        );
+        assert_eq!(ctr.is_human(carol()), vec![(issuer1(), vec![3, 4])]);
+        // dan doesn't have all required classes of any issuer
+        assert_eq!(ctr.is_human(dan()), vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "issuer alice.near is not registered")]
+    fn admin_set_iah_class_set_not_issuer() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 0);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx);
+        ctr.admin_set_iah_class_set(
+            vec![(fractal_mainnet(), vec![1]), (alice(), vec![1])],
+            ClassSetMode::Any,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn admin_set_iah_class_set_not_authority() {
+        let (_, mut ctr) = setup(&fractal_mainnet(), 0);
+        ctr.admin_set_iah_class_set(vec![(fractal_mainnet(), vec![1])], ClassSetMode::All)
+            .unwrap();
+    }
+
+    #[test]
+    fn registry_limits() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 150 * MINT_DEPOSIT);
+        assert_eq!(ctr.limits(), Limits::default());
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        let max = DEFAULT_MAX_IAH_CLASSES as u64;
+        let too_many = vec![
+            (fractal_mainnet(), (1..=max).collect()),
+            (issuer1(), vec![1]),
+        ];
+        assert_eq!(
+            ctr.admin_set_iah_class_set(too_many.clone(), ClassSetMode::All),
+            Err(LimitErr::TooManyIahClasses(DEFAULT_MAX_IAH_CLASSES))
+        );
+        assert_eq!(ctr.iah_class_set(), vec![(fractal_mainnet(), vec![1])]);
+
+        let class_set = vec![(fractal_mainnet(), vec![1]), (issuer1(), vec![1, 2])];
+        ctr.admin_set_iah_class_set(class_set, ClassSetMode::All)
+            .unwrap();
+        // the current class set has 3 classes
+        let limits = Limits {
+            max_iah_classes: 2,
+            max_mint_batch: 2,
+        };
+        assert_eq!(
+            ctr.admin_set_limits(limits.clone()),
+            Err(LimitErr::TooManyIahClasses(2))
+        );
+        assert_eq!(ctr.limits(), Limits::default());
+
+        let limits = Limits {
+            max_iah_classes: 3,
+            max_mint_batch: 2,
+        };
+        ctr.admin_set_limits(limits.clone()).unwrap();
+        assert_eq!(ctr.limits(), limits);
+        assert_eq!(
+            ctr.limits.check_mint_batch(3),
+            Err(LimitErr::MintBatchTooLarge(2))
+        );
+
+        // batch within the limit
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_mint(vec![
+            (alice(), vec![mk_metadata(1, None)]),
+            (bob(), vec![mk_metadata(1, None)]),
+        ]);
+        assert_eq!(ctr.sbt_supply(issuer1()), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't mint more than 2 tokens in a single call")]
+    fn sbt_mint_batch_too_large() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 150 * MINT_DEPOSIT);
+        ctr.limits.max_mint_batch = 2;
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_mint(vec![(alice(), mk_batch_metadata(3))]);
+    }
+
     #[test]
     fn is_human_expires_at_none() {
         let (_, mut ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
@@ -2677,7 +7070,7 @@ mod tests {
     #[test]
     fn is_human_multiple_classes() {
         let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
-        ctr.iah_sbts.1 = vec![1, 3];
+        ctr.iah_sbts[0].1 = vec![1, 3];
         ctx.current_account_id = AccountId::new_unchecked("registry.i-am-human.near".to_string());
         testing_env!(ctx);
 
@@ -2692,7 +7085,10 @@ mod tests {
         assert_eq!(ctr.is_human(alice()), vec![]);
         assert_eq!(ctr.is_human(bob()), vec![]);
         assert_eq!(ctr.is_human(carol()), vec![]);
-        assert_eq!(ctr.is_human(dan()), vec![(fractal_mainnet(), vec![6, 5])]);
+        // proof is sorted by token id, regardless of the class order
+        assert_eq!(ctr.is_human(dan()), vec![(fractal_mainnet(), vec![5, 6])]);
+        ctr.iah_sbts[0].1 = vec![3, 1];
+        assert_eq!(ctr.is_human(dan()), vec![(fractal_mainnet(), vec![5, 6])]);
     }
 
     #[test]
@@ -2769,25 +7165,25 @@ mod tests {
 
         let alice_tokens: Vec<(u64, u64)> = (1..=20).map(|i| (i, i)).collect();
 
-        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer1()), 20);
+        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer1()), None, 20);
         assert_eq!(res, &alice_tokens[0..10]);
-        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer2()), 20);
+        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer2()), None, 20);
         assert_eq!(res, &alice_tokens[0..10]);
-        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer2()), 20);
+        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer2()), None, 20);
         assert_eq!(res, &alice_tokens[0..10]);
 
         // mint more tokens for issuer1()
         ctx.predecessor_account_id = issuer1();
         testing_env!(ctx);
         ctr.sbt_mint(vec![(alice(), batch_metadata[10..20].to_vec())]);
-        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer1()), 20);
+        let res = ctr.sbt_token_ids_by_owner(alice(), ctr.assert_issuer(&issuer1()), None, 20);
         assert_eq!(res, alice_tokens);
     }
 
     #[test]
     fn is_human_multiple_classes_with_expired_tokens() {
         let (mut ctx, mut ctr) = setup(&fractal_mainnet(), 150 * MINT_DEPOSIT);
-        ctr.iah_sbts.1 = vec![1, 3];
+        ctr.iah_sbts[0].1 = vec![1, 3];
         ctx.current_account_id = AccountId::new_unchecked("registry.i-am-human.near".to_string());
         testing_env!(ctx.clone());
 
@@ -2818,10 +7214,12 @@ mod tests {
         let log_revoke = mk_log_str(
             "revoke",
             &format!(r#"{{"issuer":"{}","tokens":[1]}}"#, fractal_mainnet()),
+            2,
         );
         let log_burn = mk_log_str(
             "burn",
             &format!(r#"{{"issuer":"{}","tokens":[1]}}"#, fractal_mainnet()),
+            3,
         );
 
         // check only revoke event is emitted
@@ -2834,6 +7232,12 @@ mod tests {
         // revoke (burn == true)
         ctr.sbt_revoke(tokens, true);
 
+        let log_revoke = mk_log_str(
+            "revoke",
+            &format!(r#"{{"issuer":"{}","tokens":[1]}}"#, fractal_mainnet()),
+            4,
+        );
+
         // check both burn and revoke events are emitted
         assert_eq!(test_utils::get_logs().len(), 2); // -> only 1 event is emmited
         assert_eq!(test_utils::get_logs(), vec![log_burn, log_revoke].concat());
@@ -2879,9 +7283,9 @@ mod tests {
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
         let res = ctr._sbt_burn_all(20);
-        assert!(!res);
+        assert_eq!(res, (20, 10, false));
         let res = ctr._sbt_burn_all(20);
-        assert!(res); // make sure that after the second call true is returned (all tokens have been burned)
+        assert_eq!(res, (10, 0, true)); // make sure that after the second call true is returned (all tokens have been burned)
 
         // make sure the balances are updated correctly
         let res = ctr.sbt_tokens_by_owner(alice(), None, None, None, None);
@@ -2919,7 +7323,7 @@ mod tests {
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
         loop {
-            if ctr._sbt_burn_all(10) {
+            if ctr._sbt_burn_all(10).2 {
                 break;
             }
         }
@@ -2933,6 +7337,7 @@ mod tests {
                 r#"{{"issuer":"{}","tokens":[1,2,3,4,5,6,7,8,9,10]}}"#,
                 issuer1()
             ),
+            4,
         );
 
         let log_burn_issuer_2 = mk_log_str(
@@ -2941,6 +7346,7 @@ mod tests {
                 r#"{{"issuer":"{}","tokens":[1,2,3,4,5,6,7,8,9,10]}}"#,
                 issuer2()
             ),
+            5,
         );
 
         let log_burn_issuer_3 = mk_log_str(
@@ -2949,6 +7355,7 @@ mod tests {
                 r#"{{"issuer":"{}","tokens":[1,2,3,4,5,6,7,8,9,10]}}"#,
                 issuer3()
             ),
+            6,
         );
 
         assert_eq!(test_utils::get_logs()[0], log_burn_issuer_1[0]);
@@ -3004,7 +7411,7 @@ mod tests {
         loop {
             ctx.prepaid_gas = max_gas();
             testing_env!(ctx.clone());
-            if ctr._sbt_burn_all(41) {
+            if ctr._sbt_burn_all(41).2 {
                 //anything above 41 fails due to MaxGasLimitExceeded error
                 break;
             }
@@ -3054,6 +7461,7 @@ mod tests {
                 fractal_mainnet(),
                 tid2
             ),
+            3,
         );
         assert_eq!(test_utils::get_logs(), log);
     }
@@ -3066,103 +7474,644 @@ mod tests {
         ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
         assert_eq!(ctr.is_human(alice()), vec![(fractal_mainnet(), vec![1])]);
 
-        ctx.predecessor_account_id = alice();
-        testing_env!(ctx);
+        let consumer = AccountId::new_unchecked("registry.i-am-human.near".to_string());
+        let unregistered = AccountId::new_unchecked("unregistered.near".to_string());
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_add_consumer(consumer.clone()));
+        assert!(!ctr.admin_add_consumer(consumer.clone()));
 
+        ctx.predecessor_account_id = alice();
+        assert_eq!(
+            ctr.consumer_stats(consumer.clone()),
+            ConsumerStats::default()
+        );
+        // calls to not registered consumers are not counted
+        testing_env!(ctx.clone());
         ctr.is_human_call(
-            AccountId::new_unchecked("registry.i-am-human.near".to_string()),
+            unregistered.clone(),
             "function_name".to_string(),
             "{}".to_string(),
+            None,
         )
         .unwrap();
+        assert!(!ctr.consumer_stats.contains_key(&unregistered));
+        for _ in 0..2 {
+            testing_env!(ctx.clone()); // reset gas
+            ctr.is_human_call(
+                consumer.clone(),
+                "function_name".to_string(),
+                "{}".to_string(),
+                None,
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            ctr.consumer_stats(consumer),
+            ConsumerStats {
+                calls: 2,
+                lock_calls: 0
+            }
+        );
+    }
+
+    #[test]
+    fn is_human_call_forward_deposit() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        let consumer = AccountId::new_unchecked("poll.near".to_string());
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_add_consumer(consumer.clone());
+
+        ctx.predecessor_account_id = alice();
+        ctx.attached_deposit = 10 * MILI_NEAR;
+        testing_env!(ctx.clone());
+        let call = |ctr: &mut Contract, deposit: Option<u128>| {
+            ctr.is_human_call(
+                consumer.clone(),
+                "vote".to_string(),
+                "{}".to_string(),
+                deposit.map(U128),
+            )
+            .map(|_| ())
+        };
+        assert_eq!(
+            call(&mut ctr, Some(10 * MILI_NEAR + 1)),
+            Err(IsHumanCallErr::ForwardDepositTooBig(10 * MILI_NEAR))
+        );
+
+        let deposits = || -> Vec<(AccountId, Balance)> {
+            test_utils::get_created_receipts()
+                .into_iter()
+                .flat_map(|r| {
+                    r.actions.into_iter().filter_map(move |a| match a {
+                        VmAction::FunctionCall { deposit, .. } => {
+                            Some((r.receiver_id.clone(), deposit))
+                        }
+                        VmAction::Transfer { deposit } => Some((r.receiver_id.clone(), deposit)),
+                        _ => None,
+                    })
+                })
+                .collect()
+        };
+
+        // whole deposit is forwarded by default
+        testing_env!(ctx.clone());
+        assert_eq!(call(&mut ctr, None), Ok(()));
+        assert_eq!(deposits(), vec![(consumer.clone(), 10 * MILI_NEAR)]);
+
+        // the surplus is refunded to the caller
+        testing_env!(ctx.clone());
+        assert_eq!(call(&mut ctr, Some(3 * MILI_NEAR)), Ok(()));
+        assert_eq!(
+            deposits(),
+            vec![(alice(), 7 * MILI_NEAR), (consumer.clone(), 3 * MILI_NEAR)]
+        );
+
+        testing_env!(ctx.clone());
+        assert_eq!(call(&mut ctr, Some(10 * MILI_NEAR)), Ok(()));
+        assert_eq!(deposits(), vec![(consumer.clone(), 10 * MILI_NEAR)]);
+        assert_eq!(ctr.consumer_stats(consumer).calls, 3);
+    }
+
+    #[test]
+    fn is_human_call_gov_banned() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        let gov = AccountId::new_unchecked("congress.near".to_string());
+        let other = AccountId::new_unchecked("game.near".to_string());
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_add_gov_contract(gov.clone()));
+        assert!(!ctr.admin_add_gov_contract(gov.clone()));
+        assert_eq!(ctr.gov_contracts(), vec![gov.clone()]);
+        ctr.admin_add_authorized_flagger(admin());
+        ctr.admin_flag_accounts(AccountFlag::GovBan, vec![alice()], "memo".to_owned(), None);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        // account is still a human
+        assert_eq!(ctr.is_human(alice()), vec![(fractal_mainnet(), vec![1])]);
+        let call = |ctr: &mut Contract, target: &AccountId| {
+            ctr.is_human_call(target.clone(), "vote".to_owned(), "{}".to_owned(), None)
+                .map(|_| ())
+        };
+        assert_eq!(call(&mut ctr, &gov), Err(IsHumanCallErr::GovBanned));
+        assert_eq!(call(&mut ctr, &other), Ok(()));
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.is_human_call_lock(gov.clone(), "vote".to_owned(), "{}".to_owned(), 0, true)
+                .map(|_| ()),
+            Err(IsHumanCallErr::GovBanned)
+        );
+        ctx.attached_deposit = 0;
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.is_human_call_batch(vec![
+                (other.clone(), "play".to_owned(), "{}".to_owned(), U128(0)),
+                (gov.clone(), "vote".to_owned(), "{}".to_owned(), U128(0)),
+            ])
+            .map(|_| ()),
+            Err(IsHumanCallErr::GovBanned)
+        );
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_remove_gov_contract(gov.clone()));
+        assert!(!ctr.admin_remove_gov_contract(gov.clone()));
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx);
+        assert_eq!(call(&mut ctr, &gov), Ok(()));
+    }
+
+    #[test]
+    fn is_human_call_batch() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START))])]);
+
+        let c1 = AccountId::new_unchecked("c1.near".to_string());
+        let c2 = AccountId::new_unchecked("c2.near".to_string());
+        let call = |ctr: &AccountId, deposit: u128| {
+            (
+                ctr.clone(),
+                "function_name".to_string(),
+                "{}".to_string(),
+                U128(deposit),
+            )
+        };
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_add_consumer(c1.clone());
+        ctr.admin_add_consumer(c2.clone());
+        ctx.predecessor_account_id = alice();
+        ctx.attached_deposit = 3;
+        testing_env!(ctx.clone());
+        assert!(ctr
+            .is_human_call_batch(vec![call(&c1, 1), call(&c2, 2), call(&c1, 0)])
+            .is_ok());
+        assert_eq!(ctr.consumer_stats(c1.clone()).calls, 2);
+        assert_eq!(ctr.consumer_stats(c2.clone()).calls, 1);
+
+        testing_env!(ctx.clone());
+        assert_eq!(
+            ctr.is_human_call_batch(vec![call(&c1, 1)]).err(),
+            Some(IsHumanCallErr::WrongDeposit(1))
+        );
+        assert_eq!(
+            ctr.is_human_call_batch(vec![]).err(),
+            Some(IsHumanCallErr::WrongBatchSize(MAX_IS_HUMAN_CALL_BATCH))
+        );
+        assert_eq!(
+            ctr.is_human_call_batch(vec![call(&c1, 0); MAX_IS_HUMAN_CALL_BATCH + 1])
+                .err(),
+            Some(IsHumanCallErr::WrongBatchSize(MAX_IS_HUMAN_CALL_BATCH))
+        );
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx);
+        assert_eq!(
+            ctr.is_human_call_batch(vec![call(&c1, 3)]).err(),
+            Some(IsHumanCallErr::NotHuman)
+        );
+        assert_eq!(ctr.consumer_stats(c1).calls, 2);
+    }
+
+    #[test]
+    fn is_human_call_fail() {
+        let (_, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+
+        match ctr.is_human_call(
+            AccountId::new_unchecked("registry.i-am-human.near".to_string()),
+            "function_name".to_string(),
+            "{}".to_string(),
+            None,
+        ) {
+            Err(err) => assert_eq!(err, IsHumanCallErr::NotHuman),
+            Ok(_) => panic!("expecting Err(IsHumanCallErr::NotHuman)"),
+        };
+        // failed calls are not counted
+        assert_eq!(
+            ctr.consumer_stats(AccountId::new_unchecked(
+                "registry.i-am-human.near".to_string()
+            )),
+            ConsumerStats::default()
+        );
+    }
+
+    #[test]
+    fn admin_set_authorized_flaggers() {
+        let (mut ctx, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+
+        let flaggers = [dan()].to_vec();
+        ctr.admin_set_authorized_flaggers(flaggers);
+
+        ctx.predecessor_account_id = dan();
+        testing_env!(ctx);
+        ctr.assert_authorized_flagger();
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn admin_set_authorized_flaggers_fail() {
+        let (mut ctx, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+
+        ctx.predecessor_account_id = dan();
+        testing_env!(ctx);
+
+        let flaggers = [dan()].to_vec();
+        ctr.admin_set_authorized_flaggers(flaggers);
+    }
+
+    #[test]
+    fn admin_flag_accounts_expiration() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), MINT_DEPOSIT);
+        let t1 = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 100))])]);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_add_authorized_flagger(admin());
+
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![alice()],
+            "memo".to_owned(),
+            Some(START + 10),
+        );
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_expires_at","data":{"accounts":["alice.near"],"expires_at":20},"seq":2}"#;
+        assert_eq!(test_utils::get_logs()[0], exp);
+        assert_eq!(ctr.account_flagged(alice()), Some(AccountFlag::Blacklisted));
+        assert_eq!(ctr.account_flag_expires_at(alice()), Some(START + 10));
+        assert!(ctr.is_human(alice()).is_empty());
+        assert_eq!(ctr.flagged_accounts(None, None, None).len(), 1);
+
+        // flag stops affecting the account after the deadline
+        ctx.block_timestamp = (START + 10) * MSECOND;
+        testing_env!(ctx);
+        assert_eq!(ctr.account_flagged(alice()), None);
+        assert_eq!(ctr.account_flag_expires_at(alice()), None);
+        assert_eq!(ctr.is_human(alice()), vec![(fractal_mainnet(), t1)]);
+        assert!(ctr.flagged_accounts(None, None, None).is_empty());
+        // expired flag can be still removed
+        ctr.admin_unflag_accounts(vec![alice()], "memo".to_owned());
+        assert!(ctr.flagged.get(&alice()).is_none());
+    }
+
+    #[test]
+    fn admin_flag_accounts() {
+        let (_, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            [dan(), bob()].to_vec(),
+            "memo".to_owned(),
+            None,
+        );
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            [carol()].to_vec(),
+            "memo".to_owned(),
+            None,
+        );
+
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_blacklisted","data":["dan.near","bob.near"],"seq":1}"#;
+        // check only flag event is emitted
+        assert_eq!(test_utils::get_logs().len(), 2);
+        assert_eq!(test_utils::get_logs()[0], exp);
+
+        assert_eq!(ctr.account_flagged(dan()), Some(AccountFlag::Blacklisted));
+        assert_eq!(ctr.account_flagged(bob()), Some(AccountFlag::Blacklisted));
+        assert_eq!(ctr.account_flagged(carol()), Some(AccountFlag::Verified));
+
+        ctr.admin_unflag_accounts([dan()].to_vec(), "memo".to_owned());
+
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"unflag","data":["dan.near"],"seq":3}"#;
+        assert_eq!(test_utils::get_logs().len(), 3);
+        assert_eq!(test_utils::get_logs()[2], exp);
+
+        assert_eq!(ctr.account_flagged(dan()), None);
+        assert_eq!(ctr.account_flagged(bob()), Some(AccountFlag::Blacklisted));
+    }
+
+    #[test]
+    #[should_panic(expected = "not authorized")]
+    fn admin_flag_accounts_non_authorized() {
+        let (mut ctx, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+
+        ctx.predecessor_account_id = dan();
+        testing_env!(ctx);
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![dan()],
+            "memo".to_owned(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "account sbt.n is an issuer and can't be flagged")]
+    fn admin_flag_accounts_issuer() {
+        let (_, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![dan(), issuer1()],
+            "memo".to_owned(),
+            None,
+        );
+    }
+
+    #[test]
+    fn admin_pause_issuer() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        let tokens = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_pause_issuer(issuer1()));
+        assert!(!ctr.admin_pause_issuer(issuer1()));
+        assert_eq!(ctr.paused_issuers(), vec![issuer1()]);
+        // tokens are still queryable
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+        assert!(ctr.sbt(issuer1(), tokens[0]).is_some());
+
+        assert!(ctr.admin_unpause_issuer(issuer1()));
+        assert!(!ctr.admin_unpause_issuer(issuer1()));
+        assert_eq!(ctr.paused_issuers(), vec![]);
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_renew(tokens, START + 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "issuer is paused")]
+    fn admin_pause_issuer_mint() {
+        let (mut ctx, mut ctr) = setup(&admin(), 2 * MINT_DEPOSIT);
+        ctr.admin_pause_issuer(issuer1());
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "issuer is paused")]
+    fn admin_pause_issuer_revoke() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 2 * MINT_DEPOSIT);
+        let tokens = ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, Some(START + 10))])]);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_pause_issuer(issuer1());
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_revoke(tokens, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be called by a registered SBT Issuer")]
+    fn admin_pause_issuer_not_registered() {
+        let (_, mut ctr) = setup(&admin(), 0);
+        ctr.admin_pause_issuer(alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "account dan.near is flagged and can't be an issuer")]
+    fn admin_add_sbt_issuer_flagged() {
+        let (mut ctx, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![dan()], "memo".to_owned(), None);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx);
+        ctr.admin_add_sbt_issuer(dan());
+    }
+
+    #[test]
+    fn admin_remove_sbt_issuer() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 20 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![
+            (alice(), mk_batch_metadata(3)),
+            (bob(), mk_batch_metadata(2)),
+        ]);
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        // alice burns one token, so the purge has to skip it
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        ctr.sbt_burn(issuer1(), vec![2], None);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_remove_sbt_issuer(issuer1()));
+        assert!(!ctr.admin_remove_sbt_issuer(issuer1()));
+        assert!(!ctr.sbt_issuers().contains(&issuer1()));
+        assert_eq!(ctr.sbt_supply(issuer1()), 0);
+
+        testing_env!(ctx.clone());
+        assert!(!ctr.admin_purge_issuer_tokens(issuer1(), Some(3)));
+        let data = format!(r#"{{"issuer":"{}","tokens":[1,3]}}"#, issuer1());
+        assert_eq!(
+            test_utils::get_logs(),
+            [mk_log_str("burn", &data, 4), mk_log_str("revoke", &data, 5)].concat()
+        );
+        assert_eq!(ctr.supply_by_owner.get(&(alice(), 2)), None);
+        assert_eq!(ctr.supply_by_owner.get(&(bob(), 2)), Some(2));
+
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_purge_issuer_tokens(issuer1(), None));
+        let data = format!(r#"{{"issuer":"{}","tokens":[4,5]}}"#, issuer1());
+        assert_eq!(
+            test_utils::get_logs(),
+            [mk_log_str("burn", &data, 6), mk_log_str("revoke", &data, 7)].concat()
+        );
+        assert_eq!(ctr.supply_by_owner.get(&(bob(), 2)), None);
+        assert_eq!(ctr.supply_by_class.get(&(2, 1)), None);
+        assert_eq!(ctr.supply_by_issuer.get(&2), None);
+        assert_eq!(ctr.issuer_id_map.get(&2), None);
+        assert!(ctr.issuer_stats.get(&2).is_none());
+        // only issuer2 token is left
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(alice(), None, None, None, None)
+                .iter()
+                .map(|(i, t)| (i.clone(), t.len()))
+                .collect::<Vec<_>>(),
+            vec![(issuer2(), 1)]
+        );
+        assert_eq!(
+            ctr.sbt_tokens_by_owner(bob(), None, None, None, None),
+            vec![]
+        );
+
+        // the issuer can be added again with a new ID
+        assert!(ctr.admin_add_sbt_issuer(issuer1()));
+        assert_eq!(ctr.sbt_issuers.get(&issuer1()), Some(5));
     }
 
     #[test]
-    fn is_human_call_fail() {
-        let (_, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+    fn admin_remove_sbt_issuer_before_purge() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 20 * MINT_DEPOSIT);
+        let issuer_id = ctr.sbt_issuers.get(&issuer1()).unwrap();
+        ctr.sbt_set_tombstone_policy(Some(TombstonePolicy { retention_ms: None }));
+        ctr.sbt_mint(vec![
+            (alice(), mk_batch_metadata(2)),
+            (bob(), mk_batch_metadata(2)),
+        ]);
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx.clone());
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
 
-        match ctr.is_human_call(
-            AccountId::new_unchecked("registry.i-am-human.near".to_string()),
-            "function_name".to_string(),
-            "{}".to_string(),
-        ) {
-            Err(err) => assert_eq!(err, IsHumanCallErr::NotHuman),
-            Ok(_) => panic!("expecting Err(IsHumanCallErr::NotHuman)"),
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_block_class(bob(), issuer1(), 1));
+        assert!(ctr.admin_remove_sbt_issuer(issuer1()));
+
+        // the total supply fallback counts tokens of the removed issuer
+        ctr.total_supply_by_owner.remove(&alice());
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 3);
+
+        // alice can still burn all her tokens, including the removed issuer ones
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.sbt_burn_all(), (3, 0, true));
+        assert_eq!(ctr.sbt_total_supply_by_owner(alice()), 0);
+        let tomb = IssuerTokenId {
+            issuer_id,
+            token: 1,
         };
+        assert!(ctr.tombstones.get(&tomb).is_some());
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        assert!(ctr.admin_purge_issuer_tokens(issuer1(), None));
+        assert!(ctr.tombstones.get(&tomb).is_none());
+        assert!(!ctr
+            .blocked_classes
+            .contains(&balance_key(bob(), issuer_id, 1)));
+        assert!(ctr.tombstone_policies.get(&issuer_id).is_none());
+        assert_eq!(ctr.sbt_total_supply_by_owner(bob()), 0);
     }
 
     #[test]
-    fn admin_set_authorized_flaggers() {
+    #[should_panic(expected = "must be called by a registered SBT Issuer")]
+    fn admin_remove_sbt_issuer_blocks_mint() {
         let (mut ctx, mut ctr) = setup(&admin(), MINT_DEPOSIT);
-
-        let flaggers = [dan()].to_vec();
-        ctr.admin_set_authorized_flaggers(flaggers);
-
-        ctx.predecessor_account_id = dan();
+        ctr.admin_remove_sbt_issuer(issuer1());
+        ctx.predecessor_account_id = issuer1();
         testing_env!(ctx);
-        ctr.assert_authorized_flagger();
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
     }
 
     #[test]
-    #[should_panic(expected = "not an admin")]
-    fn admin_set_authorized_flaggers_fail() {
-        let (mut ctx, mut ctr) = setup(&admin(), MINT_DEPOSIT);
-
-        ctx.predecessor_account_id = dan();
-        testing_env!(ctx);
+    #[should_panic(expected = "can't remove an issuer used in the IAH class set")]
+    fn admin_remove_sbt_issuer_iah() {
+        let (_, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+        ctr.admin_remove_sbt_issuer(fractal_mainnet());
+    }
 
-        let flaggers = [dan()].to_vec();
-        ctr.admin_set_authorized_flaggers(flaggers);
+    #[test]
+    #[should_panic(expected = "issuer tokens must be purged before adding it again")]
+    fn admin_remove_sbt_issuer_add_before_purge() {
+        let (_, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+        ctr.admin_remove_sbt_issuer(issuer1());
+        ctr.admin_add_sbt_issuer(issuer1());
     }
 
     #[test]
-    fn admin_flag_accounts() {
-        let (_, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+    fn flagged_accounts() {
+        let (mut ctx, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+        assert_eq!(ctr.flagged_accounts(None, None, None), vec![]);
 
         ctr.admin_flag_accounts(
             AccountFlag::Blacklisted,
-            [dan(), issuer1()].to_vec(),
+            vec![dan(), bob()],
             "memo".to_owned(),
+            None,
         );
         ctr.admin_flag_accounts(
             AccountFlag::Verified,
-            [issuer2()].to_vec(),
+            vec![carol()],
             "memo".to_owned(),
+            None,
         );
 
-        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_blacklisted","data":["dan.near","sbt.n"]}"#;
-        // check only flag event is emitted
-        assert_eq!(test_utils::get_logs().len(), 2);
-        assert_eq!(test_utils::get_logs()[0], exp);
-
-        assert_eq!(ctr.account_flagged(dan()), Some(AccountFlag::Blacklisted));
+        let all = vec![
+            (dan(), AccountFlag::Blacklisted),
+            (bob(), AccountFlag::Blacklisted),
+            (carol(), AccountFlag::Verified),
+        ];
+        assert_eq!(ctr.flagged_accounts(None, None, None), all);
+        assert_eq!(ctr.flagged_accounts(None, Some(1), Some(1)), all[1..2]);
         assert_eq!(
-            ctr.account_flagged(issuer1()),
-            Some(AccountFlag::Blacklisted)
+            ctr.flagged_accounts(Some(AccountFlag::Verified), None, None),
+            vec![(carol(), AccountFlag::Verified)]
+        );
+        assert_eq!(
+            ctr.flagged_accounts(Some(AccountFlag::Blacklisted), Some(1), None),
+            vec![(bob(), AccountFlag::Blacklisted)]
+        );
+        assert_eq!(
+            ctr.flagged_accounts(Some(AccountFlag::GovBan), None, None),
+            vec![]
+        );
+        // pagination is applied before filtering
+        assert_eq!(
+            ctr.flagged_accounts(Some(AccountFlag::Verified), None, Some(2)),
+            vec![]
+        );
+        assert_eq!(
+            ctr.flagged_accounts(Some(AccountFlag::Verified), Some(2), Some(2)),
+            vec![(carol(), AccountFlag::Verified)]
         );
-        assert_eq!(ctr.account_flagged(issuer2()), Some(AccountFlag::Verified));
-
-        ctr.admin_unflag_accounts([dan()].to_vec(), "memo".to_owned());
 
-        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"unflag","data":["dan.near"]}"#;
-        assert_eq!(test_utils::get_logs().len(), 3);
-        assert_eq!(test_utils::get_logs()[2], exp);
+        // overwriting a flag doesn't duplicate the index entry
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![dan()], "memo".to_owned(), None);
+        assert_eq!(
+            ctr.flagged_accounts(Some(AccountFlag::Verified), None, None),
+            vec![
+                (dan(), AccountFlag::Verified),
+                (carol(), AccountFlag::Verified)
+            ]
+        );
 
-        assert_eq!(ctr.account_flagged(dan()), None);
+        ctr.admin_unflag_accounts(vec![dan(), carol()], "memo".to_owned());
         assert_eq!(
-            ctr.account_flagged(issuer1()),
-            Some(AccountFlag::Blacklisted)
+            ctr.flagged_accounts(None, None, None),
+            vec![(bob(), AccountFlag::Blacklisted)]
         );
-    }
 
-    #[test]
-    #[should_panic(expected = "not authorized")]
-    fn admin_flag_accounts_non_authorized() {
-        let (mut ctx, mut ctr) = setup(&alice(), MINT_DEPOSIT);
+        // accounts flagged before the index was introduced
+        ctr.flagged
+            .insert(&alice(), &FlagEntry(AccountFlag::GovBan, None));
+        // accounts flagged before the migration: flags without expiration in the old map
+        legacy_flagged().insert(&carol(), &AccountFlag::Verified);
+        legacy_flagged().insert(&dan(), &AccountFlag::Blacklisted);
+        assert_eq!(ctr.account_flagged(carol()), Some(AccountFlag::Verified));
+        assert_eq!(ctr.account_flag_expires_at(carol()), None);
+        assert_eq!(ctr.flagged_accounts(None, None, None).len(), 1);
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_index_flagged(vec![alice(), carol(), admin()]);
+        assert_eq!(
+            ctr.flagged_accounts(None, None, None),
+            vec![
+                (bob(), AccountFlag::Blacklisted),
+                (alice(), AccountFlag::GovBan),
+                (carol(), AccountFlag::Verified)
+            ]
+        );
+        assert_eq!(legacy_flagged().get(&carol()), None);
+        assert_eq!(
+            ctr.flagged.get(&carol()),
+            Some(FlagEntry(AccountFlag::Verified, None))
+        );
 
-        ctx.predecessor_account_id = dan();
+        // not moved flags can be overwritten and removed
+        assert_eq!(ctr.account_flagged(dan()), Some(AccountFlag::Blacklisted));
+        ctx.predecessor_account_id = alice();
         testing_env!(ctx);
-        ctr.admin_flag_accounts(AccountFlag::Blacklisted, vec![dan()], "memo".to_owned());
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![dan()], "memo".to_owned(), None);
+        assert_eq!(legacy_flagged().get(&dan()), None);
+        assert_eq!(ctr.account_flagged(dan()), Some(AccountFlag::Verified));
+        legacy_flagged().insert(&dan(), &AccountFlag::Blacklisted);
+        ctr.admin_unflag_accounts(vec![dan()], "memo".to_owned());
+        assert_eq!(ctr.account_flagged(dan()), None);
     }
 
     #[test]
@@ -3175,6 +8124,7 @@ mod tests {
             AccountFlag::Blacklisted,
             vec![dan(), bob()],
             "memo".to_owned(),
+            None,
         );
     }
 
@@ -3185,8 +8135,9 @@ mod tests {
 
         ctr.admin_flag_accounts(
             AccountFlag::Blacklisted,
-            vec![dan(), issuer1()],
+            vec![dan(), carol()],
             "memo".to_owned(),
+            None,
         );
         assert_eq!(ctr.account_flagged(dan()), Some(AccountFlag::Blacklisted));
 
@@ -3202,13 +8153,19 @@ mod tests {
         let m1_1 = mk_metadata(1, Some(START));
         ctr.sbt_mint(vec![(dan(), vec![m1_1])]);
         let human_proof = vec![(fractal_mainnet(), vec![1])];
-        ctr.admin_flag_accounts(AccountFlag::Verified, [dan()].to_vec(), "memo".to_owned());
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            [dan()].to_vec(),
+            "memo".to_owned(),
+            None,
+        );
         assert_eq!(ctr.is_human(dan()), human_proof);
 
         ctr.admin_flag_accounts(
             AccountFlag::Blacklisted,
             [dan()].to_vec(),
             "memo".to_owned(),
+            None,
         );
         assert_eq!(ctr.is_human(dan()), vec![]);
 
@@ -3216,6 +8173,286 @@ mod tests {
         assert_eq!(ctr.is_human(dan()), human_proof);
     }
 
+    #[test]
+    fn is_human_verified_grace() {
+        let (mut ctx, mut ctr) = setup(&fractal_mainnet(), MINT_DEPOSIT);
+
+        let m1_1 = mk_metadata(1, Some(START + 10));
+        ctr.sbt_mint(vec![(dan(), vec![m1_1])]);
+        let human_proof = vec![(fractal_mainnet(), vec![1])];
+        assert_eq!(ctr.verified_grace(), 0);
+
+        // token expired
+        ctx.block_timestamp = (START + 15) * MSECOND;
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.is_human(dan()), vec![]);
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![dan()], "memo".to_owned(), None);
+        // fast path is disabled by default
+        assert_eq!(ctr.is_human(dan()), vec![]);
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_set_verified_grace(10);
+        assert_eq!(ctr.verified_grace(), 10);
+        assert_eq!(ctr.is_human(dan()), human_proof);
+
+        // grace period passed
+        ctx.block_timestamp = (START + 21) * MSECOND;
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.is_human(dan()), vec![]);
+
+        // grace period applies only to verified accounts
+        ctx.block_timestamp = (START + 15) * MSECOND;
+        ctx.predecessor_account_id = fractal_mainnet();
+        testing_env!(ctx);
+        assert_eq!(ctr.is_human(dan()), human_proof);
+        ctr.admin_unflag_accounts(vec![dan()], "memo".to_owned());
+        assert_eq!(ctr.is_human(dan()), vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn admin_set_verified_grace_not_authority() {
+        let (_, mut ctr) = setup(&alice(), 0);
+        ctr.admin_set_verified_grace(10);
+    }
+
+    #[test]
+    fn admin_cancel_soul_transfer() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), mk_batch_metadata(3))]);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        assert_eq!(ctr._sbt_soul_transfer(alice2(), 2).unwrap(), (2, false));
+        assert!(ctr.is_banned(alice()));
+
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_cancel_soul_transfer(alice(), true);
+        assert_eq!(ctr.ongoing_transfer(alice()), None);
+        assert_eq!(ctr.soul_transfer_request(alice()), None);
+        assert!(!ctr.is_banned(alice()));
+        // already transferred tokens stay with the recipient
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 2);
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"cancel_soul_transfer","data":{"account":"alice.near","unban":true},"seq":3}"#;
+        assert_eq!(test_utils::get_logs(), vec![exp]);
+
+        // account is not blocked any more: can receive new tokens
+        ctx.predecessor_account_id = issuer2();
+        testing_env!(ctx);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer2(), None), 1);
+    }
+
+    #[test]
+    fn merge_accounts() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), 3 * MINT_DEPOSIT);
+        ctr.sbt_mint(vec![(alice(), mk_batch_metadata(3))]);
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.sbt_merge(alice2()), Err(SoulTransferErr::NotAccepted));
+        ctr.sbt_merge_init(alice2());
+        assert_eq!(
+            ctr.merge_request(alice()),
+            Some(SoulTransferRequest {
+                recipient: alice2(),
+                accepted: false
+            })
+        );
+        // soul transfer request is independent
+        assert_eq!(ctr.soul_transfer_request(alice()), None);
+        assert_eq!(ctr.sbt_merge(alice2()), Err(SoulTransferErr::NotAccepted));
+
+        ctx.predecessor_account_id = alice2();
+        testing_env!(ctx.clone());
+        ctr.sbt_merge_accept(alice());
+        assert!(ctr.merge_request(alice()).unwrap().accepted);
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        assert_eq!(ctr._sbt_merge(alice2(), 2).unwrap(), (2, false));
+        // the flag is moved, not copied
+        let flag_log = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"flag_verified","data":["alice.nea"],"seq":3}"#;
+        let unflag_log = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"unflag","data":["alice.near"],"seq":4}"#;
+        assert_eq!(test_utils::get_logs(), vec![flag_log, unflag_log]);
+        testing_env!(ctx.clone());
+        assert_eq!(ctr.account_flagged(alice()), None);
+        assert_eq!(ctr.account_flagged(alice2()), Some(AccountFlag::Verified));
+        assert_eq!(
+            ctr.flagged_accounts(None, None, None),
+            vec![(alice2(), AccountFlag::Verified)]
+        );
+        assert_eq!(
+            ctr.ongoing_transfer(alice()),
+            Some(TransferState {
+                recipient: Some(alice2()),
+                kind: Some(TransferKind::Merge),
+                issuer_id: ctr.sbt_issuers.get(&issuer1()).unwrap(),
+                class_id: 2,
+            })
+        );
+        // merge can't be resumed as a soul transfer or with other recipient
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        assert_eq!(
+            ctr.sbt_soul_transfer(alice2(), None),
+            Err(SoulTransferErr::OtherTransfer)
+        );
+        assert_eq!(ctr.sbt_merge(bob()), Err(SoulTransferErr::OtherTransfer));
+
+        assert_eq!(ctr._sbt_merge(alice2(), 2).unwrap(), (1, true));
+        let exp = r#"EVENT_JSON:{"standard":"i_am_human","version":"1.0.0","event":"merge","data":{"from":"alice.near","to":"alice.nea"},"seq":5}"#;
+        assert_eq!(test_utils::get_logs(), vec![exp]);
+        assert_eq!(ctr.ongoing_transfer(alice()), None);
+        assert_eq!(ctr.merge_request(alice()), None);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 0);
+        assert_eq!(ctr.sbt_supply_by_owner(alice2(), issuer1(), None), 3);
+        // the merged account is not banned and can receive new tokens
+        assert!(!ctr.is_banned(alice()));
+        ctx.predecessor_account_id = issuer1();
+        testing_env!(ctx);
+        ctr.sbt_mint(vec![(alice(), vec![mk_metadata(1, None)])]);
+        assert_eq!(ctr.sbt_supply_by_owner(alice(), issuer1(), None), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "no merge request from alice.near")]
+    fn merge_accept_other_recipient() {
+        let (mut ctx, mut ctr) = setup(&alice(), 0);
+        ctr.sbt_merge_init(alice2());
+
+        ctx.predecessor_account_id = bob();
+        testing_env!(ctx);
+        ctr.sbt_merge_accept(alice());
+    }
+
+    #[test]
+    fn banned_accounts() {
+        let (mut ctx, mut ctr) = setup(&issuer1(), MINT_DEPOSIT);
+        assert_eq!(ctr.banned_count(), 0);
+        assert_eq!(ctr.banned_accounts(None, None), vec![]);
+        // ban recorded before the ban timestamps were introduced
+        ctr.banlist.insert(&bob());
+
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        ctr.sbt_soul_transfer(alice2(), None).unwrap();
+        let ts = ctx.block_timestamp / MSECOND;
+        assert_eq!(ctr.banned_count(), 2);
+        assert_eq!(
+            ctr.banned_accounts(None, None),
+            vec![(bob(), None), (alice(), Some(ts))]
+        );
+        assert_eq!(
+            ctr.banned_accounts(Some(1), Some(5)),
+            vec![(alice(), Some(ts))]
+        );
+        assert_eq!(ctr.banned_accounts(None, Some(1)), vec![(bob(), None)]);
+    }
+
+    fn listener_calls() -> Vec<(AccountId, String, String)> {
+        test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|r| {
+                r.actions.into_iter().filter_map(move |a| match a {
+                    VmAction::FunctionCall {
+                        function_name,
+                        args,
+                        ..
+                    } => Some((
+                        r.receiver_id.clone(),
+                        function_name,
+                        String::from_utf8(args).unwrap(),
+                    )),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn listeners() {
+        let (mut ctx, mut ctr) = setup(&admin(), MINT_DEPOSIT);
+        let l1: AccountId = "listener1.near".parse().unwrap();
+        let l2: AccountId = "listener2.near".parse().unwrap();
+        assert!(ctr.admin_add_listener(l1.clone()));
+        assert!(ctr.admin_add_listener(l2.clone()));
+        assert!(!ctr.admin_add_listener(l2.clone()));
+        assert_eq!(ctr.listeners(), vec![l1.clone(), l2.clone()]);
+        assert!(ctr.admin_remove_listener(l2.clone()));
+        assert!(!ctr.admin_remove_listener(l2));
+        assert_eq!(ctr.listeners(), vec![l1.clone()]);
+
+        testing_env!(ctx.clone());
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+        assert_eq!(
+            listener_calls(),
+            vec![(
+                l1.clone(),
+                "on_iah_flag".to_owned(),
+                r#"{"account":"alice.near","flag":"Blacklisted"}"#.to_owned()
+            )]
+        );
+
+        // soul transfer bans the owner and transfers the flag
+        ctx.predecessor_account_id = alice();
+        testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
+        ctr.sbt_soul_transfer(alice2(), None).unwrap();
+        assert_eq!(
+            listener_calls(),
+            vec![
+                (
+                    l1.clone(),
+                    "on_iah_ban".to_owned(),
+                    r#"{"account":"alice.near"}"#.to_owned()
+                ),
+                (
+                    l1,
+                    "on_iah_flag".to_owned(),
+                    r#"{"account":"alice.nea","flag":"Blacklisted"}"#.to_owned()
+                ),
+            ]
+        );
+
+        // notifications are skipped when there is not enough gas
+        ctx.predecessor_account_id = admin();
+        ctx.prepaid_gas = LISTENER_NOTIFY_GAS;
+        testing_env!(ctx);
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![bob()], "memo".to_owned(), None);
+        assert_eq!(listener_calls(), vec![]);
+        assert_eq!(ctr.account_flagged(bob()), Some(AccountFlag::Verified));
+    }
+
+    #[test]
+    #[should_panic(expected = "account has no ongoing soul transfer")]
+    fn admin_cancel_soul_transfer_not_ongoing() {
+        let (_, mut ctr) = setup(&admin(), 0);
+        ctr.admin_cancel_soul_transfer(alice(), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an admin")]
+    fn admin_cancel_soul_transfer_not_authority() {
+        let (_, mut ctr) = setup(&alice(), 0);
+        ctr.admin_cancel_soul_transfer(alice(), false);
+    }
+
     #[test]
     #[should_panic(expected = "can't transfer soul when there is a flag conflict")]
     fn flagged_soul_transfer() {
@@ -3223,33 +8460,40 @@ mod tests {
 
         let m1_1 = mk_metadata(1, Some(START + 10));
         ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
-        ctr.admin_flag_accounts(AccountFlag::Blacklisted, vec![alice()], "memo".to_owned());
-        ctr.admin_flag_accounts(AccountFlag::Verified, vec![bob()], "memo".to_owned());
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+        ctr.admin_flag_accounts(AccountFlag::Verified, vec![bob()], "memo".to_owned(), None);
 
         // make soul transfer
         ctx.predecessor_account_id = alice();
         testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
 
         assert_eq!(
             ctr.flagged.get(&alice()),
-            Some(AccountFlag::Blacklisted),
+            Some(FlagEntry(AccountFlag::Blacklisted, None)),
             "flag must not be removed"
         );
         assert_eq!(
             ctr.flagged.get(&alice2()),
-            Some(AccountFlag::Blacklisted),
+            Some(FlagEntry(AccountFlag::Blacklisted, None)),
             "flag is transferred"
         );
         assert_eq!(
             ctr.flagged.get(&bob()),
-            Some(AccountFlag::Verified),
+            Some(FlagEntry(AccountFlag::Verified, None)),
             "bob keeps his flag"
         );
 
         // transferring from blacklisted to verified account should fail
         ctx.predecessor_account_id = alice2();
         testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice2(), &bob());
         ctr.sbt_soul_transfer(bob(), None).unwrap();
     }
 
@@ -3260,11 +8504,22 @@ mod tests {
 
         let m1_1 = mk_metadata(1, Some(START + 10));
         ctr.sbt_mint(vec![(alice(), vec![m1_1])]);
-        ctr.admin_flag_accounts(AccountFlag::Verified, vec![alice()], "memo".to_owned());
-        ctr.admin_flag_accounts(AccountFlag::Blacklisted, vec![alice2()], "memo".to_owned());
+        ctr.admin_flag_accounts(
+            AccountFlag::Verified,
+            vec![alice()],
+            "memo".to_owned(),
+            None,
+        );
+        ctr.admin_flag_accounts(
+            AccountFlag::Blacklisted,
+            vec![alice2()],
+            "memo".to_owned(),
+            None,
+        );
 
         ctx.predecessor_account_id = alice();
         testing_env!(ctx);
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         ctr.sbt_soul_transfer(alice2(), None).unwrap();
     }
 
@@ -3281,6 +8536,11 @@ mod tests {
         let payload = || "{}".to_owned();
         let lock_duration = 5000; // in ms
 
+        ctx.predecessor_account_id = admin();
+        testing_env!(ctx.clone());
+        ctr.admin_add_consumer(alice());
+        ctr.admin_add_consumer(bob());
+
         //
         // Should fail on not a human
         ctx.predecessor_account_id = carol();
@@ -3294,8 +8554,12 @@ mod tests {
         // Test transfer lock
         ctx.predecessor_account_id = alice();
         testing_env!(ctx.clone());
+        assert_eq!(ctr.account_transfer_lock(alice()), None);
         ctr.is_human_call_lock(bob(), fun(), payload(), lock_duration, false)
             .unwrap();
+        let locked_until = START + lock_duration;
+        assert_eq!(ctr.account_transfer_lock(alice()), Some(locked_until));
+        accept_soul_transfer(&mut ctr, &alice(), &alice2());
         assert_eq!(
             ctr.sbt_soul_transfer(alice2(), None),
             Err(SoulTransferErr::TransferLocked)
@@ -3303,6 +8567,7 @@ mod tests {
         // at the lock_duration we should still fail
         ctx.block_timestamp += lock_duration * MSECOND;
         testing_env!(ctx.clone());
+        assert_eq!(ctr.account_transfer_lock(alice()), Some(locked_until));
         assert_eq!(
             ctr.sbt_soul_transfer(alice2(), None),
             Err(SoulTransferErr::TransferLocked)
@@ -3310,6 +8575,7 @@ mod tests {
         // add one more millisecond, now it transfer should work.
         ctx.block_timestamp += MSECOND;
         testing_env!(ctx.clone());
+        assert_eq!(ctr.account_transfer_lock(alice()), None);
         assert_eq!(ctr.sbt_soul_transfer(alice2(), None), Ok((1, true)));
 
         //
@@ -3322,10 +8588,19 @@ mod tests {
         testing_env!(ctx.clone()); // reset gas
         ctr.is_human_call_lock(alice(), fun(), payload(), lock_duration * 3, false)
             .unwrap();
+        assert_eq!(
+            ctr.consumer_stats(bob()),
+            ConsumerStats {
+                calls: 0,
+                lock_calls: 2
+            }
+        );
+        assert_eq!(ctr.consumer_stats(alice()).lock_calls, 1);
 
         // try to call after the initial lock, but before the extended lock
         ctx.block_timestamp += (2 * lock_duration + 1) * MSECOND;
         testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &bob(), &carol());
         assert_eq!(
             ctr.sbt_soul_transfer(carol(), None),
             Err(SoulTransferErr::TransferLocked)
@@ -3348,6 +8623,7 @@ mod tests {
             .unwrap();
         ctx.block_timestamp += (lock_duration + 1) * MSECOND;
         testing_env!(ctx.clone());
+        accept_soul_transfer(&mut ctr, &carol(), &dan());
         assert_eq!(ctr.sbt_soul_transfer(dan(), None), Ok((1, true)));
     }
 }
@@ -1,13 +1,16 @@
 use crate::*;
 
-// registry/v1.6.0
+// registry/v1.8.0
 #[derive(BorshDeserialize, PanicOnDefault)]
 pub struct OldState {
     pub authority: AccountId,
     pub sbt_issuers: UnorderedMap<AccountId, IssuerId>,
     pub issuer_id_map: LookupMap<IssuerId, AccountId>, // reverse index
     pub(crate) ongoing_soul_tx: LookupMap<AccountId, IssuerTokenId>,
+    pub(crate) transfer_lock: LookupMap<AccountId, u64>,
     pub(crate) banlist: UnorderedSet<AccountId>,
+    // flags are moved to the new prefix by `admin_index_flagged`, see `legacy_flagged`
+    #[allow(dead_code)]
     pub(crate) flagged: LookupMap<AccountId, AccountFlag>,
     pub(crate) authorized_flaggers: LazyOption<Vec<AccountId>>,
     pub(crate) supply_by_owner: LookupMap<(AccountId, IssuerId), u64>,
@@ -28,13 +31,54 @@ impl Contract {
     pub fn migrate() -> Self {
         let old_state: OldState = env::state_read().expect("failed");
         // new field in the smart contract :
-        // + transfer_lock: LookupMap<AccountId, u64>,
+        // + verified_grace_ms: u64,
+        // + issuer_stats: LookupMap<IssuerId, IssuerStats>,
+        // + flagged_index: UnorderedSet<AccountId>,
+        // + iah_mode: ClassSetMode,
+        // + consumer_stats: LookupMap<AccountId, ConsumerStats>,
+        // + soul_transfer_requests: LookupMap<AccountId, SoulTransferRequest>,
+        // + ongoing_tx_info: LookupMap<AccountId, (AccountId, TransferKind)>,
+        // + paused_issuers: UnorderedSet<AccountId>,
+        // + limits: Limits,
+        // + removed_issuers: LookupMap<AccountId, RemovedIssuer>,
+        // + holders_by_issuer: LookupMap<IssuerId, u64>,
+        // + issuer_classes: LookupMap<IssuerId, Vec<ClassId>>,
+        // + event_seq: u64,
+        // + provenance_issuers: LookupMap<IssuerId, bool>,
+        // + token_provenance: LookupMap<IssuerTokenId, TokenProvenance>,
+        // + score_weights: Vec<ScoreWeight>,
+        // + humans_count: u64,
+        // + human_classes: Vec<(IssuerId, ClassId)>,
+        // + humans_by_class: LookupMap<(IssuerId, ClassId), u64>,
+        // + blocked_classes: LookupSet<BalanceKey>,
+        // + upgrade_code_hash: Option<Vec<u8>>,
+        // + gov_contracts: UnorderedSet<AccountId>,
+        // + tombstone_policies: LookupMap<IssuerId, TombstonePolicy>,
+        // + tombstones: LookupMap<IssuerTokenId, TokenTombstone>,
+        // + hidden_portfolios: LookupSet<AccountId>,
+        // + class_metadata: LookupMap<(IssuerId, ClassId), ClassMetadata>,
+        // + catalog_classes: LookupMap<IssuerId, Vec<ClassId>>,
+        // + merge_requests: LookupMap<AccountId, SoulTransferRequest>,
+        // + ban_timestamps: LookupMap<AccountId, u64>,
+        // + listeners: UnorderedSet<AccountId>,
+        // + total_supply_by_owner: LookupMap<AccountId, u64>,
+        // + counters_backfill: Option<CountersBackfill>,
+        // ~ iah_sbts: (AccountId, Vec<ClassId>) -> ClassSet
+        // ~ flagged: LookupMap<AccountId, AccountFlag> -> LookupMap<AccountId, FlagEntry>,
+        //   stored under a new prefix.
+        // NOTE: flags of accounts flagged before the migration must be moved to the new
+        // `flagged` map (and indexed) with `admin_index_flagged`. Until then they are read
+        // from the old map.
+        // NOTE: `holders_by_issuer`, `issuer_classes` and the human counters must be indexed
+        // with `admin_backfill_counters`. `total_supply_by_owner` doesn't need indexing: it
+        // is computed from `supply_by_owner` for accounts without the counter.
+        // NOTE: accounts banned before the migration don't have the ban timestamp.
 
         Self {
             authority: old_state.authority.clone(),
             sbt_issuers: old_state.sbt_issuers,
             issuer_id_map: old_state.issuer_id_map,
-            transfer_lock: LookupMap::new(StorageKey::TransferLock),
+            transfer_lock: old_state.transfer_lock,
             banlist: old_state.banlist,
             supply_by_owner: old_state.supply_by_owner,
             supply_by_class: old_state.supply_by_class,
@@ -44,9 +88,44 @@ impl Contract {
             next_token_ids: old_state.next_token_ids,
             next_issuer_id: old_state.next_issuer_id,
             ongoing_soul_tx: old_state.ongoing_soul_tx,
-            iah_sbts: old_state.iah_sbts,
-            flagged: old_state.flagged,
+            iah_sbts: vec![old_state.iah_sbts],
+            flagged: LookupMap::new(StorageKey::FlagEntries),
             authorized_flaggers: old_state.authorized_flaggers,
+            verified_grace_ms: 0,
+            issuer_stats: LookupMap::new(StorageKey::IssuerStats),
+            flagged_index: UnorderedSet::new(StorageKey::FlaggedIndex),
+            iah_mode: ClassSetMode::All,
+            consumer_stats: LookupMap::new(StorageKey::ConsumerStats),
+            soul_transfer_requests: LookupMap::new(StorageKey::SoulTransferRequests),
+            ongoing_tx_info: LookupMap::new(StorageKey::OngoingTxInfo),
+            paused_issuers: UnorderedSet::new(StorageKey::PausedIssuers),
+            limits: Limits::default(),
+            removed_issuers: LookupMap::new(StorageKey::RemovedIssuers),
+            holders_by_issuer: LookupMap::new(StorageKey::HoldersByIssuer),
+            issuer_classes: LookupMap::new(StorageKey::IssuerClasses),
+            event_seq: 0,
+            provenance_issuers: LookupMap::new(StorageKey::ProvenanceIssuers),
+            token_provenance: LookupMap::new(StorageKey::TokenProvenance),
+            score_weights: Vec::new(),
+            humans_count: 0,
+            human_classes: Vec::new(),
+            humans_by_class: LookupMap::new(StorageKey::HumansByClass),
+            blocked_classes: LookupSet::new(StorageKey::BlockedClasses),
+            upgrade_code_hash: None,
+            gov_contracts: UnorderedSet::new(StorageKey::GovContracts),
+            tombstone_policies: LookupMap::new(StorageKey::TombstonePolicies),
+            tombstones: LookupMap::new(StorageKey::Tombstones),
+            hidden_portfolios: LookupSet::new(StorageKey::HiddenPortfolios),
+            class_metadata: LookupMap::new(StorageKey::ClassMetadata),
+            catalog_classes: LookupMap::new(StorageKey::CatalogClasses),
+            merge_requests: LookupMap::new(StorageKey::MergeRequests),
+            ban_timestamps: LookupMap::new(StorageKey::BanTimestamps),
+            listeners: UnorderedSet::new(StorageKey::Listeners),
+            total_supply_by_owner: LookupMap::new(StorageKey::TotalSupplyByOwner),
+            counters_backfill: Some(CountersBackfill {
+                supply: true,
+                last_owner: None,
+            }),
         }
     }
 }
@@ -4,8 +4,26 @@ use near_sdk::{json_types::Base64VecU8, near_bindgen, AccountId};
 
 use crate::*;
 
-const MAX_LIMIT: u32 = 1000;
-const MAX_REVOKE_PER_CALL: u32 = 25;
+/// Maximum `limit` for paginated queries. Bigger values are clamped to `MAX_LIMIT`.
+pub const MAX_LIMIT: u32 = 1000;
+pub(crate) const MAX_REVOKE_PER_CALL: u32 = 25;
+
+/// Returns `limit` clamped to `MAX_LIMIT`, or `MAX_LIMIT` if the limit is not set.
+/// Panics if `limit == 0`.
+pub(crate) fn clamp_limit(limit: Option<u32>) -> u32 {
+    let limit = limit.unwrap_or(MAX_LIMIT);
+    require!(limit > 0, "limit must be bigger than 0");
+    std::cmp::min(limit, MAX_LIMIT)
+}
+
+/// Returns the token IDs of the per recipient mint result, in the mint order.
+pub(crate) fn flatten_minted(minted: Vec<(AccountId, Vec<(ClassId, TokenId)>)>) -> Vec<TokenId> {
+    minted
+        .into_iter()
+        .flat_map(|(_, tokens)| tokens)
+        .map(|(_, token)| token)
+        .collect()
+}
 
 #[near_bindgen]
 impl SBTRegistry for Contract {
@@ -104,7 +122,7 @@ impl SBTRegistry for Contract {
     /// The function search tokens sequentially. So, if empty list is returned, then a user
     /// should continue querying the contract by setting `from_token = previous from_token + limit`
     /// until the `from_token > sbt_supply(issuer)`.
-    /// If limit is not specified, default is used: 1000.
+    /// If limit is not specified or is bigger than `MAX_LIMIT`, then `MAX_LIMIT` is used.
     fn sbt_tokens(
         &self,
         issuer: AccountId,
@@ -118,20 +136,19 @@ impl SBTRegistry for Contract {
         };
         let from_token = from_token.unwrap_or(1);
         require!(from_token > 0, "from_token, if set, must be >= 1");
-        let limit = limit.unwrap_or(MAX_LIMIT);
-        require!(limit > 0, "limit must be bigger than 0");
+        let limit = clamp_limit(limit);
         let mut max_id = self.next_token_ids.get(&issuer_id).unwrap_or(0);
         if max_id < from_token {
             return vec![];
         }
         max_id = std::cmp::min(max_id + 1, from_token + limit as u64);
 
-        let now = env::block_timestamp_ms();
+        let now = MsTimestamp::now();
         let non_expired = !with_expired.unwrap_or(false);
         let mut resp = Vec::new();
         for token in from_token..max_id {
             if let Some(t) = self.issuer_tokens.get(&IssuerTokenId { issuer_id, token }) {
-                if non_expired && t.metadata.expires_at().unwrap_or(now) < now {
+                if non_expired && now.is_expired(t.metadata.expires_at()) {
                     continue;
                 }
                 resp.push(t.to_token(token))
@@ -144,10 +161,11 @@ impl SBTRegistry for Contract {
     /// If `from_class` is not specified, then `from_class` should be assumed to be the first
     /// valid class id.
     /// If `issuer` is specified, then returns only tokens minted by that issuer.
-    /// If limit is not specified, default is used: MAX_LIMIT.
+    /// If limit is not specified or is bigger than `MAX_LIMIT`, then `MAX_LIMIT` is used.
     /// Returns list of pairs: `(Issuer address, list of token IDs)`.
     /// If `with_expired` is set to `true` then all the tokens are returned including expired ones
     /// otherwise only non-expired tokens are returned.
+    /// Tokens of classes blocked for the account (see `admin_block_class`) are not returned.
     fn sbt_tokens_by_owner(
         &self,
         account: AccountId,
@@ -156,67 +174,15 @@ impl SBTRegistry for Contract {
         limit: Option<u32>,
         with_expired: Option<bool>,
     ) -> Vec<(AccountId, Vec<OwnedToken>)> {
-        if from_class.is_some() {
-            require!(
-                issuer.is_some(),
-                "issuer must be defined if from_class is defined"
-            );
-        }
-        // we don't check banlist because we should still enable banned accounts to query their tokens
-        if self.ongoing_soul_tx.contains_key(&account) {
-            return vec![];
-        }
-
-        let issuer_id = match &issuer {
-            None => 0,
-            Some(addr) => self.assert_issuer(addr),
-        };
-        let from_class = from_class.unwrap_or(0);
-        // iter_from starts from exclusive "left end". We need to iteretare from one before.
-        let first_key = balance_key(account.clone(), issuer_id, from_class.saturating_sub(1));
-        let now = env::block_timestamp_ms();
-        let with_expired = with_expired.unwrap_or(false);
-
-        let mut limit = limit.unwrap_or(MAX_LIMIT);
-        require!(limit > 0, "limit must be bigger than 0");
-
-        let mut resp = Vec::new();
-        let mut tokens = Vec::new();
-        let mut prev_issuer = issuer_id;
-
-        for (key, token_id) in self.balances.iter_from(first_key) {
-            if key.owner != account {
-                break;
-            }
-            if prev_issuer != key.issuer_id {
-                if issuer_id != 0 {
-                    break;
-                }
-                if !tokens.is_empty() {
-                    let issuer = self.issuer_by_id(prev_issuer);
-                    resp.push((issuer, tokens));
-                    tokens = Vec::new();
-                }
-                prev_issuer = key.issuer_id;
-            }
-            let t: TokenData = self.get_token(key.issuer_id, token_id);
-            if !with_expired && t.metadata.expires_at().unwrap_or(now) < now {
-                continue;
-            }
-            tokens.push(OwnedToken {
-                token: token_id,
-                metadata: t.metadata.v1(),
-            });
-            limit -= 1;
-            if limit == 0 {
-                break;
-            }
-        }
-        if prev_issuer != 0 && !tokens.is_empty() {
-            let issuer = self.issuer_by_id(prev_issuer);
-            resp.push((issuer, tokens));
-        }
-        resp
+        self._sbt_tokens_by_owner(
+            account,
+            issuer,
+            from_class,
+            limit,
+            with_expired,
+            false,
+            false,
+        )
     }
 
     /// checks if an `account` was banned by the registry.
@@ -239,7 +205,7 @@ impl SBTRegistry for Contract {
     #[payable]
     fn sbt_mint(&mut self, token_spec: Vec<(AccountId, Vec<TokenMetadata>)>) -> Vec<TokenId> {
         let issuer = &env::predecessor_account_id();
-        self._sbt_mint(issuer, token_spec)
+        flatten_minted(self._sbt_mint(issuer, token_spec, MintMethod::SbtMint, false))
     }
 
     /// sbt_recover reassigns all tokens issued by the caller, from the old owner to a new owner.
@@ -258,9 +224,11 @@ impl SBTRegistry for Contract {
     /// sbt_renew will update the expire time of provided tokens.
     /// `expires_at` is a unix timestamp miliseconds.
     /// Must be called by an SBT contract.
+    /// The batch is validated before any update: panics, without renewing any token, if one
+    /// of the tokens doesn't exist or is owned by a banned account.
     /// Must emit `Renew` event.
-    /// Use `cost::renew_gas` to calculate expected amount of gas that should be assigned for this
-    /// function
+    /// Use `cost::calculate_renew_gas` to calculate expected amount of gas that should be
+    /// assigned for this function
     fn sbt_renew(&mut self, tokens: Vec<TokenId>, expires_at: u64) {
         let issuer = env::predecessor_account_id();
         self._sbt_renew(issuer, tokens, expires_at);
@@ -271,17 +239,25 @@ impl SBTRegistry for Contract {
     /// Must be called by an SBT contract.
     /// Must emit `Revoke` event.
     /// Must also emit `Burn` event if the SBT tokens are burned (removed).
+    /// Use `cost::calculate_revoke_gas` to calculate expected amount of gas that should be
+    /// assigned for this function.
     fn sbt_revoke(&mut self, tokens: Vec<TokenId>, burn: bool) {
         let issuer = env::predecessor_account_id();
-        let issuer_id = self.assert_issuer(&issuer);
+        let issuer_id = self.assert_active_issuer(&issuer);
         if burn {
+            let has_provenance = self.provenance_issuers.contains_key(&issuer_id);
+            let has_tombstones = self.tombstone_policies.contains_key(&issuer_id);
             let mut revoked_per_class: HashMap<u64, u64> = HashMap::new();
             let mut revoked_per_owner: HashMap<AccountId, u64> = HashMap::new();
+            let mut human_before: HashMap<AccountId, HumanState> = HashMap::new();
             let tokens_burned: u64 = tokens.len().try_into().unwrap();
             for token in tokens.clone() {
                 // update balances
                 let token_object = self.get_token(issuer_id, token);
                 let owner = token_object.owner;
+                human_before
+                    .entry(owner.clone())
+                    .or_insert_with(|| self.human_state(&owner));
                 let class_id = token_object.metadata.class_id();
                 let balance_key = &BalanceKey {
                     issuer_id,
@@ -289,6 +265,9 @@ impl SBTRegistry for Contract {
                     class_id,
                 };
                 self.balances.remove(balance_key);
+                if has_tombstones {
+                    self.add_tombstone(&IssuerTokenId { issuer_id, token }, &owner, class_id);
+                }
 
                 // collect the info about the tokens revoked per owner and per class
                 // to update the balances accordingly
@@ -302,8 +281,11 @@ impl SBTRegistry for Contract {
                     .or_insert(1);
 
                 // remove from issuer_tokens
-                self.issuer_tokens
-                    .remove(&IssuerTokenId { issuer_id, token });
+                let key = IssuerTokenId { issuer_id, token };
+                self.issuer_tokens.remove(&key);
+                if has_provenance {
+                    self.token_provenance.remove(&key);
+                }
             }
 
             // update supply by owner
@@ -312,8 +294,10 @@ impl SBTRegistry for Contract {
                     .supply_by_owner
                     .get(&(owner_id.clone(), issuer_id))
                     .unwrap();
-                self.supply_by_owner
-                    .insert(&(owner_id, issuer_id), &(old_supply - tokens_revoked));
+                self.set_supply_by_owner(&owner_id, issuer_id, old_supply - tokens_revoked);
+            }
+            for (owner_id, before) in human_before {
+                self.update_human_counters(&owner_id, before);
             }
 
             // update supply by class
@@ -327,15 +311,18 @@ impl SBTRegistry for Contract {
             let supply_by_issuer = self.supply_by_issuer.get(&(issuer_id)).unwrap_or(0);
             self.supply_by_issuer
                 .insert(&(issuer_id), &(supply_by_issuer - tokens_burned));
+            self.add_issuer_stats(
+                issuer_id,
+                IssuerStats {
+                    total_burned: tokens_burned,
+                    total_revoked: tokens_burned,
+                    ..Default::default()
+                },
+            );
 
-            // emit event
-            SbtTokensEvent {
-                issuer: issuer.clone(),
-                tokens: tokens.clone(),
-            }
-            .emit_burn();
+            self.emit_burn_revoke(issuer, tokens);
         } else {
-            let current_timestamp_ms = env::block_timestamp_ms();
+            let current_timestamp_ms = MsTimestamp::now().ms();
             // revoke
             for token in tokens.clone() {
                 // update expire date for all tokens to current_timestamp
@@ -346,8 +333,15 @@ impl SBTRegistry for Contract {
                 self.issuer_tokens
                     .insert(&IssuerTokenId { issuer_id, token }, &t);
             }
+            self.add_issuer_stats(
+                issuer_id,
+                IssuerStats {
+                    total_revoked: tokens.len() as u64,
+                    ..Default::default()
+                },
+            );
+            self.emit_nep393(Nep393Event::Revoke(SbtTokensEvent { issuer, tokens }));
         }
-        SbtTokensEvent { issuer, tokens }.emit_revoke();
     }
 
     /// Revokes owners SBTs issued by the caller either by burning or updating their expire
@@ -360,127 +354,7 @@ impl SBTRegistry for Contract {
     /// Must emit `Revoke` event.
     /// Must also emit `Burn` event if the SBT tokens are burned (removed).
     fn sbt_revoke_by_owner(&mut self, owner: AccountId, burn: bool) -> bool {
-        let issuer = env::predecessor_account_id();
-        let issuer_id = self.assert_issuer(&issuer);
-
-        if burn {
-            let tokens_by_owner = self.sbt_token_ids_by_owner(owner.clone(), issuer_id, 25);
-
-            if tokens_by_owner.is_empty() {
-                return true;
-            }
-            let mut burned_per_class: HashMap<u64, u64> = HashMap::new();
-
-            // Batch updates for balances and issuer_tokens
-            for (token_id, class_id) in &tokens_by_owner {
-                let balance_key = BalanceKey {
-                    issuer_id,
-                    owner: owner.clone(),
-                    class_id: *class_id,
-                };
-
-                self.balances.remove(&balance_key);
-
-                // Collect info about tokens revoked per class to update the balance accordingly
-                burned_per_class
-                    .entry(*class_id)
-                    .and_modify(|key_value| *key_value += 1)
-                    .or_insert(1);
-
-                self.issuer_tokens.remove(&IssuerTokenId {
-                    issuer_id,
-                    token: *token_id,
-                });
-            }
-
-            // Batch updates for supply values
-            let supply_update = tokens_by_owner.len() as u64;
-
-            // Update supply_by_owner
-            let owner_key = &(owner.clone(), issuer_id);
-            let supply_owner = self.supply_by_owner.get(owner_key).unwrap_or(0);
-            let new_supply_owner = supply_owner - supply_update;
-            self.supply_by_owner.insert(owner_key, &new_supply_owner);
-
-            // Update supply_by_issuer
-            let supply_issuer = self.supply_by_issuer.get(&issuer_id).unwrap_or(0);
-            let new_supply_issuer = supply_issuer - supply_update;
-            self.supply_by_issuer.insert(&issuer_id, &new_supply_issuer);
-
-            // Update supply_by_class
-            for (class_id, tokens_revoked) in burned_per_class {
-                let class_key = &(issuer_id, class_id);
-                let supply_class = self.supply_by_class.get(class_key).unwrap_or(0);
-                let new_supply_class = supply_class - tokens_revoked;
-                self.supply_by_class.insert(class_key, &new_supply_class);
-            }
-
-            let token_ids_burned: Vec<TokenId> = tokens_by_owner
-                .iter()
-                .map(|(token_id, _)| *token_id)
-                .collect();
-
-            SbtTokensEvent {
-                issuer: issuer.clone(),
-                tokens: token_ids_burned.clone(),
-            }
-            .emit_burn();
-
-            SbtTokensEvent {
-                issuer: issuer.clone(),
-                tokens: token_ids_burned,
-            }
-            .emit_revoke();
-
-            // Check if all tokens were burned
-            return self.sbt_supply_by_owner(owner.clone(), issuer, None) == 0;
-        }
-
-        let (_, non_expired_tokens) = self
-            .sbt_tokens_by_owner(
-                owner.clone(),
-                Some(issuer.clone()),
-                None,
-                Some(MAX_REVOKE_PER_CALL),
-                Some(false),
-            )
-            .pop()
-            .unwrap();
-
-        if non_expired_tokens.is_empty() {
-            return true;
-        }
-
-        let is_finished = non_expired_tokens.len() < MAX_REVOKE_PER_CALL as usize;
-
-        let mut token_ids: Vec<TokenId> = Vec::new();
-
-        // Revoke: Update expire date for all tokens to current_timestamp
-        let now = env::block_timestamp_ms();
-        for mut t in non_expired_tokens {
-            token_ids.push(t.token);
-            t.metadata.expires_at = Some(now);
-            let token_data = TokenData {
-                owner: owner.clone(),
-                metadata: t.metadata.into(),
-            };
-            self.issuer_tokens.insert(
-                &IssuerTokenId {
-                    issuer_id,
-                    token: t.token,
-                },
-                &token_data,
-            );
-        }
-
-        SbtTokensEvent {
-            issuer,
-            tokens: token_ids,
-        }
-        .emit_revoke();
-
-        // Check if all tokens were revoked
-        is_finished
+        self._sbt_revoke_by_owner(owner, burn, None)
     }
 
     /// Allows issuer to update token metadata reference and reference_hash.
@@ -492,7 +366,7 @@ impl SBTRegistry for Contract {
         updates: Vec<(TokenId, Option<String>, Option<Base64VecU8>)>,
     ) {
         let issuer = env::predecessor_account_id();
-        let issuer_id = self.assert_issuer(&issuer);
+        let issuer_id = self.assert_active_issuer(&issuer);
         let mut token_ids = vec![0; updates.len()];
         let mut key = IssuerTokenId {
             issuer_id,
@@ -515,10 +389,9 @@ impl SBTRegistry for Contract {
             idx += 1;
         }
 
-        SbtTokensEvent {
+        self.emit_nep393(Nep393Event::TokenReference(SbtTokensEvent {
             issuer,
             tokens: token_ids,
-        }
-        .emit_token_reference();
+        }));
     }
 }
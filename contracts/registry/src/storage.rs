@@ -1,8 +1,13 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::Base64VecU8;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::value::RawValue;
 use near_sdk::{AccountId, BorshStorageKey};
-use sbt::{ClassId, SBTs, TokenId};
+use sbt::{ClassId, ClassSet, OwnedToken, SBTs, TokenId};
+
+use crate::errors::LimitErr;
+use crate::{DEFAULT_MAX_IAH_CLASSES, DEFAULT_MAX_MINT_BATCH};
 
 /// Issuer contract ID based on the SBT Contract address -> u16 map.
 pub type IssuerId = u32;
@@ -20,9 +25,34 @@ pub enum StorageKey {
     IssuerTokens,
     NextTokenId,
     OngoingSoultTx,
-    Flagged,
+    Flagged, // flags without expiration, moved to `FlagEntries` by `admin_index_flagged`
     AdminsFlagged,
     TransferLock,
+    IssuerStats,
+    FlaggedIndex,
+    ConsumerStats,
+    SoulTransferRequests,
+    OngoingTxInfo,
+    PausedIssuers,
+    RemovedIssuers,
+    HoldersByIssuer,
+    IssuerClasses,
+    ProvenanceIssuers,
+    TokenProvenance,
+    HumansByClass,
+    BlockedClasses,
+    UpgradeCode,
+    GovContracts,
+    TombstonePolicies,
+    Tombstones,
+    HiddenPortfolios,
+    ClassMetadata,
+    CatalogClasses,
+    MergeRequests,
+    BanTimestamps,
+    Listeners,
+    TotalSupplyByOwner,
+    FlagEntries,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, BorshStorageKey, Serialize, Deserialize, PartialEq)]
@@ -38,6 +68,250 @@ pub enum AccountFlag {
     GovBan,
 }
 
+/// Account flag with an optional expiration time (unix time in milliseconds). Expired flags
+/// are ignored.
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct FlagEntry(pub AccountFlag, pub Option<u64>);
+
+/// Flags recorded before the v1.8.0 migration, stored without the expiration under the old
+/// `flagged` prefix.
+pub(crate) fn legacy_flagged() -> LookupMap<AccountId, AccountFlag> {
+    LookupMap::new(StorageKey::Flagged)
+}
+
+/// Defines how the entries of the IAH class set are combined in `is_human`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum ClassSetMode {
+    /// Account must have the required tokens of every issuer in the class set.
+    All,
+    /// Account must have the required tokens of at least one issuer in the class set.
+    Any,
+}
+
+/// Monotonic counters of token operations per issuer.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct IssuerStats {
+    /// number of burned tokens (by an issuer or by an owner).
+    pub total_burned: u64,
+    /// number of revoked tokens (including revoked and burned).
+    pub total_revoked: u64,
+    /// number of renewed tokens.
+    pub total_renewed: u64,
+}
+
+/// Counters of calls made to a contract through the registry humanity proxy.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ConsumerStats {
+    /// number of `is_human_call` calls.
+    pub calls: u64,
+    /// number of `is_human_call_lock` calls.
+    pub lock_calls: u64,
+}
+
+/// Defensive limits on the registry configuration and transactions, set by the authority.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct Limits {
+    /// maximum number of classes (of all issuers) in the IAH class set.
+    pub max_iah_classes: u32,
+    /// maximum number of tokens minted in a single `sbt_mint` or `sbt_mint_iah` call.
+    pub max_mint_batch: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_iah_classes: DEFAULT_MAX_IAH_CLASSES,
+            max_mint_batch: DEFAULT_MAX_MINT_BATCH,
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_iah_classes(&self, class_set: &ClassSet) -> Result<(), LimitErr> {
+        let num_classes: usize = class_set.iter().map(|(_, classes)| classes.len()).sum();
+        if num_classes > self.max_iah_classes as usize {
+            return Err(LimitErr::TooManyIahClasses(self.max_iah_classes));
+        }
+        Ok(())
+    }
+
+    pub fn check_mint_batch(&self, num_tokens: u64) -> Result<(), LimitErr> {
+        if num_tokens > self.max_mint_batch as u64 {
+            return Err(LimitErr::MintBatchTooLarge(self.max_mint_batch));
+        }
+        Ok(())
+    }
+}
+
+/// Issuer removed with `admin_remove_sbt_issuer`, whose tokens are being purged.
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct RemovedIssuer {
+    pub issuer_id: IssuerId,
+    /// next token ID to purge.
+    pub next_token: TokenId,
+}
+
+/// Progress of the counters backfill (see `admin_backfill_counters`).
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct CountersBackfill {
+    /// true if the supply stats (issuer holders and classes) are indexed in addition to the
+    /// human counters.
+    pub supply: bool,
+    /// last account whose tokens were indexed.
+    pub last_owner: Option<AccountId>,
+}
+
+/// Weight of an issuer class token in the `humanity_score`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ScoreWeight {
+    pub issuer: AccountId,
+    pub class: ClassId,
+    pub weight: u32,
+}
+
+/// Snapshot of an account used to maintain the human counters: `human` is true if the account
+/// holds the IAH class set tokens (expiration is ignored), `classes[i]` is true if it holds
+/// a token of the i-th `human_classes` entry.
+pub(crate) struct HumanState {
+    pub human: bool,
+    pub classes: Vec<bool>,
+}
+
+/// Registry method used to mint a token.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum MintMethod {
+    SbtMint,
+    SbtMintIah,
+}
+
+/// Minting context of a token, recorded only for issuers with provenance enabled
+/// (see `admin_set_token_provenance`).
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TokenProvenance {
+    pub method: MintMethod,
+    /// ID of the mint call which created the token: the first token ID minted in that call.
+    /// Tokens minted in the same call share the batch ID.
+    pub batch: TokenId,
+}
+
+/// Burned token record, kept only for issuers with a tombstone policy
+/// (see `sbt_set_tombstone_policy`).
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TokenTombstone {
+    /// sha256 hash of the token owner account ID.
+    pub owner_hash: Base64VecU8,
+    pub class: ClassId,
+    /// burn time in milliseconds.
+    pub burned_at: u64,
+}
+
+/// Issuer policy for the burned tokens tombstones.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TombstonePolicy {
+    /// duration (in milliseconds) after which a tombstone can be pruned by anyone.
+    /// If None, tombstones are only pruned by the issuer.
+    pub retention_ms: Option<u64>,
+}
+
+/// Soul transfer request created by the "old owner" with `sbt_soul_transfer_init`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct SoulTransferRequest {
+    pub recipient: AccountId,
+    /// set by the recipient with `sbt_soul_transfer_accept`.
+    pub accepted: bool,
+}
+
+/// Kind of a multi-call transfer process.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum TransferKind {
+    /// `sbt_soul_transfer` started by the owner.
+    SoulTransfer,
+    /// `sbt_recover` started by an issuer.
+    Recover,
+    /// `sbt_merge` started by the owner.
+    Merge,
+}
+
+/// State of an unfinished soul transfer or recovery returned by `ongoing_transfer`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TransferState {
+    /// None for transfers started before the `ongoing_transfer` query was introduced.
+    pub recipient: Option<AccountId>,
+    /// None for transfers started before the `ongoing_transfer` query was introduced.
+    pub kind: Option<TransferKind>,
+    /// issuer ID of the last transferred token.
+    pub issuer_id: IssuerId,
+    /// class ID of the last transferred token.
+    pub class_id: ClassId,
+}
+
+/// Supply statistics of an issuer returned by the `sbt_stats` query.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct SupplyStats {
+    /// total number of the issuer tokens.
+    pub supply: u64,
+    /// list of (class, supply) pairs of classes with non zero supply, ordered by class.
+    pub classes: Vec<(ClassId, u64)>,
+    /// number of accounts holding at least one token of the issuer.
+    pub holders: u64,
+}
+
+/// Combined account status returned by the `account_status` query.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct AccountStatus {
+    pub is_human: bool,
+    pub banned: bool,
+    pub flag: Option<AccountFlag>,
+    /// humanity proof, same as returned by `is_human`.
+    pub proof: SBTs,
+}
+
+/// Data needed to render an account SBT profile, returned by the `account_profile` query.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct AccountProfile {
+    pub is_human: bool,
+    pub banned: bool,
+    pub flag: Option<AccountFlag>,
+    /// page of the account tokens, same as returned by `sbt_tokens_by_owner`.
+    pub sbts: Vec<(AccountId, Vec<OwnedToken>)>,
+    /// limit applied to the `sbts` query.
+    pub limit: u32,
+}
+
 /// Composition of issuer address and token id used for indexing
 #[derive(BorshSerialize, BorshDeserialize)]
 pub(crate) struct IssuerTokenId {
@@ -61,6 +335,37 @@ pub(crate) fn balance_key(owner: AccountId, issuer_id: IssuerId, class_id: Class
     }
 }
 
+/// Response of the paginated queries (v2).
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct Page<T> {
+    pub data: T,
+    /// limit applied to the query, after clamping to `MAX_LIMIT`.
+    pub limit: u32,
+}
+
+/// Response of the cursor paginated queries.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct CursorPage<T> {
+    pub data: T,
+    /// opaque cursor to continue the query, None if there is no more data.
+    pub next_cursor: Option<String>,
+}
+
+/// Encodes the balance position (issuer ID, class) as a query cursor.
+pub(crate) fn encode_cursor(issuer_id: IssuerId, class_id: ClassId) -> String {
+    format!("{}:{}", issuer_id, class_id)
+}
+
+/// Decodes a cursor created by `encode_cursor`.
+pub(crate) fn decode_cursor(cursor: &str) -> Option<(IssuerId, ClassId)> {
+    let (issuer_id, class_id) = cursor.split_once(':')?;
+    Some((issuer_id.parse().ok()?, class_id.parse().ok()?))
+}
+
 /// `is_human_call` wrapper for passing the payload args to the callback.
 #[derive(Serialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug,))]
@@ -0,0 +1,101 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near_bindgen, require, Gas, IntoStorageKey, Promise};
+
+use crate::storage::StorageKey;
+use crate::*;
+
+/// Gas reserved for the `apply_upgrade` execution and the deploy action. The remaining
+/// prepaid gas is attached to the `migrate` call.
+const APPLY_UPGRADE_GAS: Gas = Gas(20 * Gas::ONE_TERA.0);
+
+fn code_key() -> Vec<u8> {
+    StorageKey::UpgradeCode.into_storage_key()
+}
+
+/// DAO friendly upgrade flow: the authority approves the sha256 hash of the new contract code
+/// (`propose_upgrade`), uploads the code (`store_code`) and deploys it (`apply_upgrade`).
+/// No full access key is needed to upgrade the registry.
+#[near_bindgen]
+impl Contract {
+    /// Returns the sha256 hash of the contract code approved with `propose_upgrade`.
+    pub fn proposed_upgrade(&self) -> Option<Base64VecU8> {
+        self.upgrade_code_hash.clone().map(Base64VecU8)
+    }
+
+    /// Returns true if the code approved with `propose_upgrade` is stored and ready to
+    /// be deployed with `apply_upgrade`.
+    pub fn upgrade_code_stored(&self) -> bool {
+        env::storage_has_key(&code_key())
+    }
+
+    /// Approves the sha256 hash of the new contract code, overwriting the previous proposal.
+    /// Code previously stored with `store_code` is removed. Emits `propose_upgrade` event.
+    /// Must be called by the authority.
+    pub fn propose_upgrade(&mut self, code_hash: Base64VecU8) {
+        self.assert_authority();
+        require!(code_hash.0.len() == 32, "code_hash must be a sha256 hash");
+        env::storage_remove(&code_key());
+        events::emit_upgrade("propose_upgrade", &code_hash, self.next_event_seq());
+        self.upgrade_code_hash = Some(code_hash.0);
+    }
+
+    /// Deploys the code stored with `store_code` and calls `migrate` of the new code with
+    /// the remaining gas. Both actions are executed in a single batch, so the code is not
+    /// changed if the migration fails. Clears the proposal and the stored code.
+    /// Emits `apply_upgrade` event.
+    /// Must be called by the authority.
+    /// Panics if the code is not stored or doesn't match the approved hash.
+    pub fn apply_upgrade(&mut self) -> Promise {
+        self.assert_authority();
+        let code_hash = self.upgrade_code_hash.take().expect("no upgrade proposal");
+        let code = env::storage_read(&code_key()).expect("upgrade code is not stored");
+        require!(
+            env::sha256(&code) == code_hash,
+            "stored code doesn't match the approved hash"
+        );
+        env::storage_remove(&code_key());
+        events::emit_upgrade(
+            "apply_upgrade",
+            &Base64VecU8(code_hash),
+            self.next_event_seq(),
+        );
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(
+                "migrate".to_owned(),
+                vec![],
+                0,
+                env::prepaid_gas() - env::used_gas() - APPLY_UPGRADE_GAS,
+            )
+    }
+}
+
+impl Contract {
+    pub(crate) fn _store_code(&self, code: Vec<u8>) {
+        self.assert_authority();
+        require!(
+            self.upgrade_code_hash == Some(env::sha256(&code)),
+            "code doesn't match the approved hash"
+        );
+        let storage_start = env::storage_usage();
+        env::storage_write(&code_key(), &code);
+        let required_deposit =
+            env::storage_usage().saturating_sub(storage_start) as u128 * env::storage_byte_cost();
+        require!(
+            env::attached_deposit() >= required_deposit,
+            format!("not enough storage deposit, required: {}", required_deposit)
+        );
+    }
+}
+
+/// Stores the new contract code, passed as the raw transaction input (not JSON), to be
+/// deployed with `apply_upgrade`. The attached deposit must cover the code storage.
+/// Must be called by the authority.
+/// Panics if the code doesn't match the hash approved with `propose_upgrade`.
+#[cfg(not(feature = "no-entrypoints"))]
+#[no_mangle]
+pub extern "C" fn store_code() {
+    env::setup_panic_hook();
+    let contract: Contract = env::state_read().expect("contract is not initialized");
+    contract._store_code(env::input().expect("code is not provided"));
+}
@@ -1,7 +1,7 @@
 use anyhow::Ok;
 use near_sdk::serde_json::json;
 use near_units::parse_near;
-use near_workspaces::{network::Sandbox, Account, AccountId, Contract, Worker};
+use near_workspaces::{network::Sandbox, types::Gas, Account, AccountId, Contract, Worker};
 use registry::storage::AccountFlag;
 use sbt::{ClassSet, TokenMetadata};
 
@@ -336,3 +336,81 @@ async fn migration_mainnet_real_data() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn revoke_renew_gas() -> anyhow::Result<()> {
+    let worker = near_workspaces::sandbox().await?;
+    let registry = worker
+        .dev_deploy(include_bytes!("../../res/registry.wasm"))
+        .await?;
+    let authority = worker.dev_create_account().await?;
+    let issuer = worker.dev_create_account().await?;
+    let alice = worker.dev_create_account().await?;
+
+    let res = registry
+        .call("new")
+        .args_json(json!({"authority": authority.id(),
+                          "authorized_flaggers": [authority.id()],
+                          "iah_issuer": issuer.id(), "iah_classes": [1]}))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(res.is_success(), "{:?}", res.receipt_failures());
+
+    // mint the maximum number of tokens revoked by the oracle and community contracts
+    let num_tokens: u64 = 20;
+    let token_metadata: Vec<TokenMetadata> = (1..=num_tokens)
+        .map(|class| TokenMetadata {
+            class,
+            issued_at: Some(0),
+            expires_at: None,
+            reference: None,
+            reference_hash: None,
+        })
+        .collect();
+    let res = issuer
+        .call(registry.id(), "sbt_mint")
+        .args_json(json!({ "token_spec": [(alice.id(), token_metadata)] }))
+        .deposit(cost::mint_deposit(num_tokens as usize))
+        .gas(Gas::from_gas(
+            cost::calculate_mint_gas(num_tokens as usize).0,
+        ))
+        .transact()
+        .await?;
+    assert!(res.is_success(), "{:?}", res.receipt_failures());
+    let tokens: Vec<u64> = (1..=num_tokens).collect();
+    let n = tokens.len();
+
+    let res = issuer
+        .call(registry.id(), "sbt_renew")
+        .args_json(json!({"tokens": tokens, "expires_at": u64::MAX}))
+        .deposit(cost::renew_deposit(n))
+        .gas(Gas::from_gas(cost::calculate_renew_gas(n).0))
+        .transact()
+        .await?;
+    assert!(res.is_success(), "{:?}", res.receipt_failures());
+
+    for burn in [false, true] {
+        let res = issuer
+            .call(registry.id(), "sbt_revoke")
+            .args_json(json!({"tokens": tokens, "burn": burn}))
+            .deposit(cost::revoke_deposit(n, burn))
+            .gas(Gas::from_gas(cost::calculate_revoke_gas(n, burn).0))
+            .transact()
+            .await?;
+        assert!(
+            res.is_success(),
+            "burn: {}, {:?}",
+            burn,
+            res.receipt_failures()
+        );
+    }
+    let supply: u64 = registry
+        .view("sbt_supply")
+        .args_json(json!({"issuer": issuer.id()}))
+        .await?
+        .json()?;
+    assert_eq!(supply, 0);
+
+    Ok(())
+}
@@ -62,6 +62,28 @@ impl Nep393Event<'_> {
         }
         .emit()
     }
+
+    /// Emits the event with a sequence number (`seq` field, next to the event `data`).
+    /// Registries use monotonically increasing sequence numbers to let indexers detect
+    /// missing events.
+    pub fn emit_seq(self, seq: u64) {
+        NearEvent {
+            standard: STANDARD_NAME,
+            version: SPEC_VERSION,
+            event: SeqEvent { event: self, seq },
+        }
+        .emit()
+    }
+}
+
+/// Event with a sequence number (`seq` field next to the event `data`), see
+/// `Nep393Event::emit_seq`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeqEvent<T: Serialize> {
+    #[serde(flatten)]
+    pub event: T,
+    pub seq: u64,
 }
 
 /// An event emitted when an SBT token issuance succeeded.
@@ -374,6 +396,22 @@ mod tests {
         assert_eq!(expected, test_utils::get_logs()[1]);
     }
 
+    #[test]
+    fn log_format_seq() {
+        let alice = alice();
+        let expected = r#"EVENT_JSON:{"standard":"nep393","version":"1.0.0","event":"ban","data":["alice.near"],"seq":7}"#;
+        Nep393Event::Ban(vec![&alice]).emit_seq(7);
+        assert_eq!(expected, test_utils::get_logs()[0]);
+
+        let expected = r#"EVENT_JSON:{"standard":"nep393","version":"1.0.0","event":"burn","data":{"issuer":"sbt.near","tokens":[3]},"seq":8}"#;
+        Nep393Event::Burn(SbtTokensEvent {
+            issuer: sbt_issuer(),
+            tokens: vec![3],
+        })
+        .emit_seq(8);
+        assert_eq!(expected, test_utils::get_logs()[1]);
+    }
+
     #[test]
     fn log_format_ban() {
         let alice = alice();
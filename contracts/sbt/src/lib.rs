@@ -1,11 +1,15 @@
 mod events;
 mod metadata;
+mod time;
+
+use std::collections::BTreeMap;
 
 use near_sdk::json_types::Base64VecU8;
 use near_sdk::{ext_contract, AccountId};
 
 pub use crate::events::*;
 pub use crate::metadata::*;
+pub use crate::time::*;
 
 /// This spec can be treated like a version of the standard.
 pub const SPEC_VERSION: &str = "1.0.0";
@@ -35,6 +39,8 @@ pub type ClassId = u64;
 
 /// Collection of SBTs serialized as list of pairs: (Issuer Account, Vector of Token IDs).
 /// This is used for code size and processing efficiency.
+/// Canonical form of `SBTs` (see `normalize_proof`): entries are sorted by issuer account,
+/// there is at most one entry per issuer and token IDs are sorted and unique.
 pub type SBTs = Vec<(AccountId, Vec<TokenId>)>;
 
 /// List of pairs: (Issuer Account, Vector of Class IDs).
@@ -42,6 +48,24 @@ pub type SBTs = Vec<(AccountId, Vec<TokenId>)>;
 /// like set of tokens required to be verified as IAH
 pub type ClassSet = Vec<(AccountId, Vec<ClassId>)>;
 
+/// Returns `proof` in the canonical form: entries sorted by issuer account, entries of the
+/// same issuer merged, token IDs sorted and deduplicated. Proofs in the canonical form can
+/// be compared byte-wise.
+pub fn normalize_proof(proof: SBTs) -> SBTs {
+    let mut by_issuer: BTreeMap<AccountId, Vec<TokenId>> = BTreeMap::new();
+    for (issuer, tokens) in proof {
+        by_issuer.entry(issuer).or_default().extend(tokens);
+    }
+    by_issuer
+        .into_iter()
+        .map(|(issuer, mut tokens)| {
+            tokens.sort_unstable();
+            tokens.dedup();
+            (issuer, tokens)
+        })
+        .collect()
+}
+
 /// SBTIssuer is the minimum required interface to be implemented by issuer.
 /// Other methods, such as a mint function, which requests the registry to proceed with token
 /// minting, is specific to an Issuer implementation (similarly, mint is not part of the FT
@@ -177,13 +201,71 @@ pub trait SBTRegistry {
 trait ExtRegistry {
     fn sbt_mint(&mut self, token_spec: Vec<(AccountId, Vec<TokenMetadata>)>) -> Vec<TokenId>;
     fn sbt_mint_iah(&mut self, token_spec: Vec<(AccountId, Vec<TokenMetadata>)>) -> Vec<TokenId>;
+    fn sbt_mint_v2(
+        &mut self,
+        token_spec: Vec<(AccountId, Vec<TokenMetadata>)>,
+    ) -> Vec<(AccountId, Vec<(ClassId, TokenId)>)>;
+    fn sbt_mint_replace_expired(
+        &mut self,
+        token_spec: Vec<(AccountId, Vec<TokenMetadata>)>,
+    ) -> Vec<(AccountId, Vec<(ClassId, TokenId)>)>;
     fn sbt_renew(&mut self, tokens: Vec<TokenId>, expires_at: u64);
+    fn sbt_renew_many(&mut self, tokens: Vec<(TokenId, u64)>);
+    fn sbt_change_class(&mut self, changes: Vec<(TokenId, ClassId)>);
     fn sbt_revoke(&mut self, tokens: Vec<TokenId>, burn: bool);
+    fn sbt_revoke_by_owner(&mut self, owner: AccountId, burn: bool) -> bool;
+    fn sbt_revoke_by_owner_classes(
+        &mut self,
+        owner: AccountId,
+        classes: Vec<ClassId>,
+        burn: bool,
+    ) -> bool;
 
     // queries
 
     fn is_human(&self, account: AccountId) -> Vec<(AccountId, Vec<TokenId>)>;
     fn sbt(&self, issuer: AccountId, token: TokenId) -> Option<Token>;
+    fn sbt_tokens_by_owner(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)>;
+    fn sbt_tokens_by_owner_private(
+        &self,
+        account: AccountId,
+        issuer: Option<AccountId>,
+        from_class: Option<u64>,
+        limit: Option<u32>,
+        with_expired: Option<bool>,
+    ) -> Vec<(AccountId, Vec<OwnedToken>)>;
     fn sbts(&self, issuer: AccountId, tokens: Vec<TokenId>) -> Vec<Option<Token>>;
     fn sbt_classes(&self, issuer: AccountId, tokens: Vec<TokenId>) -> Vec<Option<ClassId>>;
+    fn soul_transfer_ongoing(&self, account: AccountId) -> bool;
+    fn sbt_supply(&self, issuer: AccountId) -> u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acc(name: &str) -> AccountId {
+        AccountId::new_unchecked(name.to_string())
+    }
+
+    #[test]
+    fn normalize_proof_canonical_form() {
+        assert_eq!(normalize_proof(vec![]), vec![]);
+        let proof = vec![
+            (acc("b.near"), vec![5, 2]),
+            (acc("a.near"), vec![3, 1, 3]),
+            (acc("b.near"), vec![2, 4]),
+        ];
+        let expected = vec![(acc("a.near"), vec![1, 3]), (acc("b.near"), vec![2, 4, 5])];
+        assert_eq!(normalize_proof(proof), expected);
+        // normalization is idempotent
+        assert_eq!(normalize_proof(expected.clone()), expected);
+    }
 }
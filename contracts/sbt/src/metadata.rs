@@ -132,7 +132,7 @@ impl TokenData {
 }
 
 /// token data for sbt_tokens_by_owner response
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(
     not(target_arch = "wasm32"),
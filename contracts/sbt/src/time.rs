@@ -0,0 +1,167 @@
+use near_sdk::env;
+
+/// 1ms in nano seconds.
+pub const MILLISECOND: u64 = 1_000_000;
+
+/// Unix time in milliseconds: the unit used by the token metadata (`issued_at`,
+/// `expires_at`), flags and locks. NEAR block timestamp is in nanoseconds, so it must be
+/// converted with `MsTimestamp::now` or `MsTimestamp::from_ns`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct MsTimestamp(pub u64);
+
+impl MsTimestamp {
+    /// Returns the current block timestamp.
+    pub fn now() -> Self {
+        Self::from_ns(env::block_timestamp())
+    }
+
+    /// Converts unix time in nanoseconds (truncated to the full millisecond).
+    pub const fn from_ns(ns: u64) -> Self {
+        MsTimestamp(ns / MILLISECOND)
+    }
+
+    /// Returns the timestamp in milliseconds.
+    pub const fn ms(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the timestamp moved forward by `duration_ms`, saturating at `u64::MAX`.
+    pub const fn add_ms(self, duration_ms: u64) -> Self {
+        MsTimestamp(self.0.saturating_add(duration_ms))
+    }
+
+    /// Returns the timestamp moved back by `duration_ms`, saturating at zero.
+    pub const fn sub_ms(self, duration_ms: u64) -> Self {
+        MsTimestamp(self.0.saturating_sub(duration_ms))
+    }
+
+    /// Returns true if a token with the given `expires_at` (in milliseconds) is expired at
+    /// `self`. Token is valid up to and including `expires_at`. Tokens without `expires_at`
+    /// never expire.
+    pub fn is_expired(self, expires_at: Option<u64>) -> bool {
+        self.is_expired_with_grace(expires_at, 0)
+    }
+
+    /// Same as `is_expired`, but the token is still valid during `grace_ms` after
+    /// `expires_at`.
+    pub fn is_expired_with_grace(self, expires_at: Option<u64>, grace_ms: u64) -> bool {
+        match expires_at {
+            Some(e) => e.saturating_add(grace_ms) < self.0,
+            None => false,
+        }
+    }
+}
+
+/// Error returned by `check_expires_at`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExpiresAtError {
+    /// `expires_at` is not in the future or is after the maximum (the value).
+    Wrong(u64),
+    /// `expires_at` is before the minimum (the value).
+    TooEarly(u64),
+}
+
+/// Validates a custom token `expires_at` (unix time in milliseconds) set at mint time
+/// `now_ms` and returns the token expiration: `now_ms + max_ttl` if `expires_at` is not set.
+/// `expires_at` must be in the future, not after `now_ms + max_ttl` and not before
+/// `now_ms + min_ttl`. `TooEarly` error is never returned when `min_ttl` is zero.
+pub fn check_expires_at(
+    expires_at: Option<u64>,
+    now_ms: u64,
+    min_ttl: u64,
+    max_ttl: u64,
+) -> Result<u64, ExpiresAtError> {
+    let max = now_ms.saturating_add(max_ttl);
+    let min = now_ms.saturating_add(min_ttl);
+    match expires_at {
+        None => Ok(max),
+        Some(e) if e <= now_ms || e > max => Err(ExpiresAtError::Wrong(max)),
+        Some(e) if e < min => Err(ExpiresAtError::TooEarly(min)),
+        Some(e) => Ok(e),
+    }
+}
+
+impl From<MsTimestamp> for u64 {
+    fn from(t: MsTimestamp) -> u64 {
+        t.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    use super::*;
+
+    #[test]
+    fn from_ns() {
+        assert_eq!(MsTimestamp::from_ns(0), MsTimestamp(0));
+        assert_eq!(MsTimestamp::from_ns(MILLISECOND - 1), MsTimestamp(0));
+        assert_eq!(MsTimestamp::from_ns(MILLISECOND), MsTimestamp(1));
+        assert_eq!(MsTimestamp::from_ns(crate::SECOND + 1), MsTimestamp(1000));
+        assert_eq!(
+            MsTimestamp::from_ns(u64::MAX),
+            MsTimestamp(u64::MAX / MILLISECOND)
+        );
+    }
+
+    #[test]
+    fn now() {
+        let ctx = VMContextBuilder::new()
+            .block_timestamp(5 * crate::SECOND + MILLISECOND - 1)
+            .build();
+        testing_env!(ctx);
+        assert_eq!(MsTimestamp::now(), MsTimestamp(5000));
+        assert_eq!(MsTimestamp::now().ms(), env::block_timestamp_ms());
+    }
+
+    #[test]
+    fn add_sub() {
+        let t = MsTimestamp(10);
+        assert_eq!(t.add_ms(5), MsTimestamp(15));
+        assert_eq!(t.add_ms(u64::MAX), MsTimestamp(u64::MAX));
+        assert_eq!(t.sub_ms(5), MsTimestamp(5));
+        assert_eq!(t.sub_ms(11), MsTimestamp(0));
+        assert_eq!(u64::from(t), 10);
+    }
+
+    #[test]
+    fn is_expired() {
+        let now = MsTimestamp(100);
+        assert!(!now.is_expired(None));
+        assert!(!now.is_expired(Some(101)));
+        assert!(!now.is_expired(Some(100)));
+        assert!(now.is_expired(Some(99)));
+        assert!(now.is_expired(Some(0)));
+        assert!(!MsTimestamp(0).is_expired(Some(0)));
+        assert!(!MsTimestamp(u64::MAX).is_expired(Some(u64::MAX)));
+
+        assert!(!now.is_expired_with_grace(None, 0));
+        assert!(!now.is_expired_with_grace(Some(90), 10));
+        assert!(now.is_expired_with_grace(Some(89), 10));
+        assert!(!now.is_expired_with_grace(Some(u64::MAX), u64::MAX));
+    }
+
+    #[test]
+    fn check_expires_at() {
+        use super::check_expires_at as check;
+        use ExpiresAtError::*;
+
+        // not set: max expiration
+        assert_eq!(check(None, 10, 0, 100), Ok(110));
+        assert_eq!(check(None, 10, 5, 100), Ok(110));
+        assert_eq!(check(None, u64::MAX - 1, 0, 100), Ok(u64::MAX));
+
+        // must be in the future and not after the max
+        assert_eq!(check(Some(11), 10, 0, 100), Ok(11));
+        assert_eq!(check(Some(110), 10, 0, 100), Ok(110));
+        assert_eq!(check(Some(10), 10, 0, 100), Err(Wrong(110)));
+        assert_eq!(check(Some(0), 10, 0, 100), Err(Wrong(110)));
+        assert_eq!(check(Some(111), 10, 0, 100), Err(Wrong(110)));
+
+        // not before the min
+        assert_eq!(check(Some(15), 10, 5, 100), Ok(15));
+        assert_eq!(check(Some(14), 10, 5, 100), Err(TooEarly(15)));
+        assert_eq!(check(Some(111), 10, 5, 100), Err(Wrong(110)));
+    }
+}